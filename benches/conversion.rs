@@ -2,11 +2,13 @@
 
 extern crate test;
 
+use std::path::Path;
+
 use chewing::{
-    conversion::{ChewingEngine, Composition, Symbol},
-    dictionary::{Dictionary, TrieBuf},
+    conversion::{ChewingEngine, Composition, ConversionEngine, Symbol},
+    dictionary::{Dictionary, Layered, Trie, TrieBuf},
     syl,
-    zhuyin::Bopomofo::*,
+    zhuyin::{Bopomofo::*, Syllable},
 };
 use test::Bencher;
 
@@ -17,6 +19,39 @@ fn test_dictionary() -> impl Dictionary {
     ])
 }
 
+const SENTENCE: &str =
+    "ㄐㄧㄣ ㄊㄧㄢ ㄊㄧㄢ ㄑㄧˋ ㄏㄣˇ ㄏㄠˇ ㄨㄛˇ ㄇㄣ˙ ㄧˋ ㄑㄧˇ ㄑㄩˋ ㄍㄨㄥ ㄩㄢˊ ㄙㄢˋ ㄅㄨˋ";
+
+/// The test system dictionaries and an user dictionary, like the editor
+/// uses.
+fn system_dictionary() -> impl Dictionary {
+    let data = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data");
+    let word = Trie::open(data.join("word.dat")).expect("word.dat should be valid");
+    let tsi = Trie::open(data.join("tsi.dat")).expect("tsi.dat should be valid");
+    Layered::new(vec![
+        Box::new(word),
+        Box::new(tsi),
+        Box::new(TrieBuf::new_in_memory()),
+    ])
+}
+
+/// Converts the composition after every keystroke while typing a long
+/// pre-edit buffer.
+fn type_sentence(engine: &ChewingEngine, dict: &dyn Dictionary, clear_cache: bool) {
+    let syllables: Vec<Syllable> = SENTENCE
+        .split_whitespace()
+        .map(|syl| syl.parse().expect("syllable should be valid"))
+        .collect();
+    let mut composition = Composition::new();
+    for syllable in syllables.iter().cycle().take(39) {
+        composition.push(Symbol::from(*syllable));
+        if clear_cache {
+            engine.clear_cache();
+        }
+        test::black_box(engine.convert(dict, &composition));
+    }
+}
+
 #[bench]
 fn bench_conv(b: &mut Bencher) {
     let dict = test_dictionary();
@@ -25,5 +60,19 @@ fn bench_conv(b: &mut Bencher) {
     for _ in 0..40 {
         composition.push(Symbol::from(syl![H, A]));
     }
-    b.iter(|| engine.convert(&dict, &composition).nth(1));
+    b.iter(|| engine.convert(&dict, &composition).into_iter().nth(1));
+}
+
+#[bench]
+fn bench_typing_without_cache(b: &mut Bencher) {
+    let dict = system_dictionary();
+    let engine = ChewingEngine::new();
+    b.iter(|| type_sentence(&engine, &dict, true));
+}
+
+#[bench]
+fn bench_typing_incremental(b: &mut Bencher) {
+    let dict = system_dictionary();
+    let engine = ChewingEngine::new();
+    b.iter(|| type_sentence(&engine, &dict, false));
 }
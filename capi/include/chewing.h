@@ -61,6 +61,56 @@
  */
 #define FUZZY_CHEWING_CONVERSION_ENGINE 2
 
+/**
+ * Use Chewing intelligent phrasing rescored with a phrase bigram model.
+ */
+#define BIGRAM_CONVERSION_ENGINE 3
+
+/**
+ * Treat ㄓ and ㄗ as the same sound with "chewing.phonetic_confusions".
+ */
+#define CHEWING_CONFUSION_ZH_Z (1 << 0)
+
+/**
+ * Treat ㄔ and ㄘ as the same sound with "chewing.phonetic_confusions".
+ */
+#define CHEWING_CONFUSION_CH_C (1 << 1)
+
+/**
+ * Treat ㄕ and ㄙ as the same sound with "chewing.phonetic_confusions".
+ */
+#define CHEWING_CONFUSION_SH_S (1 << 2)
+
+/**
+ * Treat ㄋ and ㄌ as the same sound with "chewing.phonetic_confusions".
+ */
+#define CHEWING_CONFUSION_N_L (1 << 3)
+
+/**
+ * Treat ㄈ and ㄏ as the same sound with "chewing.phonetic_confusions".
+ */
+#define CHEWING_CONFUSION_F_H (1 << 4)
+
+/**
+ * Treat ㄖ and ㄌ as the same sound with "chewing.phonetic_confusions".
+ */
+#define CHEWING_CONFUSION_R_L (1 << 5)
+
+/**
+ * Treat ㄢ and ㄤ as the same sound with "chewing.phonetic_confusions".
+ */
+#define CHEWING_CONFUSION_AN_ANG (1 << 6)
+
+/**
+ * Treat ㄣ and ㄥ as the same sound with "chewing.phonetic_confusions".
+ */
+#define CHEWING_CONFUSION_EN_ENG (1 << 7)
+
+/**
+ * Treat ㄧㄣ and ㄧㄥ as the same sound with "chewing.phonetic_confusions".
+ */
+#define CHEWING_CONFUSION_IN_ING (1 << 8)
+
 /**
  * Indicates automatic user phrase learning is disabled.
  */
@@ -91,6 +141,11 @@
  */
 #define MAX_PHRASE_LEN 11
 
+/**
+ * The maximum number of edits that can be undone with "chewing.undo_depth".
+ */
+#define MAX_UNDO_DEPTH 100
+
 /**
  * The number of minimum candidates that are selectable via shortcut keys.
  */
@@ -1027,6 +1082,109 @@ int chewing_clean_preedit_buf(struct ChewingContext *ctx);
  */
 int chewing_clean_bopomofo_buf(struct ChewingContext *ctx);
 
+/**
+ * Reverts the last edit of the preedit buffer.
+ *
+ * Returns 0 when success, -1 otherwise.
+ *
+ * # Errors
+ *
+ * This function fails if the IM editor is not in entering state or there
+ * is nothing to undo.
+ *
+ * # Safety
+ *
+ * This function should be called with valid pointers.
+ */
+int chewing_undo(struct ChewingContext *ctx);
+
+/**
+ * Reapplies the last edit reverted by chewing_undo.
+ *
+ * Returns 0 when success, -1 otherwise.
+ *
+ * # Errors
+ *
+ * This function fails if the IM editor is not in entering state or there
+ * is nothing to redo.
+ *
+ * # Safety
+ *
+ * This function should be called with valid pointers.
+ */
+int chewing_redo(struct ChewingContext *ctx);
+
+/**
+ * Returns the number of alternative conversions of the whole preedit
+ * buffer.
+ *
+ * # Safety
+ *
+ * This function should be called with valid pointers.
+ */
+int chewing_conversion_count(const struct ChewingContext *ctx);
+
+/**
+ * Returns the index of the selected alternative conversion.
+ *
+ * # Safety
+ *
+ * This function should be called with valid pointers.
+ */
+int chewing_conversion_selected(const struct ChewingContext *ctx);
+
+/**
+ * Returns the text of the alternative conversion by its index.
+ *
+ * The *index* must be between 0 and [chewing_conversion_count] exclusive.
+ *
+ * The returned value is a pointer to a character string. The memory must
+ * be freed by the caller using function
+ * [chewing_free][super::setup::chewing_free].
+ *
+ * # Failures
+ *
+ * This function returns NULL when memory allocation fails.
+ *
+ * # Safety
+ *
+ * This function should be called with valid pointers.
+ */
+char *chewing_conversion_string_by_index(const struct ChewingContext *ctx, int index);
+
+/**
+ * Gets the log probability score of the alternative conversion by its
+ * index.
+ *
+ * The *index* must be between 0 and [chewing_conversion_count] exclusive.
+ *
+ * Returns 0 when success, -1 otherwise.
+ *
+ * # Safety
+ *
+ * This function should be called with valid pointers.
+ */
+int chewing_conversion_score_by_index(const struct ChewingContext *ctx, int index, double *score);
+
+/**
+ * Selects the alternative conversion of the whole preedit buffer by its
+ * index.
+ *
+ * The *index* must be between 0 and [chewing_conversion_count] exclusive.
+ *
+ * Returns 0 when success, -1 otherwise.
+ *
+ * # Errors
+ *
+ * This function fails if the *index* is out of range or the IM editor is
+ * not in entering state.
+ *
+ * # Safety
+ *
+ * This function should be called with valid pointers.
+ */
+int chewing_conversion_choose_by_index(struct ChewingContext *ctx, int index);
+
 /**
  * Converts the u16 encoded syllables to a bopomofo string.
  *
@@ -1599,6 +1757,84 @@ char *chewing_cand_string_by_index(struct ChewingContext *ctx, int index);
  */
 const char *chewing_cand_string_by_index_static(struct ChewingContext *ctx, int index);
 
+/**
+ * Returns the frequency of the candidate by its index.
+ *
+ * The *index* must be between 0 and [chewing_cand_TotalChoice] inclusive.
+ *
+ * Returns the frequency, 0 for symbols, or -1 if the *index* is out of
+ * range or the candidate selection window is not currently open.
+ *
+ * # Safety
+ *
+ * This function should be called with valid pointers.
+ */
+int chewing_cand_freq_by_index(const struct ChewingContext *ctx, int index);
+
+/**
+ * Returns whether the candidate by its index is in the user dictionary.
+ *
+ * The *index* must be between 0 and [chewing_cand_TotalChoice] inclusive.
+ *
+ * Returns 1 if the candidate is an user phrase, 0 if not, or -1 if the
+ * *index* is out of range or the candidate selection window is not
+ * currently open.
+ *
+ * # Safety
+ *
+ * This function should be called with valid pointers.
+ */
+int chewing_cand_is_user_phrase_by_index(const struct ChewingContext *ctx, int index);
+
+/**
+ * Returns the bopomofo reading of the candidate by its index.
+ *
+ * The *index* must be between 0 and [chewing_cand_TotalChoice] inclusive.
+ *
+ * The syllables are separated by a space. The reading of symbols is an
+ * empty string.
+ *
+ * The return value is a const pointer to a character string. The pointer
+ * is only valid immediately after calling this function.
+ *
+ * # Safety
+ *
+ * This function should be called with valid pointers.
+ */
+const char *chewing_cand_reading_by_index_static(struct ChewingContext *ctx, int index);
+
+/**
+ * Returns the comment of the candidate in the dictionary by its index,
+ * e.g. 簡稱 or 異體字.
+ *
+ * The *index* must be between 0 and [chewing_cand_TotalChoice] inclusive.
+ *
+ * The return value is a const pointer to a character string, empty if the
+ * candidate has no comment. The pointer is only valid immediately after
+ * calling this function.
+ *
+ * # Safety
+ *
+ * This function should be called with valid pointers.
+ */
+const char *chewing_cand_comment_by_index_static(struct ChewingContext *ctx, int index);
+
+/**
+ * Returns the name of the dictionary providing the candidate by its
+ * index.
+ *
+ * The *index* must be between 0 and [chewing_cand_TotalChoice] inclusive.
+ *
+ * The return value is a const pointer to a character string, empty if the
+ * source is unknown. The pointer is only valid immediately after calling
+ * this function.
+ *
+ * # Safety
+ *
+ * This function should be called with valid pointers.
+ */
+const char *chewing_cand_source_by_index_static(struct ChewingContext *ctx, int index);
+
 /**
  * Selects the candidate by its index.
  *
@@ -1617,6 +1853,46 @@ const char *chewing_cand_string_by_index_static(struct ChewingContext *ctx, int
  */
 int chewing_cand_choose_by_index(struct ChewingContext *ctx, int index);
 
+/**
+ * Deletes the candidate by its index from the user dictionary.
+ *
+ * The *index* must be between 0 and [chewing_cand_TotalChoice] inclusive.
+ * The phrase is also recorded in the exclusion dictionary, so phrases from
+ * the system dictionaries are no longer suggested.
+ *
+ * Returns 0 when success, -1 otherwise.
+ *
+ * # Errors
+ *
+ * This function fails if the *index* is out of range, the candidate is not
+ * a phrase, or the candidate selection window is not currently open.
+ *
+ * # Safety
+ *
+ * This function should be called with valid pointers.
+ */
+int chewing_cand_delete_by_index(struct ChewingContext *ctx, int index);
+
+/**
+ * Demotes the candidate by its index by resetting its user frequency.
+ *
+ * The *index* must be between 0 and [chewing_cand_TotalChoice] inclusive.
+ * Phrases only found in the system dictionaries are recorded in the
+ * exclusion dictionary instead.
+ *
+ * Returns 0 when success, -1 otherwise.
+ *
+ * # Errors
+ *
+ * This function fails if the *index* is out of range, the candidate is not
+ * a phrase, or the candidate selection window is not currently open.
+ *
+ * # Safety
+ *
+ * This function should be called with valid pointers.
+ */
+int chewing_cand_demote_by_index(struct ChewingContext *ctx, int index);
+
 /**
  * Opens the candidate selection window.
  *
@@ -1,7 +1,7 @@
 use std::{
     cmp::min,
     collections::BTreeMap,
-    ffi::{CStr, CString, c_char, c_int, c_uint, c_ushort, c_void},
+    ffi::{CStr, CString, c_char, c_double, c_int, c_uint, c_ushort, c_void},
     mem,
     ops::Not,
     ptr::{null, null_mut},
@@ -11,11 +11,11 @@ use std::{
 };
 
 use chewing::{
-    conversion::{ChewingEngine, FuzzyChewingEngine, Interval, SimpleEngine, Symbol},
+    conversion::{Interval, Symbol},
     dictionary::{DEFAULT_DICT_NAMES, LookupStrategy},
     editor::{
-        BasicEditor, CharacterForm, ConversionEngineKind, Editor, EditorKeyBehavior, LanguageMode,
-        UserPhraseAddDirection,
+        BasicEditor, Candidate, CharacterForm, ConversionEngineKind, Editor, EditorKeyBehavior,
+        KeyAction, KeyBindings, LanguageMode, UserPhraseAddDirection,
         zhuyin_layout::{
             DaiChien26, Et, Et26, GinYieh, Hsu, Ibm, KeyboardLayoutCompat, Pinyin, Standard,
             SyllableEditor,
@@ -24,26 +24,38 @@ use chewing::{
     input::{
         KeyboardEvent,
         keycode::*,
-        keymap::{
-            DVORAK_MAP, INVERTED_COLEMAK_DH_ANSI_MAP, INVERTED_COLEMAK_DH_ORTH_MAP,
-            INVERTED_COLEMAK_MAP, INVERTED_DVORAK_MAP, INVERTED_WORKMAN_MAP, Keymap, QWERTY_MAP,
-            map_ascii,
-        },
+        keymap::{QWERTY_MAP, map_ascii},
         keysym::*,
     },
-    zhuyin::Syllable,
+    zhuyin::{Confusion, ConfusionSet, Syllable},
 };
 use log::{debug, info};
 
 use crate::{
     logger::init_scoped_logging,
     public::{
-        CHEWING_CONVERSION_ENGINE, CHINESE_MODE, ChewingConfigData, ChewingContext, FULLSHAPE_MODE,
-        FUZZY_CHEWING_CONVERSION_ENGINE, HALFSHAPE_MODE, IntervalType, MAX_SELKEY,
-        SIMPLE_CONVERSION_ENGINE, SYMBOL_MODE, SelKeys,
+        BIGRAM_CONVERSION_ENGINE, CHEWING_CONFUSION_AN_ANG, CHEWING_CONFUSION_CH_C,
+        CHEWING_CONFUSION_EN_ENG, CHEWING_CONFUSION_F_H, CHEWING_CONFUSION_IN_ING,
+        CHEWING_CONFUSION_N_L, CHEWING_CONFUSION_R_L, CHEWING_CONFUSION_SH_S,
+        CHEWING_CONFUSION_ZH_Z, CHEWING_CONVERSION_ENGINE, CHINESE_MODE, ChewingConfigData,
+        ChewingContext, FULLSHAPE_MODE, FUZZY_CHEWING_CONVERSION_ENGINE, HALFSHAPE_MODE,
+        IntervalType, MAX_PHRASE_LEN, MAX_SELKEY, MAX_UNDO_DEPTH, SIMPLE_CONVERSION_ENGINE,
+        SYMBOL_MODE, SelKeys,
     },
 };
 
+const CONFUSIONS: [(c_int, Confusion); 9] = [
+    (CHEWING_CONFUSION_ZH_Z, Confusion::ZhZ),
+    (CHEWING_CONFUSION_CH_C, Confusion::ChC),
+    (CHEWING_CONFUSION_SH_S, Confusion::ShS),
+    (CHEWING_CONFUSION_N_L, Confusion::NL),
+    (CHEWING_CONFUSION_F_H, Confusion::FH),
+    (CHEWING_CONFUSION_R_L, Confusion::RL),
+    (CHEWING_CONFUSION_AN_ANG, Confusion::AnAng),
+    (CHEWING_CONFUSION_EN_ENG, Confusion::EnEng),
+    (CHEWING_CONFUSION_IN_ING, Confusion::InIng),
+];
+
 const TRUE: c_int = 1;
 const FALSE: c_int = 0;
 const OK: c_int = 0;
@@ -379,6 +391,17 @@ pub unsafe extern "C" fn chewing_config_has_option(
             | "chewing.enable_fullwidth_toggle_key"
             | "chewing.sort_candidates_by_frequency"
             | "chewing.auto_snapshot_selections"
+            | "chewing.key_bindings"
+            | "chewing.undo_depth"
+            | "chewing.max_conversion_paths"
+            | "chewing.max_phrase_len"
+            | "chewing.conversion_prune_threshold"
+            | "chewing.toneless_input"
+            | "chewing.phonetic_confusions"
+            | "chewing.abbreviated_input"
+            | "chewing.syllable_correction"
+            | "chewing.auto_correct_syllables"
+            | "chewing.next_phrase_prediction"
     );
 
     ret as c_int
@@ -429,10 +452,24 @@ pub unsafe extern "C" fn chewing_config_get_int(
             ConversionEngineKind::SimpleEngine => SIMPLE_CONVERSION_ENGINE,
             ConversionEngineKind::ChewingEngine => CHEWING_CONVERSION_ENGINE,
             ConversionEngineKind::FuzzyChewingEngine => FUZZY_CHEWING_CONVERSION_ENGINE,
+            ConversionEngineKind::BigramEngine => BIGRAM_CONVERSION_ENGINE,
         },
         "chewing.enable_fullwidth_toggle_key" => option.enable_fullwidth_toggle_key as c_int,
         "chewing.sort_candidates_by_frequency" => option.sort_candidates_by_frequency as c_int,
         "chewing.auto_snapshot_selections" => option.auto_snapshot_selections as c_int,
+        "chewing.undo_depth" => option.undo_depth as c_int,
+        "chewing.max_conversion_paths" => option.max_conversion_paths as c_int,
+        "chewing.max_phrase_len" => option.max_phrase_len as c_int,
+        "chewing.conversion_prune_threshold" => option.conversion_prune_threshold.round() as c_int,
+        "chewing.toneless_input" => option.toneless_input as c_int,
+        "chewing.abbreviated_input" => option.abbreviated_input as c_int,
+        "chewing.syllable_correction" => option.syllable_correction as c_int,
+        "chewing.auto_correct_syllables" => option.auto_correct_syllables as c_int,
+        "chewing.next_phrase_prediction" => option.next_phrase_prediction as c_int,
+        "chewing.phonetic_confusions" => CONFUSIONS
+            .iter()
+            .filter(|(_, confusion)| option.phonetic_confusions.contains(*confusion))
+            .fold(0, |bits, (bit, _)| bits | bit),
         _ => ERROR,
     }
 }
@@ -528,24 +565,21 @@ pub unsafe extern "C" fn chewing_config_set_int(
             options.space_is_select_key = value > 0;
         }
         "chewing.conversion_engine" => {
-            options.conversion_engine = match value {
+            // The editor switches to the new engine when the option changes.
+            (options.conversion_engine, options.lookup_strategy) = match value {
                 SIMPLE_CONVERSION_ENGINE => {
-                    ctx.editor
-                        .set_conversion_engine(Box::new(SimpleEngine::new()));
-                    options.lookup_strategy = LookupStrategy::Standard;
-                    ConversionEngineKind::SimpleEngine
+                    (ConversionEngineKind::SimpleEngine, LookupStrategy::Standard)
                 }
-                CHEWING_CONVERSION_ENGINE => {
-                    ctx.editor
-                        .set_conversion_engine(Box::new(ChewingEngine::new()));
-                    options.lookup_strategy = LookupStrategy::Standard;
-                    ConversionEngineKind::ChewingEngine
-                }
-                FUZZY_CHEWING_CONVERSION_ENGINE => {
-                    ctx.editor
-                        .set_conversion_engine(Box::new(FuzzyChewingEngine::new()));
-                    options.lookup_strategy = LookupStrategy::FuzzyPartialPrefix;
-                    ConversionEngineKind::FuzzyChewingEngine
+                CHEWING_CONVERSION_ENGINE => (
+                    ConversionEngineKind::ChewingEngine,
+                    LookupStrategy::Standard,
+                ),
+                FUZZY_CHEWING_CONVERSION_ENGINE => (
+                    ConversionEngineKind::FuzzyChewingEngine,
+                    LookupStrategy::FuzzyPartialPrefix,
+                ),
+                BIGRAM_CONVERSION_ENGINE => {
+                    (ConversionEngineKind::BigramEngine, LookupStrategy::Standard)
                 }
                 _ => return ERROR,
             }
@@ -562,10 +596,67 @@ pub unsafe extern "C" fn chewing_config_set_int(
             ensure_bool!(value);
             options.auto_snapshot_selections = value > 0;
         }
+        "chewing.undo_depth" => {
+            if value < 0 || value as usize > MAX_UNDO_DEPTH {
+                return ERROR;
+            }
+            options.undo_depth = value as usize;
+        }
+        "chewing.max_conversion_paths" => {
+            if !(1..=100).contains(&value) {
+                return ERROR;
+            }
+            options.max_conversion_paths = value as usize;
+        }
+        "chewing.max_phrase_len" => {
+            if !(1..=MAX_PHRASE_LEN as c_int).contains(&value) {
+                return ERROR;
+            }
+            options.max_phrase_len = value as usize;
+        }
+        "chewing.conversion_prune_threshold" => {
+            if value < 0 {
+                return ERROR;
+            }
+            options.conversion_prune_threshold = value as f64;
+        }
+        "chewing.toneless_input" => {
+            ensure_bool!(value);
+            options.toneless_input = value > 0;
+        }
+        "chewing.abbreviated_input" => {
+            ensure_bool!(value);
+            options.abbreviated_input = value > 0;
+        }
+        "chewing.syllable_correction" => {
+            ensure_bool!(value);
+            options.syllable_correction = value > 0;
+        }
+        "chewing.auto_correct_syllables" => {
+            ensure_bool!(value);
+            options.auto_correct_syllables = value > 0;
+        }
+        "chewing.next_phrase_prediction" => {
+            ensure_bool!(value);
+            options.next_phrase_prediction = value > 0;
+        }
+        "chewing.phonetic_confusions" => {
+            let all = CONFUSIONS.iter().fold(0, |bits, (bit, _)| bits | bit);
+            if value & !all != 0 {
+                return ERROR;
+            }
+            options.phonetic_confusions = CONFUSIONS
+                .iter()
+                .fold(ConfusionSet::new(), |confusions, (bit, confusion)| {
+                    confusions.with(*confusion, value & bit != 0)
+                });
+        }
         _ => return ERROR,
     };
 
-    ctx.editor.set_editor_options(|opt| *opt = options);
+    if ctx.editor.set_editor_options(|opt| *opt = options).is_err() {
+        return ERROR;
+    }
 
     OK
 }
@@ -589,10 +680,11 @@ pub unsafe extern "C" fn chewing_config_get_str(
     let cstr = unsafe { CStr::from_ptr(name) };
     let name = cstr.to_string_lossy();
 
-    let _option = &ctx.editor.editor_options();
+    let option = &ctx.editor.editor_options();
 
     let string = match name.as_ref() {
         "chewing.keyboard_type" => ctx.kb_compat.to_string(),
+        "chewing.key_bindings" => option.key_bindings.to_string(),
         "chewing.selection_keys" => ctx
             .sel_keys
             .0
@@ -647,28 +739,29 @@ pub unsafe extern "C" fn chewing_config_set_str(
                 Ok(kbtype) => kbtype,
                 Err(_) => return ERROR,
             };
-            let (keymap, syl): (&'static Keymap, Box<dyn SyllableEditor>) = match kb_compat {
-                KB::Default => (&QWERTY_MAP, Box::new(Standard::new())),
-                KB::Hsu => (&QWERTY_MAP, Box::new(Hsu::new())),
-                KB::Ibm => (&QWERTY_MAP, Box::new(Ibm::new())),
-                KB::GinYieh => (&QWERTY_MAP, Box::new(GinYieh::new())),
-                KB::Et => (&QWERTY_MAP, Box::new(Et::new())),
-                KB::Et26 => (&QWERTY_MAP, Box::new(Et26::new())),
-                KB::Dvorak => (&INVERTED_DVORAK_MAP, Box::new(Standard::new())),
-                KB::DvorakHsu => (&DVORAK_MAP, Box::new(Hsu::new())),
-                KB::DachenCp26 => (&QWERTY_MAP, Box::new(DaiChien26::new())),
-                KB::HanyuPinyin => (&QWERTY_MAP, Box::new(Pinyin::hanyu())),
-                KB::ThlPinyin => (&QWERTY_MAP, Box::new(Pinyin::thl())),
-                KB::Mps2Pinyin => (&QWERTY_MAP, Box::new(Pinyin::mps2())),
-                KB::Carpalx => (&QWERTY_MAP, Box::new(Standard::new())),
-                KB::Colemak => (&INVERTED_COLEMAK_MAP, Box::new(Standard::new())),
-                KB::ColemakDhAnsi => (&INVERTED_COLEMAK_DH_ANSI_MAP, Box::new(Standard::new())),
-                KB::ColemakDhOrth => (&INVERTED_COLEMAK_DH_ORTH_MAP, Box::new(Standard::new())),
-                KB::Workman => (&INVERTED_WORKMAN_MAP, Box::new(Standard::new())),
+            let syl: Box<dyn SyllableEditor> = match kb_compat {
+                KB::Default => Box::new(Standard::new()),
+                KB::Hsu => Box::new(Hsu::new()),
+                KB::Ibm => Box::new(Ibm::new()),
+                KB::GinYieh => Box::new(GinYieh::new()),
+                KB::Et => Box::new(Et::new()),
+                KB::Et26 => Box::new(Et26::new()),
+                KB::Dvorak => Box::new(Standard::new()),
+                KB::DvorakHsu => Box::new(Hsu::new()),
+                KB::DachenCp26 => Box::new(DaiChien26::new()),
+                KB::HanyuPinyin => Box::new(Pinyin::hanyu()),
+                KB::ThlPinyin => Box::new(Pinyin::thl()),
+                KB::Mps2Pinyin => Box::new(Pinyin::mps2()),
+                KB::Carpalx => Box::new(Standard::new()),
+                KB::Colemak => Box::new(Standard::new()),
+                KB::ColemakDhAnsi => Box::new(Standard::new()),
+                KB::ColemakDhOrth => Box::new(Standard::new()),
+                KB::Workman => Box::new(Standard::new()),
             };
             ctx.kb_compat = kb_compat;
-            ctx.keymap = keymap;
+            ctx.keymap = kb_compat.keymap();
             ctx.editor.set_syllable_editor(syl);
+            ctx.editor.set_keyboard_layout(kb_compat);
         }
         "chewing.selection_keys" => {
             if value.len() != 10 {
@@ -681,6 +774,19 @@ pub unsafe extern "C" fn chewing_config_set_str(
                 .for_each(|(i, key)| sel_keys[i] = key as i32);
             ctx.sel_keys = SelKeys(sel_keys)
         }
+        "chewing.key_bindings" => {
+            let key_bindings: KeyBindings = match value.parse() {
+                Ok(key_bindings) => key_bindings,
+                Err(_) => return ERROR,
+            };
+            if ctx
+                .editor
+                .set_editor_options(|opt| opt.key_bindings = key_bindings)
+                .is_err()
+            {
+                return ERROR;
+            }
+        }
         _ => return ERROR,
     };
 
@@ -707,28 +813,29 @@ pub unsafe extern "C" fn chewing_set_KBType(ctx: *mut ChewingContext, kbtype: c_
         Ok(kb) => kb,
         Err(()) => KB::Default,
     };
-    let (keymap, syl): (&'static Keymap, Box<dyn SyllableEditor>) = match kb_compat {
-        KB::Default => (&QWERTY_MAP, Box::new(Standard::new())),
-        KB::Hsu => (&QWERTY_MAP, Box::new(Hsu::new())),
-        KB::Ibm => (&QWERTY_MAP, Box::new(Ibm::new())),
-        KB::GinYieh => (&QWERTY_MAP, Box::new(GinYieh::new())),
-        KB::Et => (&QWERTY_MAP, Box::new(Et::new())),
-        KB::Et26 => (&QWERTY_MAP, Box::new(Et26::new())),
-        KB::Dvorak => (&INVERTED_DVORAK_MAP, Box::new(Standard::new())),
-        KB::DvorakHsu => (&DVORAK_MAP, Box::new(Hsu::new())),
-        KB::DachenCp26 => (&QWERTY_MAP, Box::new(DaiChien26::new())),
-        KB::HanyuPinyin => (&QWERTY_MAP, Box::new(Pinyin::hanyu())),
-        KB::ThlPinyin => (&QWERTY_MAP, Box::new(Pinyin::thl())),
-        KB::Mps2Pinyin => (&QWERTY_MAP, Box::new(Pinyin::mps2())),
-        KB::Carpalx => (&QWERTY_MAP, Box::new(Standard::new())),
-        KB::Colemak => (&INVERTED_COLEMAK_MAP, Box::new(Standard::new())),
-        KB::ColemakDhAnsi => (&INVERTED_COLEMAK_DH_ANSI_MAP, Box::new(Standard::new())),
-        KB::ColemakDhOrth => (&INVERTED_COLEMAK_DH_ORTH_MAP, Box::new(Standard::new())),
-        KB::Workman => (&INVERTED_WORKMAN_MAP, Box::new(Standard::new())),
+    let syl: Box<dyn SyllableEditor> = match kb_compat {
+        KB::Default => Box::new(Standard::new()),
+        KB::Hsu => Box::new(Hsu::new()),
+        KB::Ibm => Box::new(Ibm::new()),
+        KB::GinYieh => Box::new(GinYieh::new()),
+        KB::Et => Box::new(Et::new()),
+        KB::Et26 => Box::new(Et26::new()),
+        KB::Dvorak => Box::new(Standard::new()),
+        KB::DvorakHsu => Box::new(Hsu::new()),
+        KB::DachenCp26 => Box::new(DaiChien26::new()),
+        KB::HanyuPinyin => Box::new(Pinyin::hanyu()),
+        KB::ThlPinyin => Box::new(Pinyin::thl()),
+        KB::Mps2Pinyin => Box::new(Pinyin::mps2()),
+        KB::Carpalx => Box::new(Standard::new()),
+        KB::Colemak => Box::new(Standard::new()),
+        KB::ColemakDhAnsi => Box::new(Standard::new()),
+        KB::ColemakDhOrth => Box::new(Standard::new()),
+        KB::Workman => Box::new(Standard::new()),
     };
     ctx.kb_compat = kb_compat;
-    ctx.keymap = keymap;
+    ctx.keymap = kb_compat.keymap();
     ctx.editor.set_syllable_editor(syl);
+    ctx.editor.set_keyboard_layout(kb_compat);
     if kb_compat == KB::Default && kb_compat as c_int != kbtype {
         -1
     } else {
@@ -1709,6 +1816,188 @@ pub unsafe extern "C" fn chewing_clean_bopomofo_buf(ctx: *mut ChewingContext) ->
     OK
 }
 
+/// Reverts the last edit of the preedit buffer.
+///
+/// Returns 0 when success, -1 otherwise.
+///
+/// # Errors
+///
+/// This function fails if the IM editor is not in entering state or there
+/// is nothing to undo.
+///
+/// # Safety
+///
+/// This function should be called with valid pointers.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chewing_undo(ctx: *mut ChewingContext) -> c_int {
+    let ctx = as_mut_or_return!(ctx, ERROR);
+    let _logger_guard = init_scoped_logging(ctx.logger_fn, ctx.logger_data);
+
+    match ctx.editor.undo() {
+        Ok(_) => OK,
+        Err(_) => ERROR,
+    }
+}
+
+/// Reapplies the last edit reverted by chewing_undo.
+///
+/// Returns 0 when success, -1 otherwise.
+///
+/// # Errors
+///
+/// This function fails if the IM editor is not in entering state or there
+/// is nothing to redo.
+///
+/// # Safety
+///
+/// This function should be called with valid pointers.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chewing_redo(ctx: *mut ChewingContext) -> c_int {
+    let ctx = as_mut_or_return!(ctx, ERROR);
+    let _logger_guard = init_scoped_logging(ctx.logger_fn, ctx.logger_data);
+
+    match ctx.editor.redo() {
+        Ok(_) => OK,
+        Err(_) => ERROR,
+    }
+}
+
+/// Returns the number of alternative conversions of the whole preedit
+/// buffer.
+///
+/// # Safety
+///
+/// This function should be called with valid pointers.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chewing_conversion_count(ctx: *const ChewingContext) -> c_int {
+    let ctx = as_ref_or_return!(ctx, ERROR);
+    let _logger_guard = init_scoped_logging(ctx.logger_fn, ctx.logger_data);
+
+    ctx.editor.conversion_alternatives().len() as c_int
+}
+
+/// Returns the index of the selected alternative conversion.
+///
+/// # Safety
+///
+/// This function should be called with valid pointers.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chewing_conversion_selected(ctx: *const ChewingContext) -> c_int {
+    let ctx = as_ref_or_return!(ctx, ERROR);
+    let _logger_guard = init_scoped_logging(ctx.logger_fn, ctx.logger_data);
+
+    ctx.editor.selected_conversion() as c_int
+}
+
+/// Returns the text of the alternative conversion by its index.
+///
+/// The *index* must be between 0 and [chewing_conversion_count] exclusive.
+///
+/// The returned value is a pointer to a character string. The memory must
+/// be freed by the caller using function
+/// [chewing_free][super::setup::chewing_free].
+///
+/// # Failures
+///
+/// This function returns NULL when memory allocation fails.
+///
+/// # Safety
+///
+/// This function should be called with valid pointers.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chewing_conversion_string_by_index(
+    ctx: *const ChewingContext,
+    index: c_int,
+) -> *mut c_char {
+    let ctx = as_ref_or_return!(
+        ctx,
+        owned_into_raw(Owned::CString, CString::default().into_raw())
+    );
+    let _logger_guard = init_scoped_logging(ctx.logger_fn, ctx.logger_data);
+
+    let text = usize::try_from(index)
+        .ok()
+        .and_then(|index| {
+            ctx.editor
+                .conversion_alternatives()
+                .get(index)
+                .map(|outcome| outcome.text())
+        })
+        .unwrap_or_default();
+    owned_into_raw(
+        Owned::CString,
+        CString::new(text)
+            .expect("phrase should be valid UTF-8")
+            .into_raw(),
+    )
+}
+
+/// Gets the log probability score of the alternative conversion by its
+/// index.
+///
+/// The *index* must be between 0 and [chewing_conversion_count] exclusive.
+///
+/// Returns 0 when success, -1 otherwise.
+///
+/// # Safety
+///
+/// This function should be called with valid pointers.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chewing_conversion_score_by_index(
+    ctx: *const ChewingContext,
+    index: c_int,
+    score: *mut c_double,
+) -> c_int {
+    let ctx = as_ref_or_return!(ctx, ERROR);
+    let _logger_guard = init_scoped_logging(ctx.logger_fn, ctx.logger_data);
+
+    if score.is_null() {
+        return ERROR;
+    }
+    let Ok(index) = usize::try_from(index) else {
+        return ERROR;
+    };
+    match ctx.editor.conversion_alternatives().get(index) {
+        Some(outcome) => {
+            unsafe { score.write(outcome.log_prob()) };
+            OK
+        }
+        None => ERROR,
+    }
+}
+
+/// Selects the alternative conversion of the whole preedit buffer by its
+/// index.
+///
+/// The *index* must be between 0 and [chewing_conversion_count] exclusive.
+///
+/// Returns 0 when success, -1 otherwise.
+///
+/// # Errors
+///
+/// This function fails if the *index* is out of range or the IM editor is
+/// not in entering state.
+///
+/// # Safety
+///
+/// This function should be called with valid pointers.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chewing_conversion_choose_by_index(
+    ctx: *mut ChewingContext,
+    index: c_int,
+) -> c_int {
+    let ctx = as_mut_or_return!(ctx, ERROR);
+    let _logger_guard = init_scoped_logging(ctx.logger_fn, ctx.logger_data);
+
+    let Ok(index) = usize::try_from(index) else {
+        return ERROR;
+    };
+    match ctx.editor.select_conversion(index) {
+        Ok(_) => OK,
+        Err(_) => ERROR,
+    }
+}
+
 /// Converts the u16 encoded syllables to a bopomofo string.
 ///
 /// If both of the buf and the len are 0, this function will return buf
@@ -1813,16 +2102,30 @@ pub unsafe extern "C" fn chewing_handle_KeyboardEvent(
         None
     };
 
+    let raw_evt = KeyboardEvent {
+        code: Keycode(code),
+        ksym: Keysym(ksym),
+        state,
+    };
     let evt = if let Some(key) = key {
         let mut evt = map_ascii(&ctx.keymap, key as u8);
         evt.state = state;
         evt
     } else {
-        KeyboardEvent {
-            code: Keycode(code),
-            ksym: Keysym(ksym),
-            state,
-        }
+        raw_evt
+    };
+    // Selection keys only pick predictions with the select_prediction key
+    // binding, otherwise they are typed as usual.
+    let evt = if ctx.editor.is_predicting()
+        && !ctx
+            .editor
+            .editor_options()
+            .key_bindings
+            .matches(KeyAction::SelectPrediction, &evt)
+    {
+        raw_evt
+    } else {
+        evt
     };
 
     ctx.editor.process_keyevent(evt);
@@ -2752,6 +3055,147 @@ pub unsafe extern "C" fn chewing_cand_string_by_index_static(
     global_empty_cstr()
 }
 
+fn candidate_details_by_index(ctx: &ChewingContext, index: c_int) -> Option<Candidate> {
+    let index = usize::try_from(index).ok()?;
+    ctx.editor.candidate_details(index).ok()
+}
+
+/// Returns the frequency of the candidate by its index.
+///
+/// The *index* must be between 0 and [chewing_cand_TotalChoice] inclusive.
+///
+/// Returns the frequency, 0 for symbols, or -1 if the *index* is out of
+/// range or the candidate selection window is not currently open.
+///
+/// # Safety
+///
+/// This function should be called with valid pointers.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chewing_cand_freq_by_index(
+    ctx: *const ChewingContext,
+    index: c_int,
+) -> c_int {
+    let ctx = as_ref_or_return!(ctx, ERROR);
+    let _logger_guard = init_scoped_logging(ctx.logger_fn, ctx.logger_data);
+
+    match candidate_details_by_index(ctx, index) {
+        Some(candidate) => c_int::try_from(candidate.freq).unwrap_or(c_int::MAX),
+        None => ERROR,
+    }
+}
+
+/// Returns whether the candidate by its index is in the user dictionary.
+///
+/// The *index* must be between 0 and [chewing_cand_TotalChoice] inclusive.
+///
+/// Returns 1 if the candidate is an user phrase, 0 if not, or -1 if the
+/// *index* is out of range or the candidate selection window is not
+/// currently open.
+///
+/// # Safety
+///
+/// This function should be called with valid pointers.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chewing_cand_is_user_phrase_by_index(
+    ctx: *const ChewingContext,
+    index: c_int,
+) -> c_int {
+    let ctx = as_ref_or_return!(ctx, ERROR);
+    let _logger_guard = init_scoped_logging(ctx.logger_fn, ctx.logger_data);
+
+    match candidate_details_by_index(ctx, index) {
+        Some(candidate) => candidate.user_phrase as c_int,
+        None => ERROR,
+    }
+}
+
+/// Returns the bopomofo reading of the candidate by its index.
+///
+/// The *index* must be between 0 and [chewing_cand_TotalChoice] inclusive.
+///
+/// The syllables are separated by a space. The reading of symbols is an
+/// empty string.
+///
+/// The return value is a const pointer to a character string. The pointer
+/// is only valid immediately after calling this function.
+///
+/// # Safety
+///
+/// This function should be called with valid pointers.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chewing_cand_reading_by_index_static(
+    ctx: *mut ChewingContext,
+    index: c_int,
+) -> *const c_char {
+    let ctx = as_mut_or_return!(ctx, global_empty_cstr());
+    let _logger_guard = init_scoped_logging(ctx.logger_fn, ctx.logger_data);
+
+    match candidate_details_by_index(ctx, index) {
+        Some(candidate) => {
+            let reading = candidate
+                .reading
+                .iter()
+                .map(|syl| syl.to_string())
+                .collect::<Vec<_>>()
+                .join(" ");
+            copy_cstr(&mut ctx.cand_buf, &reading)
+        }
+        None => global_empty_cstr(),
+    }
+}
+
+/// Returns the comment of the candidate in the dictionary by its index,
+/// e.g. 簡稱 or 異體字.
+///
+/// The *index* must be between 0 and [chewing_cand_TotalChoice] inclusive.
+///
+/// The return value is a const pointer to a character string, empty if the
+/// candidate has no comment. The pointer is only valid immediately after
+/// calling this function.
+///
+/// # Safety
+///
+/// This function should be called with valid pointers.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chewing_cand_comment_by_index_static(
+    ctx: *mut ChewingContext,
+    index: c_int,
+) -> *const c_char {
+    let ctx = as_mut_or_return!(ctx, global_empty_cstr());
+    let _logger_guard = init_scoped_logging(ctx.logger_fn, ctx.logger_data);
+
+    match candidate_details_by_index(ctx, index).and_then(|candidate| candidate.comment) {
+        Some(comment) => copy_cstr(&mut ctx.cand_buf, &comment),
+        None => global_empty_cstr(),
+    }
+}
+
+/// Returns the name of the dictionary providing the candidate by its
+/// index.
+///
+/// The *index* must be between 0 and [chewing_cand_TotalChoice] inclusive.
+///
+/// The return value is a const pointer to a character string, empty if the
+/// source is unknown. The pointer is only valid immediately after calling
+/// this function.
+///
+/// # Safety
+///
+/// This function should be called with valid pointers.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chewing_cand_source_by_index_static(
+    ctx: *mut ChewingContext,
+    index: c_int,
+) -> *const c_char {
+    let ctx = as_mut_or_return!(ctx, global_empty_cstr());
+    let _logger_guard = init_scoped_logging(ctx.logger_fn, ctx.logger_data);
+
+    match candidate_details_by_index(ctx, index).and_then(|candidate| candidate.source) {
+        Some(info) => copy_cstr(&mut ctx.cand_buf, &info.name),
+        None => global_empty_cstr(),
+    }
+}
+
 /// Selects the candidate by its index.
 ///
 /// The *index* must be between 0 and [chewing_cand_TotalChoice] inclusive.
@@ -2780,6 +3224,66 @@ pub unsafe extern "C" fn chewing_cand_choose_by_index(
     }
 }
 
+/// Deletes the candidate by its index from the user dictionary.
+///
+/// The *index* must be between 0 and [chewing_cand_TotalChoice] inclusive.
+/// The phrase is also recorded in the exclusion dictionary, so phrases from
+/// the system dictionaries are no longer suggested.
+///
+/// Returns 0 when success, -1 otherwise.
+///
+/// # Errors
+///
+/// This function fails if the *index* is out of range, the candidate is not
+/// a phrase, or the candidate selection window is not currently open.
+///
+/// # Safety
+///
+/// This function should be called with valid pointers.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chewing_cand_delete_by_index(
+    ctx: *mut ChewingContext,
+    index: c_int,
+) -> c_int {
+    let ctx = as_mut_or_return!(ctx, ERROR);
+    let _logger_guard = init_scoped_logging(ctx.logger_fn, ctx.logger_data);
+
+    match ctx.editor.delete_candidate(index as usize) {
+        Ok(_) => OK,
+        Err(_) => ERROR,
+    }
+}
+
+/// Demotes the candidate by its index by resetting its user frequency.
+///
+/// The *index* must be between 0 and [chewing_cand_TotalChoice] inclusive.
+/// Phrases only found in the system dictionaries are recorded in the
+/// exclusion dictionary instead.
+///
+/// Returns 0 when success, -1 otherwise.
+///
+/// # Errors
+///
+/// This function fails if the *index* is out of range, the candidate is not
+/// a phrase, or the candidate selection window is not currently open.
+///
+/// # Safety
+///
+/// This function should be called with valid pointers.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chewing_cand_demote_by_index(
+    ctx: *mut ChewingContext,
+    index: c_int,
+) -> c_int {
+    let ctx = as_mut_or_return!(ctx, ERROR);
+    let _logger_guard = init_scoped_logging(ctx.logger_fn, ctx.logger_data);
+
+    match ctx.editor.demote_candidate(index as usize) {
+        Ok(_) => OK,
+        Err(_) => ERROR,
+    }
+}
+
 /// Opens the candidate selection window.
 ///
 /// This operation is only allowed when the IM editor is in entering state.
@@ -183,7 +183,12 @@ pub mod candidates {
     pub use super::io::chewing_cand_TotalPage;
     pub use super::io::chewing_cand_choose_by_index;
     pub use super::io::chewing_cand_close;
+    pub use super::io::chewing_cand_comment_by_index_static;
+    pub use super::io::chewing_cand_delete_by_index;
+    pub use super::io::chewing_cand_demote_by_index;
+    pub use super::io::chewing_cand_freq_by_index;
     pub use super::io::chewing_cand_hasNext;
+    pub use super::io::chewing_cand_is_user_phrase_by_index;
     pub use super::io::chewing_cand_list_first;
     pub use super::io::chewing_cand_list_has_next;
     pub use super::io::chewing_cand_list_has_prev;
@@ -191,8 +196,15 @@ pub mod candidates {
     pub use super::io::chewing_cand_list_next;
     pub use super::io::chewing_cand_list_prev;
     pub use super::io::chewing_cand_open;
+    pub use super::io::chewing_cand_reading_by_index_static;
+    pub use super::io::chewing_cand_source_by_index_static;
     pub use super::io::chewing_cand_string_by_index;
     pub use super::io::chewing_cand_string_by_index_static;
+    pub use super::io::chewing_conversion_choose_by_index;
+    pub use super::io::chewing_conversion_count;
+    pub use super::io::chewing_conversion_score_by_index;
+    pub use super::io::chewing_conversion_selected;
+    pub use super::io::chewing_conversion_string_by_index;
     pub use super::io::chewing_get_candPerPage;
     pub use super::io::chewing_get_hsuSelKeyType;
     pub use super::io::chewing_get_selKey;
@@ -280,5 +292,6 @@ pub mod globals {
     pub use super::public::MAX_CHI_SYMBOL_LEN;
     pub use super::public::MAX_PHONE_SEQ_LEN;
     pub use super::public::MAX_PHRASE_LEN;
+    pub use super::public::MAX_UNDO_DEPTH;
     pub use super::public::MIN_CHI_SYMBOL_LEN;
 }
@@ -28,6 +28,26 @@ pub const SIMPLE_CONVERSION_ENGINE: c_int = 0;
 pub const CHEWING_CONVERSION_ENGINE: c_int = 1;
 /// Use original Chewing intelligent phrasing with fuzzy prefix search.
 pub const FUZZY_CHEWING_CONVERSION_ENGINE: c_int = 2;
+/// Use Chewing intelligent phrasing rescored with a phrase bigram model.
+pub const BIGRAM_CONVERSION_ENGINE: c_int = 3;
+/// Treat ㄓ and ㄗ as the same sound with "chewing.phonetic_confusions".
+pub const CHEWING_CONFUSION_ZH_Z: c_int = 1 << 0;
+/// Treat ㄔ and ㄘ as the same sound with "chewing.phonetic_confusions".
+pub const CHEWING_CONFUSION_CH_C: c_int = 1 << 1;
+/// Treat ㄕ and ㄙ as the same sound with "chewing.phonetic_confusions".
+pub const CHEWING_CONFUSION_SH_S: c_int = 1 << 2;
+/// Treat ㄋ and ㄌ as the same sound with "chewing.phonetic_confusions".
+pub const CHEWING_CONFUSION_N_L: c_int = 1 << 3;
+/// Treat ㄈ and ㄏ as the same sound with "chewing.phonetic_confusions".
+pub const CHEWING_CONFUSION_F_H: c_int = 1 << 4;
+/// Treat ㄖ and ㄌ as the same sound with "chewing.phonetic_confusions".
+pub const CHEWING_CONFUSION_R_L: c_int = 1 << 5;
+/// Treat ㄢ and ㄤ as the same sound with "chewing.phonetic_confusions".
+pub const CHEWING_CONFUSION_AN_ANG: c_int = 1 << 6;
+/// Treat ㄣ and ㄥ as the same sound with "chewing.phonetic_confusions".
+pub const CHEWING_CONFUSION_EN_ENG: c_int = 1 << 7;
+/// Treat ㄧㄣ and ㄧㄥ as the same sound with "chewing.phonetic_confusions".
+pub const CHEWING_CONFUSION_IN_ING: c_int = 1 << 8;
 /// Indicates automatic user phrase learning is disabled.
 pub const AUTOLEARN_DISABLED: usize = 1;
 /// Indicates automatic user phrase learning is enabled.
@@ -40,6 +60,8 @@ pub const MAX_CHI_SYMBOL_LEN: usize = MAX_PHONE_SEQ_LEN - MAX_PHRASE_LEN;
 pub const MAX_PHONE_SEQ_LEN: usize = 50;
 /// The maximum phrase size.
 pub const MAX_PHRASE_LEN: usize = 11;
+/// The maximum number of edits that can be undone with "chewing.undo_depth".
+pub const MAX_UNDO_DEPTH: usize = 100;
 
 /// The number of minimum candidates that are selectable via shortcut keys.
 pub const MIN_SELKEY: usize = 1;
@@ -0,0 +1,599 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufRead, BufReader},
+    mem,
+    path::Path,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use log::trace;
+
+use super::{
+    ChewingEngine, Composition, ConversionContext, ConversionEngine, ConversionTrace, Outcome,
+    TraceEdge, TracePath,
+    chewing::{
+        Edge, PossibleInterval, PossiblePhrase, glue_fn, length_log_prob, user_bigram_log_prob,
+    },
+};
+use crate::dictionary::{Dictionary, DictionaryUsage, LookupStrategy};
+
+/// Phrase bigram counts used by the [`BigramEngine`].
+///
+/// The model is usually loaded from a `bigram.dat` file stored alongside
+/// the system dictionaries. Each line of the file contains two phrases and
+/// the number of times the second phrase follows the first one, separated
+/// by whitespace. Empty lines and lines starting with `#` are ignored.
+///
+/// ```text
+/// # left right count
+/// 慢慢 地 120
+/// 跑 得 98
+/// ```
+#[derive(Debug, Default)]
+pub struct BigramModel {
+    left: HashMap<Box<str>, Successors>,
+    max_left_len: usize,
+}
+
+#[derive(Debug, Default)]
+struct Successors {
+    total: u64,
+    right: HashMap<Box<str>, u32>,
+}
+
+impl BigramModel {
+    /// Absolute discount applied to seen bigrams, reserved for backoff.
+    const DISCOUNT: f64 = 0.5;
+
+    /// Creates an empty model.
+    pub fn new() -> BigramModel {
+        BigramModel::default()
+    }
+    /// Loads a model from a bigram text file.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<BigramModel> {
+        let reader = BufReader::new(File::open(path.as_ref())?);
+        let mut model = BigramModel::new();
+        for (line_no, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut fields = line.split_whitespace();
+            let parsed = match (fields.next(), fields.next(), fields.next(), fields.next()) {
+                (Some(left), Some(right), Some(count), None) => {
+                    count.parse().ok().map(|count| (left, right, count))
+                }
+                _ => None,
+            };
+            let Some((left, right, count)) = parsed else {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("malformed bigram at line {}", line_no + 1),
+                ));
+            };
+            model.insert(left, right, count);
+        }
+        Ok(model)
+    }
+    /// Adds `count` occurrences of `right` following `left`.
+    pub fn insert(&mut self, left: &str, right: &str, count: u32) {
+        if left.is_empty() || right.is_empty() || count == 0 {
+            return;
+        }
+        let successors = self.left.entry(left.into()).or_default();
+        successors.total += count as u64;
+        *successors.right.entry(right.into()).or_default() += count;
+        self.max_left_len = self.max_left_len.max(left.chars().count());
+    }
+    /// Returns the number of occurrences of `right` following `left`.
+    pub fn count(&self, left: &str, right: &str) -> u32 {
+        self.left
+            .get(left)
+            .and_then(|successors| successors.right.get(right))
+            .copied()
+            .unwrap_or_default()
+    }
+    /// Returns true if the model contains no bigrams.
+    pub fn is_empty(&self) -> bool {
+        self.left.is_empty()
+    }
+    /// Returns the discounted log probability of `right` following `left`,
+    /// or `None` if the pair was never seen.
+    pub(crate) fn log_prob(&self, left: &str, right: &str) -> Option<f64> {
+        let successors = self.left.get(left)?;
+        let count = *successors.right.get(right)?;
+        Some(((count as f64 - Self::DISCOUNT) / successors.total as f64).ln())
+    }
+    /// Returns the log weight applied to unigram scores of phrases never
+    /// seen after `left`.
+    pub(crate) fn backoff_weight(&self, left: &str) -> f64 {
+        match self.left.get(left) {
+            Some(successors) => {
+                (Self::DISCOUNT * successors.right.len() as f64 / successors.total as f64).ln()
+            }
+            None => 0.0,
+        }
+    }
+    /// Returns the longest suffix of `text` that has been seen as the left
+    /// phrase of a bigram.
+    fn longest_left_suffix<'a>(&self, text: &'a str) -> Option<&'a str> {
+        let starts: Vec<usize> = text.char_indices().map(|(i, _)| i).collect();
+        starts
+            .iter()
+            .rev()
+            .take(self.max_left_len)
+            .rev()
+            .map(|&i| &text[i..])
+            .find(|suffix| self.left.contains_key(*suffix))
+    }
+}
+
+/// Chewing phrasing rescored with a phrase bigram model.
+///
+/// Candidate phrases are generated the same way as the [`ChewingEngine`].
+/// Paths are then scored with the conditional probability of each phrase
+/// given the previous one. Phrase pairs missing from the [`BigramModel`]
+/// back off to the unigram probability of the phrase, its frequency divided
+/// by the total frequency of the dictionary.
+#[derive(Debug, Default)]
+pub struct BigramEngine {
+    inner: ChewingEngine,
+    model: Arc<BigramModel>,
+    /// The total frequency of the dictionary, if known in advance.
+    unigram_total: Option<f64>,
+    /// The generation of the dictionary and its total frequency, computed
+    /// on first use when not known in advance.
+    scanned_unigram_total: Mutex<Option<(u64, f64)>>,
+}
+
+#[derive(Debug, Clone)]
+struct Hypothesis {
+    log_prob: f64,
+    edges: Vec<Edge>,
+}
+
+impl BigramEngine {
+    const BEAM_WIDTH: usize = 32;
+
+    /// Creates a new conversion engine using the bigram model.
+    pub fn new(model: Arc<BigramModel>) -> BigramEngine {
+        BigramEngine {
+            inner: ChewingEngine::new(),
+            model,
+            unigram_total: None,
+            scanned_unigram_total: Mutex::default(),
+        }
+    }
+    /// Sets the total frequency of the dictionary, used to normalize the
+    /// unigram probabilities of the phrases.
+    ///
+    /// Without it the total is computed by scanning the whole dictionary on
+    /// the first conversion, and again after the
+    /// [generation](Dictionary::generation) of the dictionary changes.
+    pub fn unigram_total(mut self, total: u64) -> BigramEngine {
+        self.unigram_total = Some(total.max(1) as f64);
+        self
+    }
+    /// Replaces the Chewing engine used to find the phrases, keeping its
+    /// options.
+    pub(crate) fn engine(mut self, inner: ChewingEngine) -> BigramEngine {
+        self.inner = inner;
+        self
+    }
+    /// Sets the strategy used to look up the phrases.
+    ///
+    /// See [`ChewingEngine::lookup_strategy`].
+    pub fn lookup_strategy(mut self, lookup_strategy: LookupStrategy) -> BigramEngine {
+        self.inner = self.inner.lookup_strategy(lookup_strategy);
+        self
+    }
+    /// Sets the maximum number of alternative paths to return.
+    ///
+    /// See [`ChewingEngine::max_paths`].
+    pub fn max_paths(mut self, max_paths: usize) -> BigramEngine {
+        self.inner = self.inner.max_paths(max_paths);
+        self
+    }
+    /// Sets the maximum number of syllables of the phrases to look up.
+    ///
+    /// See [`ChewingEngine::max_phrase_len`].
+    pub fn max_phrase_len(mut self, max_phrase_len: usize) -> BigramEngine {
+        self.inner = self.inner.max_phrase_len(max_phrase_len);
+        self
+    }
+    /// Drops the paths scoring much lower than the best path.
+    ///
+    /// See [`ChewingEngine::prune_threshold`].
+    pub fn prune_threshold(mut self, prune_threshold: f64) -> BigramEngine {
+        self.inner = self.inner.prune_threshold(prune_threshold);
+        self
+    }
+    /// Sets the weight of the phrases from the dictionaries of the usage.
+    ///
+    /// See [`ChewingEngine::dictionary_weight`].
+    pub fn dictionary_weight(mut self, usage: DictionaryUsage, weight: f64) -> BigramEngine {
+        self.inner = self.inner.dictionary_weight(usage, weight);
+        self
+    }
+    /// Sets the bonus of the phrases by their length.
+    ///
+    /// See [`ChewingEngine::length_bonus`].
+    pub fn length_bonus(mut self, curve: &[f64]) -> BigramEngine {
+        self.inner = self.inner.length_bonus(curve);
+        self
+    }
+    pub fn convert<'a>(&'a self, dict: &'a dyn Dictionary, comp: &'a Composition) -> Vec<Outcome> {
+        self.convert_with_context(dict, comp, &ConversionContext::new())
+    }
+    pub(crate) fn convert_with_context<'a>(
+        &'a self,
+        dict: &'a dyn Dictionary,
+        comp: &'a Composition,
+        context: &'a ConversionContext,
+    ) -> Vec<Outcome> {
+        let (_, phrases, paths) = self.find_paths(dict, comp, context);
+        if paths.is_empty() {
+            return vec![Outcome::default()];
+        }
+        paths
+            .into_iter()
+            .map(|hypothesis| {
+                let intervals = hypothesis
+                    .edges
+                    .into_iter()
+                    .map(|edge| PossibleInterval {
+                        start: edge.start,
+                        end: edge.end,
+                        phrase: phrases[edge.sn].clone(),
+                    })
+                    .map(|it| it.into())
+                    .fold(vec![], |acc, interval| glue_fn(comp, acc, interval));
+                Outcome {
+                    intervals,
+                    log_prob: hypothesis.log_prob,
+                }
+            })
+            .collect()
+    }
+    pub(crate) fn trace<'a>(
+        &'a self,
+        dict: &'a dyn Dictionary,
+        comp: &'a Composition,
+        context: &'a ConversionContext,
+    ) -> ConversionTrace {
+        let (edges, phrases, paths) = self.find_paths(dict, comp, context);
+        let strategy = self.inner.lookup_strategy;
+        let context_text = context.text();
+        let initial_left = self.model.longest_left_suffix(&context_text);
+        let trace_edges = edges
+            .iter()
+            .map(|edge| {
+                let phrase = &phrases[edge.sn];
+                TraceEdge::new(
+                    dict,
+                    strategy,
+                    comp,
+                    edge.start,
+                    edge.end,
+                    phrase,
+                    phrase.log_prob(),
+                )
+            })
+            .collect();
+        let paths = paths
+            .iter()
+            .map(|hypothesis| {
+                let mut left = initial_left;
+                let edges = hypothesis
+                    .edges
+                    .iter()
+                    .map(|edge| {
+                        let phrase = &phrases[edge.sn];
+                        let log_prob = self.transition_log_prob(dict, comp, left, phrase, edge);
+                        left = match phrase {
+                            PossiblePhrase::Phrase(phrase, _) => Some(phrase.as_str()),
+                            PossiblePhrase::Symbol(_) => None,
+                        };
+                        TraceEdge::new(dict, strategy, comp, edge.start, edge.end, phrase, log_prob)
+                    })
+                    .collect();
+                TracePath {
+                    edges,
+                    log_prob: hypothesis.log_prob,
+                }
+            })
+            .collect();
+        ConversionTrace {
+            edges: trace_edges,
+            paths,
+        }
+    }
+    /// Finds the candidate phrases and the best paths sorted by their
+    /// score, best first.
+    fn find_paths(
+        &self,
+        dict: &dyn Dictionary,
+        comp: &Composition,
+        context: &ConversionContext,
+    ) -> (Vec<Edge>, Vec<PossiblePhrase>, Vec<Hypothesis>) {
+        if comp.is_empty() {
+            return (vec![], vec![], vec![]);
+        }
+        let (edges, phrases) = self.inner.find_edges(dict, comp, context);
+        if edges.is_empty() {
+            return (edges, phrases, vec![]);
+        }
+        let context_text = context.text();
+        let initial_left = self.model.longest_left_suffix(&context_text);
+
+        let mut graph = vec![vec![]; comp.len()];
+        for edge in edges.iter().copied() {
+            graph[edge.start].push(edge);
+        }
+        let mut beams: Vec<Vec<Hypothesis>> = vec![vec![]; comp.len() + 1];
+        beams[0].push(Hypothesis {
+            log_prob: 0.0,
+            edges: vec![],
+        });
+        for position in 0..comp.len() {
+            let mut hypotheses = mem::take(&mut beams[position]);
+            hypotheses.sort_by(|a, b| b.log_prob.total_cmp(&a.log_prob));
+            hypotheses.truncate(Self::BEAM_WIDTH);
+            for hypothesis in hypotheses {
+                let left = match hypothesis.edges.last() {
+                    Some(edge) => match &phrases[edge.sn] {
+                        PossiblePhrase::Phrase(phrase, _) => Some(phrase.as_str()),
+                        PossiblePhrase::Symbol(_) => None,
+                    },
+                    None => initial_left,
+                };
+                for edge in &graph[position] {
+                    let log_prob =
+                        self.transition_log_prob(dict, comp, left, &phrases[edge.sn], edge);
+                    let mut edges = hypothesis.edges.clone();
+                    edges.push(*edge);
+                    beams[edge.end].push(Hypothesis {
+                        log_prob: hypothesis.log_prob + log_prob,
+                        edges,
+                    });
+                }
+            }
+        }
+        let mut paths = mem::take(&mut beams[comp.len()]);
+        paths.sort_by(|a, b| b.log_prob.total_cmp(&a.log_prob));
+        paths.truncate(self.inner.max_paths);
+        if let Some(best) = paths.first().map(|p| p.log_prob) {
+            paths.retain(|p| !self.inner.is_pruned(best, p.log_prob));
+        }
+        trace!("paths: {:#?}", paths);
+        (edges, phrases, paths)
+    }
+    fn transition_log_prob(
+        &self,
+        dict: &dyn Dictionary,
+        comp: &Composition,
+        left: Option<&str>,
+        phrase: &PossiblePhrase,
+        edge: &Edge,
+    ) -> f64 {
+        let PossiblePhrase::Phrase(right, _) = phrase else {
+            return phrase.log_prob();
+        };
+        let bonus = length_log_prob(edge.end - edge.start)
+            + self
+                .inner
+                .edge_bonus(dict, comp, edge.start, edge.end, right.as_str());
+        let unigram_log_prob = || self.unigram_log_prob(dict, right.freq());
+        let Some(left) = left else {
+            return unigram_log_prob() + bonus;
+        };
+        // Both branches are log probabilities of the phrase following left.
+        let log_prob = match self.model.log_prob(left, right.as_str()) {
+            Some(log_prob) => log_prob,
+            None => self.model.backoff_weight(left) + unigram_log_prob(),
+        };
+        log_prob + bonus + user_bigram_log_prob(dict, left, right.as_str())
+    }
+    /// Returns the log probability of a phrase with the frequency in the
+    /// dictionary.
+    fn unigram_log_prob(&self, dict: &dyn Dictionary, freq: u32) -> f64 {
+        let total = match self.unigram_total {
+            Some(total) => total,
+            None => self.scanned_unigram_total(dict),
+        };
+        (freq.max(1) as f64 / total).ln()
+    }
+    fn scanned_unigram_total(&self, dict: &dyn Dictionary) -> f64 {
+        let generation = dict.generation();
+        let mut scanned = self
+            .scanned_unigram_total
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        match *scanned {
+            Some((scanned_generation, total)) if scanned_generation == generation => total,
+            _ => {
+                let total = total_frequency(dict).max(1) as f64;
+                *scanned = Some((generation, total));
+                total
+            }
+        }
+    }
+}
+
+/// Returns the sum of the frequencies of all phrases in the dictionary.
+pub(crate) fn total_frequency(dict: &dyn Dictionary) -> u64 {
+    dict.entries()
+        .map(|(_, phrase)| phrase.freq().max(1) as u64)
+        .sum()
+}
+
+impl ConversionEngine for BigramEngine {
+    fn convert<'a>(&'a self, dict: &'a dyn Dictionary, comp: &'a Composition) -> Vec<Outcome> {
+        BigramEngine::convert(self, dict, comp)
+    }
+    fn convert_with_context<'a>(
+        &'a self,
+        dict: &'a dyn Dictionary,
+        comp: &'a Composition,
+        context: &'a ConversionContext,
+    ) -> Vec<Outcome> {
+        BigramEngine::convert_with_context(self, dict, comp, context)
+    }
+    fn trace<'a>(
+        &'a self,
+        dict: &'a dyn Dictionary,
+        comp: &'a Composition,
+        context: &'a ConversionContext,
+    ) -> Option<ConversionTrace> {
+        Some(BigramEngine::trace(self, dict, comp, context))
+    }
+    fn clear_cache(&self) {
+        self.inner.clear_cache();
+        *self
+            .scanned_unigram_total
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{error::Error, fs, sync::Arc};
+
+    use tempfile::NamedTempFile;
+
+    use super::{BigramEngine, BigramModel};
+    use crate::{
+        conversion::{ChewingEngine, Composition, ConversionContext, Symbol},
+        dictionary::{Dictionary, TrieBuf},
+        syl,
+        zhuyin::Bopomofo::*,
+    };
+
+    fn test_dictionary() -> impl Dictionary {
+        TrieBuf::from([
+            (vec![syl![M, AN, TONE4]], vec![("慢", 500)]),
+            (
+                vec![syl![M, AN, TONE4], syl![M, AN, TONE4]],
+                vec![("慢慢", 300)],
+            ),
+            (
+                vec![syl![D, E, TONE5]],
+                vec![("的", 10000), ("地", 500), ("得", 300)],
+            ),
+            (vec![syl![P, AU, TONE3]], vec![("跑", 200)]),
+        ])
+    }
+
+    fn composition(syllables: &[crate::zhuyin::Syllable]) -> Composition {
+        let mut composition = Composition::new();
+        for &syl in syllables {
+            composition.push(Symbol::from(syl));
+        }
+        composition
+    }
+
+    fn texts(outcome: &super::Outcome) -> Vec<&str> {
+        outcome.intervals.iter().map(|it| &*it.text).collect()
+    }
+
+    #[test]
+    fn load_bigram_file() -> Result<(), Box<dyn Error>> {
+        let file = NamedTempFile::new()?;
+        let path = file.into_temp_path();
+        fs::write(&path, "# comment\n慢慢 地 120\n\n跑 得 98\n跑 得 2\n")?;
+
+        let model = BigramModel::open(&path)?;
+        assert_eq!(120, model.count("慢慢", "地"));
+        assert_eq!(100, model.count("跑", "得"));
+        assert_eq!(0, model.count("跑", "地"));
+
+        fs::write(&path, "慢慢 地\n")?;
+        assert!(BigramModel::open(&path).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn backoff_to_unigram_without_bigrams() {
+        let dict = test_dictionary();
+        let comp = composition(&[syl![M, AN, TONE4], syl![M, AN, TONE4], syl![D, E, TONE5]]);
+        let bigram = BigramEngine::new(Arc::new(BigramModel::new()));
+        let chewing = ChewingEngine::new();
+        assert_eq!(
+            texts(&chewing.convert(&dict, &comp)[0]),
+            texts(&bigram.convert(&dict, &comp)[0])
+        );
+        assert_eq!(vec!["慢慢", "的"], texts(&bigram.convert(&dict, &comp)[0]));
+    }
+
+    #[test]
+    fn resolve_particle_from_previous_phrase() {
+        let dict = test_dictionary();
+        let mut model = BigramModel::new();
+        model.insert("慢慢", "地", 120);
+        model.insert("慢慢", "的", 10);
+        model.insert("跑", "得", 98);
+        model.insert("跑", "的", 2);
+        let engine = BigramEngine::new(Arc::new(model));
+
+        let comp = composition(&[syl![M, AN, TONE4], syl![M, AN, TONE4], syl![D, E, TONE5]]);
+        assert_eq!(vec!["慢慢", "地"], texts(&engine.convert(&dict, &comp)[0]));
+
+        let comp = composition(&[syl![P, AU, TONE3], syl![D, E, TONE5]]);
+        assert_eq!(vec!["跑", "得"], texts(&engine.convert(&dict, &comp)[0]));
+    }
+
+    #[test]
+    fn frequent_unseen_phrase_beats_rare_seen_pair() {
+        let dict = TrieBuf::from([
+            (vec![syl![P, AU, TONE3]], vec![("跑", 200)]),
+            (vec![syl![D, E, TONE5]], vec![("地", 9000), ("得", 300)]),
+        ]);
+        let mut model = BigramModel::new();
+        model.insert("跑", "得", 1);
+        model.insert("跑", "步", 99);
+        let engine = BigramEngine::new(Arc::new(model));
+
+        let comp = composition(&[syl![P, AU, TONE3], syl![D, E, TONE5]]);
+        assert_eq!(vec!["跑", "地"], texts(&engine.convert(&dict, &comp)[0]));
+    }
+
+    #[test]
+    fn rescan_unigram_total_after_dictionary_change() {
+        let mut dict = TrieBuf::from([(vec![syl![P, AU, TONE3]], vec![("跑", 200)])]);
+        let engine = BigramEngine::new(Arc::new(BigramModel::new()));
+        let comp = composition(&[syl![P, AU, TONE3]]);
+        let log_prob = engine.convert(&dict, &comp)[0].log_prob;
+
+        dict.add_phrase(&[syl![D, E, TONE5]], ("的", 200).into())
+            .unwrap();
+        assert!(engine.convert(&dict, &comp)[0].log_prob < log_prob);
+
+        // A known total is used without scanning.
+        let engine = BigramEngine::new(Arc::new(BigramModel::new())).unigram_total(400);
+        assert_eq!(
+            engine.convert(&dict, &comp)[0].log_prob,
+            BigramEngine::new(Arc::new(BigramModel::new())).convert(&dict, &comp)[0].log_prob
+        );
+    }
+
+    #[test]
+    fn use_left_context_as_previous_phrase() {
+        let dict = test_dictionary();
+        let mut model = BigramModel::new();
+        model.insert("跑", "得", 98);
+        let engine = BigramEngine::new(Arc::new(model));
+
+        let comp = composition(&[syl![D, E, TONE5]]);
+        assert_eq!(vec!["的"], texts(&engine.convert(&dict, &comp)[0]));
+
+        let mut context = ConversionContext::new();
+        context.set_text("他跑");
+        assert_eq!(
+            vec!["得"],
+            texts(&engine.convert_with_context(&dict, &comp, &context)[0])
+        );
+    }
+}
@@ -1,51 +1,228 @@
 use std::{
-    collections::{BinaryHeap, HashSet},
+    collections::{BinaryHeap, HashMap, HashSet},
     fmt::{Debug, Display, Write},
+    sync::{Mutex, PoisonError},
 };
 
 use log::trace;
 
-use super::{Composition, ConversionEngine, Gap, Interval, Outcome, Symbol};
+use super::{
+    Composition, ConversionContext, ConversionEngine, ConversionTrace, Gap, Interval, Outcome,
+    Symbol, TraceEdge, TracePath,
+};
 use crate::{
-    dictionary::{Dictionary, LookupStrategy, Phrase},
+    dictionary::{Dictionary, DictionaryUsage, LookupStrategy, Phrase},
     zhuyin::Syllable,
 };
 
 /// The default Chewing conversion method.
-#[derive(Debug, Default)]
+///
+/// The k-best search can be tuned with [`max_paths`](Self::max_paths),
+/// [`max_phrase_len`](Self::max_phrase_len), and
+/// [`prune_threshold`](Self::prune_threshold). The scores of the phrases
+/// can be tuned with [`dictionary_weight`](Self::dictionary_weight) and
+/// [`length_bonus`](Self::length_bonus).
+///
+/// Dictionary lookups are cached by syllables between conversions, so
+/// converting the composition again after an edit only looks up the
+/// affected syllables. The cache is dropped when the
+/// [generation](crate::dictionary::Dictionary::generation) of the
+/// dictionary changes.
+///
+/// # Examples
+///
+/// ```
+/// use chewing::conversion::ChewingEngine;
+///
+/// let engine = ChewingEngine::new().max_paths(20).max_phrase_len(6);
+/// ```
+///
+/// Prefer the phrases from a domain dictionary and longer phrases.
+///
+/// ```
+/// use chewing::{conversion::ChewingEngine, dictionary::DictionaryUsage};
+///
+/// let engine = ChewingEngine::new()
+///     .dictionary_weight(DictionaryUsage::Custom, 2.0)
+///     .length_bonus(&[0.0, 0.2, 0.4]);
+/// ```
+#[derive(Debug)]
 pub struct ChewingEngine {
     pub(crate) lookup_strategy: LookupStrategy,
+    pub(super) max_paths: usize,
+    max_phrase_len: usize,
+    prune_threshold: f64,
+    /// The log of the weights of the dictionary usages.
+    dictionary_weights: Vec<(DictionaryUsage, f64)>,
+    length_bonus: Vec<f64>,
+    cache: Mutex<LookupCache>,
+}
+
+impl Default for ChewingEngine {
+    fn default() -> Self {
+        ChewingEngine::new()
+    }
 }
 
 impl ChewingEngine {
-    const MAX_OUT_PATHS: usize = 10;
+    /// The default number of alternative paths.
+    pub const DEFAULT_MAX_PATHS: usize = 10;
+    /// The default maximum number of syllables of a phrase.
+    pub const DEFAULT_MAX_PHRASE_LEN: usize = 11;
+    /// The maximum number of committed characters used as context.
+    pub(crate) const MAX_CONTEXT_LEN: usize = 3;
+    /// The log probability penalty of the phrases only matched through
+    /// phonetic confusions.
+    const CONFUSION_PENALTY: f64 = -2.3;
     /// Creates a new conversion engine.
     pub fn new() -> ChewingEngine {
         ChewingEngine {
             lookup_strategy: LookupStrategy::Standard,
+            max_paths: Self::DEFAULT_MAX_PATHS,
+            max_phrase_len: Self::DEFAULT_MAX_PHRASE_LEN,
+            prune_threshold: 0.0,
+            dictionary_weights: vec![],
+            length_bonus: vec![],
+            cache: Mutex::new(LookupCache::default()),
         }
     }
+    /// Sets the strategy used to look up the phrases.
+    ///
+    /// With [`LookupStrategy::Toneless`] syllables without a tone are
+    /// converted with all tones of the syllable, ranked by the phrase
+    /// frequency and the context. With [`LookupStrategy::Confusable`] the
+    /// phrases only matched through phonetic confusions are penalized, so
+    /// exact matches are preferred.
+    pub fn lookup_strategy(mut self, lookup_strategy: LookupStrategy) -> ChewingEngine {
+        self.lookup_strategy = lookup_strategy;
+        self
+    }
+    /// Sets the maximum number of alternative paths to search.
+    ///
+    /// At least one path is always returned.
+    pub fn max_paths(mut self, max_paths: usize) -> ChewingEngine {
+        self.max_paths = max_paths.max(1);
+        self
+    }
+    /// Sets the maximum number of syllables of the phrases to look up.
+    ///
+    /// User selections longer than this are still honored.
+    pub fn max_phrase_len(mut self, max_phrase_len: usize) -> ChewingEngine {
+        self.max_phrase_len = max_phrase_len.max(1);
+        self
+    }
+    /// Drops the paths scoring lower than the best path by more than the
+    /// threshold, in log probability.
+    ///
+    /// A threshold of 0 disables pruning.
+    pub fn prune_threshold(mut self, prune_threshold: f64) -> ChewingEngine {
+        self.prune_threshold = prune_threshold.max(0.0);
+        self
+    }
+    /// Sets the weight of the phrases from the dictionaries of the usage.
+    ///
+    /// The probability of a phrase is multiplied by the weight of the
+    /// dictionary providing it, so phrases from a dictionary with a weight
+    /// above 1 win the ties against phrases from other dictionaries. The
+    /// default weight is 1.
+    pub fn dictionary_weight(mut self, usage: DictionaryUsage, weight: f64) -> ChewingEngine {
+        let log_weight = weight.max(f64::MIN_POSITIVE).ln();
+        self.dictionary_weights.retain(|(it, _)| *it != usage);
+        if log_weight != 0.0 {
+            self.dictionary_weights.push((usage, log_weight));
+        }
+        self
+    }
+    /// Sets the bonus of the phrases by their length.
+    ///
+    /// The n-th value is added to the log probability of the phrases with
+    /// n syllables. The last value also applies to longer phrases. The
+    /// default is no bonus.
+    pub fn length_bonus(mut self, curve: &[f64]) -> ChewingEngine {
+        self.length_bonus = curve.to_vec();
+        self
+    }
+    /// Returns the bonus of a phrase from the dictionary weights and the
+    /// length bonus curve.
+    fn phrase_bonus(&self, usage: Option<DictionaryUsage>, len: usize) -> f64 {
+        let weight = usage
+            .and_then(|usage| self.dictionary_weights.iter().find(|(it, _)| *it == usage))
+            .map_or(0.0, |(_, weight)| *weight);
+        let length = self
+            .length_bonus
+            .get(len.saturating_sub(1))
+            .or(self.length_bonus.last())
+            .copied()
+            .unwrap_or(0.0);
+        weight + length
+    }
+    /// Returns the bonus of a looked up phrase, including the penalty of
+    /// phrases only matched through phonetic confusions.
+    fn cached_phrase_bonus(&self, cached: &CachedPhrase, len: usize) -> f64 {
+        let penalty = if cached.exact {
+            0.0
+        } else {
+            Self::CONFUSION_PENALTY
+        };
+        self.phrase_bonus(cached.usage, len) + penalty
+    }
+    /// Returns the bonus of the phrase covering `start..end` of the
+    /// composition, like the bonus added when the phrase was looked up.
+    pub(super) fn edge_bonus(
+        &self,
+        dict: &dyn Dictionary,
+        comp: &Composition,
+        start: usize,
+        end: usize,
+        phrase: &str,
+    ) -> f64 {
+        let len = end - start;
+        let confusable = matches!(self.lookup_strategy, LookupStrategy::Confusable(_));
+        if self.dictionary_weights.is_empty() && !confusable {
+            return self.phrase_bonus(None, len);
+        }
+        let Some(syllables) = comp.symbols()[start..end]
+            .iter()
+            .map(|sym| sym.to_syllable())
+            .collect::<Option<Vec<Syllable>>>()
+        else {
+            return self.phrase_bonus(None, len);
+        };
+        let cache = self.cache.lock().unwrap_or_else(PoisonError::into_inner);
+        match cache
+            .get(dict, &syllables)
+            .and_then(|phrases| phrases.iter().find(|it| it.phrase.as_str() == phrase))
+        {
+            Some(cached) => self.cached_phrase_bonus(cached, len),
+            None => {
+                let usage = dict
+                    .lookup_source(&syllables, phrase, self.lookup_strategy)
+                    .map(|info| info.usage);
+                self.phrase_bonus(usage, len)
+            }
+        }
+    }
+    /// Returns whether a path scoring `log_prob` should be dropped.
+    pub(super) fn is_pruned(&self, best_log_prob: f64, log_prob: f64) -> bool {
+        self.prune_threshold > 0.0 && best_log_prob - log_prob > self.prune_threshold
+    }
     pub(crate) fn convert<'a>(
         &'a self,
         dict: &'a dyn Dictionary,
         comp: &'a Composition,
     ) -> Vec<Outcome> {
-        let paths = {
-            if comp.is_empty() {
-                return vec![Outcome::default()];
-            }
-            let (edges, phrases) = self.find_edges(dict, comp);
-            if edges.is_empty() {
-                return vec![Outcome::default()];
-            }
-            let mut paths = self.find_k_paths(Self::MAX_OUT_PATHS, comp.len(), edges, &phrases);
-            trace!("paths: {:#?}", paths);
-            debug_assert!(!paths.is_empty());
-
-            // TODO: Reranking
-            paths.sort_by(|a, b| b.cmp(a));
-            paths
-        };
+        self.convert_with_context(dict, comp, &ConversionContext::new())
+    }
+    pub(crate) fn convert_with_context<'a>(
+        &'a self,
+        dict: &'a dyn Dictionary,
+        comp: &'a Composition,
+        context: &'a ConversionContext,
+    ) -> Vec<Outcome> {
+        let (_, _, paths) = self.find_paths(dict, comp, context);
+        if paths.is_empty() {
+            return vec![Outcome::default()];
+        }
         paths
             .into_iter()
             .map(|p| {
@@ -62,15 +239,121 @@ impl ChewingEngine {
             })
             .collect()
     }
+    pub(crate) fn trace<'a>(
+        &'a self,
+        dict: &'a dyn Dictionary,
+        comp: &'a Composition,
+        context: &'a ConversionContext,
+    ) -> ConversionTrace {
+        let (edges, phrases, paths) = self.find_paths(dict, comp, context);
+        let trace_edge = |start, end, phrase: &PossiblePhrase, log_prob| {
+            TraceEdge::new(
+                dict,
+                self.lookup_strategy,
+                comp,
+                start,
+                end,
+                phrase,
+                log_prob,
+            )
+        };
+        let edges = edges
+            .iter()
+            .map(|edge| {
+                let phrase = &phrases[edge.sn];
+                trace_edge(edge.start, edge.end, phrase, phrase.log_prob())
+            })
+            .collect();
+        let paths = paths
+            .iter()
+            .map(|path| {
+                let mut left: Option<&PossiblePhrase> = None;
+                let edges = path
+                    .intervals
+                    .iter()
+                    .map(|it| {
+                        let mut log_prob = it.phrase.log_prob();
+                        if let (
+                            Some(PossiblePhrase::Phrase(left, _)),
+                            PossiblePhrase::Phrase(right, _),
+                        ) = (left, &it.phrase)
+                        {
+                            log_prob += user_bigram_log_prob(dict, left.as_str(), right.as_str());
+                        }
+                        left = Some(&it.phrase);
+                        trace_edge(it.start, it.end, &it.phrase, log_prob)
+                    })
+                    .collect();
+                TracePath {
+                    edges,
+                    log_prob: path.total_probability(),
+                }
+            })
+            .collect();
+        ConversionTrace { edges, paths }
+    }
+    /// Finds the candidate phrases and the best paths sorted by their
+    /// score, best first.
+    fn find_paths(
+        &self,
+        dict: &dyn Dictionary,
+        comp: &Composition,
+        context: &ConversionContext,
+    ) -> (Vec<Edge>, Vec<PossiblePhrase>, Vec<PossiblePath>) {
+        if comp.is_empty() {
+            return (vec![], vec![], vec![]);
+        }
+        let (edges, phrases) = self.find_edges(dict, comp, context);
+        if edges.is_empty() {
+            return (edges, phrases, vec![]);
+        }
+        let mut paths = self.find_k_paths(self.max_paths, comp.len(), &edges, &phrases);
+        for path in paths.iter_mut() {
+            path.score_user_bigrams(dict);
+        }
+        trace!("paths: {:#?}", paths);
+
+        paths.sort_by(|a, b| b.cmp(a));
+        if let Some(best) = paths.first().map(|p| p.total_probability()) {
+            paths.retain(|p| !self.is_pruned(best, p.total_probability()));
+        }
+        (edges, phrases, paths)
+    }
 }
 
 impl ConversionEngine for ChewingEngine {
     fn convert<'a>(&'a self, dict: &'a dyn Dictionary, comp: &'a Composition) -> Vec<Outcome> {
         ChewingEngine::convert(self, dict, comp)
     }
+    fn convert_with_context<'a>(
+        &'a self,
+        dict: &'a dyn Dictionary,
+        comp: &'a Composition,
+        context: &'a ConversionContext,
+    ) -> Vec<Outcome> {
+        ChewingEngine::convert_with_context(self, dict, comp, context)
+    }
+    fn trace<'a>(
+        &'a self,
+        dict: &'a dyn Dictionary,
+        comp: &'a Composition,
+        context: &'a ConversionContext,
+    ) -> Option<ConversionTrace> {
+        Some(ChewingEngine::trace(self, dict, comp, context))
+    }
+    fn clear_cache(&self) {
+        self.cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
 }
 
-fn glue_fn(com: &Composition, mut acc: Vec<Interval>, interval: Interval) -> Vec<Interval> {
+pub(super) fn glue_fn(
+    com: &Composition,
+    mut acc: Vec<Interval>,
+    interval: Interval,
+) -> Vec<Interval> {
     if acc.is_empty() {
         acc.push(interval);
         return acc;
@@ -100,9 +383,11 @@ impl ChewingEngine {
     fn find_best_phrases<D: Dictionary + ?Sized>(
         &self,
         dict: &D,
+        cache: &mut LookupCache,
         start: usize,
         symbols: &[Symbol],
         com: &Composition,
+        context: &ConversionContext,
     ) -> Vec<PossiblePhrase> {
         let end = start + symbols.len();
 
@@ -142,46 +427,62 @@ impl ChewingEngine {
             .collect();
 
         let max_phrases_count = 10;
-        // Approximate value. We only use this global for scaling for now, so we can
-        // use any value.
-        let global_total: f64 = 1_000_000_000.0;
-        let mut phrases = dict
-            .lookup(&syllables, self.lookup_strategy)
-            .into_iter()
-            .filter(|phrase| {
-                // If there exists a user selected interval which is a
-                // sub-interval of this phrase but the substring is
-                // different then we can skip this phrase.
-                for selection in &com.selections {
-                    debug_assert!(!selection.text.is_empty());
-                    if start <= selection.start && end >= selection.end {
-                        let offset = selection.start - start;
-                        let len = selection.end - selection.start;
-                        let substring: String =
-                            phrase.as_str().chars().skip(offset).take(len).collect();
-                        if substring != selection.text.as_ref() {
-                            return false;
-                        }
+        let matches_selections = |phrase: &str| {
+            // If there exists a user selected interval which is a
+            // sub-interval of this phrase but the substring is
+            // different then we can skip this phrase.
+            for selection in &com.selections {
+                debug_assert!(!selection.text.is_empty());
+                if start <= selection.start && end >= selection.end {
+                    let offset = selection.start - start;
+                    let len = selection.end - selection.start;
+                    let substring: String = phrase.chars().skip(offset).take(len).collect();
+                    if substring != selection.text.as_ref() {
+                        return false;
                     }
                 }
-                true
-            })
-            .map(|phrase| {
-                let log_phrase_prob = (phrase.freq().clamp(1, 9999999) as f64 / global_total).ln();
-                let log_length_prob: f64 = match syllables.len() {
-                    // log probability of phrase lenght calculated from tsi.src
-                    1 => -1.520439227173415,
-                    2 => -0.4236568120124837,
-                    3 => -1.455835986003893,
-                    4 => -1.6178072894679227,
-                    5 => -4.425765184802149,
-                    _ => -4.787357595622411,
-                };
-                let log_prob = log_phrase_prob + log_length_prob;
-                debug_assert!(log_prob.is_normal());
-                PossiblePhrase::Phrase(phrase, log_prob)
+            }
+            true
+        };
+        let with_usage = !self.dictionary_weights.is_empty();
+        let mut phrases = cache
+            .lookup(dict, &syllables, self.lookup_strategy, with_usage)
+            .into_iter()
+            .filter(|cached| matches_selections(cached.phrase.as_str()))
+            .map(|cached| {
+                let log_prob = phrase_log_prob(cached.phrase.freq(), syllables.len())
+                    + self.cached_phrase_bonus(&cached, syllables.len());
+                PossiblePhrase::Phrase(cached.phrase, log_prob)
             })
             .collect::<Vec<_>>();
+        if start == 0 {
+            // Candidates continuing the committed text before the composition
+            // score as the whole phrase. Only the candidates in the
+            // dictionary are raised, the rest of a phrase is not a word.
+            let (context_text, context_syllables) = context.known_suffix(Self::MAX_CONTEXT_LEN);
+            let context_chars: Vec<char> = context_text.chars().collect();
+            for k in 1..=context_syllables.len() {
+                let prefix: String = context_chars[context_chars.len() - k..].iter().collect();
+                let mut lookup = context_syllables[context_syllables.len() - k..].to_vec();
+                lookup.extend_from_slice(&syllables);
+                for cached in cache.lookup(dict, &lookup, self.lookup_strategy, with_usage) {
+                    let phrase = &cached.phrase;
+                    let Some(rest) = phrase.as_str().strip_prefix(prefix.as_str()) else {
+                        continue;
+                    };
+                    if rest.chars().count() != syllables.len() || !matches_selections(rest) {
+                        continue;
+                    }
+                    let log_prob = phrase_log_prob(phrase.freq(), lookup.len())
+                        + self.cached_phrase_bonus(&cached, lookup.len());
+                    if let Some(PossiblePhrase::Phrase(_, prob)) =
+                        phrases.iter_mut().find(|p| p.to_string() == rest)
+                    {
+                        *prob = prob.max(log_prob);
+                    }
+                }
+            }
+        }
         phrases.sort_by(|a, b| f64::total_cmp(&-a.log_prob(), &-b.log_prob()));
         phrases.truncate(max_phrases_count);
 
@@ -201,17 +502,35 @@ impl ChewingEngine {
         trace!("best phraces for {:?} is {:?}", symbols, phrases);
         phrases
     }
-    fn find_edges<D: Dictionary + ?Sized>(
+    pub(super) fn find_edges<D: Dictionary + ?Sized>(
         &self,
         dict: &D,
         com: &Composition,
+        context: &ConversionContext,
     ) -> (Vec<Edge>, Vec<PossiblePhrase>) {
+        let mut cache = self.cache.lock().unwrap_or_else(PoisonError::into_inner);
+        cache.begin(dict);
         let mut sn = 0;
         let mut edges = vec![];
         let mut phrases = vec![];
         for start in 0..com.symbols.len() {
             for end in (start + 1)..=com.symbols.len() {
-                for phrase in self.find_best_phrases(dict, start, &com.symbols[start..end], com) {
+                if end - start > self.max_phrase_len
+                    && !com
+                        .selections
+                        .iter()
+                        .any(|sel| sel.start == start && sel.end == end)
+                {
+                    continue;
+                }
+                for phrase in self.find_best_phrases(
+                    dict,
+                    &mut cache,
+                    start,
+                    &com.symbols[start..end],
+                    com,
+                    context,
+                ) {
                     edges.push(Edge {
                         start,
                         end,
@@ -223,6 +542,7 @@ impl ChewingEngine {
                 }
             }
         }
+        cache.end();
         (edges, phrases)
     }
 
@@ -236,7 +556,7 @@ impl ChewingEngine {
         &self,
         k: usize,
         len: usize,
-        edges: Vec<Edge>,
+        edges: &[Edge],
         phrases: &[PossiblePhrase],
     ) -> Vec<PossiblePath> {
         let mut ksp = Vec::with_capacity(k);
@@ -244,10 +564,14 @@ impl ChewingEngine {
         let mut graph = vec![vec![]; len];
         let mut removed_edges = HashSet::new();
 
-        for edge in edges.into_iter() {
+        for edge in edges.iter().copied() {
             graph[edge.start].push(edge);
         }
-        ksp.push(self.shortest_path(&graph, &removed_edges, 0, len).unwrap());
+        let Some(shortest_path) = self.shortest_path(&graph, &removed_edges, 0, len) else {
+            // Some symbols are not covered by any phrase.
+            return vec![];
+        };
+        ksp.push(shortest_path);
 
         for kth in 1..k {
             let prev = kth - 1;
@@ -292,7 +616,10 @@ impl ChewingEngine {
                     })
                     .collect()
             })
-            .map(|intervals| PossiblePath { intervals })
+            .map(|intervals| PossiblePath {
+                intervals,
+                user_bigram_log_prob: 0.0,
+            })
             .collect()
     }
 
@@ -347,14 +674,47 @@ impl ChewingEngine {
     }
 }
 
+fn phrase_log_prob(freq: u32, len: usize) -> f64 {
+    // Approximate value. We only use this global for scaling for now, so we can
+    // use any value.
+    let global_total: f64 = 1_000_000_000.0;
+    let log_phrase_prob = (freq.clamp(1, 9999999) as f64 / global_total).ln();
+    let log_prob = log_phrase_prob + length_log_prob(len);
+    debug_assert!(log_prob.is_normal());
+    log_prob
+}
+
+/// Returns the bonus of a phrase pair learned from the user's committed
+/// text.
+pub(super) fn user_bigram_log_prob(dict: &dyn Dictionary, left: &str, right: &str) -> f64 {
+    // Scaled so that a pair committed twice outweighs a 20x difference in
+    // phrase frequency.
+    const USER_BIGRAM_WEIGHT: f64 = 3.0;
+    dict.lookup_bigram(left, right).map_or(0.0, |(count, _)| {
+        USER_BIGRAM_WEIGHT * (count as f64).ln_1p()
+    })
+}
+
+pub(super) fn length_log_prob(len: usize) -> f64 {
+    match len {
+        // log probability of phrase lenght calculated from tsi.src
+        1 => -1.520439227173415,
+        2 => -0.4236568120124837,
+        3 => -1.455835986003893,
+        4 => -1.6178072894679227,
+        5 => -4.425765184802149,
+        _ => -4.787357595622411,
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
-enum PossiblePhrase {
+pub(super) enum PossiblePhrase {
     Symbol(char),
     Phrase(Phrase, f64),
 }
 
 impl PossiblePhrase {
-    fn log_prob(&self) -> f64 {
+    pub(super) fn log_prob(&self) -> f64 {
         match self {
             PossiblePhrase::Symbol(_) => 0.0,
             PossiblePhrase::Phrase(_, log_prob) => *log_prob,
@@ -380,6 +740,108 @@ impl From<PossiblePhrase> for Box<str> {
     }
 }
 
+/// Dictionary lookups of the syllable sequences in the last conversion.
+///
+/// Entries not used by a conversion are dropped at the end of it, so the
+/// cache only holds the sub-sequences of the current composition. All
+/// entries are dropped when the [generation](Dictionary::generation) of the
+/// dictionary changes.
+#[derive(Default)]
+struct LookupCache {
+    dict_generation: u64,
+    generation: u64,
+    entries: HashMap<Vec<Syllable>, (u64, Vec<CachedPhrase>)>,
+}
+
+/// A looked up phrase.
+#[derive(Debug, Clone)]
+struct CachedPhrase {
+    phrase: Phrase,
+    /// The usage of the dictionary providing the phrase.
+    usage: Option<DictionaryUsage>,
+    /// Whether the phrase matches the syllables without phonetic
+    /// confusions.
+    exact: bool,
+}
+
+impl LookupCache {
+    fn begin<D: Dictionary + ?Sized>(&mut self, dict: &D) {
+        let dict_generation = dict.generation();
+        if self.dict_generation != dict_generation {
+            self.entries.clear();
+            self.dict_generation = dict_generation;
+        }
+        self.generation += 1;
+    }
+    /// Looks up the phrases, and the usage of the dictionaries providing
+    /// them if `with_usage` is true.
+    fn lookup<D: Dictionary + ?Sized>(
+        &mut self,
+        dict: &D,
+        syllables: &[Syllable],
+        strategy: LookupStrategy,
+        with_usage: bool,
+    ) -> Vec<CachedPhrase> {
+        let generation = self.generation;
+        if let Some((used, phrases)) = self.entries.get_mut(syllables) {
+            *used = generation;
+            return phrases.clone();
+        }
+        let exact_phrases = match strategy {
+            LookupStrategy::Confusable(_) => Some(dict.lookup(syllables, LookupStrategy::Standard)),
+            _ => None,
+        };
+        let phrases: Vec<CachedPhrase> = dict
+            .lookup(syllables, strategy)
+            .into_iter()
+            .map(|phrase| {
+                let usage = with_usage
+                    .then(|| dict.lookup_source(syllables, phrase.as_str(), strategy))
+                    .flatten()
+                    .map(|info| info.usage);
+                let exact = exact_phrases
+                    .as_ref()
+                    .is_none_or(|exact| exact.iter().any(|it| it.as_str() == phrase.as_str()));
+                CachedPhrase {
+                    phrase,
+                    usage,
+                    exact,
+                }
+            })
+            .collect();
+        self.entries
+            .insert(syllables.to_vec(), (generation, phrases.clone()));
+        phrases
+    }
+    fn get<D: Dictionary + ?Sized>(
+        &self,
+        dict: &D,
+        syllables: &[Syllable],
+    ) -> Option<&[CachedPhrase]> {
+        if self.dict_generation != dict.generation() {
+            return None;
+        }
+        self.entries
+            .get(syllables)
+            .map(|(_, phrases)| phrases.as_slice())
+    }
+    fn end(&mut self) {
+        let generation = self.generation;
+        self.entries.retain(|_, (used, _)| *used == generation);
+    }
+    fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+impl Debug for LookupCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LookupCache")
+            .field("entries", &self.entries.len())
+            .finish()
+    }
+}
+
 #[derive(Debug, Copy, Clone)]
 struct FrontierNode {
     position: usize,
@@ -407,11 +869,11 @@ impl PartialEq for FrontierNode {
 }
 
 #[derive(Debug, Copy, Clone)]
-struct Edge {
-    start: usize,
-    end: usize,
-    sn: usize,
-    cost: f64,
+pub(super) struct Edge {
+    pub(super) start: usize,
+    pub(super) end: usize,
+    pub(super) sn: usize,
+    pub(super) cost: f64,
 }
 
 impl PartialEq for Edge {
@@ -421,10 +883,10 @@ impl PartialEq for Edge {
 }
 
 #[derive(Clone, PartialEq)]
-struct PossibleInterval {
-    start: usize,
-    end: usize,
-    phrase: PossiblePhrase,
+pub(super) struct PossibleInterval {
+    pub(super) start: usize,
+    pub(super) end: usize,
+    pub(super) phrase: PossiblePhrase,
 }
 
 impl Debug for PossibleInterval {
@@ -453,6 +915,7 @@ impl From<PossibleInterval> for Interval {
 #[derive(Default, Clone)]
 struct PossiblePath {
     intervals: Vec<PossibleInterval>,
+    user_bigram_log_prob: f64,
 }
 
 impl Debug for PossiblePath {
@@ -466,10 +929,22 @@ impl Debug for PossiblePath {
 
 impl PossiblePath {
     fn total_probability(&self) -> f64 {
-        let prob = self.phrase_log_probability();
+        let prob = self.phrase_log_probability() + self.user_bigram_log_prob;
         debug_assert!(!prob.is_nan());
         prob
     }
+    fn score_user_bigrams(&mut self, dict: &dyn Dictionary) {
+        self.user_bigram_log_prob = self
+            .intervals
+            .windows(2)
+            .map(|pair| match (&pair[0].phrase, &pair[1].phrase) {
+                (PossiblePhrase::Phrase(left, _), PossiblePhrase::Phrase(right, _)) => {
+                    user_bigram_log_prob(dict, left.as_str(), right.as_str())
+                }
+                _ => 0.0,
+            })
+            .sum();
+    }
     fn phrase_log_probability(&self) -> f64 {
         self.intervals.iter().map(|it| it.phrase.log_prob()).sum()
     }
@@ -513,16 +988,68 @@ impl Display for PossiblePath {
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashSet;
+    use std::{
+        cell::Cell,
+        collections::HashSet,
+        error::Error,
+        io::{Cursor, Seek},
+        path::Path,
+    };
 
     use super::ChewingEngine;
     use crate::{
-        conversion::{Composition, Gap, Interval, Outcome, Symbol, chewing::Edge},
-        dictionary::{Dictionary, TrieBuf},
+        conversion::{
+            Composition, ConversionContext, Gap, Interval, Outcome, Symbol, chewing::Edge,
+        },
+        dictionary::{
+            Dictionary, DictionaryBuilder, DictionaryInfo, DictionaryUsage, Entries, Layered,
+            LookupStrategy, Phrase, Trie, TrieBuf, TrieBuilder, UpdateDictionaryError,
+        },
         syl,
-        zhuyin::Bopomofo::*,
+        zhuyin::{Bopomofo::*, Syllable},
     };
 
+    #[derive(Debug)]
+    struct CountingDictionary {
+        inner: TrieBuf,
+        lookups: Cell<usize>,
+    }
+
+    impl CountingDictionary {
+        fn take_lookups(&self) -> usize {
+            self.lookups.replace(0)
+        }
+    }
+
+    impl Dictionary for CountingDictionary {
+        fn lookup(&self, syllables: &[Syllable], strategy: LookupStrategy) -> Vec<Phrase> {
+            self.lookups.set(self.lookups.get() + 1);
+            self.inner.lookup(syllables, strategy)
+        }
+        fn entries(&self) -> Entries<'_> {
+            self.inner.entries()
+        }
+        fn about(&self) -> DictionaryInfo {
+            self.inner.about()
+        }
+        fn path(&self) -> Option<&Path> {
+            self.inner.path()
+        }
+        fn set_usage(&mut self, usage: DictionaryUsage) {
+            self.inner.set_usage(usage);
+        }
+        fn generation(&self) -> u64 {
+            self.inner.generation()
+        }
+        fn add_phrase(
+            &mut self,
+            syllables: &[Syllable],
+            phrase: Phrase,
+        ) -> Result<(), UpdateDictionaryError> {
+            self.inner.add_phrase(syllables, phrase)
+        }
+    }
+
     fn test_dictionary() -> impl Dictionary {
         TrieBuf::from([
             (vec![syl![G, U, O, TONE2]], vec![("國", 1)]),
@@ -676,6 +1203,125 @@ mod tests {
         );
     }
 
+    #[test]
+    fn convert_with_user_bigram() {
+        let mut dict = TrieBuf::from([
+            (vec![syl![M, AN, TONE4]], vec![("慢", 500)]),
+            (
+                vec![syl![M, AN, TONE4], syl![M, AN, TONE4]],
+                vec![("慢慢", 300)],
+            ),
+            (vec![syl![D, E, TONE5]], vec![("的", 10000), ("地", 500)]),
+        ]);
+        let engine = ChewingEngine::new();
+        let mut composition = Composition::new();
+        for sym in [
+            Symbol::from(syl![M, AN, TONE4]),
+            Symbol::from(syl![M, AN, TONE4]),
+            Symbol::from(syl![D, E, TONE5]),
+        ] {
+            composition.push(sym);
+        }
+        let texts = |outcome: &Outcome| -> Vec<String> {
+            outcome
+                .intervals
+                .iter()
+                .map(|it| it.text.to_string())
+                .collect()
+        };
+        assert_eq!(
+            vec!["慢慢", "的"],
+            texts(&engine.convert(&dict, &composition)[0])
+        );
+
+        dict.update_bigram("慢慢", "地", 2, 0).unwrap();
+        assert_eq!(
+            vec!["慢慢", "地"],
+            texts(&engine.convert(&dict, &composition)[0])
+        );
+    }
+
+    #[test]
+    fn trace_conversion() {
+        let mut dict = TrieBuf::from([
+            (vec![syl![M, AN, TONE4]], vec![("慢", 500)]),
+            (
+                vec![syl![M, AN, TONE4], syl![M, AN, TONE4]],
+                vec![("慢慢", 300)],
+            ),
+            (vec![syl![D, E, TONE5]], vec![("的", 10000), ("地", 500)]),
+        ]);
+        dict.update_bigram("慢慢", "地", 2, 0).unwrap();
+        let engine = ChewingEngine::new();
+        let mut composition = Composition::new();
+        for sym in [
+            Symbol::from(syl![M, AN, TONE4]),
+            Symbol::from(syl![M, AN, TONE4]),
+            Symbol::from(syl![D, E, TONE5]),
+        ] {
+            composition.push(sym);
+        }
+        let context = ConversionContext::new();
+        let trace = engine.trace(&dict, &composition, &context);
+        let outcomes = engine.convert(&dict, &composition);
+
+        assert_eq!(5, trace.edges.len());
+        assert!(
+            trace
+                .edges
+                .iter()
+                .any(|edge| edge.start == 0 && edge.end == 2 && &*edge.text == "慢慢")
+        );
+        assert_eq!(outcomes.len(), trace.paths.len());
+        for (path, outcome) in trace.paths.iter().zip(&outcomes) {
+            assert_eq!(outcome.log_prob, path.log_prob);
+            let sum: f64 = path.edges.iter().map(|edge| edge.log_prob).sum();
+            assert!((path.log_prob - sum).abs() < 1e-9);
+        }
+        let best = &trace.paths[0].edges;
+        assert_eq!(
+            vec!["慢慢", "地"],
+            best.iter().map(|edge| &*edge.text).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![300, 500],
+            best.iter().map(|edge| edge.freq).collect::<Vec<_>>()
+        );
+        assert!(best.iter().all(|edge| edge.source.is_some()));
+    }
+
+    #[test]
+    fn convert_with_left_context() {
+        let dict = TrieBuf::from([
+            (vec![syl![SH, TONE4]], vec![("是", 100), ("試", 10)]),
+            (
+                vec![syl![C, E, TONE4], syl![SH, TONE4]],
+                vec![("測試", 9318)],
+            ),
+        ]);
+        let engine = ChewingEngine::new();
+        let mut composition = Composition::new();
+        composition.push(Symbol::from(syl![SH, TONE4]));
+        assert_eq!(
+            "是",
+            &*engine.convert(&dict, &composition)[0].intervals[0].text
+        );
+
+        let mut context = ConversionContext::new();
+        context.push('測', Some(syl![C, E, TONE4]));
+        assert_eq!(
+            "試",
+            &*engine.convert_with_context(&dict, &composition, &context)[0].intervals[0].text
+        );
+
+        context.clear();
+        context.push('測', None);
+        assert_eq!(
+            "是",
+            &*engine.convert_with_context(&dict, &composition, &context)[0].intervals[0].text
+        );
+    }
+
     // Some corrupted user dictionary may contain empty length syllables
     #[test]
     fn convert_zero_length_entry() {
@@ -915,6 +1561,41 @@ mod tests {
         );
     }
 
+    #[test]
+    fn convert_with_search_options() {
+        let dict = test_dictionary();
+        let mut composition = Composition::new();
+        for sym in [
+            Symbol::from(syl![C, E, TONE4]),
+            Symbol::from(syl![SH, TONE4]),
+            Symbol::from(syl![I, TONE2]),
+            Symbol::from(syl![X, I, A, TONE4]),
+        ] {
+            composition.push(sym);
+        }
+        let texts = |engine: &ChewingEngine| -> Vec<String> {
+            engine
+                .convert(&dict, &composition)
+                .iter()
+                .map(|outcome| outcome.intervals.iter().map(|it| &*it.text).collect())
+                .collect()
+        };
+        let all = texts(&ChewingEngine::new());
+        assert!(all.len() > 1);
+        assert!(all.contains(&"測試儀下".to_string()));
+
+        assert_eq!(all[..1], texts(&ChewingEngine::new().max_paths(1)));
+        assert!(
+            texts(&ChewingEngine::new().max_phrase_len(2))
+                .iter()
+                .all(|text| text != "測試儀下")
+        );
+        assert_eq!(
+            all[..1],
+            texts(&ChewingEngine::new().prune_threshold(f64::EPSILON))
+        );
+    }
+
     #[test]
     fn convert_cycle_alternatives() {
         let dict = test_dictionary();
@@ -998,4 +1679,115 @@ mod tests {
         assert_eq!(41, engine.convert(&dict, &composition)[1].intervals.len());
         assert_eq!(41, engine.convert(&dict, &composition)[2].intervals.len());
     }
+
+    #[test]
+    fn convert_incrementally_with_cached_lookups() {
+        let mut dict = CountingDictionary {
+            inner: TrieBuf::from([
+                (vec![syl![C, E, TONE4]], vec![("測", 1)]),
+                (vec![syl![SH, TONE4]], vec![("試", 1)]),
+                (vec![syl![I, TONE2]], vec![("儀", 1)]),
+                (vec![syl![X, I, A, TONE4]], vec![("下", 1)]),
+                (vec![syl![C, E, TONE4], syl![SH, TONE4]], vec![("測試", 1)]),
+            ]),
+            lookups: Cell::new(0),
+        };
+        let engine = ChewingEngine::new();
+        let mut composition = Composition::new();
+        for sym in [
+            Symbol::from(syl![C, E, TONE4]),
+            Symbol::from(syl![SH, TONE4]),
+            Symbol::from(syl![I, TONE2]),
+        ] {
+            composition.push(sym);
+        }
+        let expected = engine.convert(&dict, &composition);
+        assert_eq!(6, dict.take_lookups());
+        assert_eq!(expected, engine.convert(&dict, &composition));
+        assert_eq!(0, dict.take_lookups());
+
+        composition.push(Symbol::from(syl![X, I, A, TONE4]));
+        assert_eq!("測試儀下", engine.convert(&dict, &composition)[0].text());
+        assert_eq!(4, dict.take_lookups());
+
+        composition.remove(0);
+        composition.push_selection(Interval {
+            start: 0,
+            end: 1,
+            is_phrase: true,
+            text: "試".into(),
+        });
+        assert_eq!("試儀下", engine.convert(&dict, &composition)[0].text());
+        assert_eq!(0, dict.take_lookups());
+
+        dict.add_phrase(&[syl![I, TONE2], syl![X, I, A, TONE4]], ("一下", 1).into())
+            .unwrap();
+        assert_eq!("試一下", engine.convert(&dict, &composition)[0].text());
+        assert_eq!(6, dict.take_lookups());
+    }
+
+    fn trie_with_usage(
+        usage: DictionaryUsage,
+        entries: &[(&[Syllable], &str, u32)],
+    ) -> Result<Trie, Box<dyn Error>> {
+        let mut builder = TrieBuilder::new();
+        for &(syllables, phrase, freq) in entries {
+            builder.insert(syllables, (phrase, freq).into())?;
+        }
+        let mut cursor = Cursor::new(vec![]);
+        builder.write(&mut cursor)?;
+        cursor.rewind()?;
+        let mut trie = Trie::new(&mut cursor)?;
+        trie.set_usage(usage);
+        Ok(trie)
+    }
+
+    #[test]
+    fn convert_with_dictionary_weight() -> Result<(), Box<dyn Error>> {
+        let syllables = [syl![C, E, TONE4]];
+        let built_in = trie_with_usage(DictionaryUsage::BuiltIn, &[(&syllables, "測", 12)])?;
+        let custom = trie_with_usage(DictionaryUsage::Custom, &[(&syllables, "冊", 10)])?;
+        let dict = Layered::new(vec![Box::new(built_in), Box::new(custom)]);
+        let mut composition = Composition::new();
+        composition.push(Symbol::from(syllables[0]));
+
+        let best = |engine: ChewingEngine| engine.convert(&dict, &composition)[0].text();
+        assert_eq!("測", best(ChewingEngine::new()));
+        assert_eq!(
+            "冊",
+            best(ChewingEngine::new().dictionary_weight(DictionaryUsage::Custom, 2.0))
+        );
+        assert_eq!(
+            "測",
+            best(
+                ChewingEngine::new()
+                    .dictionary_weight(DictionaryUsage::Custom, 2.0)
+                    .dictionary_weight(DictionaryUsage::Custom, 1.0)
+            )
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn convert_with_length_bonus() {
+        let dict = TrieBuf::from([
+            (vec![syl![C, E, TONE4]], vec![("測", 9999999)]),
+            (vec![syl![SH, TONE4]], vec![("試", 9999999)]),
+            (vec![syl![C, E, TONE4], syl![SH, TONE4]], vec![("冊是", 1)]),
+        ]);
+        let mut composition = Composition::new();
+        composition.push(Symbol::from(syl![C, E, TONE4]));
+        composition.push(Symbol::from(syl![SH, TONE4]));
+
+        let best = |engine: ChewingEngine| engine.convert(&dict, &composition)[0].text();
+        assert_eq!("測試", best(ChewingEngine::new()));
+        assert_eq!(
+            "冊是",
+            best(ChewingEngine::new().length_bonus(&[0.0, 20.0]))
+        );
+        assert_eq!(
+            "測試",
+            best(ChewingEngine::new().length_bonus(&[20.0, 0.0]))
+        );
+    }
 }
@@ -1,5 +1,7 @@
-use super::{ChewingEngine, ConversionEngine, Outcome};
-use crate::dictionary::LookupStrategy;
+use super::{
+    ChewingEngine, Composition, ConversionContext, ConversionEngine, ConversionTrace, Outcome,
+};
+use crate::dictionary::{Dictionary, DictionaryUsage, LookupStrategy};
 
 /// Same conversion method as Chewing but uses fuzzy phrase search.
 #[derive(Debug, Default)]
@@ -10,20 +12,72 @@ pub struct FuzzyChewingEngine {
 impl FuzzyChewingEngine {
     /// Creates a new conversion engine.
     pub fn new() -> FuzzyChewingEngine {
-        FuzzyChewingEngine {
-            inner: ChewingEngine {
-                lookup_strategy: LookupStrategy::FuzzyPartialPrefix,
-            },
-        }
+        FuzzyChewingEngine::with_engine(ChewingEngine::new())
+    }
+    /// Creates a new conversion engine from a configured Chewing engine,
+    /// replacing its lookup strategy.
+    pub(crate) fn with_engine(mut inner: ChewingEngine) -> FuzzyChewingEngine {
+        inner.lookup_strategy = LookupStrategy::FuzzyPartialPrefix;
+        FuzzyChewingEngine { inner }
+    }
+    /// Sets the maximum number of alternative paths to search.
+    ///
+    /// See [`ChewingEngine::max_paths`].
+    pub fn max_paths(mut self, max_paths: usize) -> FuzzyChewingEngine {
+        self.inner = self.inner.max_paths(max_paths);
+        self
+    }
+    /// Sets the maximum number of syllables of the phrases to look up.
+    ///
+    /// See [`ChewingEngine::max_phrase_len`].
+    pub fn max_phrase_len(mut self, max_phrase_len: usize) -> FuzzyChewingEngine {
+        self.inner = self.inner.max_phrase_len(max_phrase_len);
+        self
+    }
+    /// Drops the paths scoring much lower than the best path.
+    ///
+    /// See [`ChewingEngine::prune_threshold`].
+    pub fn prune_threshold(mut self, prune_threshold: f64) -> FuzzyChewingEngine {
+        self.inner = self.inner.prune_threshold(prune_threshold);
+        self
+    }
+    /// Sets the weight of the phrases from the dictionaries of the usage.
+    ///
+    /// See [`ChewingEngine::dictionary_weight`].
+    pub fn dictionary_weight(mut self, usage: DictionaryUsage, weight: f64) -> FuzzyChewingEngine {
+        self.inner = self.inner.dictionary_weight(usage, weight);
+        self
+    }
+    /// Sets the bonus of the phrases by their length.
+    ///
+    /// See [`ChewingEngine::length_bonus`].
+    pub fn length_bonus(mut self, curve: &[f64]) -> FuzzyChewingEngine {
+        self.inner = self.inner.length_bonus(curve);
+        self
     }
 }
 
 impl ConversionEngine for FuzzyChewingEngine {
-    fn convert<'a>(
+    fn convert<'a>(&'a self, dict: &'a dyn Dictionary, comp: &'a Composition) -> Vec<Outcome> {
+        ChewingEngine::convert(&self.inner, dict, comp)
+    }
+    fn convert_with_context<'a>(
         &'a self,
-        dict: &'a dyn crate::dictionary::Dictionary,
-        comp: &'a super::Composition,
+        dict: &'a dyn Dictionary,
+        comp: &'a Composition,
+        context: &'a ConversionContext,
     ) -> Vec<Outcome> {
-        ChewingEngine::convert(&self.inner, dict, comp)
+        ChewingEngine::convert_with_context(&self.inner, dict, comp, context)
+    }
+    fn trace<'a>(
+        &'a self,
+        dict: &'a dyn Dictionary,
+        comp: &'a Composition,
+        context: &'a ConversionContext,
+    ) -> Option<ConversionTrace> {
+        Some(ChewingEngine::trace(&self.inner, dict, comp, context))
+    }
+    fn clear_cache(&self) {
+        self.inner.clear_cache();
     }
 }
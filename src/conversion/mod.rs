@@ -5,16 +5,25 @@ use std::{
     fmt::Debug,
 };
 
+pub(crate) use self::bigram::total_frequency;
+pub use self::bigram::{BigramEngine, BigramModel};
 pub use self::chewing::ChewingEngine;
 pub use self::fuzzy::FuzzyChewingEngine;
+pub use self::rerank::{
+    CharNgramScorer, RerankContext, Reranker, SingleCharPenalty, UserPhraseBonus, rerank,
+};
 pub use self::simple::SimpleEngine;
 pub(crate) use self::symbol::{full_width_symbol_input, special_symbol_input};
+pub use self::trace::{ConversionTrace, TraceEdge, TracePath};
 use crate::{dictionary::Dictionary, zhuyin::Syllable};
 
+mod bigram;
 mod chewing;
 mod fuzzy;
+mod rerank;
 mod simple;
 mod symbol;
+mod trace;
 
 /// Converts a composition buffer to list of intervals.
 ///
@@ -23,6 +32,38 @@ mod symbol;
 /// first out order.
 pub trait ConversionEngine: Debug {
     fn convert<'a>(&'a self, dict: &'a dyn Dictionary, comp: &'a Composition) -> Vec<Outcome>;
+    /// Converts a composition buffer with the committed text before it as
+    /// the left context.
+    ///
+    /// The default implementation ignores the context.
+    fn convert_with_context<'a>(
+        &'a self,
+        dict: &'a dyn Dictionary,
+        comp: &'a Composition,
+        context: &'a ConversionContext,
+    ) -> Vec<Outcome> {
+        let _ = context;
+        self.convert(dict, comp)
+    }
+    /// Returns the candidate phrases and the scored paths of converting a
+    /// composition buffer, for debugging the conversion results.
+    ///
+    /// The default implementation returns `None`.
+    fn trace<'a>(
+        &'a self,
+        dict: &'a dyn Dictionary,
+        comp: &'a Composition,
+        context: &'a ConversionContext,
+    ) -> Option<ConversionTrace> {
+        let _ = (dict, comp, context);
+        None
+    }
+    /// Drops the cached dictionary lookups, if any.
+    ///
+    /// Engines caching lookups between conversions drop them by themselves
+    /// when the [generation](Dictionary::generation) of the dictionary
+    /// changes, so this only frees the memory.
+    fn clear_cache(&self) {}
 }
 
 #[derive(Debug, Default, Clone, PartialEq)]
@@ -31,6 +72,21 @@ pub struct Outcome {
     pub(crate) log_prob: f64,
 }
 
+impl Outcome {
+    /// The converted intervals covering the whole composition.
+    pub fn intervals(&self) -> &[Interval] {
+        &self.intervals
+    }
+    /// The converted text of the outcome.
+    pub fn text(&self) -> String {
+        self.intervals.iter().map(|it| &*it.text).collect()
+    }
+    /// The log probability score of the outcome.
+    pub fn log_prob(&self) -> f64 {
+        self.log_prob
+    }
+}
+
 /// Output of conversion.
 ///
 /// Interval represents a segment of input buffer converted to a phrase.
@@ -157,7 +213,7 @@ impl From<char> for Symbol {
 }
 
 /// Input data collected by the Editor.
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, PartialEq)]
 pub struct Composition {
     /// Pre-edit inputs either syllables or symbols.
     symbols: Vec<Symbol>,
@@ -334,3 +390,166 @@ impl Composition {
         self.selections.clear();
     }
 }
+
+/// Committed text before the composition buffer.
+///
+/// Only the last few characters are kept. Syllables are known for the
+/// characters committed by the editor itself, and can be guessed from the
+/// dictionary for the text set by the application.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ConversionContext {
+    chars: Vec<(char, Option<Syllable>)>,
+}
+
+impl ConversionContext {
+    const MAX_LEN: usize = 16;
+
+    pub fn new() -> ConversionContext {
+        Default::default()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.chars.is_empty()
+    }
+    /// Returns the text of the context.
+    pub fn text(&self) -> String {
+        self.chars.iter().map(|(ch, _)| ch).collect()
+    }
+    /// Appends a committed character and its syllable, if known.
+    pub fn push(&mut self, ch: char, syllable: Option<Syllable>) {
+        self.chars.push((ch, syllable));
+        if self.chars.len() > Self::MAX_LEN {
+            self.chars.remove(0);
+        }
+    }
+    /// Replaces the context with new text.
+    ///
+    /// Syllables of the common suffix of the old and the new text are kept.
+    pub fn set_text(&mut self, text: &str) {
+        let mut chars: Vec<(char, Option<Syllable>)> = text
+            .chars()
+            .rev()
+            .take(Self::MAX_LEN)
+            .map(|ch| (ch, None))
+            .collect();
+        for (new, old) in chars.iter_mut().zip(self.chars.iter().rev()) {
+            if new.0 != old.0 {
+                break;
+            }
+            new.1 = old.1;
+        }
+        chars.reverse();
+        self.chars = chars;
+    }
+    pub fn clear(&mut self) {
+        self.chars.clear();
+    }
+    /// Guesses the unknown syllables of the last `max` characters with
+    /// reverse lookups in the dictionary.
+    ///
+    /// The unknown suffix is looked up as a phrase first, so polyphonic
+    /// characters get the reading used in the phrase, then character by
+    /// character. Characters without readings, e.g. punctuation, stay unknown.
+    pub fn guess_syllables<D: Dictionary + ?Sized>(&mut self, dict: &D, max: usize) {
+        let unknown = self
+            .chars
+            .iter()
+            .rev()
+            .take(max)
+            .take_while(|(_, syl)| syl.is_none())
+            .count();
+        if unknown == 0 {
+            return;
+        }
+        let begin = self.chars.len() - unknown;
+        let text: String = self.chars[begin..].iter().map(|(ch, _)| ch).collect();
+        if let Some((syllables, _)) = dict
+            .reverse_lookup(&text)
+            .into_iter()
+            .find(|(syllables, _)| syllables.len() == unknown)
+        {
+            for (item, syl) in self.chars[begin..].iter_mut().zip(syllables) {
+                item.1 = Some(syl);
+            }
+            return;
+        }
+        for (ch, syl) in &mut self.chars[begin..] {
+            *syl = dict
+                .reverse_lookup(ch.encode_utf8(&mut [0; 4]))
+                .into_iter()
+                .find_map(|(syllables, _)| match syllables[..] {
+                    [syl] => Some(syl),
+                    _ => None,
+                });
+        }
+    }
+    /// Returns the longest suffix, up to `max` characters, that has known
+    /// syllables.
+    pub(crate) fn known_suffix(&self, max: usize) -> (String, Vec<Syllable>) {
+        let mut text = vec![];
+        let mut syllables = vec![];
+        for &(ch, syl) in self.chars.iter().rev().take(max) {
+            match syl {
+                Some(syl) => {
+                    text.push(ch);
+                    syllables.push(syl);
+                }
+                None => break,
+            }
+        }
+        text.reverse();
+        syllables.reverse();
+        (text.into_iter().collect(), syllables)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ConversionContext;
+    use crate::{dictionary::TrieBuf, syl, zhuyin::Bopomofo::*};
+
+    #[test]
+    fn set_text_keeps_known_syllables() {
+        let mut context = ConversionContext::new();
+        context.push('測', Some(syl![C, E, TONE4]));
+        context.set_text("我要測");
+        assert_eq!("我要測", context.text());
+        assert_eq!(
+            ("測".to_string(), vec![syl![C, E, TONE4]]),
+            context.known_suffix(2)
+        );
+
+        context.set_text("我要");
+        assert_eq!((String::new(), vec![]), context.known_suffix(2));
+    }
+
+    #[test]
+    fn guess_syllables_from_dictionary() {
+        let dict = TrieBuf::from([
+            (vec![syl![X, I, ENG, TONE2]], vec![("行", 100)]),
+            (vec![syl![H, ANG, TONE2]], vec![("行", 10)]),
+            (vec![syl![I, EN, TONE2]], vec![("銀", 10)]),
+            (
+                vec![syl![I, EN, TONE2], syl![H, ANG, TONE2]],
+                vec![("銀行", 10)],
+            ),
+        ]);
+        let mut context = ConversionContext::new();
+        context.set_text("去銀行");
+        context.guess_syllables(&dict, 2);
+        assert_eq!(
+            (
+                "銀行".to_string(),
+                vec![syl![I, EN, TONE2], syl![H, ANG, TONE2]]
+            ),
+            context.known_suffix(3)
+        );
+
+        context.clear();
+        context.set_text("，行");
+        context.guess_syllables(&dict, 3);
+        assert_eq!(
+            ("行".to_string(), vec![syl![X, I, ENG, TONE2]]),
+            context.known_suffix(3)
+        );
+    }
+}
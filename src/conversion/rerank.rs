@@ -0,0 +1,245 @@
+use std::{fmt::Debug, sync::Arc};
+
+use super::{BigramModel, Composition, Outcome};
+use crate::dictionary::{Dictionary, LookupStrategy};
+
+/// Inputs available to a [`Reranker`].
+#[derive(Debug, Clone, Copy)]
+pub struct RerankContext<'a> {
+    /// All enabled dictionaries.
+    pub dict: &'a dyn Dictionary,
+    /// The user dictionary.
+    pub user_dict: &'a dyn Dictionary,
+    /// The converted composition.
+    pub composition: &'a Composition,
+}
+
+/// Rescores the outcomes of a [`ConversionEngine`](super::ConversionEngine).
+///
+/// Rerankers run after conversion. The score of each reranker is added to
+/// the `log_prob` of the outcome and the outcomes are then sorted again.
+pub trait Reranker: Debug {
+    /// Returns the log probability adjustment of the outcome.
+    fn rescore(&self, context: &RerankContext<'_>, outcome: &Outcome) -> f64;
+}
+
+/// Adds the scores of all `rerankers` to the outcomes and sorts them by the
+/// new score.
+///
+/// Outcomes with the same score keep their original order.
+pub fn rerank(rerankers: &[&dyn Reranker], context: &RerankContext<'_>, outcomes: &mut [Outcome]) {
+    if rerankers.is_empty() {
+        return;
+    }
+    for outcome in outcomes.iter_mut() {
+        let adjustment: f64 = rerankers
+            .iter()
+            .map(|reranker| reranker.rescore(context, outcome))
+            .sum();
+        outcome.log_prob += adjustment;
+    }
+    outcomes.sort_by(|a, b| b.log_prob.total_cmp(&a.log_prob));
+}
+
+/// Penalizes outcomes made of many single character phrases.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SingleCharPenalty {
+    /// Penalty of each single character phrase.
+    pub weight: f64,
+}
+
+impl Reranker for SingleCharPenalty {
+    fn rescore(&self, _context: &RerankContext<'_>, outcome: &Outcome) -> f64 {
+        let count = outcome
+            .intervals
+            .iter()
+            .filter(|it| it.is_phrase && it.len() == 1)
+            .count();
+        -self.weight * count as f64
+    }
+}
+
+/// Prefers outcomes using phrases from the user dictionary.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UserPhraseBonus {
+    /// Bonus of each phrase found in the user dictionary.
+    pub weight: f64,
+}
+
+impl Reranker for UserPhraseBonus {
+    fn rescore(&self, context: &RerankContext<'_>, outcome: &Outcome) -> f64 {
+        let symbols = context.composition.symbols();
+        let count = outcome
+            .intervals
+            .iter()
+            .filter(|it| it.is_phrase && it.end <= symbols.len())
+            .filter(|it| {
+                let Some(syllables) = symbols[it.start..it.end]
+                    .iter()
+                    .map(|sym| sym.to_syllable())
+                    .collect::<Option<Vec<_>>>()
+                else {
+                    return false;
+                };
+                context
+                    .user_dict
+                    .lookup(&syllables, LookupStrategy::Standard)
+                    .iter()
+                    .any(|phrase| phrase.as_str() == &*it.text)
+            })
+            .count();
+        self.weight * count as f64
+    }
+}
+
+/// Prefers outcomes with familiar adjacent characters.
+///
+/// Character pairs are read from the single character entries of a
+/// [`BigramModel`].
+#[derive(Debug, Clone)]
+pub struct CharNgramScorer {
+    model: Arc<BigramModel>,
+    weight: f64,
+}
+
+impl CharNgramScorer {
+    /// Creates a scorer adding `weight * ln(1 + count)` for each pair of
+    /// adjacent characters.
+    pub fn new(model: Arc<BigramModel>, weight: f64) -> CharNgramScorer {
+        CharNgramScorer { model, weight }
+    }
+}
+
+impl Reranker for CharNgramScorer {
+    fn rescore(&self, _context: &RerankContext<'_>, outcome: &Outcome) -> f64 {
+        if self.model.is_empty() {
+            return 0.0;
+        }
+        let text: Vec<char> = outcome
+            .intervals
+            .iter()
+            .flat_map(|it| it.text.chars())
+            .collect();
+        let mut left = [0; 4];
+        let mut right = [0; 4];
+        let log_count: f64 = text
+            .windows(2)
+            .map(|pair| {
+                let count = self.model.count(
+                    pair[0].encode_utf8(&mut left),
+                    pair[1].encode_utf8(&mut right),
+                );
+                (count as f64).ln_1p()
+            })
+            .sum();
+        self.weight * log_count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{
+        CharNgramScorer, RerankContext, Reranker, SingleCharPenalty, UserPhraseBonus, rerank,
+    };
+    use crate::{
+        conversion::{BigramModel, Composition, Interval, Outcome, Symbol},
+        dictionary::TrieBuf,
+        syl,
+        zhuyin::Bopomofo::*,
+    };
+
+    fn interval(start: usize, end: usize, text: &str) -> Interval {
+        Interval {
+            start,
+            end,
+            is_phrase: true,
+            text: text.into(),
+        }
+    }
+
+    fn composition() -> Composition {
+        let mut com = Composition::new();
+        com.push(Symbol::from(syl![C, E, TONE4]));
+        com.push(Symbol::from(syl![SH, TONE4]));
+        com
+    }
+
+    fn outcomes() -> Vec<Outcome> {
+        vec![
+            Outcome {
+                intervals: vec![interval(0, 1, "側"), interval(1, 2, "室")],
+                log_prob: -1.0,
+            },
+            Outcome {
+                intervals: vec![interval(0, 2, "策士")],
+                log_prob: -2.0,
+            },
+        ]
+    }
+
+    #[test]
+    fn no_reranker_keeps_order() {
+        let dict = TrieBuf::new_in_memory();
+        let com = composition();
+        let context = RerankContext {
+            dict: &dict,
+            user_dict: &dict,
+            composition: &com,
+        };
+        let mut outcomes = outcomes();
+        rerank(&[], &context, &mut outcomes);
+        assert_eq!(self::outcomes(), outcomes);
+    }
+
+    #[test]
+    fn penalize_single_chars() {
+        let dict = TrieBuf::new_in_memory();
+        let com = composition();
+        let context = RerankContext {
+            dict: &dict,
+            user_dict: &dict,
+            composition: &com,
+        };
+        let penalty = SingleCharPenalty { weight: 1.0 };
+        let mut outcomes = outcomes();
+        rerank(&[&penalty], &context, &mut outcomes);
+        assert_eq!("策士", &*outcomes[0].intervals[0].text);
+        assert_eq!(-3.0, outcomes[1].log_prob);
+    }
+
+    #[test]
+    fn prefer_user_phrases() {
+        let dict = TrieBuf::new_in_memory();
+        let mut user_dict = TrieBuf::new_in_memory();
+        user_dict
+            .add_phrase(&[syl![C, E, TONE4], syl![SH, TONE4]], ("策士", 1).into())
+            .unwrap();
+        let com = composition();
+        let context = RerankContext {
+            dict: &dict,
+            user_dict: &user_dict,
+            composition: &com,
+        };
+        let bonus = UserPhraseBonus { weight: 2.0 };
+        assert_eq!(0.0, bonus.rescore(&context, &outcomes()[0]));
+        assert_eq!(2.0, bonus.rescore(&context, &outcomes()[1]));
+    }
+
+    #[test]
+    fn score_char_pairs() {
+        let dict = TrieBuf::new_in_memory();
+        let com = composition();
+        let context = RerankContext {
+            dict: &dict,
+            user_dict: &dict,
+            composition: &com,
+        };
+        let mut model = BigramModel::new();
+        model.insert("側", "室", 1);
+        let scorer = CharNgramScorer::new(Arc::new(model), 1.0);
+        assert_eq!(2.0_f64.ln(), scorer.rescore(&context, &outcomes()[0]));
+        assert_eq!(0.0, scorer.rescore(&context, &outcomes()[1]));
+    }
+}
@@ -0,0 +1,79 @@
+use super::{Composition, chewing::PossiblePhrase};
+use crate::{
+    dictionary::{Dictionary, DictionaryInfo, LookupStrategy},
+    zhuyin::Syllable,
+};
+
+/// Intermediate results of a conversion, used to explain the outcomes.
+///
+/// See [`ConversionEngine::trace`](super::ConversionEngine::trace).
+#[derive(Debug, Clone, Default)]
+pub struct ConversionTrace {
+    /// All candidate phrases considered by the engine.
+    pub edges: Vec<TraceEdge>,
+    /// The best paths sorted by their score, best first.
+    pub paths: Vec<TracePath>,
+}
+
+/// A candidate phrase covering part of the composition.
+#[derive(Debug, Clone)]
+pub struct TraceEdge {
+    /// The starting offset of the phrase.
+    pub start: usize,
+    /// The ending offset of the phrase.
+    pub end: usize,
+    /// The phrase or symbol text.
+    pub text: Box<str>,
+    /// The frequency of the phrase. Always 0 for symbols.
+    pub freq: u32,
+    /// The dictionary providing the phrase, if it can be found.
+    pub source: Option<DictionaryInfo>,
+    /// The contribution of the phrase to the log probability of the path.
+    pub log_prob: f64,
+}
+
+/// A path through the candidate phrases.
+#[derive(Debug, Clone)]
+pub struct TracePath {
+    /// The phrases of the path.
+    ///
+    /// The `log_prob` of each phrase also includes the scores depending on
+    /// the previous phrase, so they add up to the `log_prob` of the path.
+    pub edges: Vec<TraceEdge>,
+    /// The log probability score of the path.
+    pub log_prob: f64,
+}
+
+impl TraceEdge {
+    pub(super) fn new(
+        dict: &dyn Dictionary,
+        strategy: LookupStrategy,
+        comp: &Composition,
+        start: usize,
+        end: usize,
+        phrase: &PossiblePhrase,
+        log_prob: f64,
+    ) -> TraceEdge {
+        let (freq, source) = match phrase {
+            PossiblePhrase::Symbol(_) => (0, None),
+            PossiblePhrase::Phrase(phrase, _) => {
+                let source = comp.symbols()[start..end]
+                    .iter()
+                    .map(|sym| sym.to_syllable())
+                    .collect::<Option<Vec<Syllable>>>()
+                    .and_then(|syllables| {
+                        dict.lookup_source(&syllables, phrase.as_str(), strategy)
+                    });
+                (phrase.freq(), source)
+            }
+        };
+        TraceEdge {
+            start,
+            end,
+            text: phrase.to_string().into_boxed_str(),
+            freq,
+            source,
+            log_prob,
+        }
+    }
+}
@@ -1,8 +1,14 @@
-use std::collections::{BTreeMap, BTreeSet, btree_map::Entry};
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet, btree_map::Entry},
+};
 
 use log::error;
 
-use super::{Dictionary, DictionaryInfo, Entries, LookupStrategy, Phrase, UpdateDictionaryError};
+use super::{
+    Dictionary, DictionaryInfo, Entries, LookupStrategy, Phrase, UpdateDictionaryError,
+    next_generation,
+};
 use crate::{
     dictionary::{DictionaryUsage, TrieBuf},
     zhuyin::Syllable,
@@ -50,6 +56,7 @@ use crate::{
 pub struct Layered {
     dicts: Vec<Box<dyn Dictionary>>,
     user_dict_index: usize,
+    generation: u64,
 }
 
 impl Layered {
@@ -69,11 +76,15 @@ impl Layered {
         Layered {
             dicts,
             user_dict_index,
+            generation: next_generation(),
         }
     }
     pub fn user_dict(&mut self) -> &mut dyn Dictionary {
         self.dicts[self.user_dict_index].as_mut()
     }
+    pub(crate) fn user_dict_ref(&self) -> &dyn Dictionary {
+        self.dicts[self.user_dict_index].as_ref()
+    }
     fn enabled_dicts(&self) -> impl Iterator<Item = &Box<dyn Dictionary>> {
         self.dicts
             .iter()
@@ -136,6 +147,9 @@ impl Dictionary for Layered {
                                 (None, Some(new)) => Some(new),
                                 (None, None) => None,
                             };
+                        if phrases[index].comment.is_none() {
+                            phrases[index].comment = phrase.comment;
+                        }
                     }
                     Entry::Vacant(entry) => {
                         entry.insert(phrases.len());
@@ -144,6 +158,15 @@ impl Dictionary for Layered {
                 }
             }
         });
+        if let LookupStrategy::Confusable(_) = strategy {
+            // Exact matches are ranked above the fuzzy ones
+            let exact: BTreeSet<Box<str>> = self
+                .enabled_dicts()
+                .flat_map(|d| d.lookup(syllables, LookupStrategy::Standard))
+                .map(|p| p.text)
+                .collect();
+            phrases.sort_by_key(|p| !exact.contains(&p.text));
+        }
 
         // Remove excluded
         let excluded: BTreeSet<Box<str>> = self
@@ -164,6 +187,75 @@ impl Dictionary for Layered {
         Box::new(self.enabled_dicts().flat_map(|dict| dict.entries()))
     }
 
+    /// Returns the readings of the phrase from all underlying dictionaries.
+    ///
+    /// When a reading appears in multiple dictionaries, the final frequency
+    /// is the max of all frequency in all dictionaries. Excluded readings are
+    /// removed.
+    fn reverse_lookup(&self, phrase: &str) -> Vec<(Vec<Syllable>, Phrase)> {
+        let mut readings: Vec<(Vec<Syllable>, Phrase)> = Vec::new();
+        for d in self.enabled_dicts() {
+            for (syllables, found) in d.reverse_lookup(phrase) {
+                match readings.iter_mut().find(|(it, _)| *it == syllables) {
+                    Some((_, merged)) => {
+                        merged.freq = found.freq.max(merged.freq);
+                        merged.last_used = merged.last_used.max(found.last_used);
+                        if merged.comment.is_none() {
+                            merged.comment = found.comment;
+                        }
+                    }
+                    None => readings.push((syllables, found)),
+                }
+            }
+        }
+        readings.retain(|(syllables, _)| !self.is_excluded(syllables, phrase));
+        readings.sort_by_key(|(_, it)| Reverse(it.freq()));
+        readings
+    }
+
+    /// Lookup phrases with the reading prefix from all underlying dictionaries.
+    ///
+    /// The merged frequency of a phrase is its frequency in one of the
+    /// dictionaries, so the most frequent phrases are among the top ones of
+    /// each dictionary. Each dictionary is asked for as many more phrases as
+    /// the excluded ones, which are removed after merging.
+    fn lookup_prefix(
+        &self,
+        syllables: &[Syllable],
+        text: &str,
+        limit: usize,
+    ) -> Vec<(Vec<Syllable>, Phrase)> {
+        let excluded = self
+            .exclusion_dicts()
+            .map(|d| d.lookup_prefix(syllables, text, usize::MAX).len())
+            .sum::<usize>();
+        let dict_limit = limit.saturating_add(excluded);
+        let mut sort_map: BTreeMap<(Vec<Syllable>, String), usize> = BTreeMap::new();
+        let mut phrases: Vec<(Vec<Syllable>, Phrase)> = Vec::new();
+        for d in self.enabled_dicts() {
+            for (reading, found) in d.lookup_prefix(syllables, text, dict_limit) {
+                match sort_map.entry((reading.clone(), found.to_string())) {
+                    Entry::Occupied(entry) => {
+                        let merged = &mut phrases[*entry.get()].1;
+                        merged.freq = found.freq.max(merged.freq);
+                        merged.last_used = merged.last_used.max(found.last_used);
+                        if merged.comment.is_none() {
+                            merged.comment = found.comment;
+                        }
+                    }
+                    Entry::Vacant(entry) => {
+                        entry.insert(phrases.len());
+                        phrases.push((reading, found));
+                    }
+                }
+            }
+        }
+        phrases.retain(|(reading, phrase)| !self.is_excluded(reading, phrase.as_str()));
+        phrases.sort_by_key(|(_, it)| Reverse(it.freq()));
+        phrases.truncate(limit);
+        phrases
+    }
+
     fn about(&self) -> DictionaryInfo {
         DictionaryInfo {
             name: "Built-in Layered".to_string(),
@@ -186,6 +278,15 @@ impl Dictionary for Layered {
         self.user_dict().reopen()
     }
 
+    /// Returns the latest generation of the layered dictionaries, which
+    /// changes whenever any of them changes.
+    fn generation(&self) -> u64 {
+        self.dicts
+            .iter()
+            .map(|d| d.generation())
+            .fold(self.generation, u64::max)
+    }
+
     fn flush(&mut self) -> Result<(), UpdateDictionaryError> {
         self.exclusion_dicts_mut().for_each(|d| {
             if let Err(error) = d.flush() {
@@ -241,6 +342,59 @@ impl Dictionary for Layered {
         });
         self.user_dict().remove_phrase(syllables, phrase_str)
     }
+
+    /// Phrase pairs are only learned in the user dictionary.
+    fn lookup_bigram(&self, left: &str, right: &str) -> Option<(u32, u64)> {
+        self.user_dict_ref().lookup_bigram(left, right)
+    }
+
+    /// Phrase pairs are only learned in the user dictionary.
+    fn lookup_successors(&self, left: &str) -> Vec<(String, u32, u64)> {
+        self.user_dict_ref().lookup_successors(left)
+    }
+
+    /// Returns the dictionary providing the frequency of the phrase.
+    ///
+    /// When a phrase appears in multiple dictionaries, the first dictionary
+    /// with the max frequency is returned.
+    fn lookup_source(
+        &self,
+        syllables: &[Syllable],
+        phrase: &str,
+        strategy: LookupStrategy,
+    ) -> Option<DictionaryInfo> {
+        if self.exclusion_dicts().any(|d| {
+            d.lookup(syllables, strategy)
+                .iter()
+                .any(|p| p.as_str() == phrase)
+        }) {
+            return None;
+        }
+        let mut source: Option<(u32, &Box<dyn Dictionary>)> = None;
+        for d in self.enabled_dicts() {
+            let Some(found) = d
+                .lookup(syllables, strategy)
+                .into_iter()
+                .find(|p| p.as_str() == phrase)
+            else {
+                continue;
+            };
+            if source.is_none_or(|(freq, _)| found.freq() > freq) {
+                source = Some((found.freq(), d));
+            }
+        }
+        source.map(|(_, d)| d.about())
+    }
+
+    fn update_bigram(
+        &mut self,
+        left: &str,
+        right: &str,
+        count: u32,
+        time: u64,
+    ) -> Result<(), UpdateDictionaryError> {
+        self.user_dict().update_bigram(left, right, count, time)
+    }
 }
 
 #[cfg(test)]
@@ -253,8 +407,8 @@ mod tests {
     use super::Layered;
     use crate::{
         dictionary::{
-            Dictionary, DictionaryBuilder, DictionaryUsage, LookupStrategy, Phrase, Trie, TrieBuf,
-            TrieBuilder,
+            Dictionary, DictionaryBuilder, DictionaryInfo, DictionaryUsage, LookupStrategy, Phrase,
+            Trie, TrieBuf, TrieBuilder,
         },
         syl,
         zhuyin::Bopomofo,
@@ -340,6 +494,117 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_reverse_lookup() -> Result<(), Box<dyn Error>> {
+        let xing2 = syl![Bopomofo::X, Bopomofo::I, Bopomofo::ENG, Bopomofo::TONE2];
+        let hang2 = syl![Bopomofo::H, Bopomofo::ANG, Bopomofo::TONE2];
+        let sys_dict = TrieBuf::from([
+            (vec![xing2], vec![("行", 10)]),
+            (vec![hang2], vec![("行", 5)]),
+        ]);
+        let user_dict = TrieBuf::from([(vec![hang2], vec![("行", 20)])]);
+
+        let dict = Layered::new(vec![Box::new(sys_dict), Box::new(user_dict)]);
+        assert_eq!(
+            vec![(vec![hang2], 20), (vec![xing2], 10)],
+            dict.reverse_lookup("行")
+                .into_iter()
+                .map(|(syllables, phrase)| (syllables, phrase.freq()))
+                .collect::<Vec<_>>()
+        );
+        assert!(dict.reverse_lookup("銀行").is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_lookup_prefix() -> Result<(), Box<dyn Error>> {
+        let yin2 = syl![Bopomofo::I, Bopomofo::EN, Bopomofo::TONE2];
+        let hang2 = syl![Bopomofo::H, Bopomofo::ANG, Bopomofo::TONE2];
+        let he2 = syl![Bopomofo::H, Bopomofo::E, Bopomofo::TONE2];
+        let sys_dict = TrieBuf::from([
+            (vec![yin2], vec![("銀", 50)]),
+            (vec![yin2, hang2], vec![("銀行", 80)]),
+            (vec![yin2, he2], vec![("銀河", 30)]),
+        ]);
+        let mut builder = TrieBuilder::new();
+        builder.insert(&[yin2], ("銀", 0).into())?;
+        let mut cursor = Cursor::new(vec![]);
+        builder.write(&mut cursor)?;
+        cursor.rewind()?;
+        let mut exclude_dict = Trie::new(&mut cursor)?;
+        exclude_dict.set_usage(DictionaryUsage::ExcludeList);
+        let user_dict = TrieBuf::from([(vec![yin2, he2], vec![("銀河", 90)])]);
+
+        let dict = Layered::new(vec![
+            Box::new(sys_dict),
+            Box::new(exclude_dict),
+            Box::new(user_dict),
+        ]);
+        assert_eq!(
+            vec![("銀河", 90), ("銀行", 80)],
+            dict.lookup_prefix(&[yin2], "", 10)
+                .iter()
+                .map(|(_, phrase)| (phrase.as_str(), phrase.freq()))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![(vec![yin2, he2], 90)],
+            dict.lookup_prefix(&[yin2], "", 1)
+                .into_iter()
+                .map(|(syllables, phrase)| (syllables, phrase.freq()))
+                .collect::<Vec<_>>()
+        );
+        assert!(dict.lookup_prefix(&[he2], "", 10).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn change_generation_with_underlying_dictionaries() -> Result<(), Box<dyn Error>> {
+        let mut dict = Layered::new(vec![Box::new(TrieBuf::new_in_memory())]);
+        let generation = dict.generation();
+        assert_eq!(generation, dict.generation());
+        dict.user_dict().add_phrase(
+            &[syl![Bopomofo::C, Bopomofo::E, Bopomofo::TONE4]],
+            ("測", 1).into(),
+        )?;
+        assert!(dict.generation() > generation);
+        Ok(())
+    }
+
+    #[test]
+    fn test_lookup_source() -> Result<(), Box<dyn Error>> {
+        let trie = |name: &str, phrases: &[(&str, u32)]| -> Result<Trie, Box<dyn Error>> {
+            let mut builder = TrieBuilder::new();
+            builder.set_info(DictionaryInfo {
+                name: name.to_string(),
+                ..Default::default()
+            })?;
+            for &(phrase, freq) in phrases {
+                builder.insert(
+                    &[syl![Bopomofo::C, Bopomofo::E, Bopomofo::TONE4]],
+                    (phrase, freq).into(),
+                )?;
+            }
+            let mut cursor = Cursor::new(vec![]);
+            builder.write(&mut cursor)?;
+            cursor.rewind()?;
+            Ok(Trie::new(&mut cursor)?)
+        };
+        let sys_dict = trie("sys", &[("測", 1), ("冊", 1), ("側", 1)])?;
+        let user_dict = trie("user", &[("策", 100), ("冊", 100)])?;
+
+        let dict = Layered::new(vec![Box::new(sys_dict), Box::new(user_dict)]);
+        let syllables = [syl![Bopomofo::C, Bopomofo::E, Bopomofo::TONE4]];
+        let source = |phrase| {
+            dict.lookup_source(&syllables, phrase, LookupStrategy::Standard)
+                .map(|info| info.name)
+        };
+        assert_eq!(Some("sys".to_string()), source("測"));
+        assert_eq!(Some("user".to_string()), source("冊"));
+        assert_eq!(None, source("廁"));
+        Ok(())
+    }
+
     #[test]
     fn test_readonly_user_dict() -> Result<(), Box<dyn Error>> {
         let sys_dict = TrieBuf::from([(
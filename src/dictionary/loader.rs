@@ -11,9 +11,10 @@ use log::{error, info};
 
 #[cfg(feature = "sqlite")]
 use super::SqliteDictionary;
-use super::{Dictionary, TrieBuf, uhash};
+use super::{Dictionary, Trie, TrieBuf, uhash};
 use crate::exn::{Exn, ResultExt};
 use crate::{
+    conversion::BigramModel,
     dictionary::DictionaryUsage,
     editor::{AbbrevTable, SymbolSelector},
     path::{find_files_by_names, find_path_by_files, search_path_from_env_var, userphrase_path},
@@ -25,6 +26,7 @@ const UD_SQLITE_FILE_NAME: &str = "chewing.sqlite3";
 const UD_MEM_FILE_NAME: &str = ":memory:";
 const ABBREV_FILE_NAME: &str = "swkb.dat";
 const SYMBOLS_FILE_NAME: &str = "symbols.dat";
+const BIGRAM_FILE_NAME: &str = "bigram.dat";
 
 pub const DEFAULT_DICT_NAMES: &[&str] = &["word.dat", "tsi.dat", "chewing.dat"];
 
@@ -120,6 +122,21 @@ impl AssetLoader {
         info!("Loading {SYMBOLS_FILE_NAME}");
         SymbolSelector::open(symbol_path).or_raise(error)
     }
+    /// Loads the phrase bigram model.
+    pub fn load_bigram_model(&self) -> Result<BigramModel, LoadDictionaryError> {
+        let error = || LoadDictionaryError::new("failed to load bigram model");
+        let not_found = || error().with_source(io::Error::from(io::ErrorKind::NotFound));
+        let search_path = if let Some(path) = &self.search_path {
+            path.to_owned()
+        } else {
+            search_path_from_env_var()
+        };
+        let parent_path =
+            find_path_by_files(&search_path, &[BIGRAM_FILE_NAME]).or_raise(not_found)?;
+        let bigram_path = parent_path.join(BIGRAM_FILE_NAME);
+        info!("Loading {BIGRAM_FILE_NAME}");
+        BigramModel::open(bigram_path).or_raise(error)
+    }
 }
 
 /// Automatically searches and initializes the user dictionary.
@@ -155,11 +172,17 @@ impl UserDictionaryManager {
     ///
     /// If no user dictionary were found, a new dictionary will be created at
     /// the default path.
+    ///
+    /// A Trie user dictionary is copied to the last good backup next to it
+    /// with the `.bak` suffix after each successful flush. If the dictionary
+    /// fails verification, it is renamed with the `.corrupted` suffix and
+    /// replaced by the backup.
     pub fn init(&self) -> Result<Box<dyn Dictionary>, LoadDictionaryError> {
         let error = || LoadDictionaryError::new("failed to init user dictionary");
         let not_found = || error().with_source(io::Error::from(io::ErrorKind::NotFound));
         let mut loader = SingleDictionaryLoader::new();
         loader.migrate_sqlite(true);
+        loader.keep_backup = true;
         let data_path = self
             .data_path
             .clone()
@@ -237,11 +260,13 @@ impl UserDictionaryManager {
     /// Searches and initializes the user exclusion dictionary.
     ///
     /// If no user exclusion dictionary were found, a new dictionary
-    /// will be created at the default path.
+    /// will be created at the default path. It is backed up and restored
+    /// like the user dictionary.
     pub fn init_deleted(&self) -> Result<Box<dyn Dictionary>, LoadDictionaryError> {
         let error = || LoadDictionaryError::new("failed to init user exclusion dictionary");
         let not_found = || error().with_source(io::Error::from(io::ErrorKind::NotFound));
-        let loader = SingleDictionaryLoader::new();
+        let mut loader = SingleDictionaryLoader::new();
+        loader.keep_backup = true;
         let data_path = self
             .data_path
             .clone()
@@ -264,15 +289,46 @@ impl UserDictionaryManager {
     }
 }
 
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    path.into()
+}
+
+/// Opens the Trie dictionary, or restores the last good backup if it fails
+/// verification. The backup is refreshed after each successful flush.
+fn open_with_backup(data_path: &Path) -> io::Result<TrieBuf> {
+    let backup_path = with_suffix(data_path, ".bak");
+    let mut dict = match TrieBuf::open(data_path) {
+        Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+            error!("Dictionary {} is corrupted: {e}", data_path.display());
+            if let Err(e) = Trie::open(&backup_path) {
+                error!("No valid backup at {}: {e}", backup_path.display());
+                return Err(e);
+            }
+            let corrupted_path = with_suffix(data_path, ".corrupted");
+            fs::rename(data_path, &corrupted_path)?;
+            fs::copy(&backup_path, data_path)?;
+            info!("Restored backup {}", backup_path.display());
+            TrieBuf::open(data_path)?
+        }
+        result => result?,
+    };
+    dict.keep_backup(backup_path);
+    Ok(dict)
+}
+
 #[derive(Debug)]
 pub struct SingleDictionaryLoader {
     migrate_sqlite: bool,
+    keep_backup: bool,
 }
 
 impl SingleDictionaryLoader {
     pub fn new() -> SingleDictionaryLoader {
         SingleDictionaryLoader {
             migrate_sqlite: false,
+            keep_backup: false,
         }
     }
     pub fn migrate_sqlite(&mut self, migrate: bool) {
@@ -310,8 +366,12 @@ impl SingleDictionaryLoader {
                 Err(error().with_source(io::Error::from(io::ErrorKind::Unsupported)))
             }
         } else if ext.eq_ignore_ascii_case("dat") {
-            TrieBuf::open(dict_path)
-                .map(|dict| Box::new(dict) as Box<dyn Dictionary>)
+            let dict = if self.keep_backup {
+                open_with_backup(dict_path)
+            } else {
+                TrieBuf::open(dict_path)
+            };
+            dict.map(|dict| Box::new(dict) as Box<dyn Dictionary>)
                 .or_raise(error)
         } else {
             Err(error())
@@ -342,3 +402,69 @@ impl Display for LoadDictionaryError {
 }
 
 impl_exn!(LoadDictionaryError);
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{UserDictionaryManager, with_suffix};
+    use crate::{
+        dictionary::{LookupStrategy, Phrase},
+        syl,
+        zhuyin::Bopomofo::*,
+    };
+
+    #[test]
+    fn init_restores_backup_of_corrupted_user_dict() -> Result<(), Box<dyn std::error::Error>> {
+        let tmpdir = tempfile::tempdir()?;
+        let data_path = tmpdir.path().join("chewing.dat");
+        let manager = UserDictionaryManager::new().userphrase_path(&data_path);
+        let mut dict = manager.init()?;
+        let backup_path = with_suffix(&data_path, ".bak");
+        assert!(!backup_path.exists());
+        dict.add_phrase(&[syl![H, ANG, TONE2]], ("行", 5).into())?;
+        dict.flush()?;
+        drop(dict);
+
+        // The flush backs up the dictionary
+        assert!(backup_path.exists());
+
+        let mut bytes = fs::read(&data_path)?;
+        let pos = bytes.windows(3).position(|w| w == "行".as_bytes()).unwrap();
+        bytes[pos + 2] ^= 0x01;
+        fs::write(&data_path, bytes)?;
+
+        let dict = manager.init()?;
+        assert!(with_suffix(&data_path, ".corrupted").exists());
+        assert_eq!(
+            vec![Phrase::new("行", 5).with_time(0)],
+            dict.lookup(&[syl![H, ANG, TONE2]], LookupStrategy::Standard)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn init_deleted_restores_backup() -> Result<(), Box<dyn std::error::Error>> {
+        let tmpdir = tempfile::tempdir()?;
+        let manager =
+            UserDictionaryManager::new().userphrase_path(tmpdir.path().join("chewing.dat"));
+        let mut dict = manager.init_deleted()?;
+        dict.add_phrase(&[syl![H, ANG, TONE2]], ("行", 0).into())?;
+        dict.flush()?;
+        drop(dict);
+
+        let data_path = tmpdir.path().join("chewing-deleted.dat");
+        fs::write(&data_path, b"corrupted")?;
+
+        let dict = manager.init_deleted()?;
+        assert!(with_suffix(&data_path, ".corrupted").exists());
+        assert_eq!(
+            vec!["行"],
+            dict.lookup(&[syl![H, ANG, TONE2]], LookupStrategy::Standard)
+                .iter()
+                .map(|it| it.as_str())
+                .collect::<Vec<_>>()
+        );
+        Ok(())
+    }
+}
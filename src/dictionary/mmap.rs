@@ -0,0 +1,133 @@
+//! Dictionary file contents that can be shared between processes.
+//!
+//! With the `mmap` feature on Unix, dictionary files can be memory mapped
+//! read-only so every process using the same file shares the page cache.
+//! Otherwise, or if mapping fails, the file is read into memory.
+
+use std::{
+    fs::File,
+    io::{self, Read},
+    ops::Deref,
+};
+
+/// The content of a dictionary file.
+#[derive(Debug)]
+pub(crate) enum FileBytes {
+    Read(Box<[u8]>),
+    #[cfg(all(unix, feature = "mmap"))]
+    Mapped(sys::Mmap),
+}
+
+impl FileBytes {
+    pub(crate) fn read_from<T: Read>(mut stream: T) -> io::Result<FileBytes> {
+        let mut buf = vec![];
+        stream.read_to_end(&mut buf)?;
+        Ok(FileBytes::Read(buf.into_boxed_slice()))
+    }
+    /// Maps the file if possible, otherwise reads the file.
+    pub(crate) fn map_or_read(file: &mut File) -> io::Result<FileBytes> {
+        #[cfg(all(unix, feature = "mmap"))]
+        match sys::Mmap::map(file) {
+            Ok(mmap) => return Ok(FileBytes::Mapped(mmap)),
+            Err(error) => log::info!("Failed to map dictionary file, reading it: {error}"),
+        }
+        FileBytes::read_from(file)
+    }
+    pub(crate) fn is_mapped(&self) -> bool {
+        match self {
+            FileBytes::Read(_) => false,
+            #[cfg(all(unix, feature = "mmap"))]
+            FileBytes::Mapped(_) => true,
+        }
+    }
+}
+
+impl Deref for FileBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            FileBytes::Read(bytes) => bytes,
+            #[cfg(all(unix, feature = "mmap"))]
+            FileBytes::Mapped(mmap) => mmap,
+        }
+    }
+}
+
+#[cfg(all(unix, feature = "mmap"))]
+#[allow(unsafe_code)]
+mod sys {
+    use std::{
+        fmt::{self, Debug},
+        fs::File,
+        io,
+        ops::Deref,
+        os::fd::AsRawFd,
+        ptr, slice,
+    };
+
+    /// A read-only private mapping of a whole file.
+    pub(crate) struct Mmap {
+        ptr: *const u8,
+        len: usize,
+    }
+
+    // SAFETY: the mapping is read-only and owned by this value.
+    unsafe impl Send for Mmap {}
+    // SAFETY: the mapping is never written through this value.
+    unsafe impl Sync for Mmap {}
+
+    impl Mmap {
+        pub(crate) fn map(file: &File) -> io::Result<Mmap> {
+            let len = usize::try_from(file.metadata()?.len()).map_err(io::Error::other)?;
+            if len == 0 {
+                // Empty mappings are invalid.
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "empty file"));
+            }
+            // SAFETY: a new mapping doesn't alias any Rust memory. The file
+            // must not be modified in place while mapped, dictionary files
+            // are always replaced by renaming a new file.
+            let ptr = unsafe {
+                libc::mmap(
+                    ptr::null_mut(),
+                    len,
+                    libc::PROT_READ,
+                    libc::MAP_PRIVATE,
+                    file.as_raw_fd(),
+                    0,
+                )
+            };
+            if ptr == libc::MAP_FAILED {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(Mmap {
+                ptr: ptr as *const u8,
+                len,
+            })
+        }
+    }
+
+    impl Deref for Mmap {
+        type Target = [u8];
+
+        fn deref(&self) -> &[u8] {
+            // SAFETY: the mapping is valid for len bytes until dropped.
+            unsafe { slice::from_raw_parts(self.ptr, self.len) }
+        }
+    }
+
+    impl Drop for Mmap {
+        fn drop(&mut self) {
+            // SAFETY: the mapping was created by Mmap::map with the length.
+            unsafe {
+                libc::munmap(self.ptr as *mut libc::c_void, self.len);
+            }
+        }
+    }
+
+    impl Debug for Mmap {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("Mmap").field("len", &self.len).finish()
+        }
+    }
+}
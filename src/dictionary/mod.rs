@@ -3,10 +3,11 @@
 use std::{
     any::Any,
     borrow::Borrow,
-    cmp::Ordering,
+    cmp::{Ordering, Reverse},
     error::Error,
     fmt::{Debug, Display},
     path::Path,
+    sync::atomic::{AtomicU64, Ordering as AtomicOrdering},
 };
 
 pub use self::layered::Layered;
@@ -20,10 +21,11 @@ pub use self::trie::{Trie, TrieBuilder, TrieOpenOptions, TrieStatistics};
 pub use self::trie_buf::TrieBuf;
 pub use self::usage::DictionaryUsage;
 use crate::exn::Exn;
-use crate::zhuyin::Syllable;
+use crate::zhuyin::{ConfusionSet, Syllable};
 
 mod layered;
 mod loader;
+mod mmap;
 #[cfg(feature = "sqlite")]
 mod sqlite;
 mod trie;
@@ -99,6 +101,7 @@ pub struct Phrase {
     text: Box<str>,
     freq: u32,
     last_used: Option<u64>,
+    comment: Option<Box<str>>,
 }
 
 impl Phrase {
@@ -119,6 +122,7 @@ impl Phrase {
             text: phrase.into(),
             freq,
             last_used: None,
+            comment: None,
         }
     }
     /// Sets the last used time of the phrase.
@@ -126,6 +130,24 @@ impl Phrase {
         self.last_used = Some(last_used);
         self
     }
+    /// Sets the comment of the phrase, e.g. 簡稱 or 異體字.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use chewing::dictionary::Phrase;
+    ///
+    /// let phrase = Phrase::new("北市", 1).with_comment("簡稱");
+    ///
+    /// assert_eq!(Some("簡稱"), phrase.comment());
+    /// ```
+    pub fn with_comment<S>(mut self, comment: S) -> Phrase
+    where
+        S: Into<Box<str>>,
+    {
+        self.comment = Some(comment.into());
+        self
+    }
     /// Returns the frequency of the phrase.
     ///
     /// # Examples
@@ -146,6 +168,10 @@ impl Phrase {
     pub fn last_used(&self) -> Option<u64> {
         self.last_used
     }
+    /// Returns the comment of the phrase stored in the dictionary.
+    pub fn comment(&self) -> Option<&str> {
+        self.comment.as_deref()
+    }
     /// Returns the inner str of the phrase.
     ///
     /// # Examples
@@ -269,6 +295,74 @@ pub type Phrases<'a> = Box<dyn Iterator<Item = Phrase> + 'a>;
 /// ```
 pub type Entries<'a> = Box<dyn Iterator<Item = (Vec<Syllable>, Phrase)> + 'a>;
 
+/// Returns a new dictionary generation, greater than all generations
+/// returned before.
+///
+/// See [`Dictionary::generation`].
+pub fn next_generation() -> u64 {
+    static NEXT_GENERATION: AtomicU64 = AtomicU64::new(1);
+    NEXT_GENERATION.fetch_add(1, AtomicOrdering::Relaxed)
+}
+
+/// Collects the entries of the phrase, the most frequent reading first.
+pub(crate) fn reverse_lookup_by_scan(
+    entries: Entries<'_>,
+    phrase: &str,
+) -> Vec<(Vec<Syllable>, Phrase)> {
+    let mut readings: Vec<_> = entries.filter(|(_, it)| it.as_str() == phrase).collect();
+    readings.sort_by_key(|(_, it)| Reverse(it.freq()));
+    readings
+}
+
+/// Collects the entries starting with the syllables and the text, the most
+/// frequent phrase first.
+pub(crate) fn lookup_prefix_by_scan(
+    entries: Entries<'_>,
+    syllables: &[Syllable],
+    text: &str,
+    limit: usize,
+) -> Vec<(Vec<Syllable>, Phrase)> {
+    let mut top = TopPhrases::new(limit);
+    for (reading, phrase) in entries {
+        if reading.starts_with(syllables) && phrase.as_str().starts_with(text) {
+            top.push(reading, phrase);
+        }
+    }
+    top.into_vec()
+}
+
+/// Keeps the `limit` most frequent phrases pushed, the earlier pushed first
+/// among phrases with the same frequency.
+pub(crate) struct TopPhrases {
+    limit: usize,
+    phrases: Vec<(Vec<Syllable>, Phrase)>,
+}
+
+impl TopPhrases {
+    pub(crate) fn new(limit: usize) -> TopPhrases {
+        TopPhrases {
+            limit,
+            phrases: vec![],
+        }
+    }
+    pub(crate) fn push(&mut self, reading: Vec<Syllable>, phrase: Phrase) {
+        self.phrases.push((reading, phrase));
+        // Drop the less frequent phrases once in a while so that only
+        // O(limit) phrases are kept.
+        if self.phrases.len() >= self.limit.saturating_mul(2).max(64) {
+            self.truncate();
+        }
+    }
+    pub(crate) fn into_vec(mut self) -> Vec<(Vec<Syllable>, Phrase)> {
+        self.truncate();
+        self.phrases
+    }
+    fn truncate(&mut self) {
+        self.phrases.sort_by_key(|(_, it)| Reverse(it.freq()));
+        self.phrases.truncate(self.limit);
+    }
+}
+
 /// The lookup strategy hint for dictionary.
 ///
 /// If the dictionary supports the lookup strategy it should try to use.
@@ -280,6 +374,34 @@ pub enum LookupStrategy {
     Standard,
     /// Try to fuzzy match partial syllables using only preffix.
     FuzzyPartialPrefix,
+    /// Match syllables without a tone with all tones of the syllable.
+    ///
+    /// See [`Syllable::matches_toneless`].
+    Toneless,
+    /// Match syllables with the pairs of bopomofo in the set treated as the
+    /// same sound. Exact matches are returned before the fuzzy ones.
+    ///
+    /// See [`Syllable::matches_confusable`].
+    Confusable(ConfusionSet),
+    /// Match syllables with only an initial with all syllables with the
+    /// initial, e.g. ㄊㄅ for ㄊㄞˊㄅㄟˇ.
+    ///
+    /// See [`Syllable::matches_abbreviated`].
+    Abbreviated,
+}
+
+impl LookupStrategy {
+    /// Returns whether the syllable `key` in a dictionary matches the
+    /// looked up `syllable` using this strategy.
+    pub fn matches(&self, syllable: Syllable, key: Syllable) -> bool {
+        match self {
+            LookupStrategy::Standard => syllable == key,
+            LookupStrategy::FuzzyPartialPrefix => key.starts_with(syllable),
+            LookupStrategy::Toneless => syllable.matches_toneless(key),
+            LookupStrategy::Confusable(confusions) => syllable.matches_confusable(key, *confusions),
+            LookupStrategy::Abbreviated => syllable.matches_abbreviated(key),
+        }
+    }
 }
 
 /// An interface for looking up dictionaries.
@@ -327,6 +449,16 @@ pub trait Dictionary: Debug {
     fn reopen(&mut self) -> Result<(), UpdateDictionaryError> {
         Err(UpdateDictionaryError::new("unimplemented"))
     }
+    /// Returns the generation of the phrases in the dictionary.
+    ///
+    /// The dictionary takes a new generation from [`next_generation()`]
+    /// when it's created and whenever its phrases or usage change, so the
+    /// phrases looked up from it can be cached until the generation
+    /// changes. The default implementation returns a new generation each
+    /// time, which disables such caches.
+    fn generation(&self) -> u64 {
+        next_generation()
+    }
     /// Flushes all the changes back to the filesystem
     ///
     /// The change made to the dictionary might not be persisted without
@@ -377,6 +509,64 @@ pub trait Dictionary: Debug {
     ) -> Result<(), UpdateDictionaryError> {
         Err(UpdateDictionaryError::new("unimplemented"))
     }
+    /// Returns how many times the phrase `right` was committed right after
+    /// the phrase `left`, and the last time it happened.
+    fn lookup_bigram(&self, _left: &str, _right: &str) -> Option<(u32, u64)> {
+        None
+    }
+    /// Returns the phrases committed right after the phrase `left`, with
+    /// their counts and last used times, the most frequent first.
+    fn lookup_successors(&self, _left: &str) -> Vec<(String, u32, u64)> {
+        vec![]
+    }
+    /// Returns the information of the dictionary providing the phrase.
+    ///
+    /// The default implementation returns [`about()`](Dictionary::about) if
+    /// the phrase can be found in this dictionary.
+    fn lookup_source(
+        &self,
+        syllables: &[Syllable],
+        phrase: &str,
+        strategy: LookupStrategy,
+    ) -> Option<DictionaryInfo> {
+        self.lookup(syllables, strategy)
+            .iter()
+            .any(|p| p.as_str() == phrase)
+            .then(|| self.about())
+    }
+    /// Returns all readings of the phrase with their frequencies, the most
+    /// frequent reading first.
+    ///
+    /// The default implementation scans all
+    /// [`entries()`](Dictionary::entries).
+    fn reverse_lookup(&self, phrase: &str) -> Vec<(Vec<Syllable>, Phrase)> {
+        reverse_lookup_by_scan(self.entries(), phrase)
+    }
+    /// Returns at most `limit` phrases whose readings start with the
+    /// syllables and whose texts start with `text`, together with their
+    /// full readings, the most frequent phrase first.
+    ///
+    /// Phrases with exactly the syllables are included, and an empty `text`
+    /// matches all phrases. The default implementation scans all
+    /// [`entries()`](Dictionary::entries).
+    fn lookup_prefix(
+        &self,
+        syllables: &[Syllable],
+        text: &str,
+        limit: usize,
+    ) -> Vec<(Vec<Syllable>, Phrase)> {
+        lookup_prefix_by_scan(self.entries(), syllables, text, limit)
+    }
+    /// Sets the count and the last used time of the phrase pair.
+    fn update_bigram(
+        &mut self,
+        _left: &str,
+        _right: &str,
+        _count: u32,
+        _time: u64,
+    ) -> Result<(), UpdateDictionaryError> {
+        Err(UpdateDictionaryError::new("unimplemented"))
+    }
 }
 
 /// TODO: doc
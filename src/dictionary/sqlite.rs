@@ -10,7 +10,7 @@ use rusqlite::{Connection, Error as RusqliteError, OpenFlags, OptionalExtension,
 
 use super::{
     BuildDictionaryError, Dictionary, DictionaryBuilder, DictionaryInfo, Entries, LookupStrategy,
-    Phrase, UpdateDictionaryError,
+    Phrase, UpdateDictionaryError, next_generation,
 };
 use crate::{dictionary::DictionaryUsage, exn::ResultExt, zhuyin::Syllable};
 
@@ -85,6 +85,7 @@ pub struct SqliteDictionary {
     path: Option<PathBuf>,
     info: DictionaryInfo,
     readonly: bool,
+    generation: u64,
 }
 
 impl SqliteDictionary {
@@ -107,6 +108,7 @@ impl SqliteDictionary {
             path: Some(path),
             info,
             readonly: false,
+            generation: next_generation(),
         })
     }
 
@@ -124,6 +126,7 @@ impl SqliteDictionary {
             path: Some(path),
             info,
             readonly: true,
+            generation: next_generation(),
         })
     }
 
@@ -139,6 +142,7 @@ impl SqliteDictionary {
             path: None,
             info,
             readonly: false,
+            generation: next_generation(),
         })
     }
 
@@ -160,6 +164,11 @@ impl SqliteDictionary {
             [],
         )?;
 
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS dictionary_v1_phrase ON dictionary_v1 (phrase)",
+            [],
+        )?;
+
         conn.execute(
             "CREATE TABLE IF NOT EXISTS userphrase_v2 (
                 id INTEGER PRIMARY KEY,
@@ -169,6 +178,17 @@ impl SqliteDictionary {
             [],
         )?;
 
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS userbigram_v1 (
+                left_phrase TEXT NOT NULL,
+                right_phrase TEXT NOT NULL,
+                count INTEGER NOT NULL,
+                time INTEGER NOT NULL,
+                PRIMARY KEY (left_phrase, right_phrase)
+            ) WITHOUT ROWID",
+            [],
+        )?;
+
         conn.execute(
             "CREATE TABLE IF NOT EXISTS migration_v1 (name TEXT PRIMARY KEY) WITHOUT ROWID",
             [],
@@ -324,11 +344,70 @@ impl SqliteDictionary {
         }
         Ok(info)
     }
+
+    /// Looks up the phrases matching syllables without a tone with all
+    /// tones, abbreviated syllables with all syllables of the initial, or
+    /// matching the syllables with phonetic confusions.
+    ///
+    /// The matching syllables are not indexed so all phrases of the same
+    /// length are scanned. Exact matches are returned first.
+    fn lookup_by_scan(&self, syllables: &[Syllable], strategy: LookupStrategy) -> Vec<Phrase> {
+        let mut stmt = self
+            .conn
+            .prepare_cached(
+                "SELECT
+                    syllables,
+                    phrase,
+                    max(freq, coalesce(user_freq, 0)),
+                    time
+                FROM dictionary_v1 LEFT JOIN userphrase_v2 ON userphrase_id = id
+                WHERE length(syllables) = ?
+                ORDER BY sort_id ASC, max(freq, coalesce(user_freq, 0)) DESC, phrase DESC",
+            )
+            .expect("SQL error");
+        let exact_bytes = syllables.to_bytes();
+        let mut phrases = stmt
+            .query_map([(syllables.len() * 2) as i64], |row| {
+                let (syllables_bytes, phrase, freq, time): (Vec<u8>, Box<str>, _, Option<i64>) =
+                    row.try_into()?;
+                let mut phrase = Phrase::new(phrase, freq);
+                if let Some(last_used) = time {
+                    phrase = phrase.with_time(last_used as u64);
+                }
+                Ok((syllables_bytes, phrase))
+            })
+            .unwrap()
+            .map(|r| r.unwrap())
+            .filter(|(syllables_bytes, _)| {
+                syllables_bytes
+                    .chunks_exact(2)
+                    .zip(syllables)
+                    .all(|(bytes, syl)| {
+                        let syl_u16 = u16::from_le_bytes([bytes[0], bytes[1]]);
+                        Syllable::try_from(syl_u16).is_ok_and(|other| strategy.matches(*syl, other))
+                    })
+            })
+            .map(|(syllables_bytes, phrase)| (syllables_bytes == exact_bytes, phrase))
+            .collect::<Vec<_>>();
+        phrases.sort_by_key(|(exact, _)| !exact);
+        phrases.into_iter().map(|(_, phrase)| phrase).collect()
+    }
 }
 
 impl Dictionary for SqliteDictionary {
     fn lookup(&self, syllables: &[Syllable], strategy: LookupStrategy) -> Vec<Phrase> {
-        let _ = strategy;
+        match strategy {
+            LookupStrategy::Toneless if syllables.iter().any(|syl| !syl.has_tone()) => {
+                return self.lookup_by_scan(syllables, strategy);
+            }
+            LookupStrategy::Confusable(confusions) if !confusions.is_empty() => {
+                return self.lookup_by_scan(syllables, strategy);
+            }
+            LookupStrategy::Abbreviated if syllables.iter().any(|syl| syl.is_initial_only()) => {
+                return self.lookup_by_scan(syllables, strategy);
+            }
+            _ => (),
+        }
         let syllables_bytes = syllables.to_bytes();
         let mut stmt = self
             .conn
@@ -390,6 +469,77 @@ impl Dictionary for SqliteDictionary {
         )
     }
 
+    fn reverse_lookup(&self, phrase: &str) -> Vec<(Vec<Syllable>, Phrase)> {
+        let mut stmt = self
+            .conn
+            .prepare_cached(
+                "SELECT syllables, max(freq, coalesce(user_freq, 0)), time
+                FROM dictionary_v1 LEFT JOIN userphrase_v2 ON userphrase_id = id
+                WHERE phrase = ?
+                ORDER BY max(freq, coalesce(user_freq, 0)) DESC, sort_id ASC",
+            )
+            .expect("SQL error");
+        stmt.query_map([phrase], |row| {
+            let (syllables_bytes, freq, time): (Vec<u8>, _, Option<i64>) = row.try_into()?;
+            let syllables = syllables_bytes
+                .chunks_exact(2)
+                .map(|bytes| Syllable::try_from(u16::from_le_bytes([bytes[0], bytes[1]])))
+                .collect::<Result<Vec<_>, _>>();
+            let mut phrase = Phrase::new(phrase, freq);
+            if let Some(last_used) = time {
+                phrase = phrase.with_time(last_used as u64);
+            }
+            Ok(syllables.ok().map(|syllables| (syllables, phrase)))
+        })
+        .unwrap()
+        .filter_map(|r| r.unwrap())
+        .collect()
+    }
+
+    fn lookup_prefix(
+        &self,
+        syllables: &[Syllable],
+        text: &str,
+        limit: usize,
+    ) -> Vec<(Vec<Syllable>, Phrase)> {
+        let lower = syllables.to_bytes();
+        // Readings with the prefix sort before the prefix with its last
+        // byte incremented. No byte of an encoded syllable reaches 0xFF.
+        let mut upper = lower.clone();
+        match upper.last_mut() {
+            Some(last) => *last += 1,
+            None => upper.push(0xFF),
+        }
+        let mut stmt = self
+            .conn
+            .prepare_cached(
+                "SELECT syllables, phrase, max(freq, coalesce(user_freq, 0)), time
+                FROM dictionary_v1 LEFT JOIN userphrase_v2 ON userphrase_id = id
+                WHERE syllables >= ?1 AND syllables < ?2
+                AND substr(phrase, 1, length(?3)) = ?3
+                ORDER BY max(freq, coalesce(user_freq, 0)) DESC, sort_id ASC
+                LIMIT ?4",
+            )
+            .expect("SQL error");
+        let limit = i64::try_from(limit).unwrap_or(i64::MAX);
+        stmt.query_map(params![lower, upper, text, limit], |row| {
+            let (syllables_bytes, phrase, freq, time): (Vec<u8>, Box<str>, _, Option<i64>) =
+                row.try_into()?;
+            let syllables = syllables_bytes
+                .chunks_exact(2)
+                .map(|bytes| Syllable::try_from(u16::from_le_bytes([bytes[0], bytes[1]])))
+                .collect::<Result<Vec<_>, _>>();
+            let mut phrase = Phrase::new(phrase, freq);
+            if let Some(last_used) = time {
+                phrase = phrase.with_time(last_used as u64);
+            }
+            Ok(syllables.ok().map(|syllables| (syllables, phrase)))
+        })
+        .unwrap()
+        .filter_map(|r| r.unwrap())
+        .collect()
+    }
+
     fn about(&self) -> DictionaryInfo {
         self.info.clone()
     }
@@ -400,10 +550,17 @@ impl Dictionary for SqliteDictionary {
 
     fn set_usage(&mut self, _usage: DictionaryUsage) {}
 
+    /// Other processes may have changed the database, so the dictionary
+    /// takes a new generation.
     fn reopen(&mut self) -> Result<(), UpdateDictionaryError> {
+        self.generation = next_generation();
         Ok(())
     }
 
+    fn generation(&self) -> u64 {
+        self.generation
+    }
+
     fn flush(&mut self) -> Result<(), UpdateDictionaryError> {
         let make_error = |e| UpdateDictionaryError {
             message: "flush sqlite failed",
@@ -449,6 +606,7 @@ impl Dictionary for SqliteDictionary {
             .map_err(make_error)?;
         stmt.execute(params![syllables_bytes, phrase.as_str(), phrase.freq()])
             .map_err(make_error)?;
+        self.generation = next_generation();
         Ok(())
     }
 
@@ -517,6 +675,7 @@ impl Dictionary for SqliteDictionary {
             }
         }
         tx.commit().map_err(make_error)?;
+        self.generation = next_generation();
         Ok(())
     }
 
@@ -536,6 +695,75 @@ impl Dictionary for SqliteDictionary {
             .map_err(make_error)?;
         stmt.execute(params![syllables_bytes, phrase_str])
             .map_err(make_error)?;
+        self.generation = next_generation();
+        Ok(())
+    }
+
+    fn lookup_bigram(&self, left: &str, right: &str) -> Option<(u32, u64)> {
+        // Dictionaries created by older versions might not have the table.
+        let mut stmt = self
+            .conn
+            .prepare_cached(
+                "SELECT count, time FROM userbigram_v1
+                WHERE left_phrase = ? AND right_phrase = ?",
+            )
+            .ok()?;
+        let (count, time): (u32, i64) = stmt
+            .query_row(params![left, right], |row| row.try_into())
+            .optional()
+            .ok()??;
+        Some((count, time.max(0) as u64))
+    }
+
+    fn lookup_successors(&self, left: &str) -> Vec<(String, u32, u64)> {
+        // Dictionaries created by older versions might not have the table.
+        let Ok(mut stmt) = self.conn.prepare_cached(
+            "SELECT right_phrase, count, time FROM userbigram_v1
+            WHERE left_phrase = ?
+            ORDER BY count DESC, time DESC",
+        ) else {
+            return vec![];
+        };
+        stmt.query_map([left], |row| {
+            let (right, count, time): (String, u32, i64) = row.try_into()?;
+            Ok((right, count, time.max(0) as u64))
+        })
+        .map(|rows| rows.filter_map(|row| row.ok()).collect())
+        .unwrap_or_default()
+    }
+
+    fn update_bigram(
+        &mut self,
+        left: &str,
+        right: &str,
+        count: u32,
+        time: u64,
+    ) -> Result<(), UpdateDictionaryError> {
+        let make_error = |e| UpdateDictionaryError {
+            message: "update phrase pair in sqlite failed",
+            source: Some(Box::new(e)),
+        };
+        // sqlite only supports i64
+        let time: i64 = time.clamp(0, i64::MAX as u64) as i64;
+        if self.readonly {
+            return Err(UpdateDictionaryError {
+                message: "sqlite dictionary is readonly",
+                source: None,
+            });
+        }
+        let mut stmt = self
+            .conn
+            .prepare_cached(
+                "INSERT OR REPLACE INTO userbigram_v1 (
+                    left_phrase,
+                    right_phrase,
+                    count,
+                    time
+                ) VALUES (?, ?, ?, ?)",
+            )
+            .map_err(make_error)?;
+        stmt.execute(params![left, right, count, time])
+            .map_err(make_error)?;
         Ok(())
     }
 }
@@ -640,7 +868,7 @@ mod tests {
             Dictionary, DictionaryBuilder, LookupStrategy, Phrase, SqliteDictionaryBuilder,
         },
         syl,
-        zhuyin::Bopomofo,
+        zhuyin::{Bopomofo, ConfusionSet},
     };
 
     #[test]
@@ -736,6 +964,42 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn lookup_toneless() -> Result<(), Box<dyn Error>> {
+        let mut dict = SqliteDictionary::open_in_memory()?;
+        dict.add_phrase(
+            &[
+                syl![Bopomofo::C, Bopomofo::E, Bopomofo::TONE4],
+                syl![Bopomofo::SH, Bopomofo::TONE4],
+            ],
+            ("測試", 9318).into(),
+        )?;
+        dict.add_phrase(&[syl![Bopomofo::SH, Bopomofo::TONE4]], ("試", 100).into())?;
+        assert_eq!(
+            vec![Phrase::new("測試", 9318)],
+            dict.lookup(
+                &[syl![Bopomofo::C, Bopomofo::E], syl![Bopomofo::SH]],
+                LookupStrategy::Toneless
+            )
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn lookup_confusable() -> Result<(), Box<dyn Error>> {
+        let mut dict = SqliteDictionary::open_in_memory()?;
+        dict.add_phrase(&[syl![Bopomofo::ZH, Bopomofo::TONE4]], ("至", 100).into())?;
+        dict.add_phrase(&[syl![Bopomofo::Z, Bopomofo::TONE4]], ("字", 10).into())?;
+        assert_eq!(
+            vec![Phrase::new("字", 10), Phrase::new("至", 100)],
+            dict.lookup(
+                &[syl![Bopomofo::Z, Bopomofo::TONE4]],
+                LookupStrategy::Confusable(ConfusionSet::all())
+            )
+        );
+        Ok(())
+    }
+
     #[test]
     fn update_user_freq() -> Result<(), Box<dyn Error>> {
         let mut dict = SqliteDictionary::open_in_memory()?;
@@ -757,4 +1021,79 @@ mod tests {
         );
         Ok(())
     }
+
+    #[test]
+    fn reverse_lookup() -> Result<(), Box<dyn Error>> {
+        let mut dict = SqliteDictionary::open_in_memory()?;
+        let xing2 = syl![Bopomofo::X, Bopomofo::I, Bopomofo::ENG, Bopomofo::TONE2];
+        let hang2 = syl![Bopomofo::H, Bopomofo::ANG, Bopomofo::TONE2];
+        dict.add_phrase(&[xing2], ("行", 10).into())?;
+        dict.add_phrase(&[hang2], ("行", 5).into())?;
+        dict.add_phrase(&[xing2], ("型", 3).into())?;
+        dict.update_phrase(&[hang2], ("行", 5).into(), 20, 1)?;
+        assert_eq!(
+            vec![(vec![hang2], 20), (vec![xing2], 10)],
+            dict.reverse_lookup("行")
+                .into_iter()
+                .map(|(syllables, phrase)| (syllables, phrase.freq()))
+                .collect::<Vec<_>>()
+        );
+        assert!(dict.reverse_lookup("銀行").is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn lookup_prefix() -> Result<(), Box<dyn Error>> {
+        let mut dict = SqliteDictionary::open_in_memory()?;
+        let yin2 = syl![Bopomofo::I, Bopomofo::EN, Bopomofo::TONE2];
+        let yin3 = syl![Bopomofo::I, Bopomofo::EN, Bopomofo::TONE3];
+        let hang2 = syl![Bopomofo::H, Bopomofo::ANG, Bopomofo::TONE2];
+        let he2 = syl![Bopomofo::H, Bopomofo::E, Bopomofo::TONE2];
+        dict.add_phrase(&[yin2], ("銀", 50).into())?;
+        dict.add_phrase(&[yin2, hang2], ("銀行", 80).into())?;
+        dict.add_phrase(&[yin2, he2], ("銀河", 30).into())?;
+        dict.add_phrase(&[yin3], ("引", 100).into())?;
+        dict.update_phrase(&[yin2, he2], ("銀河", 30).into(), 90, 1)?;
+        assert_eq!(
+            vec![("銀河", 90), ("銀行", 80), ("銀", 50)],
+            dict.lookup_prefix(&[yin2], "", 10)
+                .iter()
+                .map(|(_, phrase)| (phrase.as_str(), phrase.freq()))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![(vec![yin2, he2], 90)],
+            dict.lookup_prefix(&[yin2], "", 1)
+                .into_iter()
+                .map(|(syllables, phrase)| (syllables, phrase.freq()))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![("銀行", 80)],
+            dict.lookup_prefix(&[yin2], "銀行", 10)
+                .iter()
+                .map(|(_, phrase)| (phrase.as_str(), phrase.freq()))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(4, dict.lookup_prefix(&[], "", 10).len());
+        assert!(dict.lookup_prefix(&[he2], "", 10).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn insert_and_update_bigram() -> Result<(), Box<dyn Error>> {
+        let mut dict = SqliteDictionary::open_in_memory()?;
+        assert_eq!(None, dict.lookup_bigram("慢慢", "地"));
+        dict.update_bigram("慢慢", "地", 1, 10)?;
+        dict.update_bigram("慢慢", "地", 2, 20)?;
+        assert_eq!(Some((2, 20)), dict.lookup_bigram("慢慢", "地"));
+        dict.update_bigram("慢慢", "走", 5, 10)?;
+        assert_eq!(
+            vec![("走".to_string(), 5, 10), ("地".to_string(), 2, 20)],
+            dict.lookup_successors("慢慢")
+        );
+        assert!(dict.lookup_successors("地").is_empty());
+        assert_eq!(None, dict.lookup_bigram("地", "慢慢"));
+        Ok(())
+    }
 }
@@ -7,19 +7,21 @@ use std::{
     io::{self, BufWriter, Read, Write},
     iter,
     num::NonZeroUsize,
+    ops::Range,
     path::{Path, PathBuf},
+    sync::Arc,
 };
 
 use der::{
-    DecodeValue, Document, Encode, EncodeValue, ErrorKind, FixedTag, Length, Reader, Sequence,
-    SliceReader, Tag, TagMode, TagNumber, Tagged, Writer,
+    Decode, DecodeValue, Document, Encode, EncodeValue, ErrorKind, FixedTag, Length, Reader,
+    Sequence, SliceReader, Tag, TagMode, TagNumber, Tagged, Writer,
     asn1::{ContextSpecificRef, OctetStringRef, Utf8StringRef},
 };
 use log::{debug, error};
 
 use super::{
     BuildDictionaryError, Dictionary, DictionaryBuilder, DictionaryInfo, Entries, LookupStrategy,
-    Phrase,
+    Phrase, TopPhrases, mmap::FileBytes, next_generation, reverse_lookup_by_scan,
 };
 use crate::{dictionary::DictionaryUsage, exn::ResultExt, zhuyin::Syllable};
 
@@ -58,6 +60,36 @@ impl TrieLeafView<'_> {
     }
 }
 
+struct ReverseRecordView<'a>(&'a [u8]);
+
+impl ReverseRecordView<'_> {
+    const SIZE: usize = 8;
+    fn phrase_begin(&self) -> usize {
+        u32::from_be_bytes(self.0[..4].try_into().unwrap()) as usize
+    }
+    fn reading_begin(&self) -> usize {
+        u32::from_be_bytes(self.0[4..8].try_into().unwrap()) as usize
+    }
+}
+
+/// The optional index from phrases to their readings.
+struct ReverseIndex<'a> {
+    entries: &'a [u8],
+    readings: &'a [u8],
+}
+
+impl ReverseIndex<'_> {
+    fn reading(&self, begin: usize) -> Option<Vec<Syllable>> {
+        let count = u16::from_be_bytes(self.readings.get(begin..begin + 2)?.try_into().unwrap());
+        let end = begin + 2 + count as usize * 2;
+        let (syllables, _) = self.readings.get(begin + 2..end)?.as_chunks::<2>();
+        syllables
+            .iter()
+            .map(|bytes| Syllable::try_from(u16::from_be_bytes(*bytes)).ok())
+            .collect()
+    }
+}
+
 /// A read-only dictionary using a pre-built [Trie][] index that is both space
 /// efficient and fast to lookup.
 ///
@@ -107,8 +139,13 @@ impl TrieLeafView<'_> {
 pub struct Trie {
     info: DictionaryInfo,
     path: Option<PathBuf>,
-    index: Box<[u8]>,
-    phrase_seq: Box<[u8]>,
+    /// The file content shared by the clones.
+    data: Arc<FileBytes>,
+    index: Range<usize>,
+    phrase_seq: Range<usize>,
+    /// The ranges of the reverse index entries and readings.
+    reverse_index: Option<(Range<usize>, Range<usize>)>,
+    generation: u64,
 
     fuzzy_search: bool,
 }
@@ -117,6 +154,10 @@ fn io_error(e: impl Into<Box<dyn Error + Send + Sync>>) -> io::Error {
     io::Error::other(e)
 }
 
+fn invalid_data(e: impl Into<Box<dyn Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
 impl Trie {
     /// Creates a new `Trie` instance from a file.
     ///
@@ -167,13 +208,38 @@ impl Trie {
     pub fn enable_fuzzy_search(&mut self, fuzzy_search: bool) {
         self.fuzzy_search = fuzzy_search;
     }
+    fn index(&self) -> &[u8] {
+        &self.data[self.index.clone()]
+    }
+    fn phrase_seq(&self) -> &[u8] {
+        &self.data[self.phrase_seq.clone()]
+    }
+    fn reverse_index(&self) -> Option<ReverseIndex<'_>> {
+        let (entries, readings) = self.reverse_index.clone()?;
+        Some(ReverseIndex {
+            entries: &self.data[entries],
+            readings: &self.data[readings],
+        })
+    }
 }
 
 /// Options and flags which can be used to configure how a trie dictionary is
 /// opened.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct TrieOpenOptions {
     fuzzy_search: bool,
+    mmap: bool,
+    verify_checksum: bool,
+}
+
+impl Default for TrieOpenOptions {
+    fn default() -> TrieOpenOptions {
+        TrieOpenOptions {
+            fuzzy_search: false,
+            mmap: false,
+            verify_checksum: true,
+        }
+    }
 }
 
 impl TrieOpenOptions {
@@ -184,34 +250,114 @@ impl TrieOpenOptions {
         self.fuzzy_search = fuzzy_search;
         self
     }
+    /// Memory maps the file opened with [`open`](Self::open) instead of
+    /// reading it, so processes using the same dictionary share the page
+    /// cache.
+    ///
+    /// Mapping requires the `mmap` feature and is only available on Unix.
+    /// The file is read as usual if mapping is not available or fails.
+    /// Mapped files must not be modified in place, [`TrieBuilder`] replaces
+    /// the files it builds atomically.
+    pub fn mmap(&mut self, mmap: bool) -> &mut Self {
+        self.mmap = mmap;
+        self
+    }
+    /// Verifies the checksum of the file if it has one. Enabled by default.
+    ///
+    /// Opening a file that fails verification returns an error with
+    /// [`io::ErrorKind::InvalidData`]. Files written before checksums were
+    /// added are opened without verification.
+    pub fn verify_checksum(&mut self, verify_checksum: bool) -> &mut Self {
+        self.verify_checksum = verify_checksum;
+        self
+    }
     pub fn open<P: AsRef<Path>>(&self, path: P) -> io::Result<Trie> {
         let path = path.as_ref().to_path_buf();
         let mut file = File::open(&path)?;
-        let mut trie = self.read_from(&mut file)?;
+        let data = if self.mmap {
+            FileBytes::map_or_read(&mut file)?
+        } else {
+            FileBytes::read_from(&mut file)?
+        };
+        debug!("Opening {} mapped: {}", path.display(), data.is_mapped());
+        let mut trie = self.decode(data)?;
         trie.path = Some(path);
         Ok(trie)
     }
-    pub fn read_from<T>(&self, mut stream: T) -> io::Result<Trie>
+    pub fn read_from<T>(&self, stream: T) -> io::Result<Trie>
     where
         T: Read,
     {
-        let mut buf = vec![];
-        stream.read_to_end(&mut buf)?;
-        let trie_dict_doc = Document::try_from(buf).map_err(io_error)?;
-        let trie_ref: TrieFileRef<'_> = trie_dict_doc.decode_msg().map_err(io_error)?;
+        self.decode(FileBytes::read_from(stream)?)
+    }
+    fn decode(&self, data: FileBytes) -> io::Result<Trie> {
+        let trie_ref = TrieFileRef::from_der(&data).map_err(invalid_data)?;
+        if self.verify_checksum
+            && let Some(checksum) = trie_ref.checksum
+        {
+            let checked_len = usize::try_from(trie_ref.checked_len).map_err(invalid_data)?;
+            let value = value_of(&data).map_err(invalid_data)?;
+            if crc32(&value[..checked_len]) != checksum {
+                return Err(invalid_data("dictionary checksum mismatch"));
+            }
+        }
+        // The decoded slices borrow from the data, store their positions.
+        let range_of = |bytes: &[u8]| {
+            let begin = bytes.as_ptr() as usize - data.as_ptr() as usize;
+            begin..begin + bytes.len()
+        };
+        check_sorted_children(trie_ref.index.as_bytes())?;
         let info = trie_ref.info.into();
-        let index = trie_ref.index.as_bytes().into();
-        let phrase_seq = trie_ref.phrase_seq.der_bytes.into();
+        let index = range_of(trie_ref.index.as_bytes());
+        let phrase_seq = range_of(trie_ref.phrase_seq.der_bytes);
+        let reverse_index = trie_ref.reverse_index.map(|reverse_index| {
+            (
+                range_of(reverse_index.entries.as_bytes()),
+                range_of(reverse_index.readings.as_bytes()),
+            )
+        });
         Ok(Trie {
             info,
             path: None,
+            data: Arc::new(data),
             index,
             phrase_seq,
+            reverse_index,
+            generation: next_generation(),
             fuzzy_search: self.fuzzy_search,
         })
     }
 }
 
+/// Checks that the children of every node are sorted by their syllables,
+/// which [`Trie::lookup`] relies on to binary search them.
+///
+/// Out of bound nodes are skipped here and reported by the lookups.
+fn check_sorted_children(dict: &[u8]) -> io::Result<()> {
+    let node_at = |i: usize| TrieNodeView(&dict[i * TrieNodeView::SIZE..][..TrieNodeView::SIZE]);
+    let len = dict.len() / TrieNodeView::SIZE;
+    if len == 0 {
+        return Ok(());
+    }
+    let mut queue = VecDeque::from([0]);
+    while let Some(i) = queue.pop_front() {
+        let node = node_at(i);
+        let begin = node.child_begin() / TrieNodeView::SIZE;
+        let end = node.child_end() / TrieNodeView::SIZE;
+        // Nodes are stored in BFS order, children always come after parents.
+        if begin <= i || begin > end || end > len {
+            continue;
+        }
+        let children = (begin..end).map(|c| (c, node_at(c).syllable()));
+        if !children.clone().is_sorted_by_key(|(_, syl)| syl) {
+            return Err(invalid_data("trie children are not sorted"));
+        }
+        // Leaf nodes have syllable 0 and no children.
+        queue.extend(children.filter(|&(_, syl)| syl != 0).map(|(c, _)| c));
+    }
+    Ok(())
+}
+
 struct PhrasesIter<'a> {
     reader: SliceReader<'a>,
 }
@@ -254,10 +400,38 @@ macro_rules! iter_bail_if_oob {
     };
 }
 
+/// Returns the range of the syllables matching `syl` with the lookup
+/// strategy, if they are contiguous in the sort order of syllables.
+fn matching_range(strategy: LookupStrategy, syl: Syllable) -> Option<(u16, u16)> {
+    let value = syl.to_u16();
+    let mask: u16 = match strategy {
+        LookupStrategy::Standard => 0,
+        LookupStrategy::Abbreviated if syl.is_initial_only() => 0b1_1111_1111,
+        LookupStrategy::Abbreviated => 0,
+        LookupStrategy::FuzzyPartialPrefix => {
+            // See Syllable::starts_with
+            let trailing_zeros = value.trailing_zeros();
+            if trailing_zeros >= 9 {
+                0b1_1111_1111
+            } else if trailing_zeros >= 7 {
+                0b111_1111
+            } else if trailing_zeros >= 3 {
+                0b111
+            } else {
+                0
+            }
+        }
+        LookupStrategy::Toneless if !syl.has_tone() => 0b111,
+        LookupStrategy::Toneless => 0,
+        LookupStrategy::Confusable(_) => return None,
+    };
+    Some((value & !mask, value | mask))
+}
+
 impl Dictionary for Trie {
     fn lookup(&self, syllables: &[Syllable], strategy: LookupStrategy) -> Vec<Phrase> {
-        let dict = self.index.as_ref();
-        let data = self.phrase_seq.as_ref();
+        let dict = self.index();
+        let data = self.phrase_seq();
 
         bail_if_oob!(0, TrieNodeView::SIZE, dict.len());
         let root = TrieNodeView(&dict[..TrieNodeView::SIZE]);
@@ -267,34 +441,37 @@ impl Dictionary for Trie {
             return vec![];
         }
 
-        let search_predicate = match strategy {
-            LookupStrategy::Standard => |n: u16, syl: &Syllable| n == syl.to_u16(),
-            LookupStrategy::FuzzyPartialPrefix => |n: u16, syl: &Syllable| {
-                if n == 0 {
-                    return false;
-                }
-                if let Ok(syllable) = Syllable::try_from(n) {
-                    syllable.starts_with(*syl)
-                } else {
-                    false
-                }
-            },
+        let search_predicate = |n: u16, syl: &Syllable| match strategy {
+            LookupStrategy::Standard => n == syl.to_u16(),
+            _ => Syllable::try_from(n).is_ok_and(|key| strategy.matches(*syl, key)),
         };
 
         // Perform a BFS search to find all leaf nodes
-        let mut threads: VecDeque<TrieNodeView<'_>> = VecDeque::new();
-        threads.push_back(root);
+        let mut threads: VecDeque<(TrieNodeView<'_>, bool)> = VecDeque::new();
+        threads.push_back((root, true));
         for syl in syllables {
             debug_assert!(syl.to_u16() != 0);
             for _ in 0..threads.len() {
-                let node = threads.pop_front().unwrap();
+                let (node, exact) = threads.pop_front().unwrap();
                 bail_if_oob!(node.child_begin(), node.child_end(), dict.len());
-                let child_nodes = dict[node.child_begin()..node.child_end()]
-                    .chunks_exact(TrieNodeView::SIZE)
-                    .map(TrieNodeView);
-                for n in child_nodes {
+                let (child_nodes, _) = dict[node.child_begin()..node.child_end()]
+                    .as_chunks::<{ TrieNodeView::SIZE }>();
+                let child_nodes = match matching_range(strategy, *syl) {
+                    Some((first, last)) => {
+                        // The children are sorted by their syllables, and the
+                        // leaf node, if any, comes first with syllable 0.
+                        let begin = child_nodes
+                            .partition_point(|n| TrieNodeView(n.as_slice()).syllable() < first);
+                        let end = child_nodes
+                            .partition_point(|n| TrieNodeView(n.as_slice()).syllable() <= last);
+                        &child_nodes[begin..end]
+                    }
+                    None => child_nodes,
+                };
+                for n in child_nodes.iter().map(|n| TrieNodeView(n.as_slice())) {
                     if search_predicate(n.syllable(), syl) {
-                        threads.push_back(n);
+                        let exact = exact && n.syllable() == syl.to_u16();
+                        threads.push_back((n, exact));
                     }
                 }
             }
@@ -302,10 +479,14 @@ impl Dictionary for Trie {
                 return vec![];
             }
         }
+        if let LookupStrategy::Confusable(_) = strategy {
+            // Exact matches are ranked above the fuzzy ones
+            threads.make_contiguous().sort_by_key(|(_, exact)| !exact);
+        }
 
         // Collect result from all threads
         let mut result = vec![];
-        for node in threads.into_iter() {
+        for (node, _) in threads.into_iter() {
             bail_if_oob!(node.child_begin(), node.child_end(), dict.len());
             let leaf_data = &dict[node.child_begin()..];
             bail_if_oob!(0, TrieLeafView::SIZE, leaf_data.len());
@@ -321,8 +502,8 @@ impl Dictionary for Trie {
     }
 
     fn entries(&self) -> Entries<'_> {
-        let dict = self.index.as_ref();
-        let data = self.phrase_seq.as_ref();
+        let dict = self.index();
+        let data = self.phrase_seq();
         let mut results = Vec::new();
         let mut stack = Vec::new();
         let mut syllables = Vec::new();
@@ -417,8 +598,107 @@ impl Dictionary for Trie {
         self.path.as_ref().map(|p| p as &Path)
     }
 
+    /// Walks the children of the node matching the syllables, keeping only
+    /// the most frequent phrases matching the text.
+    fn lookup_prefix(
+        &self,
+        syllables: &[Syllable],
+        text: &str,
+        limit: usize,
+    ) -> Vec<(Vec<Syllable>, Phrase)> {
+        let dict = self.index();
+        let data = self.phrase_seq();
+
+        bail_if_oob!(0, TrieNodeView::SIZE, dict.len());
+        let mut node = TrieNodeView(&dict[..TrieNodeView::SIZE]);
+
+        // Return early for empty dictionary
+        if node.child_begin() == node.child_end() {
+            return vec![];
+        }
+
+        for syl in syllables {
+            bail_if_oob!(node.child_begin(), node.child_end(), dict.len());
+            let (child_nodes, _) =
+                dict[node.child_begin()..node.child_end()].as_chunks::<{ TrieNodeView::SIZE }>();
+            let Ok(i) = child_nodes
+                .binary_search_by_key(&syl.to_u16(), |n| TrieNodeView(n.as_slice()).syllable())
+            else {
+                return vec![];
+            };
+            node = TrieNodeView(child_nodes[i].as_slice());
+        }
+
+        // Perform a DFS search to find all leaf nodes under the node
+        let mut top = TopPhrases::new(limit);
+        let mut stack = vec![(node, syllables.to_vec())];
+        while let Some((node, reading)) = stack.pop() {
+            bail_if_oob!(node.child_begin(), node.child_end(), dict.len());
+            let (child_nodes, _) =
+                dict[node.child_begin()..node.child_end()].as_chunks::<{ TrieNodeView::SIZE }>();
+            for child in child_nodes.iter().rev().map(|n| n.as_slice()) {
+                let syllable = TrieNodeView(child).syllable();
+                if syllable == 0 {
+                    let leaf = TrieLeafView(child);
+                    bail_if_oob!(leaf.data_begin(), leaf.data_end(), data.len());
+                    for phrase in PhrasesIter::new(&data[leaf.data_begin()..leaf.data_end()]) {
+                        if phrase.as_str().starts_with(text) {
+                            top.push(reading.clone(), phrase);
+                        }
+                    }
+                } else if let Ok(syl) = Syllable::try_from(syllable) {
+                    let mut child_reading = reading.clone();
+                    child_reading.push(syl);
+                    stack.push((TrieNodeView(child), child_reading));
+                }
+            }
+        }
+        top.into_vec()
+    }
+
+    /// Binary searches the reverse index if the dictionary has one,
+    /// otherwise scans all entries.
+    fn reverse_lookup(&self, phrase: &str) -> Vec<(Vec<Syllable>, Phrase)> {
+        let Some(reverse_index) = self.reverse_index() else {
+            return reverse_lookup_by_scan(self.entries(), phrase);
+        };
+        let data = self.phrase_seq();
+        let decode_phrase = |record: &[u8]| -> Option<Phrase> {
+            let begin = ReverseRecordView(record).phrase_begin();
+            SliceReader::new(data.get(begin..)?).ok()?.decode().ok()
+        };
+        let (records, _) = reverse_index
+            .entries
+            .as_chunks::<{ ReverseRecordView::SIZE }>();
+        let begin = records.partition_point(|record| {
+            decode_phrase(record.as_slice()).is_some_and(|it| it.as_str() < phrase)
+        });
+        let mut result = vec![];
+        for record in &records[begin..] {
+            let Some(found) = decode_phrase(record.as_slice()) else {
+                error!("[!] file corruption detected: index out of bound.");
+                break;
+            };
+            if found.as_str() != phrase {
+                break;
+            }
+            let reading_begin = ReverseRecordView(record.as_slice()).reading_begin();
+            let Some(syllables) = reverse_index.reading(reading_begin) else {
+                error!("[!] file corruption detected: index out of bound.");
+                break;
+            };
+            result.push((syllables, found));
+        }
+        result
+    }
+
     fn set_usage(&mut self, usage: DictionaryUsage) {
         self.info.usage = usage;
+        self.generation = next_generation();
+    }
+
+    fn generation(&self) -> u64 {
+        self.generation
     }
 }
 
@@ -538,6 +818,16 @@ struct TrieFileRef<'a> {
     info: DictionaryInfoRef<'a>,
     index: OctetStringRef<'a>,
     phrase_seq: PhraseSeqRef<'a>,
+    reverse_index: Option<ReverseIndexRef<'a>>,
+    checksum: Option<u32>,
+    /// The length of the encoded fields before the checksum. Only used when
+    /// decoding.
+    checked_len: Length,
+}
+
+struct ReverseIndexRef<'a> {
+    entries: OctetStringRef<'a>,
+    readings: OctetStringRef<'a>,
 }
 
 struct PhraseSeqRef<'a> {
@@ -557,12 +847,18 @@ impl<'a> DecodeValue<'a> for TrieFileRef<'a> {
             let info = reader.decode()?;
             let index = reader.decode()?;
             let phrase_seq = reader.decode()?;
+            let reverse_index = reader.context_specific(TagNumber::N0, TagMode::Implicit)?;
+            let checked_len = reader.position();
+            let checksum = reader.context_specific(TagNumber::N1, TagMode::Implicit)?;
             // consume the remaining unknown data
             let _ = reader.read_slice(reader.remaining_len());
             Ok(Self {
                 info,
                 index,
                 phrase_seq,
+                reverse_index,
+                checksum,
+                checked_len,
             })
         })
     }
@@ -575,6 +871,8 @@ impl EncodeValue for TrieFileRef<'_> {
             + self.info.encoded_len()?
             + self.index.encoded_len()?
             + self.phrase_seq.encoded_len()?
+            + context_specific_opt(0, TagMode::Implicit, &self.reverse_index).encoded_len()?
+            + context_specific_opt(1, TagMode::Implicit, &self.checksum).encoded_len()?
     }
 
     fn encode_value(&self, encoder: &mut impl Writer) -> der::Result<()> {
@@ -583,6 +881,70 @@ impl EncodeValue for TrieFileRef<'_> {
         self.info.encode(encoder)?;
         self.index.encode(encoder)?;
         self.phrase_seq.encode(encoder)?;
+        context_specific_opt(0, TagMode::Implicit, &self.reverse_index).encode(encoder)?;
+        context_specific_opt(1, TagMode::Implicit, &self.checksum).encode(encoder)?;
+        Ok(())
+    }
+}
+
+/// Returns the value octets of a DER encoded document.
+fn value_of(der_bytes: &[u8]) -> der::Result<&[u8]> {
+    let mut reader = SliceReader::new(der_bytes)?;
+    let header = der::Header::decode(&mut reader)?;
+    reader.read_slice(header.length)
+}
+
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                0xEDB88320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// Computes the CRC-32 (IEEE 802.3) checksum of the bytes.
+fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0, |crc, &byte| {
+        CRC32_TABLE[((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+impl FixedTag for ReverseIndexRef<'_> {
+    const TAG: Tag = Tag::Sequence;
+}
+
+impl<'a> DecodeValue<'a> for ReverseIndexRef<'a> {
+    fn decode_value<R: Reader<'a>>(reader: &mut R, header: der::Header) -> der::Result<Self> {
+        reader.read_nested(header.length, |reader| {
+            let entries = reader.decode()?;
+            let readings = reader.decode()?;
+            // consume the remaining unknown data
+            let _ = reader.read_slice(reader.remaining_len());
+            Ok(Self { entries, readings })
+        })
+    }
+}
+
+impl EncodeValue for ReverseIndexRef<'_> {
+    fn value_len(&self) -> der::Result<Length> {
+        self.entries.encoded_len()? + self.readings.encoded_len()?
+    }
+
+    fn encode_value(&self, encoder: &mut impl Writer) -> der::Result<()> {
+        self.entries.encode(encoder)?;
+        self.readings.encode(encoder)?;
         Ok(())
     }
 }
@@ -597,12 +959,15 @@ impl<'a> DecodeValue<'a> for Phrase {
             let phrase: Utf8StringRef<'_> = reader.decode()?;
             let freq = reader.decode()?;
             let last_used = reader.context_specific(TagNumber::N0, TagMode::Implicit)?;
+            let comment: Option<Utf8StringRef<'_>> =
+                reader.context_specific(TagNumber::N1, TagMode::Implicit)?;
             // consume the remaining unknown data
             let _ = reader.read_slice(reader.remaining_len());
             Ok(Phrase {
                 text: String::from(phrase).into_boxed_str(),
                 freq,
                 last_used,
+                comment: comment.map(|comment| String::from(comment).into_boxed_str()),
             })
         })
     }
@@ -610,15 +975,19 @@ impl<'a> DecodeValue<'a> for Phrase {
 
 impl EncodeValue for Phrase {
     fn value_len(&self) -> der::Result<Length> {
+        let comment = self.comment().map(Utf8StringRef::new).transpose()?;
         Utf8StringRef::new(self.as_str())?.encoded_len()?
             + self.freq.encoded_len()?
             + context_specific_opt(0, TagMode::Implicit, &self.last_used).encoded_len()?
+            + context_specific_opt(1, TagMode::Implicit, &comment).encoded_len()?
     }
 
     fn encode_value(&self, encoder: &mut impl Writer) -> der::Result<()> {
+        let comment = self.comment().map(Utf8StringRef::new).transpose()?;
         Utf8StringRef::new(self.as_ref())?.encode(encoder)?;
         self.freq.encode(encoder)?;
         context_specific_opt(0, TagMode::Implicit, &self.last_used).encode(encoder)?;
+        context_specific_opt(1, TagMode::Implicit, &comment).encode(encoder)?;
         Ok(())
     }
 }
@@ -769,7 +1138,9 @@ impl Writer for VecWriter {
 /// - **Child Begin: 32 bits (u32)**
 ///     - The record index of the first child node.
 /// - **Child Len: 16 bits (u16)**
-///     - The number of the child nodes.
+///     - The number of the child nodes. The child nodes must be sorted by
+///       their SyllableU16, the leaf node, if any, comes first. Files with
+///       unsorted child nodes are rejected when opened.
 /// - **SyllableU16: 16 bits (u16)**
 ///     - The [`Syllable`] encoded as an u16 integer.
 ///
@@ -801,6 +1172,37 @@ impl Writer for VecWriter {
 ///     - The frequency of the phrase.
 /// - **Last used: 64 bits (u64) optional**
 ///     - The last used timestamp of a user phrase.
+/// - **Comment: variable length optional**
+///     - UTF-8 encoded note shown with the phrase, e.g. 簡稱 or 異體字.
+///
+/// ### ReverseIndex object
+///
+/// The optional reverseIndex object maps phrases to their readings. It is
+/// only written when enabled with [`enable_reverse_index`][Self::enable_reverse_index].
+///
+/// The entries OCTET STRING contains fixed size records sorted by the UTF-8
+/// order of their phrases, then by the frequency in descending order.
+///
+/// ```text
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |          Phrase Begin           |        Reading Begin        |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// ```
+///
+/// - **Phrase Begin: 32 bits (u32)**
+///     - The offset into the Phrases chunk for the phrase data.
+/// - **Reading Begin: 32 bits (u32)**
+///     - The offset into the readings OCTET STRING for the syllables.
+///
+/// Each reading in the readings OCTET STRING is a 16 bits (u16) count
+/// followed by the count of [`Syllable`]s encoded as u16 integers.
+///
+/// ### Checksum
+///
+/// The optional checksum is the CRC-32 (IEEE 802.3) of the DER encoding of
+/// all the fields before it, from the magic string to the reverseIndex. It
+/// is always written by the builder and verified when the file is opened,
+/// see [`TrieOpenOptions::verify_checksum`].
 ///
 /// [Trie]: https://en.m.wikipedia.org/wiki/Trie
 /// [DER]: https://en.m.wikipedia.org/wiki/X.690#DER_encoding
@@ -810,6 +1212,7 @@ pub struct TrieBuilder {
     // node index.
     arena: Vec<TrieBuilderNode>,
     info: DictionaryInfo,
+    reverse_index: bool,
 }
 
 #[derive(Debug, PartialEq, Default)]
@@ -860,6 +1263,7 @@ impl TrieBuilder {
         TrieBuilder {
             arena: vec![root],
             info: Default::default(),
+            reverse_index: false,
         }
     }
 
@@ -918,6 +1322,16 @@ impl TrieBuilder {
         self.info.usage = usage;
     }
 
+    /// Enable or disable writing the reverse index from phrases to their
+    /// readings.
+    ///
+    /// The reverse index makes [`Dictionary::reverse_lookup`] a binary
+    /// search instead of a scan of all entries, at the cost of a larger
+    /// file. It is disabled by default.
+    pub fn enable_reverse_index(&mut self, reverse_index: bool) {
+        self.reverse_index = reverse_index;
+    }
+
     /// Writes the dictionary to an output stream and returns the number of
     /// bytes written.
     ///
@@ -944,18 +1358,22 @@ impl TrieBuilder {
         let mut dict_buf = Vec::new();
         let mut data_buf = VecWriter::new();
         let mut queue = VecDeque::new();
+        // The phrases with their data offsets and the index of their
+        // syllables, collected only when the reverse index is enabled.
+        let mut reverse_entries: Vec<(&Phrase, usize, usize)> = vec![];
+        let mut leaf_syllables: Vec<Vec<Syllable>> = vec![];
 
         // The root node's child index starts from 1 (0 is the root).
         let mut child_begin = 1;
 
         // Walk the tree in BFS order and write the nodes to the dict buffer.
-        queue.push_back(ROOT_ID);
+        queue.push_back((ROOT_ID, vec![]));
         while !queue.is_empty() {
             // Insert nodes layer by layer.
             let layer_nodes_count = queue.len();
             for _ in 0..layer_nodes_count {
                 // OK to unwrap, we always have at least one queued item.
-                let id = queue.pop_front().unwrap();
+                let (id, syllables) = queue.pop_front().unwrap();
                 let node = &self.arena[id];
 
                 // An internal node has an associated syllable. The root node is
@@ -968,7 +1386,7 @@ impl TrieBuilder {
                     dict_buf.write_all(&(child_len as u16).to_be_bytes())?;
                     dict_buf.write_all(&syllable_u16.to_be_bytes())?;
                 } else {
-                    let mut phrases = node.phrases.clone();
+                    let mut phrases: Vec<&Phrase> = node.phrases.iter().collect();
                     phrases.sort_by(|a, b| {
                         // Don't sort single word leaves.
                         // But sort phrases first by the frequency, then by the UTF-8 order.
@@ -990,8 +1408,14 @@ impl TrieBuilder {
                     let data_begin = data_buf.len();
 
                     for phrase in phrases {
+                        if self.reverse_index {
+                            reverse_entries.push((phrase, data_buf.len(), leaf_syllables.len()));
+                        }
                         phrase.encode(&mut data_buf).map_err(io_error)?;
                     }
+                    if self.reverse_index {
+                        leaf_syllables.push(syllables.clone());
+                    }
 
                     let data_len = data_buf.len() - data_begin;
                     dict_buf.write_all(&(data_begin as u32).to_be_bytes())?;
@@ -999,30 +1423,69 @@ impl TrieBuilder {
                     dict_buf.write_all(&0u16.to_be_bytes())?;
                 }
 
-                // Sort the children nodes by their syllables. Not really required,
-                // but it makes using binary search possible in the future.
+                // Sort the children nodes by their syllables. Required by the
+                // file format, lookups binary search the children.
                 let mut children = node.children.clone();
                 children.sort_by(|&a, &b| self.arena[a].syllable.cmp(&self.arena[b].syllable));
                 if let Some(leaf_id) = node.leaf_id {
                     child_begin += 1;
-                    queue.push_back(leaf_id.get());
+                    queue.push_back((leaf_id.get(), syllables.clone()));
                 }
                 for child_id in children {
                     child_begin += 1;
-                    queue.push_back(child_id);
+                    let mut child_syllables = syllables.clone();
+                    child_syllables.extend(self.arena[child_id].syllable);
+                    queue.push_back((child_id, child_syllables));
                 }
             }
         }
 
+        // Write the reverse index records sorted by the phrases, the most
+        // frequent reading first.
+        let mut reverse_buf = Vec::new();
+        let mut readings_buf = Vec::new();
+        let mut reading_offsets = Vec::new();
+        for syllables in &leaf_syllables {
+            reading_offsets.push(readings_buf.len());
+            readings_buf.write_all(&(syllables.len() as u16).to_be_bytes())?;
+            for syl in syllables {
+                readings_buf.write_all(&syl.to_u16().to_be_bytes())?;
+            }
+        }
+        reverse_entries.sort_by(|a, b| {
+            a.0.as_str()
+                .cmp(b.0.as_str())
+                .then_with(|| b.0.freq().cmp(&a.0.freq()))
+        });
+        for (_, data_begin, reading_id) in reverse_entries {
+            reverse_buf.write_all(&(data_begin as u32).to_be_bytes())?;
+            reverse_buf.write_all(&(reading_offsets[reading_id] as u32).to_be_bytes())?;
+        }
+        let reverse_index = if self.reverse_index {
+            Some(ReverseIndexRef {
+                entries: OctetStringRef::new(&reverse_buf).map_err(io_error)?,
+                readings: OctetStringRef::new(&readings_buf).map_err(io_error)?,
+            })
+        } else {
+            None
+        };
+
         let info = DictionaryInfoRef::new(&self.info);
-        let trie_dict_ref = TrieFileRef {
+        let mut trie_dict_ref = TrieFileRef {
             info,
             index: OctetStringRef::new(&dict_buf).map_err(io_error)?,
             phrase_seq: PhraseSeqRef {
                 der_bytes: &data_buf.buf,
             },
+            reverse_index,
+            checksum: None,
+            checked_len: Length::ZERO,
         };
 
+        // The checksum covers all the fields before it.
+        let document = Document::encode_msg(&trie_dict_ref).map_err(io_error)?;
+        let checksum = crc32(value_of(document.as_bytes()).map_err(io_error)?);
+        trie_dict_ref.checksum = Some(checksum);
         let document = Document::encode_msg(&trie_dict_ref).map_err(io_error)?;
         writer.write_all(document.as_bytes())?;
         Ok(document.as_bytes().len())
@@ -1210,18 +1673,20 @@ impl Default for TrieBuilder {
 #[cfg(test)]
 mod tests {
     use std::{
-        io::{Cursor, Seek},
+        io::{self, Cursor, Seek},
         num::NonZeroUsize,
     };
 
-    use super::{Trie, TrieBuilder};
+    use der::{Decode, Encode};
+
+    use super::{Trie, TrieBuilder, TrieFileRef, TrieNodeView, crc32};
     use crate::{
         dictionary::{
             Dictionary, DictionaryBuilder, DictionaryInfo, DictionaryUsage, LookupStrategy, Phrase,
             TrieOpenOptions, trie::TrieBuilderNode,
         },
         syl,
-        zhuyin::Bopomofo,
+        zhuyin::{Bopomofo, Confusion, ConfusionSet},
     };
 
     #[test]
@@ -1317,6 +1782,209 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn tree_lookup_phrase_with_comment() -> Result<(), Box<dyn std::error::Error>> {
+        let mut builder = TrieBuilder::new();
+        builder.insert(
+            &[syl![Bopomofo::C, Bopomofo::E, Bopomofo::TONE4]],
+            Phrase::new("測", 1).with_comment("測量"),
+        )?;
+        builder.insert(
+            &[syl![Bopomofo::C, Bopomofo::E, Bopomofo::TONE4]],
+            ("冊", 1).into(),
+        )?;
+        let mut cursor = Cursor::new(vec![]);
+        builder.write(&mut cursor)?;
+        cursor.rewind()?;
+        let dict = Trie::new(&mut cursor)?;
+        let phrases = dict.lookup(
+            &[syl![Bopomofo::C, Bopomofo::E, Bopomofo::TONE4]],
+            LookupStrategy::Standard,
+        );
+        assert_eq!(
+            vec![Some("測量"), None],
+            phrases.iter().map(Phrase::comment).collect::<Vec<_>>()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn tree_reverse_lookup() -> Result<(), Box<dyn std::error::Error>> {
+        let xing2 = syl![Bopomofo::X, Bopomofo::I, Bopomofo::ENG, Bopomofo::TONE2];
+        let hang2 = syl![Bopomofo::H, Bopomofo::ANG, Bopomofo::TONE2];
+        let yin2 = syl![Bopomofo::I, Bopomofo::EN, Bopomofo::TONE2];
+        for reverse_index in [false, true] {
+            let mut builder = TrieBuilder::new();
+            builder.enable_reverse_index(reverse_index);
+            builder.insert(&[hang2], ("行", 5).into())?;
+            builder.insert(&[xing2], ("行", 10).into())?;
+            builder.insert(&[xing2], ("型", 3).into())?;
+            builder.insert(&[yin2, hang2], ("銀行", 8).into())?;
+            let mut cursor = Cursor::new(vec![]);
+            builder.write(&mut cursor)?;
+            cursor.rewind()?;
+            let dict = Trie::new(&mut cursor)?;
+            assert_eq!(reverse_index, dict.reverse_index.is_some());
+            assert_eq!(
+                vec![
+                    (vec![xing2], Phrase::new("行", 10)),
+                    (vec![hang2], Phrase::new("行", 5))
+                ],
+                dict.reverse_lookup("行")
+            );
+            assert_eq!(
+                vec![(vec![yin2, hang2], Phrase::new("銀行", 8))],
+                dict.reverse_lookup("銀行")
+            );
+            assert!(dict.reverse_lookup("銀").is_empty());
+            assert!(dict.reverse_lookup("").is_empty());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn open_mmap() -> Result<(), Box<dyn std::error::Error>> {
+        let tmpdir = tempfile::tempdir()?;
+        let path = tmpdir.path().join("dict.dat");
+        let xing2 = syl![Bopomofo::X, Bopomofo::I, Bopomofo::ENG, Bopomofo::TONE2];
+        let hang2 = syl![Bopomofo::H, Bopomofo::ANG, Bopomofo::TONE2];
+        let mut builder = TrieBuilder::new();
+        builder.enable_reverse_index(true);
+        builder.insert(&[hang2], ("行", 5).into())?;
+        builder.insert(&[xing2], ("行", 10).into())?;
+        DictionaryBuilder::build(&mut builder, &path)?;
+        for mmap in [false, true] {
+            let dict = TrieOpenOptions::new().mmap(mmap).open(&path)?;
+            #[cfg(all(unix, feature = "mmap"))]
+            assert_eq!(mmap, dict.data.is_mapped());
+            assert_eq!(
+                vec![Phrase::new("行", 10)],
+                dict.lookup(&[xing2], LookupStrategy::Standard)
+            );
+            assert_eq!(
+                vec![
+                    (vec![xing2], Phrase::new("行", 10)),
+                    (vec![hang2], Phrase::new("行", 5))
+                ],
+                dict.clone().reverse_lookup("行")
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(0, crc32(b""));
+        assert_eq!(0xCBF43926, crc32(b"123456789"));
+    }
+
+    #[test]
+    fn verify_checksum() -> Result<(), Box<dyn std::error::Error>> {
+        let hang2 = syl![Bopomofo::H, Bopomofo::ANG, Bopomofo::TONE2];
+        let mut builder = TrieBuilder::new();
+        builder.insert(&[hang2], ("行", 5).into())?;
+        let mut bytes = vec![];
+        builder.write(&mut bytes)?;
+        let trie_ref = TrieFileRef::from_der(&bytes)?;
+        assert!(trie_ref.checksum.is_some());
+        assert!(TrieOpenOptions::new().read_from(bytes.as_slice()).is_ok());
+
+        // Corrupt the phrase
+        let pos = bytes.windows(3).position(|w| w == "行".as_bytes()).unwrap();
+        bytes[pos + 2] ^= 0x01;
+        let error = TrieOpenOptions::new()
+            .read_from(bytes.as_slice())
+            .unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, error.kind());
+        assert!(
+            TrieOpenOptions::new()
+                .verify_checksum(false)
+                .read_from(bytes.as_slice())
+                .is_ok()
+        );
+
+        // Files without checksum are not verified
+        let mut trie_ref = TrieFileRef::from_der(&bytes)?;
+        trie_ref.checksum = None;
+        let bytes = trie_ref.to_der()?;
+        assert!(TrieOpenOptions::new().read_from(bytes.as_slice()).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn reject_unsorted_children() -> Result<(), Box<dyn std::error::Error>> {
+        let xing2 = syl![Bopomofo::X, Bopomofo::I, Bopomofo::ENG, Bopomofo::TONE2];
+        let hang2 = syl![Bopomofo::H, Bopomofo::ANG, Bopomofo::TONE2];
+        let mut builder = TrieBuilder::new();
+        builder.insert(&[hang2], ("行", 5).into())?;
+        builder.insert(&[xing2], ("行", 10).into())?;
+        let mut bytes = vec![];
+        builder.write(&mut bytes)?;
+
+        // Swap the two children of the root node
+        let trie_ref = TrieFileRef::from_der(&bytes)?;
+        let index = trie_ref.index.as_bytes();
+        let begin = index.as_ptr() as usize - bytes.as_ptr() as usize;
+        let size = TrieNodeView::SIZE;
+        let (first, second) = bytes[begin + size..].split_at_mut(size);
+        first.swap_with_slice(&mut second[..size]);
+
+        let error = TrieOpenOptions::new()
+            .verify_checksum(false)
+            .read_from(bytes.as_slice())
+            .unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, error.kind());
+        Ok(())
+    }
+
+    #[test]
+    fn tree_lookup_prefix() -> Result<(), Box<dyn std::error::Error>> {
+        let yin2 = syl![Bopomofo::I, Bopomofo::EN, Bopomofo::TONE2];
+        let hang2 = syl![Bopomofo::H, Bopomofo::ANG, Bopomofo::TONE2];
+        let he2 = syl![Bopomofo::H, Bopomofo::E, Bopomofo::TONE2];
+        let mut builder = TrieBuilder::new();
+        builder.insert(&[yin2], ("銀", 50).into())?;
+        builder.insert(&[yin2, hang2], ("銀行", 80).into())?;
+        builder.insert(&[yin2, he2], ("銀河", 30).into())?;
+        builder.insert(&[yin2, hang2, he2], ("銀行河", 1).into())?;
+        builder.insert(&[hang2], ("行", 100).into())?;
+        let mut cursor = Cursor::new(vec![]);
+        builder.write(&mut cursor)?;
+        cursor.rewind()?;
+        let dict = Trie::new(&mut cursor)?;
+        assert_eq!(
+            vec![
+                (vec![yin2, hang2], Phrase::new("銀行", 80)),
+                (vec![yin2], Phrase::new("銀", 50)),
+                (vec![yin2, he2], Phrase::new("銀河", 30)),
+                (vec![yin2, hang2, he2], Phrase::new("銀行河", 1)),
+            ],
+            dict.lookup_prefix(&[yin2], "", 10)
+        );
+        assert_eq!(
+            vec![
+                (vec![yin2, hang2], Phrase::new("銀行", 80)),
+                (vec![yin2, hang2, he2], Phrase::new("銀行河", 1)),
+            ],
+            dict.lookup_prefix(&[yin2, hang2], "", 10)
+        );
+        assert_eq!(
+            vec![(vec![yin2, he2], Phrase::new("銀河", 30))],
+            dict.lookup_prefix(&[yin2], "銀河", 10)
+        );
+        assert_eq!(2, dict.lookup_prefix(&[yin2], "", 2).len());
+        assert_eq!(5, dict.lookup_prefix(&[], "", 10).len());
+        assert!(dict.lookup_prefix(&[he2], "", 10).is_empty());
+
+        let mut cursor = Cursor::new(vec![]);
+        TrieBuilder::new().write(&mut cursor)?;
+        cursor.rewind()?;
+        let empty = Trie::new(&mut cursor)?;
+        assert!(empty.lookup_prefix(&[], "", 10).is_empty());
+        Ok(())
+    }
+
     #[test]
     fn tree_lookup_word_fuzzy() -> Result<(), Box<dyn std::error::Error>> {
         let mut builder = TrieBuilder::new();
@@ -1347,6 +2015,114 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn tree_lookup_phrase_confusable() -> Result<(), Box<dyn std::error::Error>> {
+        let mut builder = TrieBuilder::new();
+        builder.insert(
+            &[
+                syl![Bopomofo::ZH, Bopomofo::U, Bopomofo::ENG],
+                syl![Bopomofo::U, Bopomofo::EN, Bopomofo::TONE2],
+            ],
+            ("中文", 100).into(),
+        )?;
+        builder.insert(
+            &[
+                syl![Bopomofo::Z, Bopomofo::U, Bopomofo::ENG],
+                syl![Bopomofo::U, Bopomofo::EN, Bopomofo::TONE2],
+            ],
+            ("宗文", 1).into(),
+        )?;
+        let mut cursor = Cursor::new(vec![]);
+        builder.write(&mut cursor)?;
+        cursor.rewind()?;
+        let dict = TrieOpenOptions::new().read_from(&mut cursor)?;
+        let syllables = [
+            syl![Bopomofo::Z, Bopomofo::U, Bopomofo::ENG],
+            syl![Bopomofo::U, Bopomofo::EN, Bopomofo::TONE2],
+        ];
+        assert_eq!(
+            vec![Phrase::new("宗文", 1), Phrase::new("中文", 100)],
+            dict.lookup(
+                &syllables,
+                LookupStrategy::Confusable(ConfusionSet::new().with(Confusion::ZhZ, true))
+            )
+        );
+        assert_eq!(
+            vec![Phrase::new("宗文", 1)],
+            dict.lookup(
+                &syllables,
+                LookupStrategy::Confusable(ConfusionSet::new().with(Confusion::ChC, true))
+            )
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn tree_lookup_phrase_abbreviated() -> Result<(), Box<dyn std::error::Error>> {
+        let mut builder = TrieBuilder::new();
+        builder.insert(
+            &[syl![Bopomofo::T, Bopomofo::I, Bopomofo::AN]],
+            ("天", 100).into(),
+        )?;
+        builder.insert(
+            &[syl![Bopomofo::T, Bopomofo::AI, Bopomofo::TONE2]],
+            ("台", 50).into(),
+        )?;
+        builder.insert(
+            &[syl![Bopomofo::B, Bopomofo::EI, Bopomofo::TONE3]],
+            ("北", 50).into(),
+        )?;
+        builder.insert(
+            &[
+                syl![Bopomofo::T, Bopomofo::AI, Bopomofo::TONE2],
+                syl![Bopomofo::B, Bopomofo::EI, Bopomofo::TONE3],
+            ],
+            ("台北", 1000).into(),
+        )?;
+        builder.insert(
+            &[
+                syl![Bopomofo::T, Bopomofo::AI, Bopomofo::TONE2],
+                syl![Bopomofo::N, Bopomofo::AN, Bopomofo::TONE2],
+            ],
+            ("台南", 500).into(),
+        )?;
+        let mut cursor = Cursor::new(vec![]);
+        builder.write(&mut cursor)?;
+        cursor.rewind()?;
+        let dict = TrieOpenOptions::new().read_from(&mut cursor)?;
+        assert_eq!(
+            vec![Phrase::new("台", 50), Phrase::new("天", 100)],
+            dict.lookup(&[syl![Bopomofo::T]], LookupStrategy::Abbreviated)
+        );
+        assert_eq!(
+            vec![Phrase::new("台北", 1000)],
+            dict.lookup(
+                &[syl![Bopomofo::T], syl![Bopomofo::B]],
+                LookupStrategy::Abbreviated
+            )
+        );
+        assert_eq!(
+            vec![Phrase::new("台南", 500)],
+            dict.lookup(
+                &[
+                    syl![Bopomofo::T],
+                    syl![Bopomofo::N, Bopomofo::AN, Bopomofo::TONE2]
+                ],
+                LookupStrategy::Abbreviated
+            )
+        );
+        assert_eq!(
+            Vec::<Phrase>::new(),
+            dict.lookup(
+                &[syl![Bopomofo::T, Bopomofo::AI]],
+                LookupStrategy::Abbreviated
+            )
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn tree_lookup_phrase() -> Result<(), Box<dyn std::error::Error>> {
         let mut builder = TrieBuilder::new();
@@ -3,7 +3,8 @@ use std::{
     cmp,
     collections::{BTreeMap, BTreeSet, btree_map::Entry},
     error::Error,
-    io,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, BufWriter, Write},
     path::{Path, PathBuf},
     thread::{self, JoinHandle},
 };
@@ -12,7 +13,7 @@ use log::{debug, error, info, warn};
 
 use super::{
     Dictionary, DictionaryBuilder, DictionaryInfo, DictionaryUsage, Entries, LookupStrategy,
-    Phrase, Trie, TrieBuilder, UpdateDictionaryError,
+    Phrase, Trie, TrieBuilder, TrieOpenOptions, UpdateDictionaryError, next_generation,
 };
 use crate::zhuyin::Syllable;
 
@@ -22,13 +23,19 @@ pub struct TrieBuf {
     trie: Option<Trie>,
     btree: BTreeMap<PhraseKey, (u32, u64)>,
     graveyard: BTreeSet<PhraseKey>,
+    bigrams: BTreeMap<BigramKey, (u32, u64)>,
     join_handle: Option<JoinHandle<Result<(), UpdateDictionaryError>>>,
     dirty: bool,
     // TODO: currently usage is not saved in file
     usage: DictionaryUsage,
+    /// The generation of the changes on top of the trie.
+    generation: u64,
+    /// The path the dictionary is copied to after each successful flush.
+    backup_path: Option<PathBuf>,
 }
 
 type PhraseKey = (Cow<'static, [Syllable]>, Cow<'static, str>);
+type BigramKey = (Box<str>, Box<str>);
 
 const MIN_PHRASE: &str = "";
 const MAX_PHRASE: &str = "\u{10FFFF}";
@@ -37,6 +44,56 @@ fn software_version() -> String {
     format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))
 }
 
+/// Phrase pairs are stored next to the trie file, one `left right count
+/// time` record per line.
+fn bigram_path(path: &Path) -> PathBuf {
+    path.with_extension("bigram")
+}
+
+fn read_bigrams(path: &Path) -> io::Result<BTreeMap<BigramKey, (u32, u64)>> {
+    let mut bigrams = BTreeMap::new();
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(bigrams),
+        Err(e) => return Err(e),
+    };
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let [left, right, count, time] = fields[..] else {
+            return Err(io::Error::from(io::ErrorKind::InvalidData));
+        };
+        let count = count
+            .parse()
+            .map_err(|_| io::Error::from(io::ErrorKind::InvalidData))?;
+        let time = time
+            .parse()
+            .map_err(|_| io::Error::from(io::ErrorKind::InvalidData))?;
+        bigrams.insert((left.into(), right.into()), (count, time));
+    }
+    Ok(bigrams)
+}
+
+fn write_bigrams(path: &Path, bigrams: &BTreeMap<BigramKey, (u32, u64)>) -> io::Result<()> {
+    let mut tmpname = path.to_path_buf();
+    tmpname.set_extension("bigram~");
+    let file = File::create(&tmpname)?;
+    let mut writer = BufWriter::new(&file);
+    for ((left, right), (count, time)) in bigrams {
+        writeln!(writer, "{left} {right} {count} {time}")?;
+    }
+    writer.flush()?;
+    file.sync_data()?;
+    fs::rename(&tmpname, path)
+}
+
+fn load_bigrams(path: &Path) -> BTreeMap<BigramKey, (u32, u64)> {
+    read_bigrams(&bigram_path(path)).unwrap_or_else(|error| {
+        error!("Failed to load phrase pairs of {}: {error}", path.display());
+        BTreeMap::new()
+    })
+}
+
 impl TrieBuf {
     /// Open the target Trie dictionary and wrap it create a TrieBuf.
     pub fn open<P: Into<PathBuf>>(path: P) -> io::Result<TrieBuf> {
@@ -58,26 +115,39 @@ impl TrieBuf {
                 .build(&path)
                 .map_err(|_| io::Error::from(io::ErrorKind::Other))?;
         }
-        let trie = Trie::open(&path)?;
+        let trie = TrieOpenOptions::new().mmap(true).open(&path)?;
+        let bigrams = load_bigrams(&path);
         Ok(TrieBuf {
             trie: Some(trie),
             btree: BTreeMap::new(),
             graveyard: BTreeSet::new(),
+            bigrams,
             join_handle: None,
             dirty: false,
             usage: DictionaryUsage::Unknown,
+            generation: next_generation(),
+            backup_path: None,
         })
     }
 
+    /// Keeps a copy of the dictionary at the path, refreshed after each
+    /// successful flush.
+    pub(crate) fn keep_backup(&mut self, backup_path: PathBuf) {
+        self.backup_path = Some(backup_path);
+    }
+
     /// Creates a pure in memory dictionary.
     pub fn new_in_memory() -> TrieBuf {
         TrieBuf {
             trie: None,
             btree: BTreeMap::new(),
             graveyard: BTreeSet::new(),
+            bigrams: BTreeMap::new(),
             join_handle: None,
             dirty: false,
             usage: DictionaryUsage::Unknown,
+            generation: next_generation(),
+            backup_path: None,
         }
     }
 
@@ -93,19 +163,43 @@ impl TrieBuf {
             .trie
             .iter()
             .flat_map(move |trie| trie.lookup(syllables, strategy));
-        let btree_iter = self
-            .btree
-            .range(min_key..max_key)
-            .map(|(key, value)| Phrase {
-                text: key.1.clone().into(),
-                freq: value.0,
-                last_used: Some(value.1),
-            });
+        let scan = match strategy {
+            LookupStrategy::Toneless => syllables.iter().any(|syl| !syl.has_tone()),
+            LookupStrategy::Confusable(confusions) => !confusions.is_empty(),
+            LookupStrategy::Abbreviated => syllables.iter().any(|syl| syl.is_initial_only()),
+            _ => false,
+        };
+        let matches = move |key: &[Syllable]| {
+            key.len() == syllables.len()
+                && syllables
+                    .iter()
+                    .zip(key)
+                    .all(|(syl, other)| strategy.matches(*syl, *other))
+        };
+        let btree_iter: Box<dyn Iterator<Item = _>> = if scan {
+            // Matching syllables are not adjacent in the tree
+            Box::new(self.btree.iter().filter(move |(key, _)| matches(&key.0)))
+        } else {
+            Box::new(self.btree.range(min_key..max_key))
+        };
+        let btree_iter = btree_iter.map(|(key, value)| Phrase {
+            text: key.1.clone().into(),
+            freq: value.0,
+            last_used: Some(value.1),
+            comment: None,
+        });
 
         store_iter.chain(btree_iter).filter(move |it| {
-            !self
-                .graveyard
-                .contains(&(syllables_key.clone(), Cow::from(it.as_str())))
+            if scan {
+                !self
+                    .graveyard
+                    .iter()
+                    .any(|(key, phrase)| phrase == it.as_str() && matches(key))
+            } else {
+                !self
+                    .graveyard
+                    .contains(&(syllables_key.clone(), Cow::from(it.as_str())))
+            }
         })
     }
 
@@ -121,6 +215,7 @@ impl TrieBuf {
                         text: key.1.clone().into(),
                         freq: value.0,
                         last_used: Some(value.1),
+                        comment: None,
                     },
                 )
             })
@@ -133,7 +228,7 @@ impl TrieBuf {
     }
 
     pub(crate) fn lookup(&self, syllables: &[Syllable], strategy: LookupStrategy) -> Vec<Phrase> {
-        let mut sort_map = BTreeMap::new();
+        let mut sort_map: BTreeMap<String, usize> = BTreeMap::new();
         let mut phrases: Vec<Phrase> = Vec::new();
 
         debug!(
@@ -145,7 +240,9 @@ impl TrieBuf {
             match sort_map.entry(phrase.to_string()) {
                 Entry::Occupied(entry) => {
                     let index = *entry.get();
+                    let comment = phrases[index].comment.take().or(phrase.comment.clone());
                     phrases[index] = cmp::max(&phrase, &phrases[index]).clone();
+                    phrases[index].comment = comment;
                 }
                 Entry::Vacant(entry) => {
                     entry.insert(phrases.len());
@@ -153,6 +250,13 @@ impl TrieBuf {
                 }
             }
         }
+        if let LookupStrategy::Confusable(_) = strategy {
+            // Exact matches are ranked above the fuzzy ones
+            let exact: Vec<Phrase> = self
+                .entries_iter_for(syllables, LookupStrategy::Standard)
+                .collect();
+            phrases.sort_by_key(|phrase| !exact.iter().any(|it| it.as_str() == phrase.as_str()));
+        }
         phrases
     }
 
@@ -160,6 +264,97 @@ impl TrieBuf {
         Box::new(self.entries_iter())
     }
 
+    pub(crate) fn reverse_lookup(&self, phrase: &str) -> Vec<(Vec<Syllable>, Phrase)> {
+        let trie_iter = self
+            .trie
+            .iter()
+            .flat_map(|trie| trie.reverse_lookup(phrase));
+        let btree_iter =
+            self.btree
+                .iter()
+                .filter(|(key, _)| key.1 == phrase)
+                .map(|(key, value)| {
+                    (
+                        key.0.clone().into_owned(),
+                        Phrase {
+                            text: key.1.clone().into(),
+                            freq: value.0,
+                            last_used: Some(value.1),
+                            comment: None,
+                        },
+                    )
+                });
+        self.merge_entries(trie_iter.chain(btree_iter))
+    }
+
+    pub(crate) fn lookup_prefix(
+        &self,
+        syllables: &[Syllable],
+        text: &str,
+        limit: usize,
+    ) -> Vec<(Vec<Syllable>, Phrase)> {
+        // The removed phrases are skipped after the lookup, and the updated
+        // ones only raise the frequencies, so the most frequent phrases are
+        // among the top ones of the trie.
+        let trie_limit = limit.saturating_add(self.graveyard.len());
+        let trie_iter = self
+            .trie
+            .iter()
+            .flat_map(|trie| trie.lookup_prefix(syllables, text, trie_limit));
+        let min_key = (Cow::from(syllables.to_vec()), Cow::from(MIN_PHRASE));
+        let btree_iter = self
+            .btree
+            .range(min_key..)
+            .take_while(|(key, _)| key.0.starts_with(syllables))
+            .filter(|(key, _)| key.1.starts_with(text))
+            .map(|(key, value)| {
+                (
+                    key.0.clone().into_owned(),
+                    Phrase {
+                        text: key.1.clone().into(),
+                        freq: value.0,
+                        last_used: Some(value.1),
+                        comment: None,
+                    },
+                )
+            });
+        let mut phrases = self.merge_entries(trie_iter.chain(btree_iter));
+        phrases.truncate(limit);
+        phrases
+    }
+
+    /// Merges entries of the same reading and phrase, skipping the removed
+    /// ones, and returns the most frequent first.
+    fn merge_entries(
+        &self,
+        entries: impl Iterator<Item = (Vec<Syllable>, Phrase)>,
+    ) -> Vec<(Vec<Syllable>, Phrase)> {
+        let mut sort_map: BTreeMap<(Vec<Syllable>, String), usize> = BTreeMap::new();
+        let mut merged: Vec<(Vec<Syllable>, Phrase)> = Vec::new();
+        for (syllables, phrase) in entries {
+            if self
+                .graveyard
+                .contains(&(Cow::from(syllables.as_slice()), Cow::from(phrase.as_str())))
+            {
+                continue;
+            }
+            match sort_map.entry((syllables.clone(), phrase.to_string())) {
+                Entry::Occupied(entry) => {
+                    let found = &mut merged[*entry.get()].1;
+                    let comment = found.comment.take().or(phrase.comment.clone());
+                    *found = cmp::max(&phrase, found).clone();
+                    found.comment = comment;
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(merged.len());
+                    merged.push((syllables, phrase));
+                }
+            }
+        }
+        merged.sort_by_key(|(_, it)| cmp::Reverse(it.freq()));
+        merged
+    }
+
     pub(crate) fn add_phrase(
         &mut self,
         syllables: &[Syllable],
@@ -185,6 +380,7 @@ impl TrieBuf {
             (phrase.freq, phrase.last_used.unwrap_or_default()),
         );
         self.dirty = true;
+        self.generation = next_generation();
 
         Ok(())
     }
@@ -209,6 +405,7 @@ impl TrieBuf {
             (user_freq, time),
         );
         self.dirty = true;
+        self.generation = next_generation();
         debug!("{:?}", self.btree);
 
         Ok(())
@@ -225,11 +422,50 @@ impl TrieBuf {
         self.graveyard
             .insert((syllables_key, phrase_str.to_owned().into()));
         self.dirty = true;
+        self.generation = next_generation();
 
         debug!("removed phrase {phrase_str} {syllables:?}");
         Ok(())
     }
 
+    pub(crate) fn lookup_bigram(&self, left: &str, right: &str) -> Option<(u32, u64)> {
+        self.bigrams.get(&(left.into(), right.into())).copied()
+    }
+
+    pub(crate) fn lookup_successors(&self, left: &str) -> Vec<(String, u32, u64)> {
+        let mut successors: Vec<_> = self
+            .bigrams
+            .range((Box::from(left), Box::from(MIN_PHRASE))..)
+            .take_while(|(key, _)| &*key.0 == left)
+            .map(|(key, &(count, time))| (key.1.to_string(), count, time))
+            .collect();
+        successors.sort_by_key(|&(_, count, time)| cmp::Reverse((count, time)));
+        successors
+    }
+
+    pub(crate) fn update_bigram(
+        &mut self,
+        left: &str,
+        right: &str,
+        count: u32,
+        time: u64,
+    ) -> Result<(), UpdateDictionaryError> {
+        if [left, right]
+            .iter()
+            .any(|phrase| phrase.is_empty() || phrase.contains(char::is_whitespace))
+        {
+            return Err(UpdateDictionaryError::new(
+                "phrase pair must not be empty or contain whitespace",
+            ));
+        }
+        self.bigrams
+            .insert((left.into(), right.into()), (count, time));
+        self.dirty = true;
+
+        debug!("updated phrase pair {left} {right} {count}");
+        Ok(())
+    }
+
     pub(crate) fn wait(&mut self) {
         if let Some(join_handle) = self.join_handle.take() {
             match join_handle.join() {
@@ -264,12 +500,16 @@ impl TrieBuf {
             match join_handle.join() {
                 Ok(Ok(())) => {
                     info!("Reloading...");
-                    let mut trie = Trie::open(self.path().unwrap()).map_err(make_error)?;
+                    let mut trie = TrieOpenOptions::new()
+                        .mmap(true)
+                        .open(self.path().unwrap())
+                        .map_err(make_error)?;
                     trie.set_usage(self.usage);
                     self.trie = Some(trie);
                     if !self.dirty {
                         self.btree.clear();
                         self.graveyard.clear();
+                        self.bigrams = load_bigrams(self.path().unwrap());
                     }
                 }
                 Ok(Err(e)) => {
@@ -288,9 +528,15 @@ impl TrieBuf {
             // TODO: reduce reading
             if self.path().is_some() {
                 info!("Reloading...");
-                let mut trie = Trie::open(self.path().unwrap()).map_err(make_error)?;
+                let mut trie = TrieOpenOptions::new()
+                    .mmap(true)
+                    .open(self.path().unwrap())
+                    .map_err(make_error)?;
                 trie.set_usage(self.usage);
                 self.trie = Some(trie);
+                if !self.dirty {
+                    self.bigrams = load_bigrams(self.path().unwrap());
+                }
             }
         }
         Ok(())
@@ -314,10 +560,14 @@ impl TrieBuf {
             trie: self.trie.clone(),
             btree: self.btree.clone(),
             graveyard: self.graveyard.clone(),
+            bigrams: self.bigrams.clone(),
             join_handle: None,
             dirty: false,
             usage: self.usage,
+            generation: self.generation,
+            backup_path: None,
         };
+        let backup_path = self.backup_path.clone();
         self.join_handle = Some(thread::spawn(move || {
             let mut builder = TrieBuilder::new();
             info!("Saving snapshot...");
@@ -337,6 +587,21 @@ impl TrieBuf {
             builder
                 .build(snapshot.path().unwrap())
                 .map_err(make_error)?;
+            write_bigrams(&bigram_path(snapshot.path().unwrap()), &snapshot.bigrams).map_err(
+                |e| UpdateDictionaryError {
+                    message: "failed to save phrase pairs",
+                    source: Some(Box::new(e)),
+                },
+            )?;
+            if let Some(backup_path) = backup_path {
+                info!("Backing up to {}...", backup_path.display());
+                fs::copy(snapshot.path().unwrap(), backup_path).map_err(|e| {
+                    UpdateDictionaryError {
+                        message: "failed to back up dictionary",
+                        source: Some(Box::new(e)),
+                    }
+                })?;
+            }
             info!("    Done");
             Ok(())
         }));
@@ -353,6 +618,19 @@ impl Dictionary for TrieBuf {
         TrieBuf::entries(self)
     }
 
+    fn reverse_lookup(&self, phrase: &str) -> Vec<(Vec<Syllable>, Phrase)> {
+        TrieBuf::reverse_lookup(self, phrase)
+    }
+
+    fn lookup_prefix(
+        &self,
+        syllables: &[Syllable],
+        text: &str,
+        limit: usize,
+    ) -> Vec<(Vec<Syllable>, Phrase)> {
+        TrieBuf::lookup_prefix(self, syllables, text, limit)
+    }
+
     fn about(&self) -> DictionaryInfo {
         self.trie
             .as_ref()
@@ -365,11 +643,19 @@ impl Dictionary for TrieBuf {
 
     fn set_usage(&mut self, usage: DictionaryUsage) {
         self.usage = usage;
+        self.generation = next_generation();
         if let Some(trie) = self.trie.as_mut() {
             trie.set_usage(usage);
         }
     }
 
+    /// The trie takes a new generation when it's reloaded.
+    fn generation(&self) -> u64 {
+        self.trie.as_ref().map_or(self.generation, |trie| {
+            trie.generation().max(self.generation)
+        })
+    }
+
     fn reopen(&mut self) -> Result<(), UpdateDictionaryError> {
         self.sync()?;
         Ok(())
@@ -405,6 +691,24 @@ impl Dictionary for TrieBuf {
     ) -> Result<(), UpdateDictionaryError> {
         TrieBuf::remove_phrase(self, syllables, phrase_str)
     }
+
+    fn lookup_bigram(&self, left: &str, right: &str) -> Option<(u32, u64)> {
+        TrieBuf::lookup_bigram(self, left, right)
+    }
+
+    fn lookup_successors(&self, left: &str) -> Vec<(String, u32, u64)> {
+        TrieBuf::lookup_successors(self, left)
+    }
+
+    fn update_bigram(
+        &mut self,
+        left: &str,
+        right: &str,
+        count: u32,
+        time: u64,
+    ) -> Result<(), UpdateDictionaryError> {
+        TrieBuf::update_bigram(self, left, right, count, time)
+    }
 }
 
 impl<P: Into<Phrase>, const N: usize> From<[(Vec<Syllable>, Vec<P>); N]> for TrieBuf {
@@ -436,7 +740,7 @@ mod tests {
     use crate::{
         dictionary::{LookupStrategy, Phrase},
         syl,
-        zhuyin::Bopomofo::*,
+        zhuyin::{Bopomofo::*, ConfusionSet},
     };
 
     #[test]
@@ -462,6 +766,50 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn lookup_toneless() -> Result<(), Box<dyn Error>> {
+        let mut dict = TrieBuf::new_in_memory();
+        dict.add_phrase(&[syl![C, E, TONE4], syl![SH, TONE4]], ("測試", 1).into())?;
+        dict.add_phrase(&[syl![C, E, TONE4], syl![SH, TONE4]], ("策士", 2).into())?;
+        dict.add_phrase(&[syl![C, E, TONE4], syl![SH, TONE2]], ("測時", 3).into())?;
+        dict.add_phrase(
+            &[syl![C, E, TONE4], syl![SH, TONE2], syl![H, OU, TONE4]],
+            ("測時候", 4).into(),
+        )?;
+        dict.remove_phrase(&[syl![C, E, TONE4], syl![SH, TONE4]], "策士")?;
+        assert_eq!(
+            vec!["測時", "測試"],
+            dict.lookup(&[syl![C, E, TONE4], syl![SH]], LookupStrategy::Toneless)
+                .iter()
+                .map(|it| it.as_str())
+                .collect::<Vec<_>>()
+        );
+        assert!(
+            dict.lookup(&[syl![C, E, TONE4], syl![SH]], LookupStrategy::Standard)
+                .is_empty()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn lookup_confusable() -> Result<(), Box<dyn Error>> {
+        let mut dict = TrieBuf::new_in_memory();
+        dict.add_phrase(&[syl![ZH, TONE4]], ("至", 100).into())?;
+        dict.add_phrase(&[syl![Z, TONE4]], ("字", 10).into())?;
+        dict.add_phrase(&[syl![C, TONE4]], ("次", 10).into())?;
+        assert_eq!(
+            vec!["字", "至"],
+            dict.lookup(
+                &[syl![Z, TONE4]],
+                LookupStrategy::Confusable(ConfusionSet::all())
+            )
+            .iter()
+            .map(|it| it.as_str())
+            .collect::<Vec<_>>()
+        );
+        Ok(())
+    }
+
     #[test]
     fn remove_then_add_phrase() -> Result<(), Box<dyn Error>> {
         let tmp_dir = tempfile::tempdir()?;
@@ -518,6 +866,66 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn reverse_lookup() -> Result<(), Box<dyn Error>> {
+        let mut dict = TrieBuf::from([
+            (vec![syl![X, I, ENG, TONE2]], vec![("行", 10)]),
+            (vec![syl![H, ANG, TONE2]], vec![("行", 5)]),
+        ]);
+        dict.update_phrase(&[syl![H, ANG, TONE2]], ("行", 5).into(), 20, 1)?;
+        dict.add_phrase(&[syl![H, ENG, TONE2]], ("行", 1).into())?;
+        dict.remove_phrase(&[syl![X, I, ENG, TONE2]], "行")?;
+        assert_eq!(
+            vec![
+                (vec![syl![H, ANG, TONE2]], 20),
+                (vec![syl![H, ENG, TONE2]], 1)
+            ],
+            dict.reverse_lookup("行")
+                .into_iter()
+                .map(|(syllables, phrase)| (syllables, phrase.freq()))
+                .collect::<Vec<_>>()
+        );
+        assert!(dict.reverse_lookup("型").is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn lookup_prefix() -> Result<(), Box<dyn Error>> {
+        let mut dict = TrieBuf::from([
+            (vec![syl![I, EN, TONE2]], vec![("銀", 50)]),
+            (
+                vec![syl![I, EN, TONE2], syl![H, ANG, TONE2]],
+                vec![("銀行", 80)],
+            ),
+            (
+                vec![syl![I, EN, TONE2], syl![H, E, TONE2]],
+                vec![("銀河", 30)],
+            ),
+            (vec![syl![I, EN, TONE3]], vec![("引", 100)]),
+        ]);
+        dict.update_phrase(
+            &[syl![I, EN, TONE2], syl![H, E, TONE2]],
+            ("銀河", 30).into(),
+            90,
+            1,
+        )?;
+        dict.add_phrase(
+            &[syl![I, EN, TONE2], syl![X, I, ENG, TONE2]],
+            ("銀行", 1).into(),
+        )?;
+        dict.remove_phrase(&[syl![I, EN, TONE2]], "銀")?;
+        assert_eq!(
+            vec![("銀河", 90), ("銀行", 80), ("銀行", 1)],
+            dict.lookup_prefix(&[syl![I, EN, TONE2]], "", 10)
+                .iter()
+                .map(|(_, phrase)| (phrase.as_str(), phrase.freq()))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(1, dict.lookup_prefix(&[syl![I, EN, TONE2]], "", 1).len());
+        assert!(dict.lookup_prefix(&[syl![H, E, TONE2]], "", 10).is_empty());
+        Ok(())
+    }
+
     #[test]
     fn create_new_dictionary_and_query() -> Result<(), Box<dyn Error>> {
         let tmp_dir = tempfile::tempdir()?;
@@ -565,4 +973,40 @@ mod tests {
         );
         Ok(())
     }
+
+    #[test]
+    fn update_bigram_and_reopen() -> Result<(), Box<dyn Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let file_path = tmp_dir.path().join("user.dat");
+        {
+            let mut dict = TrieBuf::open(&file_path)?;
+            dict.update_bigram("慢慢", "地", 2, 42)?;
+            assert!(dict.update_bigram("慢 慢", "地", 1, 42).is_err());
+            assert_eq!(Some((2, 42)), dict.lookup_bigram("慢慢", "地"));
+            assert_eq!(None, dict.lookup_bigram("地", "慢慢"));
+            dict.flush()?;
+        }
+        let dict = TrieBuf::open(file_path)?;
+        assert_eq!(Some((2, 42)), dict.lookup_bigram("慢慢", "地"));
+        Ok(())
+    }
+
+    #[test]
+    fn lookup_successors() -> Result<(), Box<dyn Error>> {
+        let mut dict = TrieBuf::new_in_memory();
+        dict.update_bigram("慢慢", "地", 2, 42)?;
+        dict.update_bigram("慢慢", "走", 5, 40)?;
+        dict.update_bigram("慢慢", "來", 2, 50)?;
+        dict.update_bigram("慢", "跑", 9, 50)?;
+        assert_eq!(
+            vec![
+                ("走".to_string(), 5, 40),
+                ("來".to_string(), 2, 50),
+                ("地".to_string(), 2, 42)
+            ],
+            dict.lookup_successors("慢慢")
+        );
+        assert!(dict.lookup_successors("地").is_empty());
+        Ok(())
+    }
 }
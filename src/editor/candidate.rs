@@ -0,0 +1,31 @@
+//! Candidates with their details.
+
+use crate::{dictionary::DictionaryInfo, zhuyin::Syllable};
+
+/// A candidate in the candidate window with its details.
+///
+/// See [`Editor::all_candidate_details`](super::Editor::all_candidate_details).
+#[derive(Debug, Clone, Default)]
+pub struct Candidate {
+    /// The phrase or symbol text.
+    pub text: String,
+    /// The frequency of the phrase. Always 0 for symbols.
+    pub freq: u32,
+    /// The full bopomofo reading of the phrase. Empty for symbols.
+    pub reading: Vec<Syllable>,
+    /// The dictionary providing the phrase, if it can be found.
+    pub source: Option<DictionaryInfo>,
+    /// Whether the phrase is in the user dictionary.
+    pub user_phrase: bool,
+    /// The comment of the phrase in the dictionary, e.g. 簡稱 or 異體字.
+    pub comment: Option<String>,
+}
+
+impl Candidate {
+    pub(crate) fn symbol(text: String) -> Candidate {
+        Candidate {
+            text,
+            ..Default::default()
+        }
+    }
+}
@@ -4,7 +4,10 @@ use std::cmp::min;
 
 use log::warn;
 
-use crate::conversion::{Composition, Gap, Interval, Symbol};
+use crate::{
+    conversion::{Composition, Gap, Interval, Symbol},
+    zhuyin::Syllable,
+};
 
 /// TODO
 #[derive(Debug, Default, Clone)]
@@ -14,9 +17,20 @@ pub(crate) struct CompositionEditor {
     cursor_stack: Vec<usize>,
     /// TODO
     inner: Composition,
+    /// Other suggestions for the corrected syllables, indexed like the
+    /// symbols.
+    corrections: Vec<Vec<Syllable>>,
 }
 
 impl CompositionEditor {
+    pub(crate) fn from_composition(inner: Composition, cursor: usize) -> CompositionEditor {
+        CompositionEditor {
+            cursor: min(cursor, inner.len()),
+            cursor_stack: vec![],
+            corrections: vec![vec![]; inner.len()],
+            inner,
+        }
+    }
     pub(crate) fn to_composition(&self) -> Composition {
         self.inner.clone()
     }
@@ -35,6 +49,10 @@ impl CompositionEditor {
         }
         self.cursor = min(self.cursor, self.inner.len());
     }
+    /// Returns the cursor before the last [`push_cursor`](Self::push_cursor).
+    pub(crate) fn saved_cursor(&self) -> usize {
+        self.cursor_stack.last().copied().unwrap_or(self.cursor)
+    }
     pub(crate) fn clamp_cursor(&mut self) {
         if self.cursor == self.inner.len() {
             self.cursor = self.cursor.saturating_sub(1);
@@ -77,16 +95,23 @@ impl CompositionEditor {
     pub(crate) fn is_end_of_buffer(&self) -> bool {
         self.inner.len() == self.cursor
     }
+    /// Returns the other suggestions for the corrected syllable at `index`.
+    pub(crate) fn corrections(&self, index: usize) -> &[Syllable] {
+        self.corrections.get(index).map_or(&[], Vec::as_slice)
+    }
     pub(crate) fn clear(&mut self) {
         self.inner.clear();
+        self.corrections.clear();
         self.cursor = 0;
     }
     pub(crate) fn remove_front(&mut self, n: usize) {
         self.inner.remove_front(n);
+        self.corrections.drain(..n);
         self.cursor = self.cursor.saturating_sub(n);
     }
     pub(crate) fn remove_after_cursor(&mut self) {
         self.inner.remove(self.cursor);
+        self.corrections.remove(self.cursor);
     }
     pub(crate) fn remove_before_cursor(&mut self) {
         if self.cursor == 0 {
@@ -94,6 +119,7 @@ impl CompositionEditor {
         }
         self.cursor -= 1;
         self.inner.remove(self.cursor);
+        self.corrections.remove(self.cursor);
     }
     pub(crate) fn move_cursor_to_end(&mut self) {
         self.cursor = self.inner.len();
@@ -108,7 +134,12 @@ impl CompositionEditor {
         self.cursor = self.cursor.saturating_add(n).min(self.inner.len());
     }
     pub(crate) fn insert(&mut self, sym: Symbol) {
+        self.insert_corrected(sym, vec![]);
+    }
+    /// Inserts a corrected syllable with the other suggestions for it.
+    pub(crate) fn insert_corrected(&mut self, sym: Symbol, suggestions: Vec<Syllable>) {
         self.inner.insert(self.cursor, sym);
+        self.corrections.insert(self.cursor, suggestions);
         self.cursor += 1;
     }
     pub(crate) fn insert_glue(&mut self) {
@@ -127,6 +158,7 @@ impl CompositionEditor {
     }
     pub(crate) fn replace(&mut self, sym: Symbol) {
         self.inner.replace(self.cursor, sym);
+        self.corrections[self.cursor].clear();
     }
     pub(crate) fn symbol_for_select(&self) -> Option<Symbol> {
         let cursor = if self.is_end_of_buffer() {
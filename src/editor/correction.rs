@@ -0,0 +1,293 @@
+//! Suggestions for mistyped syllables.
+//!
+//! A syllable accepted by the keyboard layout may still have no entry in any
+//! dictionary, e.g. when a neighbouring key was pressed by mistake. The
+//! suggestions are the syllables with dictionary entries that differ from
+//! the typed syllable by one component, replaced by a bopomofo on an
+//! adjacent key of the active layout, a similar sounding bopomofo, another
+//! tone, or removed.
+
+use std::cmp::Reverse;
+
+use super::zhuyin_layout::SyllableEditor;
+use crate::{
+    dictionary::{Dictionary, LookupStrategy},
+    input::{
+        KeyboardEvent,
+        keymap::{Keymap, QWERTY_MAP, map_ascii},
+    },
+    zhuyin::{Bopomofo, BopomofoKind, Confusion, Syllable},
+};
+
+/// The maximum number of suggestions.
+const MAX_SUGGESTIONS: usize = 8;
+
+/// Rows of physical keys of an ANSI keyboard, named by their QWERTY symbols.
+const KEY_ROWS: [&[u8]; 4] = [b"1234567890-", b"qwertyuiop", b"asdfghjkl;", b"zxcvbnm,./"];
+
+/// Pairs of bopomofo that sound similar, in addition to the confusions.
+const SIMILAR: [(Bopomofo, Bopomofo); 9] = [
+    (Bopomofo::B, Bopomofo::P),
+    (Bopomofo::D, Bopomofo::T),
+    (Bopomofo::G, Bopomofo::K),
+    (Bopomofo::J, Bopomofo::Q),
+    (Bopomofo::ZH, Bopomofo::CH),
+    (Bopomofo::Z, Bopomofo::C),
+    (Bopomofo::I, Bopomofo::IU),
+    (Bopomofo::O, Bopomofo::E),
+    (Bopomofo::EH, Bopomofo::EI),
+];
+
+const TONES: [Option<Bopomofo>; 5] = [
+    None,
+    Some(Bopomofo::TONE2),
+    Some(Bopomofo::TONE3),
+    Some(Bopomofo::TONE4),
+    Some(Bopomofo::TONE5),
+];
+
+/// The cost of replacing a component with a similar one or changing the
+/// tone.
+const REPLACE_COST: u8 = 1;
+/// The cost of removing a component.
+const REMOVE_COST: u8 = 2;
+
+/// Returns the key event the keymap produces for a physical key, named by
+/// its QWERTY symbol.
+///
+/// Keymaps of remapped layouts send different key codes or symbols for the
+/// same physical key, e.g. the Dvorak keymaps.
+fn key_event(keymap: &Keymap, key: u8) -> KeyboardEvent {
+    let code = map_ascii(&QWERTY_MAP, key).code;
+    keymap
+        .iter()
+        .find(|(_, evt)| evt.code == code && evt.state == 0)
+        .map_or_else(|| map_ascii(&QWERTY_MAP, key), |(_, evt)| *evt)
+}
+
+/// The bopomofo produced by each key of a layout, indexed like
+/// [`KEY_ROWS`].
+pub(crate) type KeyBopomofo = Vec<Vec<Vec<Bopomofo>>>;
+
+/// Returns the bopomofo produced by each key of the layout.
+///
+/// Keys are pressed on an empty buffer and after an initial, so layouts
+/// mapping a key to both an initial and a rime report both.
+pub(crate) fn key_bopomofo(layout: &dyn SyllableEditor, keymap: &Keymap) -> KeyBopomofo {
+    let press = |prefix: Option<u8>, key: u8| {
+        let mut editor = layout.clone();
+        editor.clear();
+        let before = prefix.map(|prefix| {
+            editor.key_press(key_event(keymap, prefix));
+            editor.read()
+        });
+        editor.key_press(key_event(keymap, key));
+        let mut produced = components(editor.read());
+        if let Some(before) = before {
+            produced.retain(|bopomofo| !components(before).contains(bopomofo));
+        }
+        produced
+    };
+    // Any key producing an initial makes the next key a medial or a rime.
+    let prefix = KEY_ROWS
+        .iter()
+        .flat_map(|row| row.iter().copied())
+        .find(|&key| {
+            press(None, key)
+                .first()
+                .is_some_and(|bopomofo| bopomofo.kind() == BopomofoKind::Initial)
+        });
+    KEY_ROWS
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|&key| {
+                    let mut bopomofo = press(None, key);
+                    if prefix.is_some() {
+                        for other in press(prefix, key) {
+                            if !bopomofo.contains(&other) {
+                                bopomofo.push(other);
+                            }
+                        }
+                    }
+                    bopomofo
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn components(syl: Syllable) -> Vec<Bopomofo> {
+    [syl.initial(), syl.medial(), syl.rime(), syl.tone()]
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+/// Returns the positions of the keys next to a key, assuming the rows are
+/// staggered to the right.
+fn adjacent_keys(row: usize, col: usize) -> Vec<(usize, usize)> {
+    let mut keys = vec![(row, col.wrapping_sub(1)), (row, col + 1)];
+    if row > 0 {
+        keys.extend([(row - 1, col), (row - 1, col + 1)]);
+    }
+    if row + 1 < KEY_ROWS.len() {
+        keys.extend([(row + 1, col.wrapping_sub(1)), (row + 1, col)]);
+    }
+    keys.retain(|&(row, col)| col < KEY_ROWS[row].len());
+    keys
+}
+
+/// Returns the bopomofo that may be typed instead of `bopomofo`.
+fn replacements(bopomofo: Bopomofo, keys: &KeyBopomofo) -> Vec<Bopomofo> {
+    let mut result = vec![];
+    let mut push = |other: Bopomofo| {
+        if other != bopomofo && other.kind() == bopomofo.kind() && !result.contains(&other) {
+            result.push(other);
+        }
+    };
+    for (x, y) in SIMILAR
+        .into_iter()
+        .chain(Confusion::ALL.map(Confusion::pair))
+    {
+        if x == bopomofo {
+            push(y);
+        } else if y == bopomofo {
+            push(x);
+        }
+    }
+    for (row, cols) in keys.iter().enumerate() {
+        for (col, produced) in cols.iter().enumerate() {
+            if !produced.contains(&bopomofo) {
+                continue;
+            }
+            for (adj_row, adj_col) in adjacent_keys(row, col) {
+                keys[adj_row][adj_col].iter().copied().for_each(&mut push);
+            }
+        }
+    }
+    result
+}
+
+/// Returns the syllables in the dictionary that may be meant by a mistyped
+/// syllable, the most likely first.
+///
+/// `keys` are the [`key_bopomofo`] of the active syllable editor and
+/// keyboard layout, used to find bopomofo on adjacent keys. The typed
+/// syllable itself is never suggested.
+pub(crate) fn suggest_syllables(
+    syl: Syllable,
+    keys: &KeyBopomofo,
+    dict: &dyn Dictionary,
+    strategy: LookupStrategy,
+) -> Vec<Syllable> {
+    let mut variants: Vec<(u8, Syllable)> = vec![];
+    for bopomofo in [syl.initial(), syl.medial(), syl.rime()]
+        .into_iter()
+        .flatten()
+    {
+        for other in replacements(bopomofo, keys) {
+            let mut variant = syl;
+            variant.update(other);
+            variants.push((REPLACE_COST, variant));
+        }
+        let mut variant = syl;
+        match bopomofo.kind() {
+            BopomofoKind::Initial => variant.remove_initial(),
+            BopomofoKind::Medial => variant.remove_medial(),
+            _ => variant.remove_rime(),
+        };
+        variants.push((REMOVE_COST, variant));
+    }
+    for tone in TONES {
+        let mut variant = syl;
+        match tone {
+            Some(tone) => variant.update(tone),
+            None => {
+                variant.remove_tone();
+            }
+        }
+        variants.push((REPLACE_COST, variant));
+    }
+    let mut found: Vec<(u8, Reverse<u32>, Syllable)> = variants
+        .into_iter()
+        .filter(|&(_, variant)| variant != syl && (variant.has_medial() || variant.has_rime()))
+        .filter_map(|(cost, variant)| {
+            let freq = dict
+                .lookup(&[variant], strategy)
+                .iter()
+                .map(|phrase| phrase.freq())
+                .max()?;
+            Some((cost, Reverse(freq), variant))
+        })
+        .collect();
+    found.sort();
+    let mut suggestions: Vec<Syllable> = vec![];
+    for (_, _, variant) in found {
+        if !suggestions.contains(&variant) {
+            suggestions.push(variant);
+        }
+    }
+    suggestions.truncate(MAX_SUGGESTIONS);
+    suggestions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{key_bopomofo, suggest_syllables};
+    use crate::{
+        dictionary::{LookupStrategy, TrieBuf},
+        editor::zhuyin_layout::{Hsu, Standard},
+        input::keymap::{DVORAK_MAP, INVERTED_DVORAK_MAP, QWERTY_MAP},
+        syl,
+        zhuyin::Bopomofo::*,
+    };
+
+    #[test]
+    fn suggest_adjacent_and_similar_syllables() {
+        let dict = TrieBuf::from([
+            (vec![syl![C, E, TONE4]], vec![("測", 100)]),
+            (vec![syl![S, E, TONE4]], vec![("色", 10)]),
+            (vec![syl![C, E, TONE2]], vec![("策", 1)]),
+            (vec![syl![K, E, TONE4]], vec![("客", 1000)]),
+        ]);
+        // ㄘ (h) is next to ㄕ (g), ㄙ is similar to ㄕ.
+        let suggestions = suggest_syllables(
+            syl![SH, E, TONE4],
+            &key_bopomofo(&Standard::new(), &QWERTY_MAP),
+            &dict,
+            LookupStrategy::Standard,
+        );
+        assert_eq!(
+            vec![syl![C, E, TONE4], syl![S, E, TONE4]],
+            suggestions[..2].to_vec()
+        );
+        assert!(!suggestions.contains(&syl![K, E, TONE4]));
+    }
+
+    #[test]
+    fn suggest_other_tones() {
+        let dict = TrieBuf::from([(vec![syl![C, E, TONE4]], vec![("測", 100)])]);
+        let suggestions = suggest_syllables(
+            syl![C, E, TONE3],
+            &key_bopomofo(&Hsu::new(), &QWERTY_MAP),
+            &dict,
+            LookupStrategy::Standard,
+        );
+        assert_eq!(vec![syl![C, E, TONE4]], suggestions);
+    }
+
+    #[test]
+    fn keys_of_remapped_layouts() {
+        // The keys of the Dvorak keymaps are mapped back to physical keys.
+        assert_eq!(
+            key_bopomofo(&Standard::new(), &QWERTY_MAP),
+            key_bopomofo(&Standard::new(), &INVERTED_DVORAK_MAP)
+        );
+        // Hsu on Dvorak reads the symbols, the physical X key types q.
+        let qwerty = key_bopomofo(&Hsu::new(), &QWERTY_MAP);
+        let dvorak = key_bopomofo(&Hsu::new(), &DVORAK_MAP);
+        assert_ne!(qwerty, dvorak);
+        assert_eq!(qwerty[1][0], dvorak[3][1]);
+    }
+}
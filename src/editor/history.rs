@@ -0,0 +1,146 @@
+//! Undo and redo history of the pre-edit buffer.
+
+use std::collections::VecDeque;
+
+use super::composition_editor::CompositionEditor;
+use crate::conversion::Composition;
+
+/// Bounded history of composition editor states.
+///
+/// Only changes to the composition (symbols, gaps and selections) create new
+/// history entries. The cursor is saved along with the composition but moving
+/// the cursor alone is not an undoable edit.
+#[derive(Debug, Default)]
+pub(crate) struct EditHistory {
+    /// The state after the last recorded key event.
+    last: CompositionEditor,
+    undo: VecDeque<CompositionEditor>,
+    redo: Vec<CompositionEditor>,
+}
+
+impl EditHistory {
+    /// Records the current state, pushing the previous state to the undo
+    /// history if the composition was changed.
+    pub(crate) fn record(&mut self, com: &CompositionEditor, depth: usize) {
+        if !same_composition(com, &self.last) {
+            let prev = std::mem::replace(&mut self.last, com.clone());
+            self.undo.push_back(prev);
+            while self.undo.len() > depth {
+                self.undo.pop_front();
+            }
+            self.redo.clear();
+        } else {
+            self.last = com.clone();
+        }
+    }
+    /// Forgets all history, e.g. after the buffer was committed.
+    pub(crate) fn reset(&mut self, com: &CompositionEditor) {
+        self.last = com.clone();
+        self.undo.clear();
+        self.redo.clear();
+    }
+    pub(crate) fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+    pub(crate) fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+    /// Returns the state before the last edit.
+    pub(crate) fn undo(
+        &mut self,
+        com: &CompositionEditor,
+        depth: usize,
+    ) -> Option<CompositionEditor> {
+        self.record(com, depth);
+        let prev = self.undo.pop_back()?;
+        let current = std::mem::replace(&mut self.last, prev.clone());
+        self.redo.push(current);
+        Some(prev)
+    }
+    /// Returns the state before the last undo.
+    pub(crate) fn redo(&mut self, com: &CompositionEditor) -> Option<CompositionEditor> {
+        if !same_composition(com, &self.last) {
+            // The buffer was edited after the last undo.
+            return None;
+        }
+        let next = self.redo.pop()?;
+        let current = std::mem::replace(&mut self.last, next.clone());
+        self.undo.push_back(current);
+        Some(next)
+    }
+}
+
+fn same_composition(a: &CompositionEditor, b: &CompositionEditor) -> bool {
+    AsRef::<Composition>::as_ref(a) == AsRef::<Composition>::as_ref(b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EditHistory;
+    use crate::{conversion::Symbol, editor::composition_editor::CompositionEditor};
+
+    #[test]
+    fn undo_redo_round_trip() {
+        let mut history = EditHistory::default();
+        let mut com = CompositionEditor::default();
+        com.insert(Symbol::from('a'));
+        history.record(&com, 10);
+        com.insert(Symbol::from('b'));
+        history.record(&com, 10);
+
+        let com = history.undo(&com, 10).unwrap();
+        assert_eq!(1, com.len());
+        let com = history.undo(&com, 10).unwrap();
+        assert!(com.is_empty());
+        assert!(history.undo(&com, 10).is_none());
+
+        let com = history.redo(&com).unwrap();
+        assert_eq!(1, com.len());
+        let com = history.redo(&com).unwrap();
+        assert_eq!(2, com.len());
+        assert!(history.redo(&com).is_none());
+    }
+
+    #[test]
+    fn cursor_movement_is_not_an_edit() {
+        let mut history = EditHistory::default();
+        let mut com = CompositionEditor::default();
+        com.insert(Symbol::from('a'));
+        history.record(&com, 10);
+        com.move_cursor_to_beginning();
+        history.record(&com, 10);
+
+        let com = history.undo(&com, 10).unwrap();
+        assert!(com.is_empty());
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn bounded_by_depth() {
+        let mut history = EditHistory::default();
+        let mut com = CompositionEditor::default();
+        for ch in ['a', 'b', 'c', 'd'] {
+            com.insert(Symbol::from(ch));
+            history.record(&com, 2);
+        }
+
+        let com = history.undo(&com, 2).unwrap();
+        let com = history.undo(&com, 2).unwrap();
+        assert_eq!(2, com.len());
+        assert!(history.undo(&com, 2).is_none());
+    }
+
+    #[test]
+    fn new_edit_clears_redo() {
+        let mut history = EditHistory::default();
+        let mut com = CompositionEditor::default();
+        com.insert(Symbol::from('a'));
+        history.record(&com, 10);
+
+        let mut com = history.undo(&com, 10).unwrap();
+        com.insert(Symbol::from('b'));
+        history.record(&com, 10);
+        assert!(!history.can_redo());
+        assert!(history.redo(&com).is_none());
+    }
+}
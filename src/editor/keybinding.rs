@@ -0,0 +1,644 @@
+//! Configurable key bindings for editor actions.
+//!
+//! Each [`KeyAction`] the editor can perform is bound to a list of
+//! [`KeyPattern`]s. The editor states consult the [`KeyBindings`] table when
+//! handling a key event instead of matching keysyms directly, so frontends can
+//! remap the keys used by the input method.
+//!
+//! Key bindings can be loaded from a plain text file. Each line has the form
+//!
+//! ```text
+//! # comments start with a hash
+//! action_name = Pattern Pattern ...
+//! ```
+//!
+//! Patterns are separated by whitespace. A pattern is a key name optionally
+//! prefixed by modifiers joined with `+`, e.g. `Ctrl+Shift+Left`. Listed
+//! modifiers are required, modifiers prefixed with `!` must not be active,
+//! and other modifiers are not checked. Key names are either one printable
+//! ASCII character, a named key such as `Left` or `BackSpace`, a raw keysym
+//! in hex like `0xff51`, or `Digit` to match any digit key.
+//!
+//! Actions not listed in the file keep their default bindings. An action with
+//! no patterns after `=` is unbound.
+
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fmt::{Display, Write},
+    fs,
+    path::Path,
+    str::FromStr,
+};
+
+use crate::{
+    exn::{Exn, ResultExt},
+    input::{KeyState, KeyboardEvent, keysym::*},
+};
+
+/// Actions that can be bound to keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum KeyAction {
+    /// Switch between Chinese and English mode.
+    ToggleLanguageMode,
+    /// Switch between halfwidth and fullwidth character form.
+    ToggleCharacterForm,
+    /// Delete the symbol before the cursor or the last bopomofo.
+    DeleteBackward,
+    /// Delete the symbol after the cursor.
+    DeleteForward,
+    /// Add the phrase of length N near the cursor to the user dictionary.
+    ///
+    /// The length is read from the digit of the key event.
+    AddPhrase,
+    /// Open the symbol table.
+    OpenSymbolTable,
+    /// Cycle through alternative conversions when the cursor is at the end.
+    NextConversion,
+    /// Insert a break or glue between symbols at the cursor.
+    InsertBreakOrGlue,
+    /// Move the cursor left.
+    CursorLeft,
+    /// Move the cursor right.
+    CursorRight,
+    /// Move the cursor to the beginning of the buffer.
+    CursorToBeginning,
+    /// Move the cursor to the end of the buffer.
+    CursorToEnd,
+    /// Start or extend highlighting to the left.
+    HighlightLeft,
+    /// Start or extend highlighting to the right.
+    HighlightRight,
+    /// Add the highlighted range to the user dictionary.
+    LearnHighlighted,
+    /// Open the candidate list.
+    OpenCandidates,
+    /// Close the candidate list.
+    CloseCandidates,
+    /// Go to the next candidate page, or the next phrase length at the last page.
+    CycleCandidates,
+    /// Go to the previous candidate page.
+    PrevCandidatePage,
+    /// Go to the next candidate page.
+    NextCandidatePage,
+    /// Move the selection point one symbol to the left.
+    PrevSelectionPoint,
+    /// Move the selection point one symbol to the right.
+    NextSelectionPoint,
+    /// Delete the Nth candidate of the current page from the user dictionary.
+    ///
+    /// The index is read from the digit of the key event.
+    DeleteCandidate,
+    /// Reset the user frequency of the Nth candidate of the current page.
+    ///
+    /// The index is read from the digit of the key event.
+    DemoteCandidate,
+    /// Commit the Nth phrase predicted after the last commit.
+    ///
+    /// The index is read from the digit of the key event.
+    SelectPrediction,
+    /// Commit the pre-edit buffer.
+    Commit,
+    /// Cancel the current operation.
+    Cancel,
+    /// Undo the last edit of the pre-edit buffer.
+    Undo,
+    /// Redo the last undone edit of the pre-edit buffer.
+    Redo,
+}
+
+impl KeyAction {
+    const ALL: [KeyAction; 29] = [
+        KeyAction::ToggleLanguageMode,
+        KeyAction::ToggleCharacterForm,
+        KeyAction::DeleteBackward,
+        KeyAction::DeleteForward,
+        KeyAction::AddPhrase,
+        KeyAction::OpenSymbolTable,
+        KeyAction::NextConversion,
+        KeyAction::InsertBreakOrGlue,
+        KeyAction::CursorLeft,
+        KeyAction::CursorRight,
+        KeyAction::CursorToBeginning,
+        KeyAction::CursorToEnd,
+        KeyAction::HighlightLeft,
+        KeyAction::HighlightRight,
+        KeyAction::LearnHighlighted,
+        KeyAction::OpenCandidates,
+        KeyAction::CloseCandidates,
+        KeyAction::CycleCandidates,
+        KeyAction::PrevCandidatePage,
+        KeyAction::NextCandidatePage,
+        KeyAction::PrevSelectionPoint,
+        KeyAction::NextSelectionPoint,
+        KeyAction::DeleteCandidate,
+        KeyAction::DemoteCandidate,
+        KeyAction::SelectPrediction,
+        KeyAction::Commit,
+        KeyAction::Cancel,
+        KeyAction::Undo,
+        KeyAction::Redo,
+    ];
+
+    /// Returns all actions.
+    pub fn all() -> &'static [KeyAction] {
+        &Self::ALL
+    }
+
+    /// Returns the name used in key binding files.
+    pub const fn name(&self) -> &'static str {
+        match self {
+            KeyAction::ToggleLanguageMode => "toggle_language_mode",
+            KeyAction::ToggleCharacterForm => "toggle_character_form",
+            KeyAction::DeleteBackward => "delete_backward",
+            KeyAction::DeleteForward => "delete_forward",
+            KeyAction::AddPhrase => "add_phrase",
+            KeyAction::OpenSymbolTable => "open_symbol_table",
+            KeyAction::NextConversion => "next_conversion",
+            KeyAction::InsertBreakOrGlue => "insert_break_or_glue",
+            KeyAction::CursorLeft => "cursor_left",
+            KeyAction::CursorRight => "cursor_right",
+            KeyAction::CursorToBeginning => "cursor_to_beginning",
+            KeyAction::CursorToEnd => "cursor_to_end",
+            KeyAction::HighlightLeft => "highlight_left",
+            KeyAction::HighlightRight => "highlight_right",
+            KeyAction::LearnHighlighted => "learn_highlighted",
+            KeyAction::OpenCandidates => "open_candidates",
+            KeyAction::CloseCandidates => "close_candidates",
+            KeyAction::CycleCandidates => "cycle_candidates",
+            KeyAction::PrevCandidatePage => "prev_candidate_page",
+            KeyAction::NextCandidatePage => "next_candidate_page",
+            KeyAction::PrevSelectionPoint => "prev_selection_point",
+            KeyAction::NextSelectionPoint => "next_selection_point",
+            KeyAction::DeleteCandidate => "delete_candidate",
+            KeyAction::DemoteCandidate => "demote_candidate",
+            KeyAction::SelectPrediction => "select_prediction",
+            KeyAction::Commit => "commit",
+            KeyAction::Cancel => "cancel",
+            KeyAction::Undo => "undo",
+            KeyAction::Redo => "redo",
+        }
+    }
+}
+
+impl Display for KeyAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for KeyAction {
+    type Err = KeyBindingsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        KeyAction::all()
+            .iter()
+            .find(|action| action.name() == s)
+            .copied()
+            .ok_or_else(|| KeyBindingsError::new(format!("unknown action '{s}'")))
+    }
+}
+
+const MODIFIERS: [(KeyState, &str); 4] = [
+    (KeyState::Control, "Ctrl"),
+    (KeyState::Shift, "Shift"),
+    (KeyState::Alt, "Alt"),
+    (KeyState::Super, "Super"),
+];
+
+const MODIFIER_MASK: u32 = KeyState::Control as u32
+    | KeyState::Shift as u32
+    | KeyState::Alt as u32
+    | KeyState::Super as u32;
+
+const KEY_NAMES: [(Keysym, &str); 27] = [
+    (SYM_BACKSPACE, "BackSpace"),
+    (SYM_TAB, "Tab"),
+    (SYM_RETURN, "Return"),
+    (SYM_ESC, "Escape"),
+    (SYM_DELETE, "Delete"),
+    (SYM_HOME, "Home"),
+    (SYM_LEFT, "Left"),
+    (SYM_UP, "Up"),
+    (SYM_RIGHT, "Right"),
+    (SYM_DOWN, "Down"),
+    (SYM_PAGEUP, "PageUp"),
+    (SYM_PAGEDOWN, "PageDown"),
+    (SYM_END, "End"),
+    (SYM_CAPSLOCK, "CapsLock"),
+    (SYM_SPACE, "Space"),
+    (SYM_F1, "F1"),
+    (SYM_F2, "F2"),
+    (SYM_F3, "F3"),
+    (SYM_F4, "F4"),
+    (SYM_F5, "F5"),
+    (SYM_F6, "F6"),
+    (SYM_F7, "F7"),
+    (SYM_F8, "F8"),
+    (SYM_F9, "F9"),
+    (SYM_F10, "F10"),
+    (SYM_F11, "F11"),
+    (SYM_F12, "F12"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum KeyMatch {
+    Sym(Keysym),
+    Digit,
+}
+
+/// A key with a set of required and forbidden modifiers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyPattern {
+    key: KeyMatch,
+    modifiers: u32,
+    mask: u32,
+}
+
+impl KeyPattern {
+    /// Creates a pattern matching the keysym regardless of modifiers.
+    pub const fn new(ksym: Keysym) -> KeyPattern {
+        KeyPattern {
+            key: KeyMatch::Sym(ksym),
+            modifiers: 0,
+            mask: 0,
+        }
+    }
+    /// Creates a pattern matching any digit key regardless of modifiers.
+    pub const fn digit() -> KeyPattern {
+        KeyPattern {
+            key: KeyMatch::Digit,
+            modifiers: 0,
+            mask: 0,
+        }
+    }
+    /// Requires the modifier to be active.
+    pub const fn with(mut self, modifier: KeyState) -> KeyPattern {
+        self.modifiers |= modifier as u32;
+        self.mask |= modifier as u32;
+        self
+    }
+    /// Requires the modifier to be inactive.
+    pub const fn without(mut self, modifier: KeyState) -> KeyPattern {
+        self.modifiers &= !(modifier as u32);
+        self.mask |= modifier as u32;
+        self
+    }
+    /// Requires all modifiers not explicitly required to be inactive.
+    pub const fn exact(mut self) -> KeyPattern {
+        self.mask = MODIFIER_MASK;
+        self
+    }
+    /// Returns whether the key event matches this pattern.
+    pub fn matches(&self, ev: &KeyboardEvent) -> bool {
+        let key_matches = match self.key {
+            KeyMatch::Sym(ksym) => ev.ksym == ksym,
+            KeyMatch::Digit => ev.ksym.is_digit(),
+        };
+        key_matches && ev.state & self.mask == self.modifiers
+    }
+}
+
+impl Display for KeyPattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (modifier, name) in MODIFIERS {
+            let bit = modifier as u32;
+            if self.mask & bit != 0 {
+                if self.modifiers & bit == 0 {
+                    f.write_char('!')?;
+                }
+                write!(f, "{name}+")?;
+            }
+        }
+        match self.key {
+            KeyMatch::Digit => f.write_str("Digit"),
+            KeyMatch::Sym(ksym) => {
+                if let Some((_, name)) = KEY_NAMES.iter().find(|(sym, _)| *sym == ksym) {
+                    f.write_str(name)
+                } else if ksym.is_ascii() {
+                    f.write_char(ksym.to_unicode())
+                } else {
+                    write!(f, "{:#x}", ksym.0)
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for KeyPattern {
+    type Err = KeyBindingsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || KeyBindingsError::new(format!("invalid key pattern '{s}'"));
+        // The plus key itself is written as a trailing '+'
+        let (mods, key) = match s.strip_suffix("++") {
+            Some(mods) => (mods, "+"),
+            None if s == "+" => ("", "+"),
+            None => s.rsplit_once('+').unwrap_or(("", s)),
+        };
+        let key = if key == "Digit" {
+            KeyMatch::Digit
+        } else if let Some((sym, _)) = KEY_NAMES.iter().find(|(_, name)| *name == key) {
+            KeyMatch::Sym(*sym)
+        } else if let Some(hex) = key.strip_prefix("0x") {
+            KeyMatch::Sym(Keysym(u32::from_str_radix(hex, 16).or_raise(error)?))
+        } else {
+            let mut chars = key.chars();
+            match (chars.next(), chars.next()) {
+                (Some(ch), None) if ch.is_ascii_graphic() => KeyMatch::Sym(Keysym::from_char(ch)),
+                _ => return Err(error()),
+            }
+        };
+        let mut pattern = KeyPattern {
+            key,
+            modifiers: 0,
+            mask: 0,
+        };
+        for token in mods.split('+').filter(|token| !token.is_empty()) {
+            let (negated, name) = match token.strip_prefix('!') {
+                Some(name) => (true, name),
+                None => (false, token),
+            };
+            let (modifier, _) = MODIFIERS
+                .iter()
+                .find(|(_, mod_name)| *mod_name == name)
+                .or_raise(error)?;
+            pattern = if negated {
+                pattern.without(*modifier)
+            } else {
+                pattern.with(*modifier)
+            };
+        }
+        Ok(pattern)
+    }
+}
+
+/// A table mapping editor actions to key patterns.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyBindings {
+    table: BTreeMap<KeyAction, Vec<KeyPattern>>,
+}
+
+impl KeyBindings {
+    /// Creates the default key bindings.
+    pub fn new() -> KeyBindings {
+        use KeyAction::*;
+        use KeyState::*;
+
+        let mut bindings = KeyBindings::empty();
+        for (action, patterns) in [
+            (ToggleLanguageMode, vec![KeyPattern::new(SYM_CAPSLOCK)]),
+            (
+                ToggleCharacterForm,
+                vec![KeyPattern::new(SYM_SPACE).with(Shift)],
+            ),
+            (DeleteBackward, vec![KeyPattern::new(SYM_BACKSPACE)]),
+            (DeleteForward, vec![KeyPattern::new(SYM_DELETE)]),
+            (AddPhrase, vec![KeyPattern::digit().with(Control)]),
+            (
+                OpenSymbolTable,
+                vec![
+                    KeyPattern::new(SYM_0).with(Control),
+                    KeyPattern::new(SYM_1).with(Control),
+                    KeyPattern::new(SYM_GRAVE).exact(),
+                ],
+            ),
+            (NextConversion, vec![KeyPattern::new(SYM_TAB)]),
+            (InsertBreakOrGlue, vec![KeyPattern::new(SYM_TAB)]),
+            (CursorLeft, vec![KeyPattern::new(SYM_LEFT)]),
+            (CursorRight, vec![KeyPattern::new(SYM_RIGHT)]),
+            (CursorToBeginning, vec![KeyPattern::new(SYM_HOME)]),
+            (
+                CursorToEnd,
+                vec![
+                    KeyPattern::new(SYM_END),
+                    KeyPattern::new(SYM_PAGEUP),
+                    KeyPattern::new(SYM_PAGEDOWN),
+                ],
+            ),
+            (HighlightLeft, vec![KeyPattern::new(SYM_LEFT).with(Shift)]),
+            (HighlightRight, vec![KeyPattern::new(SYM_RIGHT).with(Shift)]),
+            (LearnHighlighted, vec![KeyPattern::new(SYM_RETURN)]),
+            (OpenCandidates, vec![KeyPattern::new(SYM_DOWN)]),
+            (
+                CloseCandidates,
+                vec![KeyPattern::new(SYM_UP), KeyPattern::new(SYM_BACKSPACE)],
+            ),
+            (
+                CycleCandidates,
+                vec![KeyPattern::new(SYM_DOWN), KeyPattern::new(SYM_SPACE)],
+            ),
+            (
+                PrevCandidatePage,
+                vec![KeyPattern::new(SYM_LEFT), KeyPattern::new(SYM_PAGEUP)],
+            ),
+            (
+                NextCandidatePage,
+                vec![KeyPattern::new(SYM_RIGHT), KeyPattern::new(SYM_PAGEDOWN)],
+            ),
+            (PrevSelectionPoint, vec![KeyPattern::new(SYM_LOWER_J)]),
+            (NextSelectionPoint, vec![KeyPattern::new(SYM_LOWER_K)]),
+            (DeleteCandidate, vec![KeyPattern::digit().with(Control)]),
+            (DemoteCandidate, vec![KeyPattern::digit().with(Alt)]),
+            (SelectPrediction, vec![KeyPattern::digit().with(Alt)]),
+            (Commit, vec![KeyPattern::new(SYM_RETURN)]),
+            (Cancel, vec![KeyPattern::new(SYM_ESC)]),
+            (Undo, vec![KeyPattern::new(SYM_LOWER_Z).with(Control)]),
+            (
+                Redo,
+                vec![
+                    KeyPattern::new(SYM_LOWER_Y).with(Control),
+                    KeyPattern::new(Keysym::from_char('Z')).with(Control),
+                ],
+            ),
+        ] {
+            bindings.set(action, patterns);
+        }
+        bindings
+    }
+    /// Creates key bindings without any bound action.
+    pub fn empty() -> KeyBindings {
+        KeyBindings {
+            table: BTreeMap::new(),
+        }
+    }
+    /// Loads key bindings from a file, starting from the default bindings.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<KeyBindings, KeyBindingsError> {
+        let error = || KeyBindingsError::new("failed to read key bindings file".to_owned());
+        let content = fs::read_to_string(path).or_raise(error)?;
+        content.parse()
+    }
+    /// Returns the patterns bound to the action.
+    pub fn patterns(&self, action: KeyAction) -> &[KeyPattern] {
+        self.table
+            .get(&action)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+    /// Adds a pattern to the action.
+    pub fn bind(&mut self, action: KeyAction, pattern: KeyPattern) {
+        self.table.entry(action).or_default().push(pattern);
+    }
+    /// Replaces all patterns of the action.
+    pub fn set(&mut self, action: KeyAction, patterns: Vec<KeyPattern>) {
+        if patterns.is_empty() {
+            self.unbind(action);
+        } else {
+            self.table.insert(action, patterns);
+        }
+    }
+    /// Removes all patterns of the action.
+    pub fn unbind(&mut self, action: KeyAction) {
+        self.table.remove(&action);
+    }
+    /// Returns whether the key event triggers the action.
+    pub fn matches(&self, action: KeyAction, ev: &KeyboardEvent) -> bool {
+        self.patterns(action).iter().any(|pat| pat.matches(ev))
+    }
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        KeyBindings::new()
+    }
+}
+
+impl Display for KeyBindings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for action in KeyAction::all() {
+            write!(f, "{action} =")?;
+            for pattern in self.patterns(*action) {
+                write!(f, " {pattern}")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for KeyBindings {
+    type Err = KeyBindingsError;
+
+    /// Parses key bindings, starting from the default bindings.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut bindings = KeyBindings::new();
+        for (line_no, line) in s.lines().enumerate() {
+            let error =
+                || KeyBindingsError::new(format!("invalid key binding at line {}", line_no + 1));
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (action, patterns) = line.split_once('=').or_raise(error)?;
+            let action: KeyAction = action.trim().parse().or_raise(error)?;
+            let patterns = patterns
+                .split_whitespace()
+                .map(KeyPattern::from_str)
+                .collect::<Result<Vec<_>, _>>()
+                .or_raise(error)?;
+            bindings.set(action, patterns);
+        }
+        Ok(bindings)
+    }
+}
+
+/// Errors during parsing or loading key bindings.
+#[derive(Debug)]
+pub struct KeyBindingsError {
+    msg: String,
+    source: Option<Box<dyn Error + Send + Sync + 'static>>,
+}
+
+impl KeyBindingsError {
+    fn new(msg: String) -> KeyBindingsError {
+        KeyBindingsError { msg, source: None }
+    }
+}
+
+impl Display for KeyBindingsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.msg)
+    }
+}
+
+impl_exn!(KeyBindingsError);
+
+#[cfg(test)]
+mod tests {
+    use super::{KeyAction, KeyBindings, KeyPattern};
+    use crate::input::{KeyState, KeyboardEvent, keycode, keysym};
+
+    #[test]
+    fn pattern_round_trip() {
+        for s in [
+            "Ctrl+Digit",
+            "!Ctrl+!Shift+!Alt+!Super+`",
+            "Shift+Left",
+            "Ctrl++",
+            "0xff61",
+        ] {
+            let pattern: KeyPattern = s.parse().unwrap();
+            assert_eq!(s, pattern.to_string());
+        }
+    }
+
+    #[test]
+    fn pattern_matches_modifiers() {
+        let pattern: KeyPattern = "Ctrl+!Shift+z".parse().unwrap();
+        let ctrl_z = KeyboardEvent::builder()
+            .code(keycode::KEY_Z)
+            .ksym(keysym::SYM_LOWER_Z)
+            .control()
+            .build();
+        let ctrl_alt_z = KeyboardEvent {
+            state: ctrl_z.state | KeyState::Alt as u32,
+            ..ctrl_z
+        };
+        let ctrl_shift_z = KeyboardEvent::builder()
+            .code(keycode::KEY_Z)
+            .ksym(keysym::SYM_LOWER_Z)
+            .control()
+            .shift()
+            .build();
+        assert!(pattern.matches(&ctrl_z));
+        assert!(pattern.matches(&ctrl_alt_z));
+        assert!(!pattern.matches(&ctrl_shift_z));
+    }
+
+    #[test]
+    fn parse_overrides_defaults() {
+        let bindings: KeyBindings = "# comment\ncommit = Ctrl+j Return\n\ncancel =\n"
+            .parse()
+            .unwrap();
+        let ctrl_j = KeyboardEvent::builder()
+            .code(keycode::KEY_J)
+            .ksym(keysym::SYM_LOWER_J)
+            .control()
+            .build();
+        let esc = KeyboardEvent::builder()
+            .code(keycode::KEY_ESC)
+            .ksym(keysym::SYM_ESC)
+            .build();
+        let tab = KeyboardEvent::builder()
+            .code(keycode::KEY_TAB)
+            .ksym(keysym::SYM_TAB)
+            .build();
+        assert!(bindings.matches(KeyAction::Commit, &ctrl_j));
+        assert!(!bindings.matches(KeyAction::Cancel, &esc));
+        assert!(bindings.matches(KeyAction::InsertBreakOrGlue, &tab));
+    }
+
+    #[test]
+    fn display_round_trip() {
+        let bindings = KeyBindings::new();
+        let parsed: KeyBindings = bindings.to_string().parse().unwrap();
+        assert_eq!(bindings, parsed);
+    }
+
+    #[test]
+    fn reject_unknown_action() {
+        assert!("no_such_action = Tab".parse::<KeyBindings>().is_err());
+        assert!("commit = Hyper+Tab".parse::<KeyBindings>().is_err());
+    }
+}
@@ -2,25 +2,39 @@
 
 use std::{
     any::Any,
-    cmp::{max, min},
+    cmp::{Reverse, max, min},
+    collections::BTreeSet,
     error::Error,
     fmt::{Debug, Display},
     mem,
+    sync::Arc,
 };
 
 use log::{debug, error, info, trace, warn};
 
+pub use self::candidate::Candidate;
 pub use self::estimate::{LaxUserFreqEstimate, UserFreqEstimate};
+pub use self::keybinding::{KeyAction, KeyBindings, KeyBindingsError, KeyPattern};
+pub use self::observer::{EditorEvent, SubscriptionId};
+pub use self::preedit::{PreeditAttributes, PreeditSegment};
 pub use self::{abbrev::AbbrevTable, selection::symbol::SymbolSelector};
 use self::{
     composition_editor::CompositionEditor,
-    selection::{phrase::PhraseSelector, symbol::SpecialSymbolSelector},
-    zhuyin_layout::{KeyBehavior, Standard, SyllableEditor},
+    correction::KeyBopomofo,
+    history::EditHistory,
+    observer::{EditorSnapshot, Observers},
+    selection::{
+        phrase::PhraseSelector, prediction::PredictionSelector, symbol::SpecialSymbolSelector,
+    },
+    session::{Session, SessionStateKind},
+    zhuyin_layout::{KeyBehavior, KeyboardLayoutCompat, Standard, SyllableEditor},
 };
 use crate::{
     conversion::{
-        ChewingEngine, ConversionEngine, Interval, Symbol, full_width_symbol_input,
-        special_symbol_input,
+        BigramEngine, BigramModel, CharNgramScorer, ChewingEngine, ConversionContext,
+        ConversionEngine, FuzzyChewingEngine, Interval, Outcome, RerankContext, Reranker,
+        SimpleEngine, SingleCharPenalty, Symbol, UserPhraseBonus, full_width_symbol_input, rerank,
+        special_symbol_input, total_frequency,
     },
     dictionary::{
         AssetLoader, Dictionary, DictionaryUsage, Layered, LookupStrategy, Trie,
@@ -28,13 +42,20 @@ use crate::{
     },
     exn::{Exn, ResultExt},
     input::{KeyState, KeyboardEvent, keysym::*},
-    zhuyin::Syllable,
+    zhuyin::{Bopomofo, Confusion, ConfusionSet, Syllable},
 };
 
 mod abbrev;
+mod candidate;
 mod composition_editor;
+mod correction;
 mod estimate;
+mod history;
+mod keybinding;
+mod observer;
+mod preedit;
 mod selection;
+mod session;
 pub mod zhuyin_layout;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -60,9 +81,10 @@ pub enum ConversionEngineKind {
     SimpleEngine,
     ChewingEngine,
     FuzzyChewingEngine,
+    BigramEngine,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub struct EditorOptions {
     pub easy_symbol_input: bool,
     pub esc_clear_all_buffer: bool,
@@ -80,6 +102,73 @@ pub struct EditorOptions {
     pub enable_fullwidth_toggle_key: bool,
     pub sort_candidates_by_frequency: bool,
     pub auto_snapshot_selections: bool,
+    pub key_bindings: KeyBindings,
+    /// Maximum number of edits that can be undone. Each edit keeps a copy of
+    /// the composition, so frontends should keep it small. The C API limits
+    /// it to `MAX_UNDO_DEPTH` (100).
+    pub undo_depth: usize,
+    /// Penalty of each single character phrase when reranking conversion
+    /// outcomes. Zero disables the reranker.
+    pub single_char_penalty: f64,
+    /// Bonus of each user dictionary phrase when reranking conversion
+    /// outcomes. Zero disables the reranker.
+    pub user_phrase_bonus: f64,
+    /// Weight of the character pairs in the bigram model when reranking
+    /// conversion outcomes. Zero disables the reranker.
+    pub char_ngram_weight: f64,
+    /// Maximum number of alternative conversions searched by the built-in
+    /// conversion engines.
+    pub max_conversion_paths: usize,
+    /// Maximum number of syllables of the phrases considered by the
+    /// built-in conversion engines.
+    pub max_phrase_len: usize,
+    /// Drops conversions scoring lower than the best one by more than this
+    /// log probability. Zero disables pruning.
+    pub conversion_prune_threshold: f64,
+    /// Weights of the phrases from the dictionaries of each usage. See
+    /// [`ChewingEngine::dictionary_weight`].
+    pub dictionary_weights: Vec<(DictionaryUsage, f64)>,
+    /// Bonus of the phrases by their length. See
+    /// [`ChewingEngine::length_bonus`].
+    pub length_bonus: Vec<f64>,
+    /// Accepts syllables without a tone, completed by the initial of the
+    /// next syllable, and converts them with all tones of the syllable.
+    ///
+    /// It replaces the standard lookup strategy with
+    /// [`LookupStrategy::Toneless`]. See [`EditorOptions::validate`] for the
+    /// options it can't be combined with.
+    pub toneless_input: bool,
+    /// Pairs of bopomofo treated as the same sound when converting and
+    /// looking up candidates, e.g. ㄓ and ㄗ. Exact matches are preferred.
+    ///
+    /// It replaces the standard lookup strategy with
+    /// [`LookupStrategy::Confusable`]. See [`EditorOptions::validate`] for
+    /// the options it can't be combined with.
+    pub phonetic_confusions: ConfusionSet,
+    /// Accepts syllables with only an initial, completed by the initial of
+    /// the next syllable, and converts them with all syllables with the
+    /// initial, e.g. ㄊㄅ for 台北.
+    ///
+    /// It replaces the standard lookup strategy with
+    /// [`LookupStrategy::Abbreviated`]. See [`EditorOptions::validate`] for
+    /// the options it can't be combined with.
+    pub abbreviated_input: bool,
+    /// Replaces a syllable without any dictionary entry, e.g. typed with an
+    /// adjacent key by mistake, with the most likely valid syllable and
+    /// opens the candidate window at it. Other suggested syllables are
+    /// added to the candidates.
+    pub syllable_correction: bool,
+    /// Replaces mistyped syllables without opening the candidate window.
+    /// It has no effect without `syllable_correction`.
+    pub auto_correct_syllables: bool,
+    /// Opens the candidate window with the phrases likely following the
+    /// committed phrase, from the learned phrase pairs and the longer
+    /// phrases starting with it.
+    ///
+    /// Predictions are picked with the `select_prediction` key action,
+    /// Alt+Digit by default. Cancel closes them and other keys, including
+    /// plain digits, close them before working as usual.
+    pub next_phrase_prediction: bool,
 }
 
 impl Default for EditorOptions {
@@ -102,7 +191,46 @@ impl Default for EditorOptions {
             enable_fullwidth_toggle_key: true,
             sort_candidates_by_frequency: false,
             auto_snapshot_selections: false,
+            key_bindings: KeyBindings::default(),
+            undo_depth: 20,
+            single_char_penalty: 0.0,
+            user_phrase_bonus: 0.0,
+            char_ngram_weight: 0.0,
+            max_conversion_paths: ChewingEngine::DEFAULT_MAX_PATHS,
+            max_phrase_len: ChewingEngine::DEFAULT_MAX_PHRASE_LEN,
+            conversion_prune_threshold: 0.0,
+            dictionary_weights: vec![],
+            length_bonus: vec![],
+            toneless_input: false,
+            phonetic_confusions: ConfusionSet::new(),
+            abbreviated_input: false,
+            syllable_correction: false,
+            auto_correct_syllables: false,
+            next_phrase_prediction: false,
+        }
+    }
+}
+
+impl EditorOptions {
+    /// Checks that the options can be used together.
+    ///
+    /// Each of `toneless_input`, `abbreviated_input`, and
+    /// `phonetic_confusions` replaces the standard lookup strategy, so at most
+    /// one of them can be enabled, and only with the standard lookup
+    /// strategy and a conversion engine other than the fuzzy one.
+    pub fn validate(&self) -> Result<(), EditorError> {
+        let replacements = [
+            self.toneless_input,
+            self.abbreviated_input,
+            !self.phonetic_confusions.is_empty(),
+        ];
+        let replaced = replacements.iter().filter(|&&it| it).count();
+        let fuzzy = self.lookup_strategy != LookupStrategy::Standard
+            || self.conversion_engine == ConversionEngineKind::FuzzyChewingEngine;
+        if replaced > 1 || (replaced == 1 && fuzzy) {
+            return Err(EditorError::new(EditorErrorKind::InvalidInput));
         }
+        Ok(())
     }
 }
 
@@ -147,10 +275,23 @@ pub enum EditorKeyBehavior {
     Absorb,
 }
 
+/// The options used to build the conversion engine.
+#[derive(Debug, PartialEq)]
+struct ConversionEngineOptions {
+    kind: ConversionEngineKind,
+    lookup_strategy: LookupStrategy,
+    max_paths: usize,
+    max_phrase_len: usize,
+    prune_threshold: f64,
+    dictionary_weights: Vec<(DictionaryUsage, f64)>,
+    length_bonus: Vec<f64>,
+}
+
 #[derive(Debug)]
 pub struct Editor {
     shared: SharedState,
     state: Box<dyn State>,
+    observers: Observers,
 }
 
 #[derive(Debug)]
@@ -158,17 +299,34 @@ pub(crate) struct SharedState {
     com: CompositionEditor,
     syl: Box<dyn SyllableEditor>,
     conv: Box<dyn ConversionEngine>,
+    /// Whether the engine was set with [`Editor::set_conversion_engine`]
+    /// instead of created from the options.
+    custom_conv: bool,
     dict: Layered,
     abbr: AbbrevTable,
     sym_sel: SymbolSelector,
+    bigram: Arc<BigramModel>,
+    rerankers: Vec<Box<dyn Reranker>>,
     estimate: LaxUserFreqEstimate,
     options: EditorOptions,
+    history: EditHistory,
+    context: Option<ConversionContext>,
     last_key_behavior: EditorKeyBehavior,
+    /// The keyboard layout the key events are mapped with.
+    keyboard_layout: KeyboardLayoutCompat,
+    /// The bopomofo produced by each key with the syllable editor and the
+    /// keyboard layout, used by syllable correction.
+    key_bopomofo: KeyBopomofo,
+    /// Predictions after the last commit, not shown yet.
+    predictions: Option<PredictionSelector>,
+    /// The last phrase auto committed, paired with the next committed phrase.
+    last_auto_committed: Option<Box<str>>,
 
     dirty_level: u16,
     nth_conversion: usize,
     commit_buffer: String,
     notice_buffer: String,
+    events: Vec<EditorEvent>,
 }
 
 impl Editor {
@@ -250,10 +408,18 @@ impl Editor {
                 SymbolSelector::new(b"".as_slice()).unwrap()
             }
         };
+        let bigram = match loader.load_bigram_model() {
+            Ok(bigram) => bigram,
+            Err(e) => {
+                info!("Bigram model is not available: {e}");
+                BigramModel::new()
+            }
+        };
         let mut dict = Layered::new(dicts);
         let estimate = LaxUserFreqEstimate::max_from(dict.user_dict());
         let conversion_engine = Box::new(ChewingEngine::new());
-        let editor = Editor::new(conversion_engine, dict, estimate, abbrev, sym_sel);
+        let mut editor = Editor::new(conversion_engine, dict, estimate, abbrev, sym_sel);
+        editor.shared.bigram = Arc::new(bigram);
         editor
     }
 
@@ -269,32 +435,78 @@ impl Editor {
                 com: CompositionEditor::default(),
                 syl: Box::new(Standard::new()),
                 conv,
+                custom_conv: false,
                 dict,
                 abbr,
                 sym_sel,
+                bigram: Arc::new(BigramModel::new()),
+                rerankers: vec![],
                 estimate,
                 options: EditorOptions::default(),
+                history: EditHistory::default(),
+                context: None,
                 last_key_behavior: EditorKeyBehavior::Absorb,
+                keyboard_layout: KeyboardLayoutCompat::Default,
+                key_bopomofo: correction::key_bopomofo(
+                    &Standard::new(),
+                    KeyboardLayoutCompat::Default.keymap(),
+                ),
+                predictions: None,
+                last_auto_committed: None,
                 dirty_level: 0,
                 nth_conversion: 0,
                 commit_buffer: String::new(),
                 notice_buffer: String::new(),
+                events: vec![],
             },
             state: Box::new(Entering),
+            observers: Observers::default(),
         }
     }
 
     pub fn set_syllable_editor(&mut self, syl: Box<dyn SyllableEditor>) {
         self.shared.syl = syl;
+        self.shared.update_key_bopomofo();
         info!("Set syllable editor: {:?}", self.shared.syl);
     }
+    /// Sets the keyboard layout the key events are mapped with, see
+    /// [`KeyboardLayoutCompat::keymap`].
+    ///
+    /// Syllable correction uses it to find the keys next to a mistyped key.
+    pub fn set_keyboard_layout(&mut self, layout: KeyboardLayoutCompat) {
+        self.shared.keyboard_layout = layout;
+        self.shared.update_key_bopomofo();
+        info!("Set keyboard layout: {layout}");
+    }
+    /// Sets a custom conversion engine.
+    ///
+    /// The engine is kept when other options change. It is only replaced
+    /// when [`EditorOptions::conversion_engine`] selects another engine.
     pub fn set_conversion_engine(&mut self, engine: Box<dyn ConversionEngine>) {
         self.shared.conv = engine;
+        self.shared.custom_conv = true;
         info!("Set conversion engine: {:?}", self.shared.conv);
     }
+    /// Adds a custom reranker run after the built-in rerankers.
+    pub fn add_reranker(&mut self, reranker: Box<dyn Reranker>) {
+        info!("Add reranker: {reranker:?}");
+        self.shared.rerankers.push(reranker);
+    }
+    /// Removes all custom rerankers.
+    pub fn clear_rerankers(&mut self) {
+        self.shared.rerankers.clear();
+    }
+    /// Returns the phrase bigram model loaded with the system dictionaries.
+    ///
+    /// The model is empty if no `bigram.dat` were found.
+    pub fn bigram_model(&self) -> Arc<BigramModel> {
+        self.shared.bigram.clone()
+    }
     pub fn clear(&mut self) {
+        let before = self.observe();
         self.state = Box::new(Entering);
         self.shared.clear();
+        self.notify(before);
     }
     pub fn ack(&mut self) {
         self.shared.commit_buffer.clear();
@@ -311,17 +523,36 @@ impl Editor {
 
     // TODO: deprecate other direct set methods
     pub fn editor_options(&self) -> EditorOptions {
-        self.shared.options
+        self.shared.options.clone()
     }
-    pub fn set_editor_options<F>(&mut self, update_op: F)
+    /// Updates the editor options.
+    ///
+    /// Returns an error and keeps the previous options if the updated
+    /// options fail [`EditorOptions::validate`].
+    pub fn set_editor_options<F>(&mut self, update_op: F) -> Result<(), EditorError>
     where
         F: FnOnce(&mut EditorOptions),
     {
-        let old = self.shared.options;
-        update_op(&mut self.shared.options);
-        if self.shared.options.language_mode != old.language_mode {
+        let before = self.observe();
+        let old_language_mode = self.shared.options.language_mode;
+        let old_engine = self.shared.conversion_engine_options();
+        let mut options = self.shared.options.clone();
+        update_op(&mut options);
+        options.validate()?;
+        self.shared.options = options;
+        if self.shared.options.language_mode != old_language_mode {
             self.cancel_entering_syllable();
         }
+        if self.shared.conversion_engine_options() != old_engine
+            && (!self.shared.custom_conv
+                || self.shared.options.conversion_engine != old_engine.kind)
+        {
+            self.shared.conv = self.shared.new_conversion_engine();
+            self.shared.custom_conv = false;
+            info!("Set conversion engine: {:?}", self.shared.conv);
+        }
+        self.notify(before);
+        Ok(())
     }
     pub fn entering_syllable(&self) -> bool {
         !self.shared.syl.is_empty()
@@ -346,18 +577,24 @@ impl Editor {
         syllables: &[Syllable],
         phrase: &str,
     ) -> Result<(), EditorError> {
-        self.shared
+        let result = self
+            .shared
             .learn_phrase(syllables, phrase)
-            .or_raise(|| EditorError::new(EditorErrorKind::InvalidState))
+            .or_raise(|| EditorError::new(EditorErrorKind::InvalidState));
+        self.notify(None);
+        result
     }
     pub fn unlearn_phrase(
         &mut self,
         syllables: &[Syllable],
         phrase: &str,
     ) -> Result<(), EditorError> {
-        self.shared
+        let result = self
+            .shared
             .unlearn_phrase(syllables, phrase)
-            .or_raise(|| EditorError::new(EditorErrorKind::InvalidState))
+            .or_raise(|| EditorError::new(EditorErrorKind::InvalidState));
+        self.notify(None);
+        result
     }
     /// All candidates after current page
     pub fn paginated_candidates(&self) -> Result<Vec<String>, EditorError> {
@@ -372,6 +609,56 @@ impl Editor {
             Err(EditorError::new(EditorErrorKind::InvalidState))
         }
     }
+    /// Returns the alternative conversions of the whole pre-edit buffer,
+    /// best first.
+    ///
+    /// Outcomes with the same text as a better outcome are skipped. The
+    /// NextConversion key cycles through the alternatives. Frontends can
+    /// also show them in a list and select one with
+    /// [`select_conversion`](Self::select_conversion).
+    pub fn conversion_alternatives(&self) -> Vec<Outcome> {
+        if self.shared.com.is_empty() {
+            return vec![];
+        }
+        let outcomes = self.shared.conversion_outcomes();
+        distinct_outcomes(&outcomes)
+            .into_iter()
+            .map(|i| outcomes[i].clone())
+            .collect()
+    }
+    /// Returns the index of the selected alternative conversion.
+    pub fn selected_conversion(&self) -> usize {
+        if self.shared.com.is_empty() {
+            return 0;
+        }
+        let outcomes = self.shared.conversion_outcomes();
+        if outcomes.is_empty() {
+            return 0;
+        }
+        let selected = outcomes[self.shared.nth_conversion % outcomes.len()].text();
+        distinct_outcomes(&outcomes)
+            .into_iter()
+            .position(|i| outcomes[i].text() == selected)
+            .unwrap_or_default()
+    }
+    /// Selects an alternative conversion of the whole pre-edit buffer by
+    /// its index in [`conversion_alternatives`](Self::conversion_alternatives).
+    pub fn select_conversion(&mut self, index: usize) -> Result<(), EditorError> {
+        if !self.is_entering() || self.entering_syllable() {
+            return Err(EditorError::new(EditorErrorKind::InvalidState));
+        }
+        if self.shared.com.is_empty() {
+            return Err(EditorError::new(EditorErrorKind::InvalidInput));
+        }
+        let outcomes = self.shared.conversion_outcomes();
+        let Some(&nth) = distinct_outcomes(&outcomes).get(index) else {
+            return Err(EditorError::new(EditorErrorKind::InvalidInput));
+        };
+        let before = self.observe();
+        self.shared.nth_conversion = nth;
+        self.notify(before);
+        Ok(())
+    }
     pub fn all_candidates(&self) -> Result<Vec<String>, EditorError> {
         let any = self.state.as_ref() as &dyn Any;
         if let Some(selecting) = any.downcast_ref::<Selecting>() {
@@ -380,6 +667,43 @@ impl Editor {
             Err(EditorError::new(EditorErrorKind::InvalidState))
         }
     }
+    /// Returns the details of all candidates, in the same order as
+    /// [`all_candidates`](Self::all_candidates).
+    pub fn all_candidate_details(&self) -> Result<Vec<Candidate>, EditorError> {
+        let any = self.state.as_ref() as &dyn Any;
+        if let Some(selecting) = any.downcast_ref::<Selecting>() {
+            Ok(selecting.candidate_details(&self.shared, &self.shared.dict))
+        } else {
+            Err(EditorError::new(EditorErrorKind::InvalidState))
+        }
+    }
+    /// Returns the details of the candidate by its index in
+    /// [`all_candidates`](Self::all_candidates), without computing the
+    /// details of the other candidates.
+    pub fn candidate_details(&self, index: usize) -> Result<Candidate, EditorError> {
+        let any = self.state.as_ref() as &dyn Any;
+        if let Some(selecting) = any.downcast_ref::<Selecting>() {
+            selecting
+                .nth_candidate_details(&self.shared, &self.shared.dict, index)
+                .ok_or(EditorError::new(EditorErrorKind::InvalidInput))
+        } else {
+            Err(EditorError::new(EditorErrorKind::InvalidState))
+        }
+    }
+    /// Returns the details of all candidates after current page, in the
+    /// same order as [`paginated_candidates`](Self::paginated_candidates).
+    pub fn paginated_candidate_details(&self) -> Result<Vec<Candidate>, EditorError> {
+        let any = self.state.as_ref() as &dyn Any;
+        if let Some(selecting) = any.downcast_ref::<Selecting>() {
+            Ok(selecting
+                .candidate_details(&self.shared, &self.shared.dict)
+                .into_iter()
+                .skip(selecting.page_no * self.shared.options.candidates_per_page)
+                .collect())
+        } else {
+            Err(EditorError::new(EditorErrorKind::InvalidState))
+        }
+    }
     pub fn current_page_no(&self) -> Result<usize, EditorError> {
         let any = self.state.as_ref() as &dyn Any;
         if let Some(selecting) = any.downcast_ref::<Selecting>() {
@@ -397,6 +721,7 @@ impl Editor {
         }
     }
     pub fn select(&mut self, n: usize) -> Result<(), EditorError> {
+        let before = self.observe();
         let any = self.state.as_mut() as &mut dyn Any;
         let selecting = match any.downcast_mut::<Selecting>() {
             Some(selecting) => selecting,
@@ -412,6 +737,49 @@ impl Editor {
         if self.shared.last_key_behavior == EditorKeyBehavior::Absorb {
             self.shared.try_auto_commit();
         }
+        self.start_predicting();
+        self.notify(before);
+        if self.shared.last_key_behavior == EditorKeyBehavior::Bell {
+            Err(EditorError::new(EditorErrorKind::InvalidState))
+        } else {
+            Ok(())
+        }
+    }
+    /// Deletes the nth candidate of the current page from the user
+    /// dictionary.
+    ///
+    /// The phrase is also recorded in the exclusion dictionary so phrases
+    /// from read-only system dictionaries are no longer suggested.
+    pub fn delete_candidate(&mut self, n: usize) -> Result<(), EditorError> {
+        self.edit_candidate(n, Selecting::delete_candidate)
+    }
+    /// Demotes the nth candidate of the current page by resetting its user
+    /// frequency.
+    ///
+    /// Phrases only found in read-only system dictionaries are recorded in
+    /// the exclusion dictionary instead.
+    pub fn demote_candidate(&mut self, n: usize) -> Result<(), EditorError> {
+        self.edit_candidate(n, Selecting::demote_candidate)
+    }
+    fn edit_candidate(
+        &mut self,
+        n: usize,
+        edit: fn(&mut Selecting, &mut SharedState, usize) -> Transition,
+    ) -> Result<(), EditorError> {
+        let before = self.observe();
+        let any = self.state.as_mut() as &mut dyn Any;
+        let selecting = match any.downcast_mut::<Selecting>() {
+            Some(selecting) => selecting,
+            None => return Err(EditorError::new(EditorErrorKind::InvalidState)),
+        };
+        match edit(selecting, &mut self.shared, n) {
+            Transition::ToState(to_state) => {
+                self.shared.last_key_behavior = EditorKeyBehavior::Absorb;
+                self.state = to_state;
+            }
+            Transition::Spin(behavior) => self.shared.last_key_behavior = behavior,
+        }
+        self.notify(before);
         if self.shared.last_key_behavior == EditorKeyBehavior::Bell {
             Err(EditorError::new(EditorErrorKind::InvalidState))
         } else {
@@ -420,8 +788,10 @@ impl Editor {
     }
     pub fn cancel_selecting(&mut self) -> Result<(), EditorError> {
         if self.is_selecting() {
+            let before = self.observe();
             self.shared.cancel_selecting();
             self.state = Box::new(Entering);
+            self.notify(before);
             Ok(())
         } else {
             Err(EditorError::new(EditorErrorKind::InvalidState))
@@ -442,6 +812,31 @@ impl Editor {
         let any = self.state.as_ref() as &dyn Any;
         any.is::<Selecting>()
     }
+    /// Returns true if the candidate window shows the phrases predicted
+    /// after the last commit.
+    ///
+    /// See [`EditorOptions::next_phrase_prediction`].
+    pub fn is_predicting(&self) -> bool {
+        let any = self.state.as_ref() as &dyn Any;
+        any.downcast_ref::<Selecting>()
+            .is_some_and(|selecting| matches!(selecting.sel, Selector::Prediction(_)))
+    }
+    /// Shows the predictions after a commit, or closes the previous ones.
+    fn start_predicting(&mut self) {
+        if self.shared.last_key_behavior != EditorKeyBehavior::Commit {
+            return;
+        }
+        let predictions = self.shared.predictions.take();
+        if self.is_predicting() {
+            self.state = Box::new(Entering);
+        }
+        if let Some(sel) = predictions
+            && self.is_entering()
+            && self.shared.com.is_empty()
+        {
+            self.state = Box::new(Selecting::new_prediction(sel));
+        }
+    }
     pub fn intervals(&self) -> impl Iterator<Item = Interval> {
         self.shared.intervals()
     }
@@ -459,6 +854,29 @@ impl Editor {
             .map(|interval| interval.text)
             .collect::<String>()
     }
+    /// Returns the pre-edit buffer split into segments with display
+    /// attributes.
+    ///
+    /// The in-progress syllable is included inline at the cursor.
+    pub fn preedit(&self) -> Vec<PreeditSegment> {
+        let any = self.state.as_ref() as &dyn Any;
+        let highlight = any.downcast_ref::<Highlighting>().map(|highlighting| {
+            let cursor = self.shared.cursor();
+            min(highlighting.moving_cursor, cursor)..max(highlighting.moving_cursor, cursor)
+        });
+        let syllable = if self.entering_syllable() {
+            self.syllable_buffer_display()
+        } else {
+            String::new()
+        };
+        preedit::build_segments(
+            &self.shared.conversion(),
+            self.shared.com.as_ref(),
+            self.shared.cursor(),
+            highlight,
+            &syllable,
+        )
+    }
     // TODO: decide the return type
     pub fn display_commit(&self) -> &str {
         &self.shared.commit_buffer
@@ -467,7 +885,133 @@ impl Editor {
         if self.shared.com.is_empty() {
             return Err(EditorError::new(EditorErrorKind::InvalidState));
         }
+        let before = self.observe();
         self.shared.commit();
+        self.start_predicting();
+        self.notify(before);
+        Ok(())
+    }
+    /// Reverts the last edit of the pre-edit buffer.
+    ///
+    /// Only available when the editor is not selecting candidates or
+    /// entering a syllable.
+    pub fn undo(&mut self) -> Result<(), EditorError> {
+        if !self.is_entering() || self.entering_syllable() {
+            return Err(EditorError::new(EditorErrorKind::InvalidState));
+        }
+        let before = self.observe();
+        let undone = self.shared.undo();
+        self.notify(before);
+        if undone {
+            Ok(())
+        } else {
+            Err(EditorError::new(EditorErrorKind::Impossible))
+        }
+    }
+    /// Reapplies the last edit reverted by [`Editor::undo`].
+    pub fn redo(&mut self) -> Result<(), EditorError> {
+        if !self.is_entering() || self.entering_syllable() {
+            return Err(EditorError::new(EditorErrorKind::InvalidState));
+        }
+        let before = self.observe();
+        let redone = self.shared.redo();
+        self.notify(before);
+        if redone {
+            Ok(())
+        } else {
+            Err(EditorError::new(EditorErrorKind::Impossible))
+        }
+    }
+    pub fn can_undo(&self) -> bool {
+        self.shared.history.can_undo()
+    }
+    pub fn can_redo(&self) -> bool {
+        self.shared.history.can_redo()
+    }
+    /// Encodes the in-flight session state in a versioned byte format.
+    ///
+    /// The saved state includes the pre-edit buffer with its gaps and
+    /// selections, the cursor, the syllable editor buffer, and the current
+    /// editor state. It can be restored later, possibly by another editor,
+    /// with [`Editor::restore_state`].
+    ///
+    /// Candidate selection is saved only when selecting phrases. Symbol
+    /// table selections and predictions are saved as entering state.
+    pub fn save_state(&self) -> Result<Vec<u8>, EditorError> {
+        let any = self.state.as_ref() as &dyn Any;
+        let mut cursor = self.shared.cursor();
+        let state = if let Some(selecting) = any.downcast_ref::<Selecting>() {
+            cursor = self.shared.com.saved_cursor();
+            match selecting.sel {
+                Selector::Phrase(_) => SessionStateKind::Selecting {
+                    page_no: selecting.page_no,
+                },
+                _ => SessionStateKind::Entering,
+            }
+        } else if let Some(highlighting) = any.downcast_ref::<Highlighting>() {
+            SessionStateKind::Highlighting {
+                moving_cursor: highlighting.moving_cursor,
+            }
+        } else if any.is::<EnteringSyllable>() {
+            SessionStateKind::EnteringSyllable
+        } else {
+            SessionStateKind::Entering
+        };
+        let session = Session {
+            state,
+            cursor,
+            com: self.shared.com.to_composition(),
+            syllable: self.shared.syl.read(),
+            key_seq: self.shared.syl.key_seq(),
+        };
+        session
+            .encode()
+            .or_raise(|| EditorError::new(EditorErrorKind::InvalidState))
+    }
+    /// Restores the session state saved by [`Editor::save_state`].
+    ///
+    /// The current pre-edit buffer and undo history are discarded. Returns
+    /// an error and keeps the current state if the data is invalid.
+    pub fn restore_state(&mut self, bytes: &[u8]) -> Result<(), EditorError> {
+        let session =
+            Session::decode(bytes).or_raise(|| EditorError::new(EditorErrorKind::InvalidInput))?;
+        let before = self.observe();
+        let shared = &mut self.shared;
+        shared.com = CompositionEditor::from_composition(session.com, session.cursor);
+        shared.history.reset(&shared.com);
+        shared.nth_conversion = 0;
+        shared.syl.clear();
+        self.state = match session.state {
+            SessionStateKind::Entering => Box::new(Entering),
+            SessionStateKind::EnteringSyllable => {
+                shared
+                    .syl
+                    .restore(session.syllable, session.key_seq.as_deref());
+                if shared.syl.is_empty() {
+                    Box::new(Entering)
+                } else {
+                    Box::new(EnteringSyllable)
+                }
+            }
+            SessionStateKind::Selecting { page_no } => match shared.com.symbol_for_select() {
+                Some(symbol) if symbol.is_syllable() => {
+                    let mut selecting =
+                        if shared.options.conversion_engine == ConversionEngineKind::SimpleEngine {
+                            Selecting::new_phrase_for_simple_engine(shared)
+                        } else {
+                            Selecting::new_phrase(shared)
+                        };
+                    let total_page = selecting.total_page(shared, &shared.dict);
+                    selecting.page_no = page_no.min(total_page.saturating_sub(1));
+                    Box::new(selecting)
+                }
+                _ => Box::new(Entering),
+            },
+            SessionStateKind::Highlighting { moving_cursor } => {
+                Box::new(Highlighting::new(moving_cursor.min(shared.com.len())))
+            }
+        };
+        self.notify(before);
         Ok(())
     }
     pub fn has_next_selection_point(&self) -> bool {
@@ -477,6 +1021,7 @@ impl Editor {
                 Selector::Phrase(s) => s.next_selection_point(&self.shared.dict).is_some(),
                 Selector::Symbol(_) => false,
                 Selector::SpecialSymmbol(_) => false,
+                Selector::Prediction(_) => false,
             }
         } else {
             false
@@ -489,6 +1034,7 @@ impl Editor {
                 Selector::Phrase(s) => s.prev_selection_point(&self.shared.dict).is_some(),
                 Selector::Symbol(_) => false,
                 Selector::SpecialSymmbol(_) => false,
+                Selector::Prediction(_) => false,
             }
         } else {
             false
@@ -545,6 +1091,7 @@ impl Editor {
         }
     }
     pub fn start_selecting(&mut self) -> Result<(), EditorError> {
+        let before = self.observe();
         let any = self.state.as_mut() as &mut dyn Any;
         let transition = if let Some(s) = any.downcast_mut::<Entering>() {
             s.start_selecting(&mut self.shared)
@@ -561,6 +1108,7 @@ impl Editor {
             }
             Transition::Spin(behavior) => self.shared.last_key_behavior = behavior,
         }
+        self.notify(before);
         if self.is_selecting() {
             Ok(())
         } else {
@@ -570,6 +1118,84 @@ impl Editor {
     pub fn notification(&self) -> &str {
         &self.shared.notice_buffer
     }
+    /// Sets the committed text around the cursor in the application.
+    ///
+    /// The text before the cursor is used as the left context of the
+    /// conversion. The readings of the text are guessed from the dictionaries
+    /// unless the text was committed by the editor. Text committed by the
+    /// editor afterwards is appended to the context automatically. The text
+    /// after the cursor is currently not used.
+    pub fn set_surrounding_text(&mut self, before: &str, after: &str) {
+        let _ = after;
+        let before_snapshot = self.observe();
+        let context = self.shared.context.get_or_insert_default();
+        context.set_text(before);
+        context.guess_syllables(&self.shared.dict, ChewingEngine::MAX_CONTEXT_LEN);
+        self.notify(before_snapshot);
+    }
+    /// Stops using the surrounding text as conversion context.
+    pub fn clear_surrounding_text(&mut self) {
+        let before = self.observe();
+        self.shared.context = None;
+        self.notify(before);
+    }
+    /// Registers an observer that is called with every [`EditorEvent`].
+    ///
+    /// Events are emitted after the editor finished processing a key event
+    /// or a state changing method call.
+    pub fn subscribe<F>(&mut self, observer: F) -> SubscriptionId
+    where
+        F: FnMut(&EditorEvent) + 'static,
+    {
+        self.observers.subscribe(Box::new(observer))
+    }
+    /// Removes a previously registered observer.
+    ///
+    /// Returns false if the observer was not found.
+    pub fn unsubscribe(&mut self, id: SubscriptionId) -> bool {
+        self.observers.unsubscribe(id)
+    }
+    fn observe(&self) -> Option<EditorSnapshot> {
+        if self.observers.is_empty() {
+            return None;
+        }
+        let any = self.state.as_ref() as &dyn Any;
+        let candidates = any.downcast_ref::<Selecting>().map(|selecting| {
+            (
+                selecting.page_no,
+                selecting.total_page(&self.shared, &self.shared.dict),
+            )
+        });
+        Some(EditorSnapshot {
+            display: self.display(),
+            syllable: self.syllable_buffer_display(),
+            cursor: self.cursor(),
+            candidates,
+            language_mode: self.shared.options.language_mode,
+            character_form: self.shared.options.character_form,
+            commit: self.shared.commit_buffer.clone(),
+            notification: self.shared.notice_buffer.clone(),
+        })
+    }
+    fn notify(&mut self, before: Option<EditorSnapshot>) {
+        let mut events = mem::take(&mut self.shared.events);
+        if let Some(before) = before
+            && let Some(after) = self.observe()
+        {
+            events.extend(before.diff(&after));
+        }
+        if !events.is_empty() {
+            self.observers.notify(&events);
+        }
+    }
+}
+
+/// Returns the index of the first outcome of each distinct text.
+fn distinct_outcomes(outcomes: &[Outcome]) -> Vec<usize> {
+    let mut seen = BTreeSet::new();
+    (0..outcomes.len())
+        .filter(|&i| seen.insert(outcomes[i].text()))
+        .collect()
 }
 
 impl SharedState {
@@ -580,13 +1206,134 @@ impl SharedState {
         self.commit_buffer.clear();
         self.notice_buffer.clear();
         self.nth_conversion = 0;
+        self.history.reset(&self.com);
+    }
+    /// Returns the reranked conversion outcomes, best first.
+    fn conversion_outcomes(&self) -> Vec<Outcome> {
+        let mut paths = match &self.context {
+            Some(context) => self
+                .conv
+                .convert_with_context(&self.dict, self.com.as_ref(), context),
+            None => self.conv.convert(&self.dict, self.com.as_ref()),
+        };
+        self.rerank(&mut paths);
+        paths
     }
     fn conversion(&self) -> Vec<Interval> {
-        let paths = self.conv.convert(&self.dict, self.com.as_ref());
+        let mut paths = self.conversion_outcomes();
         if paths.is_empty() {
             return vec![];
         }
-        paths[self.nth_conversion % paths.len()].intervals.clone()
+        let nth = self.nth_conversion % paths.len();
+        paths.swap_remove(nth).intervals
+    }
+    /// Returns the lookup strategy used by the editor.
+    ///
+    /// Tone-less input, abbreviated input, and phonetic confusions replace
+    /// the standard strategy. The options are validated to enable at most
+    /// one of them.
+    fn lookup_strategy(&self) -> LookupStrategy {
+        match self.options.lookup_strategy {
+            LookupStrategy::Standard if self.options.toneless_input => LookupStrategy::Toneless,
+            LookupStrategy::Standard if self.options.abbreviated_input => {
+                LookupStrategy::Abbreviated
+            }
+            LookupStrategy::Standard if !self.options.phonetic_confusions.is_empty() => {
+                LookupStrategy::Confusable(self.options.phonetic_confusions)
+            }
+            strategy => strategy,
+        }
+    }
+    fn update_key_bopomofo(&mut self) {
+        self.key_bopomofo =
+            correction::key_bopomofo(self.syl.as_ref(), self.keyboard_layout.keymap());
+    }
+    /// Returns the most likely valid syllable for a syllable without any
+    /// dictionary entry, if syllable correction is enabled, and the other
+    /// suggestions for the candidate window.
+    fn correct_syllable(&self, syl: Syllable) -> Option<(Syllable, Vec<Syllable>)> {
+        if !self.options.syllable_correction {
+            return None;
+        }
+        let mut suggestions = correction::suggest_syllables(
+            syl,
+            &self.key_bopomofo,
+            &self.dict,
+            self.lookup_strategy(),
+        );
+        if suggestions.is_empty() {
+            return None;
+        }
+        let corrected = suggestions.remove(0);
+        debug!("corrected {syl} to {corrected}, other suggestions {suggestions:?}");
+        Some((corrected, suggestions))
+    }
+    fn conversion_engine_options(&self) -> ConversionEngineOptions {
+        ConversionEngineOptions {
+            kind: self.options.conversion_engine,
+            lookup_strategy: self.lookup_strategy(),
+            max_paths: self.options.max_conversion_paths,
+            max_phrase_len: self.options.max_phrase_len,
+            prune_threshold: self.options.conversion_prune_threshold,
+            dictionary_weights: self.options.dictionary_weights.clone(),
+            length_bonus: self.options.length_bonus.clone(),
+        }
+    }
+    /// Creates the conversion engine selected by the options.
+    fn new_conversion_engine(&self) -> Box<dyn ConversionEngine> {
+        match self.options.conversion_engine {
+            ConversionEngineKind::SimpleEngine => Box::new(SimpleEngine::new()),
+            ConversionEngineKind::ChewingEngine => Box::new(self.new_chewing_engine()),
+            ConversionEngineKind::FuzzyChewingEngine => {
+                Box::new(FuzzyChewingEngine::with_engine(self.new_chewing_engine()))
+            }
+            ConversionEngineKind::BigramEngine => Box::new(
+                BigramEngine::new(self.bigram.clone())
+                    .unigram_total(total_frequency(&self.dict))
+                    .engine(self.new_chewing_engine()),
+            ),
+        }
+    }
+    /// Creates a Chewing engine with the conversion options, shared by the
+    /// engines built on it.
+    fn new_chewing_engine(&self) -> ChewingEngine {
+        let options = &self.options;
+        let mut engine = ChewingEngine::new()
+            .lookup_strategy(self.lookup_strategy())
+            .max_paths(options.max_conversion_paths)
+            .max_phrase_len(options.max_phrase_len)
+            .prune_threshold(options.conversion_prune_threshold)
+            .length_bonus(&options.length_bonus);
+        for &(usage, weight) in &options.dictionary_weights {
+            engine = engine.dictionary_weight(usage, weight);
+        }
+        engine
+    }
+    fn rerank(&self, outcomes: &mut [Outcome]) {
+        let single_char = SingleCharPenalty {
+            weight: self.options.single_char_penalty,
+        };
+        let user_phrase = UserPhraseBonus {
+            weight: self.options.user_phrase_bonus,
+        };
+        let char_ngram = CharNgramScorer::new(self.bigram.clone(), self.options.char_ngram_weight);
+        let mut rerankers: Vec<&dyn Reranker> = vec![];
+        if single_char.weight != 0.0 {
+            rerankers.push(&single_char);
+        }
+        if user_phrase.weight != 0.0 {
+            rerankers.push(&user_phrase);
+        }
+        if self.options.char_ngram_weight != 0.0 {
+            rerankers.push(&char_ngram);
+        }
+        rerankers.extend(self.rerankers.iter().map(|reranker| reranker.as_ref()));
+        let context = RerankContext {
+            dict: &self.dict,
+            user_dict: self.dict.user_dict_ref(),
+            composition: self.com.as_ref(),
+        };
+        rerank(&rerankers, &context, outcomes);
     }
     fn intervals(&self) -> impl Iterator<Item = Interval> + use<> {
         self.conversion().into_iter()
@@ -665,6 +1412,92 @@ impl SharedState {
         }
         result.map(|_| phrase)
     }
+    /// Resolves the syllables typed in tone-less input mode or with
+    /// phonetic confusions to the reading of the phrase, so it's learned
+    /// with the right syllables.
+    ///
+    /// Returns `None` if the reading of any character cannot be found, so
+    /// that incomplete syllables are never learned.
+    fn resolve_reading(&self, syllables: &[Syllable], phrase: &str) -> Option<Vec<Syllable>> {
+        const MAX_READINGS: usize = 64;
+        let strategy = self.lookup_strategy();
+        let confusable = matches!(strategy, LookupStrategy::Confusable(_));
+        if !(confusable
+            || strategy == LookupStrategy::Toneless
+            || strategy == LookupStrategy::Abbreviated)
+            || syllables.len() != phrase.chars().count()
+        {
+            return Some(syllables.to_vec());
+        }
+        let freq_of = |syllables: &[Syllable], phrase: &str| {
+            self.dict
+                .lookup(syllables, LookupStrategy::Standard)
+                .into_iter()
+                .find(|it| it.as_str() == phrase)
+                .map(|it| it.freq())
+        };
+        // The readings of each character, exact matches first and then the
+        // most frequent. Characters without a reading of their own may still
+        // be found in the phrase with any of the matching syllables.
+        let mut unresolved = false;
+        let variants: Vec<Vec<Syllable>> = syllables
+            .iter()
+            .zip(phrase.chars())
+            .map(|(&syl, ch)| {
+                let ch = ch.to_string();
+                let mut found: Vec<(bool, u32, Syllable)> = matching_syllables(syl, strategy)
+                    .into_iter()
+                    .filter_map(|variant| {
+                        let freq = freq_of(&[variant], &ch)?;
+                        Some((confusable && variant != syl, freq, variant))
+                    })
+                    .collect();
+                found.sort_by_key(|&(fuzzy, freq, _)| (fuzzy, Reverse(freq)));
+                if found.is_empty() {
+                    unresolved = true;
+                    return matching_syllables(syl, strategy);
+                }
+                found.into_iter().map(|(_, _, variant)| variant).collect()
+            })
+            .collect();
+        if variants
+            .iter()
+            .zip(syllables)
+            .all(|(options, syl)| options == &[*syl])
+        {
+            return Some(syllables.to_vec());
+        }
+        let mut readings: Vec<Vec<Syllable>> = vec![vec![]];
+        for options in &variants {
+            readings = readings
+                .iter()
+                .flat_map(|reading| {
+                    options.iter().map(move |&syl| {
+                        let mut reading = reading.clone();
+                        reading.push(syl);
+                        reading
+                    })
+                })
+                .take(MAX_READINGS)
+                .collect();
+        }
+        // The first reading with the highest frequency.
+        let found = readings
+            .into_iter()
+            .filter_map(|reading| Some((freq_of(&reading, phrase)?, reading)))
+            .rev()
+            .max_by_key(|(freq, _)| *freq)
+            .map(|(_, reading)| reading);
+        if found.is_none() && unresolved {
+            return None;
+        }
+        found.or_else(|| {
+            variants
+                .iter()
+                .map(|options| options.first().copied())
+                .collect()
+        })
+    }
     fn learn_phrase(&mut self, syllables: &[Syllable], phrase: &str) -> Result<(), EditorError> {
         if syllables.len() != phrase.chars().count() {
             warn!(
@@ -679,11 +1512,21 @@ impl SharedState {
             ))
             .or_raise(|| EditorError::new(EditorErrorKind::InvalidState));
         }
+        let Some(syllables) = &self.resolve_reading(syllables, phrase) else {
+            warn!("failed to resolve the reading of phrase({phrase}) from {syllables:?}");
+            return Err(UpdateDictionaryError::new(
+                "failed to learn phrase: the reading cannot be resolved",
+            ))
+            .or_raise(|| EditorError::new(EditorErrorKind::InvalidInput));
+        };
         let phrases = self.dict.lookup(syllables, LookupStrategy::Standard);
         if phrases.is_empty() {
             self.dict
                 .add_phrase(syllables, (phrase, 10).into())
                 .or_raise(|| EditorError::new(EditorErrorKind::InvalidState))?;
+            self.events.push(EditorEvent::PhraseLearned {
+                phrase: phrase.to_owned(),
+            });
             return Ok(());
         }
         let phrase = phrases
@@ -696,55 +1539,174 @@ impl SharedState {
         let user_freq = self.estimate.estimate(&phrase, max_freq);
         let time = self.estimate.now();
 
+        let text = phrase.as_str().to_owned();
         let _ = self.dict.update_phrase(syllables, phrase, user_freq, time);
         self.dirty_level += 1;
+        self.events
+            .push(EditorEvent::PhraseLearned { phrase: text });
         Ok(())
     }
     fn unlearn_phrase(&mut self, syllables: &[Syllable], phrase: &str) -> Result<(), EditorError> {
+        let Some(syllables) = &self.resolve_reading(syllables, phrase) else {
+            return Err(EditorError::new(EditorErrorKind::InvalidInput));
+        };
         let _ = self
             .dict
             .remove_phrase(syllables, phrase)
             .or_raise(|| EditorError::new(EditorErrorKind::InvalidState))?;
         self.dirty_level += 1;
+        self.events.push(EditorEvent::PhraseUnlearned {
+            phrase: phrase.to_owned(),
+        });
+        Ok(())
+    }
+    fn demote_phrase(&mut self, syllables: &[Syllable], phrase: &str) -> Result<(), EditorError> {
+        let Some(syllables) = &self.resolve_reading(syllables, phrase) else {
+            return Err(EditorError::new(EditorErrorKind::InvalidInput));
+        };
+        let learned = self
+            .dict
+            .user_dict_ref()
+            .lookup(syllables, LookupStrategy::Standard)
+            .iter()
+            .any(|p| p.as_str() == phrase);
+        if learned {
+            // Other dictionaries still provide their own frequency.
+            let time = self.estimate.now();
+            self.dict
+                .update_phrase(syllables, (phrase, 0).into(), 0, time)
+                .or_raise(|| EditorError::new(EditorErrorKind::InvalidState))?;
+        } else {
+            // System dictionaries are read-only, so exclude the phrase.
+            self.dict
+                .remove_phrase(syllables, phrase)
+                .or_raise(|| EditorError::new(EditorErrorKind::InvalidState))?;
+            self.events.push(EditorEvent::PhraseUnlearned {
+                phrase: phrase.to_owned(),
+            });
+        }
+        self.dirty_level += 1;
         Ok(())
     }
     fn switch_language_mode(&mut self) {
-        self.options = EditorOptions {
-            language_mode: match self.options.language_mode {
-                LanguageMode::English => LanguageMode::Chinese,
-                LanguageMode::Chinese => LanguageMode::English,
-            },
-            ..self.options
+        self.options.language_mode = match self.options.language_mode {
+            LanguageMode::English => LanguageMode::Chinese,
+            LanguageMode::Chinese => LanguageMode::English,
         };
     }
     fn switch_character_form(&mut self) {
-        self.options = EditorOptions {
-            character_form: match self.options.character_form {
-                CharacterForm::Halfwidth => CharacterForm::Fullwidth,
-                CharacterForm::Fullwidth => CharacterForm::Halfwidth,
-            },
-            ..self.options
+        self.options.character_form = match self.options.character_form {
+            CharacterForm::Halfwidth => CharacterForm::Fullwidth,
+            CharacterForm::Fullwidth => CharacterForm::Halfwidth,
         };
     }
     fn cancel_selecting(&mut self) {
         self.com.pop_cursor();
     }
+    fn undo(&mut self) -> bool {
+        match self.history.undo(&self.com, self.options.undo_depth) {
+            Some(com) => {
+                self.com = com;
+                true
+            }
+            None => false,
+        }
+    }
+    fn redo(&mut self) -> bool {
+        match self.history.redo(&self.com) {
+            Some(com) => {
+                self.com = com;
+                true
+            }
+            None => false,
+        }
+    }
+    fn key_matches(&self, action: KeyAction, ev: &KeyboardEvent) -> bool {
+        self.options.key_bindings.matches(action, ev)
+    }
     fn commit(&mut self) {
         self.commit_buffer.clear();
         let intervals = self.conversion();
         debug!("buffer {:?}", self.com);
         if !self.options.disable_auto_learn_phrase {
             self.auto_learn(&intervals);
+            self.learn_bigrams(&intervals);
         }
+        self.last_auto_committed = None;
+        self.update_context(&intervals);
+        let last_phrase = intervals.last().filter(|it| it.is_phrase).map(|it| {
+            let syllables: Vec<Syllable> = self.com.symbols()[it.start..it.end]
+                .iter()
+                .filter_map(|sym| sym.to_syllable())
+                .collect();
+            let reading = self.resolve_reading(&syllables, &it.text);
+            (reading.unwrap_or_default(), it.text.clone())
+        });
         let output = intervals
             .into_iter()
             .map(|interval| interval.text)
             .collect::<String>();
         self.commit_buffer.push_str(&output);
         self.com.clear();
+        self.history.reset(&self.com);
         self.nth_conversion = 0;
+        self.predictions = None;
+        if let Some((reading, phrase)) = last_phrase {
+            self.predict(&phrase, &reading);
+        }
+        self.last_key_behavior = EditorKeyBehavior::Commit;
+    }
+    /// Commits the phrase predicted after the phrase `left`.
+    ///
+    /// Predictions learned from phrase pairs have no reading, the most
+    /// frequent one is looked up for the committed phrase only.
+    fn commit_prediction(&mut self, left: &str, mut prediction: Candidate) {
+        if prediction.reading.is_empty()
+            && let Some((reading, _)) = self
+                .dict
+                .reverse_lookup(&prediction.text)
+                .into_iter()
+                .next()
+        {
+            prediction.reading = reading;
+        }
+        self.commit_buffer.clear();
+        self.commit_buffer.push_str(&prediction.text);
+        if !self.options.disable_auto_learn_phrase && self.learn_bigram(left, &prediction.text) {
+            self.dirty_level += 1;
+        }
+        if let Some(context) = &mut self.context {
+            for (i, ch) in prediction.text.chars().enumerate() {
+                context.push(ch, prediction.reading.get(i).copied());
+            }
+        }
+        self.predictions = None;
+        self.predict(&prediction.text, &prediction.reading);
         self.last_key_behavior = EditorKeyBehavior::Commit;
     }
+    /// Predicts the phrases following the committed phrase, if enabled.
+    fn predict(&mut self, phrase: &str, reading: &[Syllable]) {
+        if !self.options.next_phrase_prediction
+            || self.options.language_mode != LanguageMode::Chinese
+        {
+            return;
+        }
+        let sel = PredictionSelector::new(
+            &self.dict,
+            phrase,
+            reading,
+            self.options.candidates_per_page,
+        );
+        if !sel.is_empty() {
+            self.predictions = Some(sel);
+        }
+    }
+    /// Returns true if the key picks or closes the predictions.
+    ///
+    /// Plain digits are bopomofo in some layouts so they are not used.
+    fn is_prediction_key(&self, ev: &KeyboardEvent) -> bool {
+        self.key_matches(KeyAction::SelectPrediction, ev) || self.key_matches(KeyAction::Cancel, ev)
+    }
     fn try_auto_commit(&mut self) {
         let len = self.com.len();
         if len <= self.options.auto_commit_threshold {
@@ -753,23 +1715,90 @@ impl SharedState {
         let intervals: Vec<_> = self.intervals().collect();
 
         let mut remove = 0;
+        let mut committed = vec![];
         self.commit_buffer.clear();
         for it in intervals {
             self.commit_buffer.push_str(&it.text);
+            self.update_context(std::slice::from_ref(&it));
             remove += it.len();
+            committed.push(it);
             if len - remove <= self.options.auto_commit_threshold {
                 break;
             }
         }
+        if self.options.disable_auto_learn_phrase {
+            self.last_auto_committed = None;
+        } else {
+            self.learn_bigrams(&committed);
+            self.last_auto_committed = committed
+                .last()
+                .filter(|it| it.is_phrase)
+                .map(|it| it.text.clone());
+        }
         self.com.remove_front(remove);
+        self.history.reset(&self.com);
         debug!(
             "buffer has {} symbols left after auto commit",
             self.com.len()
         );
         self.last_key_behavior = EditorKeyBehavior::Commit;
     }
+    fn update_context(&mut self, intervals: &[Interval]) {
+        let Some(context) = &mut self.context else {
+            return;
+        };
+        for interval in intervals {
+            let symbols = &self.com.symbols()[interval.start..interval.end];
+            for (ch, sym) in interval.text.chars().zip(symbols) {
+                context.push(ch, sym.to_syllable());
+            }
+        }
+    }
+    /// Records adjacent phrases of the committed text in the user dictionary.
+    ///
+    /// The first phrase is paired with the last auto committed phrase.
+    fn learn_bigrams(&mut self, intervals: &[Interval]) {
+        let mut learned = false;
+        let mut left = self.last_auto_committed.clone();
+        for right in intervals {
+            if !right.is_phrase {
+                left = None;
+                continue;
+            }
+            if let Some(left) = &left {
+                learned |= self.learn_bigram(left, &right.text);
+            }
+            left = Some(right.text.clone());
+        }
+        if learned {
+            self.dirty_level += 1;
+        }
+    }
+    /// Counts the phrase `right` committed after the phrase `left`.
+    fn learn_bigram(&mut self, left: &str, right: &str) -> bool {
+        const STALE_BIGRAM_TICKS: u64 = 50000;
+        let now = self.estimate.now();
+        let count = match self.dict.lookup_bigram(left, right) {
+            // Halve the count of pairs not used for a long time so that
+            // recent habits win.
+            Some((count, time)) if now.saturating_sub(time) >= STALE_BIGRAM_TICKS => count / 2 + 1,
+            Some((count, _)) => count.saturating_add(1),
+            None => 1,
+        };
+        match self.dict.update_bigram(left, right, count, now) {
+            Ok(()) => true,
+            Err(error) => {
+                debug!("Failed to learn phrase pair {left} {right}: {error:#}");
+                false
+            }
+        }
+    }
     fn auto_learn(&mut self, intervals: &[Interval]) {
         for (syllables, phrase) in collect_new_phrases(intervals, self.com.symbols()) {
+            let Some(syllables) = self.resolve_reading(&syllables, &phrase) else {
+                debug!("skip autolearn unresolved phrase {phrase} {syllables:?}");
+                continue;
+            };
             if self.dict.is_excluded(&syllables, &phrase) {
                 debug!("skip autolearn excluded phrase {phrase} {syllables:?}");
                 continue;
@@ -781,6 +1810,63 @@ impl SharedState {
     }
 }
 
+/// Returns the syllables in dictionaries that `syl` can match with the
+/// tone-less, confusable, or abbreviated lookup strategy.
+fn matching_syllables(syl: Syllable, strategy: LookupStrategy) -> Vec<Syllable> {
+    use Bopomofo::*;
+    let abbreviated = strategy == LookupStrategy::Abbreviated && syl.is_initial_only();
+    let alternatives = |part: Option<Bopomofo>| {
+        let mut parts = vec![part];
+        for (x, y) in Confusion::ALL.map(Confusion::pair) {
+            let other = if part == Some(x) {
+                y
+            } else if part == Some(y) {
+                x
+            } else {
+                continue;
+            };
+            if !parts.contains(&Some(other)) {
+                parts.push(Some(other));
+            }
+        }
+        parts
+    };
+    let (medials, rimes) = if abbreviated {
+        (
+            vec![None, Some(I), Some(U), Some(IU)],
+            [None]
+                .into_iter()
+                .chain([A, O, E, EH, AI, EI, AU, OU, AN, EN, ANG, ENG, ER].map(Some))
+                .collect(),
+        )
+    } else {
+        (vec![syl.medial()], alternatives(syl.rime()))
+    };
+    let tones = if syl.has_tone() {
+        vec![syl.tone()]
+    } else {
+        vec![None, Some(TONE2), Some(TONE3), Some(TONE4), Some(TONE5)]
+    };
+    let mut syllables = vec![];
+    for initial in alternatives(syl.initial()) {
+        for &medial in &medials {
+            for &rime in &rimes {
+                for &tone in &tones {
+                    let mut variant = Syllable::new();
+                    [initial, medial, rime, tone]
+                        .into_iter()
+                        .flatten()
+                        .for_each(|bopomofo| variant.update(bopomofo));
+                    if strategy.matches(syl, variant) {
+                        syllables.push(variant);
+                    }
+                }
+            }
+        }
+    }
+    syllables
+}
+
 #[rustfmt::skip]
 fn is_break_word(word: &str) -> bool {
     ["是", "的", "了", "不",
@@ -859,6 +1945,11 @@ impl BasicEditor for Editor {
         if self.shared.last_key_behavior == EditorKeyBehavior::Commit {
             self.shared.commit_buffer.clear();
         }
+        let before = self.observe();
+        if self.is_predicting() && !self.shared.is_prediction_key(&key_event) {
+            // Other keys close the predictions and work as usual.
+            self.state = Box::new(Entering);
+        }
 
         match self.state.next(&mut self.shared, key_event) {
             Transition::ToState(to_state) => {
@@ -878,6 +1969,10 @@ impl BasicEditor for Editor {
         if self.is_entering() && self.shared.last_key_behavior == EditorKeyBehavior::Absorb {
             self.shared.try_auto_commit();
         }
+        self.start_predicting();
+        self.shared
+            .history
+            .record(&self.shared.com, self.shared.options.undo_depth);
         trace!("last_key_behavior = {:?}", self.shared.last_key_behavior);
         trace!("comp: {:?}", &self.shared.com);
         const DIRTY_THRESHOLD: u16 = 0;
@@ -886,6 +1981,7 @@ impl BasicEditor for Editor {
             let _ = self.shared.dict.flush();
             self.shared.dirty_level = 0;
         }
+        self.notify(before);
         self.shared.last_key_behavior
     }
 }
@@ -914,6 +2010,7 @@ enum Selector {
     Phrase(PhraseSelector),
     Symbol(SymbolSelector),
     SpecialSymmbol(SpecialSymbolSelector),
+    Prediction(PredictionSelector),
 }
 
 #[derive(Debug)]
@@ -975,7 +2072,7 @@ impl Entering {
 impl State for Entering {
     fn next(&mut self, shared: &mut SharedState, ev: KeyboardEvent) -> Transition {
         match ev.ksym {
-            SYM_BACKSPACE => {
+            _ if shared.key_matches(KeyAction::DeleteBackward, &ev) => {
                 if shared.com.is_empty() {
                     self.spin_ignore()
                 } else {
@@ -983,15 +2080,25 @@ impl State for Entering {
                     self.spin_absorb()
                 }
             }
-            SYM_CAPSLOCK => {
+            _ if shared.key_matches(KeyAction::ToggleLanguageMode, &ev) => {
                 shared.switch_language_mode();
                 self.spin_absorb()
             }
-            code if ev.ksym.is_digit() && ev.is_state_on(KeyState::Control) => {
-                let n = code.to_digit().unwrap_or_default() as usize;
-                if n == 0 || n == 1 {
-                    return self.start_symbol_input(shared);
+            // Unmodified keys are typed as is in English mode
+            _ if shared.key_matches(KeyAction::OpenSymbolTable, &ev)
+                && (ev.has_modifiers()
+                    || shared.options.language_mode == LanguageMode::Chinese) =>
+            {
+                if shared.nth_conversion != 0 {
+                    shared.snapshot();
                 }
+                self.start_symbol_input(shared)
+            }
+            _ if shared.key_matches(KeyAction::AddPhrase, &ev) => {
+                let n = match ev.ksym.to_digit() {
+                    Some(n) => n as usize,
+                    None => return self.spin_bell(),
+                };
                 let result = match shared.options.user_phrase_add_dir {
                     UserPhraseAddDirection::Forward => {
                         shared.learn_phrase_in_range_notify(shared.cursor(), shared.cursor() + n)
@@ -1011,17 +2118,47 @@ impl State for Entering {
                     Err(_) => self.spin_bell(),
                 }
             }
-            SYM_RETURN | SYM_ESC | SYM_TAB | SYM_HOME | SYM_END | SYM_LEFT | SYM_RIGHT | SYM_UP
-            | SYM_DOWN | SYM_PAGEUP | SYM_PAGEDOWN
-                if shared.com.is_empty() =>
+            _ if shared.key_matches(KeyAction::Undo, &ev) => {
+                if shared.undo() {
+                    self.spin_absorb()
+                } else {
+                    self.spin_bell()
+                }
+            }
+            _ if shared.key_matches(KeyAction::Redo, &ev) => {
+                if shared.redo() {
+                    self.spin_absorb()
+                } else {
+                    self.spin_bell()
+                }
+            }
+            _ if shared.com.is_empty()
+                && [
+                    KeyAction::Commit,
+                    KeyAction::Cancel,
+                    KeyAction::NextConversion,
+                    KeyAction::InsertBreakOrGlue,
+                    KeyAction::CursorToBeginning,
+                    KeyAction::CursorToEnd,
+                    KeyAction::CursorLeft,
+                    KeyAction::CursorRight,
+                    KeyAction::HighlightLeft,
+                    KeyAction::HighlightRight,
+                    KeyAction::OpenCandidates,
+                    KeyAction::CloseCandidates,
+                ]
+                .into_iter()
+                .any(|action| shared.key_matches(action, &ev)) =>
             {
                 self.spin_ignore()
             }
-            SYM_TAB if shared.com.is_end_of_buffer() => {
+            _ if shared.key_matches(KeyAction::NextConversion, &ev)
+                && shared.com.is_end_of_buffer() =>
+            {
                 shared.nth_conversion += 1;
                 self.spin_absorb()
             }
-            SYM_TAB => {
+            _ if shared.key_matches(KeyAction::InsertBreakOrGlue, &ev) => {
                 let interval_ends: Vec<_> = shared.conversion().iter().map(|it| it.end).collect();
                 if interval_ends.contains(&shared.cursor()) {
                     shared.com.insert_glue();
@@ -1034,7 +2171,7 @@ impl State for Entering {
             //     // editor.reset_user_break_and_connect_at_cursor();
             //     (EditorKeyBehavior::Absorb, &Entering)
             // }
-            SYM_DELETE => {
+            _ if shared.key_matches(KeyAction::DeleteForward, &ev) => {
                 if shared.com.is_end_of_buffer() {
                     self.spin_ignore()
                 } else {
@@ -1042,39 +2179,38 @@ impl State for Entering {
                     self.spin_absorb()
                 }
             }
-            SYM_HOME => {
+            _ if shared.key_matches(KeyAction::CursorToBeginning, &ev) => {
                 shared.snapshot();
                 shared.com.move_cursor_to_beginning();
                 self.spin_absorb()
             }
-            SYM_LEFT if ev.is_state_on(KeyState::Shift) => {
+            _ if shared.key_matches(KeyAction::HighlightLeft, &ev) => {
                 if shared.com.is_beginning_of_buffer() {
                     return self.spin_ignore();
                 }
                 shared.snapshot();
                 self.start_highlighting(shared.cursor() - 1)
             }
-            SYM_RIGHT if ev.is_state_on(KeyState::Shift) => {
+            _ if shared.key_matches(KeyAction::HighlightRight, &ev) => {
                 if shared.com.is_end_of_buffer() {
                     return self.spin_ignore();
                 }
                 shared.snapshot();
                 self.start_highlighting(shared.cursor() + 1)
             }
-            SYM_LEFT => {
+            _ if shared.key_matches(KeyAction::CursorLeft, &ev) => {
                 shared.snapshot();
                 shared.com.move_cursor_left(1);
                 self.spin_absorb()
             }
-            SYM_RIGHT => {
+            _ if shared.key_matches(KeyAction::CursorRight, &ev) => {
                 shared.snapshot();
                 shared.com.move_cursor_right(1);
                 self.spin_absorb()
             }
-            SYM_UP => self.spin_ignore(),
-            SYM_SPACE
-                if ev.is_state_on(KeyState::Shift)
-                    && shared.options.enable_fullwidth_toggle_key =>
+            _ if shared.key_matches(KeyAction::CloseCandidates, &ev) => self.spin_ignore(),
+            _ if shared.key_matches(KeyAction::ToggleCharacterForm, &ev)
+                && shared.options.enable_fullwidth_toggle_key =>
             {
                 shared.switch_character_form();
                 self.spin_absorb()
@@ -1085,20 +2221,20 @@ impl State for Entering {
             {
                 self.start_selecting_or_input_space(shared)
             }
-            SYM_DOWN => {
+            _ if shared.key_matches(KeyAction::OpenCandidates, &ev) => {
                 debug!("buffer {:?}", shared.com);
                 self.start_selecting(shared)
             }
-            SYM_END | SYM_PAGEUP | SYM_PAGEDOWN => {
+            _ if shared.key_matches(KeyAction::CursorToEnd, &ev) => {
                 shared.snapshot();
                 shared.com.move_cursor_to_end();
                 self.spin_absorb()
             }
-            SYM_RETURN => {
+            _ if shared.key_matches(KeyAction::Commit, &ev) => {
                 shared.commit();
                 self.spin_commit()
             }
-            SYM_ESC => {
+            _ if shared.key_matches(KeyAction::Cancel, &ev) => {
                 if shared.options.esc_clear_all_buffer && !shared.com.is_empty() {
                     shared.com.clear();
                     self.spin_absorb()
@@ -1121,9 +2257,6 @@ impl State for Entering {
                     shared.snapshot();
                 }
                 match shared.options.language_mode {
-                    LanguageMode::Chinese if ev.ksym == SYM_GRAVE && !ev.has_modifiers() => {
-                        self.start_symbol_input(shared)
-                    }
                     LanguageMode::Chinese if ev.ksym == SYM_SPACE => {
                         match shared.options.character_form {
                             CharacterForm::Halfwidth => {
@@ -1256,7 +2389,7 @@ impl EnteringSyllable {
 impl State for EnteringSyllable {
     fn next(&mut self, shared: &mut SharedState, ev: KeyboardEvent) -> Transition {
         match ev.ksym {
-            SYM_BACKSPACE => {
+            _ if shared.key_matches(KeyAction::DeleteBackward, &ev) => {
                 shared.syl.remove_last();
 
                 if !shared.syl.is_empty() {
@@ -1265,12 +2398,12 @@ impl State for EnteringSyllable {
                     self.start_entering()
                 }
             }
-            SYM_CAPSLOCK => {
+            _ if shared.key_matches(KeyAction::ToggleLanguageMode, &ev) => {
                 shared.syl.clear();
                 shared.switch_language_mode();
                 self.start_entering()
             }
-            SYM_ESC => {
+            _ if shared.key_matches(KeyAction::Cancel, &ev) => {
                 shared.syl.clear();
                 if shared.options.esc_clear_all_buffer {
                     shared.com.clear();
@@ -1278,16 +2411,20 @@ impl State for EnteringSyllable {
                 self.start_entering()
             }
             _ => {
-                let key_behavior = match shared.options.lookup_strategy {
+                let key_behavior = match shared.lookup_strategy() {
                     LookupStrategy::FuzzyPartialPrefix => shared.syl.fuzzy_key_press(ev),
-                    LookupStrategy::Standard => shared.syl.key_press(ev),
+                    LookupStrategy::Toneless => shared.syl.toneless_key_press(ev),
+                    LookupStrategy::Abbreviated => shared.syl.abbreviated_key_press(ev),
+                    LookupStrategy::Standard | LookupStrategy::Confusable(_) => {
+                        shared.syl.key_press(ev)
+                    }
                 };
                 match key_behavior {
                     KeyBehavior::Absorb => self.spin_absorb(),
                     KeyBehavior::Fuzzy(syl) => {
                         if !shared
                             .dict
-                            .lookup(&[syl], shared.options.lookup_strategy)
+                            .lookup(&[syl], shared.lookup_strategy())
                             .is_empty()
                         {
                             shared.com.insert(Symbol::from(syl));
@@ -1297,7 +2434,7 @@ impl State for EnteringSyllable {
                     KeyBehavior::Commit => {
                         if !shared
                             .dict
-                            .lookup(&[shared.syl.read()], shared.options.lookup_strategy)
+                            .lookup(&[shared.syl.read()], shared.lookup_strategy())
                             .is_empty()
                         {
                             shared.com.insert(Symbol::from(shared.syl.read()));
@@ -1309,6 +2446,20 @@ impl State for EnteringSyllable {
                             } else {
                                 self.start_entering()
                             }
+                        } else if let Some((syl, suggestions)) =
+                            shared.correct_syllable(shared.syl.read())
+                        {
+                            shared.com.insert_corrected(Symbol::from(syl), suggestions);
+                            shared.syl.clear();
+                            if shared.options.conversion_engine
+                                == ConversionEngineKind::SimpleEngine
+                            {
+                                self.start_selecting_simple_engine(shared)
+                            } else if shared.options.auto_correct_syllables {
+                                self.start_entering()
+                            } else {
+                                Transition::ToState(Box::new(Selecting::new_phrase(shared)))
+                            }
                         } else {
                             shared.syl.clear();
                             self.start_entering()
@@ -1328,7 +2479,7 @@ impl Selecting {
 
         let mut sel = PhraseSelector::new(
             !editor.options.phrase_choice_rearward,
-            editor.options.lookup_strategy,
+            editor.lookup_strategy(),
             editor.com.to_composition(),
         );
         sel.init(editor.cursor(), &editor.dict);
@@ -1343,11 +2494,8 @@ impl Selecting {
         editor.com.push_cursor();
         // editor.com.clamp_cursor();
 
-        let mut sel = PhraseSelector::new(
-            false,
-            editor.options.lookup_strategy,
-            editor.com.to_composition(),
-        );
+        let mut sel =
+            PhraseSelector::new(false, editor.lookup_strategy(), editor.com.to_composition());
         sel.init_single_word(editor.cursor());
 
         Selecting {
@@ -1381,11 +2529,42 @@ impl Selecting {
             }
         }
     }
+    fn new_prediction(sel: PredictionSelector) -> Self {
+        Selecting {
+            page_no: 0,
+            action: SelectingAction::Insert,
+            sel: Selector::Prediction(sel),
+        }
+    }
     fn candidates(&self, editor: &SharedState, dict: &Layered) -> Vec<String> {
         match &self.sel {
             Selector::Phrase(sel) => sel.candidates(editor, dict),
             Selector::Symbol(sel) => sel.menu(),
             Selector::SpecialSymmbol(sel) => sel.menu(),
+            Selector::Prediction(sel) => sel.menu(),
+        }
+    }
+    fn candidate_details(&self, editor: &SharedState, dict: &Layered) -> Vec<Candidate> {
+        match &self.sel {
+            Selector::Phrase(sel) => sel.candidate_details(editor, dict),
+            Selector::Symbol(sel) => sel.menu().into_iter().map(Candidate::symbol).collect(),
+            Selector::SpecialSymmbol(sel) => {
+                sel.menu().into_iter().map(Candidate::symbol).collect()
+            }
+            Selector::Prediction(sel) => sel.candidate_details(),
+        }
+    }
+    fn nth_candidate_details(
+        &self,
+        editor: &SharedState,
+        dict: &Layered,
+        n: usize,
+    ) -> Option<Candidate> {
+        match &self.sel {
+            Selector::Phrase(sel) => sel.nth_candidate_details(editor, dict, n),
+            Selector::Symbol(sel) => sel.menu().into_iter().nth(n).map(Candidate::symbol),
+            Selector::SpecialSymmbol(sel) => sel.menu().into_iter().nth(n).map(Candidate::symbol),
+            Selector::Prediction(sel) => sel.select(n).cloned(),
         }
     }
     fn total_page(&self, editor: &SharedState, dict: &Layered) -> usize {
@@ -1446,7 +2625,59 @@ impl Selecting {
                     self.spin_absorb()
                 }
             },
+            Selector::Prediction(ref sel) => match sel.select(offset) {
+                Some(prediction) => {
+                    editor.commit_prediction(sel.phrase(), prediction.clone());
+                    Transition::Spin(EditorKeyBehavior::Commit)
+                }
+                None => self.spin_bell(),
+            },
+        }
+    }
+    fn phrase_candidate(&self, editor: &SharedState, n: usize) -> Option<Candidate> {
+        let offset = self.page_no * editor.options.candidates_per_page + n;
+        match &self.sel {
+            Selector::Phrase(sel) => sel
+                .candidate_details(editor, &editor.dict)
+                .into_iter()
+                .nth(offset),
+            Selector::Symbol(_) | Selector::SpecialSymmbol(_) | Selector::Prediction(_) => None,
+        }
+    }
+    fn delete_candidate(&mut self, editor: &mut SharedState, n: usize) -> Transition {
+        let Some(candidate) = self.phrase_candidate(editor, n) else {
+            return self.spin_bell();
+        };
+        if editor
+            .unlearn_phrase(&candidate.reading, &candidate.text)
+            .is_err()
+        {
+            return self.spin_bell();
+        }
+        editor.notice_buffer = format!("刪除：{}", candidate.text);
+        self.refresh_candidates(editor)
+    }
+    fn demote_candidate(&mut self, editor: &mut SharedState, n: usize) -> Transition {
+        let Some(candidate) = self.phrase_candidate(editor, n) else {
+            return self.spin_bell();
+        };
+        if editor
+            .demote_phrase(&candidate.reading, &candidate.text)
+            .is_err()
+        {
+            return self.spin_bell();
         }
+        editor.notice_buffer = format!("調降：{}", candidate.text);
+        self.refresh_candidates(editor)
+    }
+    fn refresh_candidates(&mut self, editor: &mut SharedState) -> Transition {
+        let total_page = self.total_page(editor, &editor.dict);
+        if total_page == 0 {
+            editor.cancel_selecting();
+            return self.start_entering();
+        }
+        self.page_no = self.page_no.min(total_page - 1);
+        self.spin_absorb()
     }
     fn start_entering(&self) -> Transition {
         Transition::ToState(Box::new(Entering))
@@ -1455,25 +2686,37 @@ impl Selecting {
 
 impl State for Selecting {
     fn next(&mut self, shared: &mut SharedState, ev: KeyboardEvent) -> Transition {
+        if ev.ksym.is_digit() {
+            let n = ev.ksym.to_digit().unwrap() as usize;
+            let n = if n == 0 { 9 } else { n - 1 };
+            if let Selector::Prediction(_) = self.sel {
+                if shared.key_matches(KeyAction::SelectPrediction, &ev) {
+                    return self.select(shared, n);
+                }
+                return self.spin_bell();
+            }
+            if shared.key_matches(KeyAction::DeleteCandidate, &ev) {
+                return self.delete_candidate(shared, n);
+            }
+            if shared.key_matches(KeyAction::DemoteCandidate, &ev) {
+                return self.demote_candidate(shared, n);
+            }
+        }
         if ev.is_state_on(KeyState::Control) || ev.is_state_on(KeyState::Shift) {
             return self.spin_bell();
         }
 
         match ev.ksym {
-            SYM_BACKSPACE => {
-                shared.cancel_selecting();
-                self.start_entering()
-            }
-            SYM_CAPSLOCK => {
+            _ if shared.key_matches(KeyAction::ToggleLanguageMode, &ev) => {
                 shared.switch_language_mode();
                 shared.cancel_selecting();
                 self.start_entering()
             }
-            SYM_UP => {
+            _ if shared.key_matches(KeyAction::CloseCandidates, &ev) => {
                 shared.cancel_selecting();
                 self.start_entering()
             }
-            SYM_DOWN | SYM_SPACE => {
+            _ if shared.key_matches(KeyAction::CycleCandidates, &ev) => {
                 if self.page_no + 1 < self.total_page(shared, &shared.dict) {
                     self.page_no += 1;
                 } else {
@@ -1484,11 +2727,12 @@ impl State for Selecting {
                         }
                         Selector::Symbol(_sel) => (),
                         Selector::SpecialSymmbol(_sel) => (),
+                        Selector::Prediction(_sel) => (),
                     }
                 }
                 self.spin_absorb()
             }
-            SYM_LOWER_J => {
+            _ if shared.key_matches(KeyAction::PrevSelectionPoint, &ev) => {
                 if shared.com.is_empty() {
                     return self.spin_ignore();
                 }
@@ -1496,13 +2740,14 @@ impl State for Selecting {
                     Selector::Phrase(sel) => sel.begin(),
                     Selector::Symbol(_) => shared.com.cursor(),
                     Selector::SpecialSymmbol(_) => shared.com.cursor(),
+                    Selector::Prediction(_) => shared.com.cursor(),
                 };
                 shared.com.move_cursor(begin.saturating_sub(1));
                 let sym = shared.com.symbol().expect("should have symbol");
                 if sym.is_syllable() {
                     let mut sel = PhraseSelector::new(
                         !shared.options.phrase_choice_rearward,
-                        shared.options.lookup_strategy,
+                        shared.lookup_strategy(),
                         shared.com.to_composition(),
                     );
                     sel.init(shared.cursor(), &shared.dict);
@@ -1513,7 +2758,7 @@ impl State for Selecting {
                 }
                 self.spin_absorb()
             }
-            SYM_LOWER_K => {
+            _ if shared.key_matches(KeyAction::NextSelectionPoint, &ev) => {
                 if shared.com.is_empty() {
                     return self.spin_ignore();
                 }
@@ -1521,6 +2766,7 @@ impl State for Selecting {
                     Selector::Phrase(sel) => sel.begin(),
                     Selector::Symbol(_) => shared.com.cursor(),
                     Selector::SpecialSymmbol(_) => shared.com.cursor(),
+                    Selector::Prediction(_) => shared.com.cursor(),
                 };
                 shared.com.move_cursor(begin.saturating_add(1));
                 shared.com.clamp_cursor();
@@ -1528,7 +2774,7 @@ impl State for Selecting {
                 if sym.is_syllable() {
                     let mut sel = PhraseSelector::new(
                         !shared.options.phrase_choice_rearward,
-                        shared.options.lookup_strategy,
+                        shared.lookup_strategy(),
                         shared.com.to_composition(),
                     );
                     sel.init(shared.cursor(), &shared.dict);
@@ -1539,7 +2785,7 @@ impl State for Selecting {
                 }
                 self.spin_absorb()
             }
-            SYM_LEFT | SYM_PAGEUP => {
+            _ if shared.key_matches(KeyAction::PrevCandidatePage, &ev) => {
                 if self.page_no > 0 {
                     self.page_no -= 1;
                 } else {
@@ -1547,7 +2793,7 @@ impl State for Selecting {
                 }
                 self.spin_absorb()
             }
-            SYM_RIGHT | SYM_PAGEDOWN => {
+            _ if shared.key_matches(KeyAction::NextCandidatePage, &ev) => {
                 if self.page_no + 1 < self.total_page(shared, &shared.dict) {
                     self.page_no += 1;
                 } else {
@@ -1560,7 +2806,7 @@ impl State for Selecting {
                 let n = if n == 0 { 9 } else { n - 1 };
                 self.select(shared, n)
             }
-            SYM_ESC => {
+            _ if shared.key_matches(KeyAction::Cancel, &ev) => {
                 shared.cancel_selecting();
                 shared.com.pop_cursor();
                 if shared.options.conversion_engine == ConversionEngineKind::SimpleEngine {
@@ -1568,7 +2814,7 @@ impl State for Selecting {
                 }
                 self.start_entering()
             }
-            SYM_DELETE => {
+            _ if shared.key_matches(KeyAction::DeleteForward, &ev) => {
                 // NB: should be Ignore but return Absorb for backward compat
                 self.spin_absorb()
             }
@@ -1589,23 +2835,23 @@ impl Highlighting {
 impl State for Highlighting {
     fn next(&mut self, shared: &mut SharedState, ev: KeyboardEvent) -> Transition {
         match ev.ksym {
-            SYM_CAPSLOCK => {
+            _ if shared.key_matches(KeyAction::ToggleLanguageMode, &ev) => {
                 shared.switch_language_mode();
                 self.start_entering()
             }
-            SYM_LEFT if ev.is_state_on(KeyState::Shift) => {
+            _ if shared.key_matches(KeyAction::HighlightLeft, &ev) => {
                 if self.moving_cursor != 0 {
                     self.moving_cursor -= 1;
                 }
                 self.spin_absorb()
             }
-            SYM_RIGHT if ev.is_state_on(KeyState::Shift) => {
+            _ if shared.key_matches(KeyAction::HighlightRight, &ev) => {
                 if self.moving_cursor != shared.com.len() {
                     self.moving_cursor += 1;
                 }
                 self.spin_absorb()
             }
-            SYM_RETURN => {
+            _ if shared.key_matches(KeyAction::LearnHighlighted, &ev) => {
                 let start = min(self.moving_cursor, shared.com.cursor());
                 let end = max(self.moving_cursor, shared.com.cursor());
                 shared.com.move_cursor(self.moving_cursor);
@@ -1652,11 +2898,14 @@ impl_exn!(EditorError);
 mod tests {
     use super::collect_new_phrases;
     use super::estimate::LaxUserFreqEstimate;
-    use super::{BasicEditor, Editor};
+    use super::matching_syllables;
+    use super::{BasicEditor, ConversionEngineKind, Editor};
     use crate::editor::LanguageMode;
     use crate::{
-        conversion::{ChewingEngine, Interval, Symbol},
-        dictionary::{Layered, TrieBuf},
+        conversion::{
+            ChewingEngine, Interval, Outcome, RerankContext, Reranker, SimpleEngine, Symbol,
+        },
+        dictionary::{Dictionary, Layered, LookupStrategy, TrieBuf},
         editor::{EditorKeyBehavior, SymbolSelector, abbrev::AbbrevTable},
         input::{
             KeyboardEvent, keycode,
@@ -1664,7 +2913,7 @@ mod tests {
             keysym,
         },
         syl,
-        zhuyin::Bopomofo as bpmf,
+        zhuyin::{Bopomofo as bpmf, Confusion, ConfusionSet},
     };
 
     const CAPSLOCK_EVENT: KeyboardEvent = KeyboardEvent::builder()
@@ -1673,6 +2922,23 @@ mod tests {
         .caps_lock_if(true)
         .build();
 
+    /// Creates an editor using the Chewing engine, with the dictionary and
+    /// an empty user dictionary.
+    fn editor_with_dict(dict: TrieBuf) -> Editor {
+        editor_with_layered(Layered::new(vec![
+            Box::new(dict),
+            Box::new(TrieBuf::new_in_memory()),
+        ]))
+    }
+
+    fn editor_with_layered(dict: Layered) -> Editor {
+        let conversion_engine = Box::new(ChewingEngine::new());
+        let estimate = LaxUserFreqEstimate::new(0);
+        let abbrev = AbbrevTable::new();
+        let sym_sel = SymbolSelector::default();
+        Editor::new(conversion_engine, dict, estimate, abbrev, sym_sel)
+    }
+
     #[test]
     fn editing_mode_input_bopomofo() {
         let dict = Layered::new(vec![Box::new(TrieBuf::new_in_memory())]);
@@ -1735,6 +3001,1106 @@ mod tests {
         assert_eq!("冊", editor.display());
     }
 
+    #[test]
+    fn editing_mode_remapped_commit_key() {
+        let dict = TrieBuf::from([(
+            vec![crate::syl![bpmf::C, bpmf::E, bpmf::TONE4]],
+            vec![("冊", 100)],
+        )]);
+        let mut editor = editor_with_dict(dict);
+        editor
+            .set_editor_options(|opt| {
+                opt.key_bindings = "commit = Ctrl+m\n".parse().unwrap();
+            })
+            .unwrap();
+
+        for key in [b'h', b'k', b'4'] {
+            editor.process_keyevent(map_ascii(&QWERTY_MAP, key));
+        }
+        let enter = KeyboardEvent::builder()
+            .code(keycode::KEY_ENTER)
+            .ksym(keysym::SYM_RETURN)
+            .build();
+        let ctrl_m = KeyboardEvent::builder()
+            .code(keycode::KEY_M)
+            .ksym(keysym::SYM_LOWER_M)
+            .control()
+            .build();
+
+        assert_eq!(EditorKeyBehavior::Bell, editor.process_keyevent(enter));
+        assert_eq!("冊", editor.display());
+        assert_eq!(EditorKeyBehavior::Commit, editor.process_keyevent(ctrl_m));
+        assert_eq!("冊", editor.display_commit());
+        assert!(editor.is_empty());
+    }
+
+    #[test]
+    fn editing_mode_undo_redo() {
+        let dict = TrieBuf::from([(
+            vec![crate::syl![bpmf::C, bpmf::E, bpmf::TONE4]],
+            vec![("冊", 100)],
+        )]);
+        let mut editor = editor_with_dict(dict);
+
+        for key in [b'h', b'k', b'4', b'h', b'k', b'4'] {
+            editor.process_keyevent(map_ascii(&QWERTY_MAP, key));
+        }
+        assert_eq!("冊冊", editor.display());
+        assert!(editor.can_undo());
+
+        let ctrl_z = KeyboardEvent::builder()
+            .code(keycode::KEY_Z)
+            .ksym(keysym::SYM_LOWER_Z)
+            .control()
+            .build();
+        assert_eq!(EditorKeyBehavior::Absorb, editor.process_keyevent(ctrl_z));
+        assert_eq!("冊", editor.display());
+        assert!(editor.redo().is_ok());
+        assert_eq!("冊冊", editor.display());
+        assert!(editor.undo().is_ok());
+        assert!(editor.undo().is_ok());
+        assert!(editor.is_empty());
+        assert!(editor.undo().is_err());
+        assert_eq!(EditorKeyBehavior::Bell, editor.process_keyevent(ctrl_z));
+    }
+
+    #[test]
+    fn save_and_restore_state() {
+        let new_editor = || {
+            let dict = TrieBuf::from([
+                (
+                    vec![crate::syl![bpmf::C, bpmf::E, bpmf::TONE4]],
+                    vec![("冊", 100)],
+                ),
+                (vec![crate::syl![bpmf::SH, bpmf::TONE4]], vec![("試", 100)]),
+            ]);
+            editor_with_dict(dict)
+        };
+        let mut editor = new_editor();
+        for key in [b'h', b'k', b'4', b'g'] {
+            editor.process_keyevent(map_ascii(&QWERTY_MAP, key));
+        }
+        let state = editor.save_state().unwrap();
+
+        let mut restored = new_editor();
+        restored.restore_state(&state).unwrap();
+        assert_eq!("冊", restored.display());
+        assert_eq!(crate::syl![bpmf::SH], restored.syllable_buffer());
+        restored.process_keyevent(map_ascii(&QWERTY_MAP, b'4'));
+        assert_eq!("冊試", restored.display());
+
+        let down = KeyboardEvent::builder()
+            .code(keycode::KEY_DOWN)
+            .ksym(keysym::SYM_DOWN)
+            .build();
+        restored.process_keyevent(down);
+        assert!(restored.is_selecting());
+        let cursor = restored.cursor();
+        let state = restored.save_state().unwrap();
+
+        let mut restored = new_editor();
+        restored.restore_state(&state).unwrap();
+        assert!(restored.is_selecting());
+        assert_eq!(cursor, restored.cursor());
+        assert!(restored.restore_state(b"garbage").is_err());
+        assert!(restored.is_selecting());
+    }
+
+    #[test]
+    fn subscribe_editor_events() {
+        use std::{cell::RefCell, rc::Rc};
+
+        use super::EditorEvent;
+
+        let dict = TrieBuf::from([(
+            vec![crate::syl![bpmf::C, bpmf::E, bpmf::TONE4]],
+            vec![("冊", 100), ("測", 200)],
+        )]);
+        let mut editor = editor_with_dict(dict);
+
+        let events = Rc::new(RefCell::new(vec![]));
+        let sink = events.clone();
+        let id = editor.subscribe(move |ev| sink.borrow_mut().push(ev.clone()));
+
+        editor.process_keyevent(map_ascii(&QWERTY_MAP, b'h'));
+        assert_eq!(
+            vec![EditorEvent::PreeditChanged {
+                display: "".to_string(),
+                syllable: "ㄘ".to_string()
+            }],
+            events.take()
+        );
+        for key in [b'k', b'4'] {
+            editor.process_keyevent(map_ascii(&QWERTY_MAP, key));
+        }
+        assert!(
+            events
+                .take()
+                .contains(&EditorEvent::CursorMoved { cursor: 1 })
+        );
+
+        let down = KeyboardEvent::builder()
+            .code(keycode::KEY_DOWN)
+            .ksym(keysym::SYM_DOWN)
+            .build();
+        editor.process_keyevent(down);
+        assert_eq!(
+            vec![
+                EditorEvent::CursorMoved { cursor: 0 },
+                EditorEvent::CandidatesOpened {
+                    page_no: 0,
+                    total_page: 1
+                }
+            ],
+            events.take()
+        );
+        let candidates = editor.all_candidates().unwrap();
+        let n = candidates.iter().position(|c| c == "冊").unwrap();
+        editor.select(n).unwrap();
+        let received = events.take();
+        assert!(received.contains(&EditorEvent::CandidatesClosed));
+        assert!(received.contains(&EditorEvent::PreeditChanged {
+            display: "冊".to_string(),
+            syllable: "".to_string()
+        }));
+
+        let enter = KeyboardEvent::builder()
+            .code(keycode::KEY_ENTER)
+            .ksym(keysym::SYM_RETURN)
+            .build();
+        editor.process_keyevent(enter);
+        let received = events.take();
+        assert!(received.contains(&EditorEvent::Commit {
+            text: "冊".to_string()
+        }));
+        assert!(received.contains(&EditorEvent::PhraseLearned {
+            phrase: "冊".to_string()
+        }));
+
+        editor.process_keyevent(CAPSLOCK_EVENT);
+        assert_eq!(
+            vec![EditorEvent::LanguageModeChanged(LanguageMode::English)],
+            events.take()
+        );
+
+        assert!(editor.unsubscribe(id));
+        editor.process_keyevent(CAPSLOCK_EVENT);
+        assert!(events.take().is_empty());
+    }
+
+    #[test]
+    fn rerank_conversion_outcomes() {
+        #[derive(Debug)]
+        struct AvoidChar(char);
+
+        impl Reranker for AvoidChar {
+            fn rescore(&self, _context: &RerankContext<'_>, outcome: &Outcome) -> f64 {
+                if outcome
+                    .intervals()
+                    .iter()
+                    .any(|it| it.text.contains(self.0))
+                {
+                    -100.0
+                } else {
+                    0.0
+                }
+            }
+        }
+
+        let dict = TrieBuf::from([
+            (
+                vec![crate::syl![bpmf::C, bpmf::E, bpmf::TONE4]],
+                vec![("測", 1000000)],
+            ),
+            (
+                vec![crate::syl![bpmf::SH, bpmf::TONE4]],
+                vec![("是", 1000000)],
+            ),
+            (
+                vec![
+                    crate::syl![bpmf::C, bpmf::E, bpmf::TONE4],
+                    crate::syl![bpmf::SH, bpmf::TONE4],
+                ],
+                vec![("測試", 1)],
+            ),
+        ]);
+        let mut editor = editor_with_dict(dict);
+
+        for key in [b'h', b'k', b'4', b'g', b'4'] {
+            editor.process_keyevent(map_ascii(&QWERTY_MAP, key));
+        }
+        assert_eq!("測是", editor.display());
+
+        editor
+            .set_editor_options(|opt| opt.single_char_penalty = 5.0)
+            .unwrap();
+        assert_eq!("測試", editor.display());
+
+        editor.add_reranker(Box::new(AvoidChar('試')));
+        assert_eq!("測是", editor.display());
+
+        editor.clear_rerankers();
+        assert_eq!("測試", editor.display());
+    }
+
+    #[test]
+    fn conversion_search_options() {
+        let dict = TrieBuf::from([
+            (
+                vec![crate::syl![bpmf::C, bpmf::E, bpmf::TONE4]],
+                vec![("測", 1)],
+            ),
+            (vec![crate::syl![bpmf::SH, bpmf::TONE4]], vec![("是", 1)]),
+            (
+                vec![
+                    crate::syl![bpmf::C, bpmf::E, bpmf::TONE4],
+                    crate::syl![bpmf::SH, bpmf::TONE4],
+                ],
+                vec![("測試", 100)],
+            ),
+        ]);
+        let mut editor = editor_with_dict(dict);
+
+        for key in [b'h', b'k', b'4', b'g', b'4'] {
+            editor.process_keyevent(map_ascii(&QWERTY_MAP, key));
+        }
+        assert_eq!("測試", editor.display());
+
+        editor
+            .set_editor_options(|opt| opt.max_phrase_len = 1)
+            .unwrap();
+        assert_eq!("測是", editor.display());
+
+        editor
+            .set_editor_options(|opt| opt.max_phrase_len = 2)
+            .unwrap();
+        assert_eq!("測試", editor.display());
+    }
+
+    #[test]
+    fn toneless_input() {
+        let dict = TrieBuf::from([
+            (
+                vec![crate::syl![bpmf::C, bpmf::E, bpmf::TONE4]],
+                vec![("測", 1)],
+            ),
+            (vec![crate::syl![bpmf::SH]], vec![("詩", 10)]),
+            (vec![crate::syl![bpmf::SH, bpmf::TONE4]], vec![("試", 100)]),
+        ]);
+        let mut editor = editor_with_dict(dict);
+        editor
+            .set_editor_options(|opt| opt.toneless_input = true)
+            .unwrap();
+
+        for key in [b'h', b'k', b'g'] {
+            editor.process_keyevent(map_ascii(&QWERTY_MAP, key));
+        }
+        assert_eq!("測", editor.display());
+        assert_eq!(syl![bpmf::SH], editor.syllable_buffer());
+
+        editor.process_keyevent(map_ascii(&QWERTY_MAP, b' '));
+        assert_eq!("測試", editor.display());
+
+        editor.commit().unwrap();
+        let toneless = [crate::syl![bpmf::C, bpmf::E], crate::syl![bpmf::SH]];
+        let toned = [
+            crate::syl![bpmf::C, bpmf::E, bpmf::TONE4],
+            crate::syl![bpmf::SH, bpmf::TONE4],
+        ];
+        let user_dict = editor.user_dict();
+        assert!(
+            user_dict
+                .lookup(&toneless, LookupStrategy::Standard)
+                .is_empty()
+        );
+        assert!(
+            user_dict
+                .lookup(&toned, LookupStrategy::Standard)
+                .iter()
+                .any(|it| it.as_str() == "測試")
+        );
+    }
+
+    #[test]
+    fn abbreviated_input() {
+        let dict = TrieBuf::from([
+            (
+                vec![crate::syl![bpmf::T, bpmf::AI, bpmf::TONE2]],
+                vec![("台", 50)],
+            ),
+            (
+                vec![crate::syl![bpmf::T, bpmf::I, bpmf::AN]],
+                vec![("天", 100)],
+            ),
+            (
+                vec![crate::syl![bpmf::B, bpmf::EI, bpmf::TONE3]],
+                vec![("北", 50)],
+            ),
+            (
+                vec![
+                    crate::syl![bpmf::T, bpmf::AI, bpmf::TONE2],
+                    crate::syl![bpmf::B, bpmf::EI, bpmf::TONE3],
+                ],
+                vec![("台北", 1000)],
+            ),
+        ]);
+        let mut editor = editor_with_dict(dict);
+        editor
+            .set_editor_options(|opt| opt.abbreviated_input = true)
+            .unwrap();
+
+        editor.process_keyevent(map_ascii(&QWERTY_MAP, b'w'));
+        assert_eq!(syl![bpmf::T], editor.syllable_buffer());
+        editor.process_keyevent(map_ascii(&QWERTY_MAP, b'1'));
+        assert_eq!("天", editor.display());
+        assert_eq!(syl![bpmf::B], editor.syllable_buffer());
+
+        editor.process_keyevent(map_ascii(&QWERTY_MAP, b' '));
+        assert_eq!("台北", editor.display());
+    }
+
+    #[test]
+    fn syllable_correction() {
+        let dict = TrieBuf::from([
+            (
+                vec![crate::syl![bpmf::C, bpmf::E, bpmf::TONE4]],
+                vec![("測", 100)],
+            ),
+            (
+                vec![crate::syl![bpmf::S, bpmf::E, bpmf::TONE4]],
+                vec![("色", 10)],
+            ),
+        ]);
+        let mut editor = editor_with_dict(dict);
+
+        // ㄕㄜˋ has no dictionary entry.
+        for key in [b'g', b'k', b'4'] {
+            editor.process_keyevent(map_ascii(&QWERTY_MAP, key));
+        }
+        assert_eq!("", editor.display());
+
+        editor
+            .set_editor_options(|opt| opt.syllable_correction = true)
+            .unwrap();
+        for key in [b'g', b'k', b'4'] {
+            editor.process_keyevent(map_ascii(&QWERTY_MAP, key));
+        }
+        assert_eq!("測", editor.display());
+        assert!(editor.is_selecting());
+        assert_eq!(
+            vec!["測".to_string(), "色".to_string()],
+            editor.all_candidates().unwrap()
+        );
+        editor.select(1).unwrap();
+        assert_eq!("色", editor.display());
+
+        editor
+            .set_editor_options(|opt| opt.auto_correct_syllables = true)
+            .unwrap();
+        for key in [b'g', b'k', b'4'] {
+            editor.process_keyevent(map_ascii(&QWERTY_MAP, key));
+        }
+        assert_eq!("色測", editor.display());
+        assert!(!editor.is_selecting());
+    }
+
+    #[test]
+    fn syllable_correction_follows_position() {
+        let dict = TrieBuf::from([
+            (
+                vec![crate::syl![bpmf::C, bpmf::E, bpmf::TONE4]],
+                vec![("測", 100)],
+            ),
+            (
+                vec![crate::syl![bpmf::S, bpmf::E, bpmf::TONE4]],
+                vec![("色", 10)],
+            ),
+        ]);
+        let mut editor = editor_with_dict(dict);
+        editor
+            .set_editor_options(|opt| {
+                opt.syllable_correction = true;
+                opt.auto_correct_syllables = true;
+            })
+            .unwrap();
+        let left = KeyboardEvent::builder().ksym(keysym::SYM_LEFT).build();
+        let home = KeyboardEvent::builder().ksym(keysym::SYM_HOME).build();
+
+        // ㄕㄜˋ is corrected to ㄘㄜˋ, ㄘㄜˋ is typed as is.
+        for key in [b'g', b'k', b'4', b'h', b'k', b'4'] {
+            editor.process_keyevent(map_ascii(&QWERTY_MAP, key));
+        }
+        assert_eq!("測測", editor.display());
+        editor.start_selecting().unwrap();
+        assert_eq!(vec!["測".to_string()], editor.all_candidates().unwrap());
+        editor.cancel_selecting().unwrap();
+
+        // Inserting before the corrected syllable moves its suggestions.
+        editor.process_keyevent(home);
+        for key in [b'h', b'k', b'4'] {
+            editor.process_keyevent(map_ascii(&QWERTY_MAP, key));
+        }
+        assert_eq!("測測測", editor.display());
+        editor.start_selecting().unwrap();
+        assert_eq!(
+            vec!["測".to_string(), "色".to_string()],
+            editor.all_candidates().unwrap()
+        );
+        editor.cancel_selecting().unwrap();
+        editor.process_keyevent(left);
+        editor.start_selecting().unwrap();
+        assert_eq!(vec!["測".to_string()], editor.all_candidates().unwrap());
+    }
+
+    #[test]
+    fn candidate_details() -> Result<(), Box<dyn std::error::Error>> {
+        use std::io::{Cursor, Seek};
+
+        use crate::dictionary::{DictionaryBuilder, DictionaryInfo, Phrase, Trie, TrieBuilder};
+
+        let mut builder = TrieBuilder::new();
+        builder.set_info(DictionaryInfo {
+            name: "system".to_string(),
+            ..Default::default()
+        })?;
+        builder.insert(
+            &[crate::syl![bpmf::C, bpmf::E, bpmf::TONE4]],
+            Phrase::new("測", 100).with_comment("測量"),
+        )?;
+        builder.insert(
+            &[crate::syl![bpmf::C, bpmf::E, bpmf::TONE4]],
+            Phrase::new("冊", 1),
+        )?;
+        let mut cursor = Cursor::new(vec![]);
+        builder.write(&mut cursor)?;
+        cursor.rewind()?;
+        let system_dict = Trie::new(&mut cursor)?;
+        let mut dict = Layered::new(vec![Box::new(system_dict)]);
+        dict.user_dict().add_phrase(
+            &[crate::syl![bpmf::C, bpmf::E, bpmf::TONE4]],
+            ("策", 10).into(),
+        )?;
+        let mut editor = editor_with_layered(dict);
+
+        for key in [b'h', b'k', b'4'] {
+            editor.process_keyevent(map_ascii(&QWERTY_MAP, key));
+        }
+        editor.start_selecting()?;
+        let candidates = editor.all_candidate_details()?;
+        assert_eq!(
+            editor.all_candidates()?,
+            candidates
+                .iter()
+                .map(|candidate| candidate.text.clone())
+                .collect::<Vec<_>>()
+        );
+        let ce = candidates.iter().find(|it| it.text == "測").unwrap();
+        assert_eq!(100, ce.freq);
+        assert_eq!(vec![crate::syl![bpmf::C, bpmf::E, bpmf::TONE4]], ce.reading);
+        assert_eq!(Some("測量"), ce.comment.as_deref());
+        assert_eq!("system", ce.source.as_ref().unwrap().name);
+        assert!(!ce.user_phrase);
+        let ce = candidates.iter().find(|it| it.text == "策").unwrap();
+        assert_eq!(10, ce.freq);
+        assert_eq!(None, ce.comment);
+        assert!(ce.user_phrase);
+        for (i, candidate) in candidates.iter().enumerate() {
+            let details = editor.candidate_details(i)?;
+            assert_eq!(candidate.text, details.text);
+            assert_eq!(candidate.reading, details.reading);
+            assert_eq!(candidate.user_phrase, details.user_phrase);
+        }
+        assert!(editor.candidate_details(candidates.len()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn delete_and_demote_candidates() -> Result<(), Box<dyn std::error::Error>> {
+        use std::io::{Cursor, Seek};
+
+        use crate::dictionary::{DictionaryBuilder, Phrase, Trie, TrieBuilder};
+
+        let ce4 = crate::syl![bpmf::C, bpmf::E, bpmf::TONE4];
+        let mut builder = TrieBuilder::new();
+        builder.insert(&[ce4], Phrase::new("測", 100))?;
+        builder.insert(&[ce4], Phrase::new("冊", 1))?;
+        let mut cursor = Cursor::new(vec![]);
+        builder.write(&mut cursor)?;
+        cursor.rewind()?;
+        let system_dict = Trie::new(&mut cursor)?;
+        let mut dict = Layered::new(vec![Box::new(system_dict)]);
+        dict.user_dict().add_phrase(&[ce4], ("策", 10).into())?;
+        dict.user_dict()
+            .update_phrase(&[ce4], ("冊", 1).into(), 500, 0)?;
+        let mut editor = editor_with_layered(dict);
+
+        for key in [b'h', b'k', b'4'] {
+            editor.process_keyevent(map_ascii(&QWERTY_MAP, key));
+        }
+        editor.start_selecting()?;
+        assert_eq!(vec!["測", "冊", "策"], editor.all_candidates()?);
+        let alt_2 = KeyboardEvent::builder()
+            .code(keycode::KEY_2)
+            .ksym(keysym::SYM_2)
+            .alt_if(true)
+            .build();
+        editor.process_keyevent(alt_2);
+        assert!(editor.is_selecting());
+        assert_eq!("調降：冊", editor.notification());
+        let details = editor.all_candidate_details()?;
+        let ce = details.iter().find(|it| it.text == "冊").unwrap();
+        assert_eq!(1, ce.freq);
+
+        let ctrl_3 = KeyboardEvent::builder()
+            .code(keycode::KEY_3)
+            .ksym(keysym::SYM_3)
+            .control()
+            .build();
+        editor.process_keyevent(ctrl_3);
+        assert!(editor.is_selecting());
+        assert_eq!("刪除：策", editor.notification());
+        assert_eq!(vec!["測", "冊"], editor.all_candidates()?);
+
+        assert!(editor.delete_candidate(5).is_err());
+        editor.demote_candidate(0)?;
+        editor.cancel_selecting()?;
+        assert!(editor.delete_candidate(0).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn next_phrase_prediction() -> Result<(), Box<dyn std::error::Error>> {
+        let yin2 = crate::syl![bpmf::I, bpmf::EN, bpmf::TONE2];
+        let dict = TrieBuf::from([
+            (vec![yin2], vec![("銀", 50)]),
+            (
+                vec![yin2, crate::syl![bpmf::H, bpmf::ANG, bpmf::TONE2]],
+                vec![("銀行", 80)],
+            ),
+            (
+                vec![yin2, crate::syl![bpmf::H, bpmf::E, bpmf::TONE2]],
+                vec![("銀河", 30)],
+            ),
+        ]);
+        let mut editor = editor_with_dict(dict);
+        let enter = KeyboardEvent::builder()
+            .code(keycode::KEY_ENTER)
+            .ksym(keysym::SYM_RETURN)
+            .build();
+        let esc = KeyboardEvent::builder()
+            .code(keycode::KEY_ESC)
+            .ksym(keysym::SYM_ESC)
+            .build();
+        let type_yin2 = |editor: &mut Editor| {
+            for key in [b'u', b'p', b'6'] {
+                editor.process_keyevent(map_ascii(&QWERTY_MAP, key));
+            }
+            editor.process_keyevent(enter);
+            assert_eq!("銀", editor.display_commit());
+        };
+
+        type_yin2(&mut editor);
+        assert!(!editor.is_selecting());
+
+        editor
+            .set_editor_options(|opt| opt.next_phrase_prediction = true)
+            .unwrap();
+        type_yin2(&mut editor);
+        assert!(editor.is_predicting());
+        assert_eq!(vec!["行", "河"], editor.all_candidates()?);
+        assert_eq!(
+            vec![crate::syl![bpmf::H, bpmf::ANG, bpmf::TONE2]],
+            editor.all_candidate_details()?[0].reading
+        );
+        let alt_2 = KeyboardEvent::builder()
+            .code(keycode::KEY_2)
+            .ksym(keysym::SYM_2)
+            .alt_if(true)
+            .build();
+        assert_eq!(EditorKeyBehavior::Commit, editor.process_keyevent(alt_2));
+        assert_eq!("河", editor.display_commit());
+        assert!(editor.is_entering());
+        assert_eq!(
+            Some(1),
+            editor
+                .shared
+                .dict
+                .lookup_bigram("銀", "河")
+                .map(|(count, _)| count)
+        );
+
+        // Learned phrase pairs are predicted first.
+        type_yin2(&mut editor);
+        assert_eq!(vec!["河", "行"], editor.all_candidates()?);
+        editor.select(1)?;
+        assert_eq!("行", editor.display_commit());
+        assert!(!editor.is_selecting());
+
+        type_yin2(&mut editor);
+        assert_eq!(EditorKeyBehavior::Absorb, editor.process_keyevent(esc));
+        assert!(editor.is_entering());
+
+        type_yin2(&mut editor);
+        editor.process_keyevent(map_ascii(&QWERTY_MAP, b'u'));
+        assert!(!editor.is_selecting());
+        assert_eq!("ㄧ", editor.syllable_buffer_display());
+        editor.clear();
+
+        // Digits are bopomofo in the standard layout.
+        type_yin2(&mut editor);
+        assert!(editor.is_predicting());
+        editor.process_keyevent(map_ascii(&QWERTY_MAP, b'1'));
+        assert!(!editor.is_selecting());
+        assert_eq!("ㄅ", editor.syllable_buffer_display());
+        Ok(())
+    }
+
+    #[test]
+    fn abbreviated_input_learns_full_syllables() {
+        let dict = TrieBuf::from([
+            (
+                vec![crate::syl![bpmf::T, bpmf::I, bpmf::AN]],
+                vec![("天", 100)],
+            ),
+            (
+                vec![crate::syl![bpmf::B, bpmf::EI, bpmf::TONE3]],
+                vec![("北", 50)],
+            ),
+        ]);
+        let mut editor = editor_with_dict(dict);
+        editor
+            .set_editor_options(|opt| opt.abbreviated_input = true)
+            .unwrap();
+
+        for key in [b'w', b'1', b' '] {
+            editor.process_keyevent(map_ascii(&QWERTY_MAP, key));
+        }
+        assert_eq!("天北", editor.display());
+        editor.commit().unwrap();
+
+        let user_dict = editor.user_dict();
+        assert!(
+            user_dict
+                .lookup(
+                    &[crate::syl![bpmf::T], crate::syl![bpmf::B]],
+                    LookupStrategy::Standard
+                )
+                .is_empty()
+        );
+        assert!(
+            user_dict
+                .lookup(
+                    &[
+                        crate::syl![bpmf::T, bpmf::I, bpmf::AN],
+                        crate::syl![bpmf::B, bpmf::EI, bpmf::TONE3]
+                    ],
+                    LookupStrategy::Standard
+                )
+                .iter()
+                .any(|it| it.as_str() == "天北")
+        );
+    }
+
+    #[test]
+    fn skip_learning_unresolved_reading() {
+        let dict = TrieBuf::from([(
+            vec![crate::syl![bpmf::T, bpmf::I, bpmf::AN]],
+            vec![("天", 100)],
+        )]);
+        let mut editor = editor_with_dict(dict);
+        editor
+            .set_editor_options(|opt| opt.abbreviated_input = true)
+            .unwrap();
+
+        let typed = [crate::syl![bpmf::T], crate::syl![bpmf::B]];
+        assert!(editor.learn_phrase(&typed, "天白").is_err());
+        assert!(editor.user_dict().entries().next().is_none());
+    }
+
+    #[test]
+    fn phonetic_confusions() {
+        let dict = TrieBuf::from([
+            (
+                vec![crate::syl![bpmf::ZH, bpmf::U, bpmf::ENG]],
+                vec![("中", 50)],
+            ),
+            (
+                vec![crate::syl![bpmf::Z, bpmf::U, bpmf::ENG]],
+                vec![("宗", 10)],
+            ),
+            (
+                vec![crate::syl![bpmf::U, bpmf::EN, bpmf::TONE2]],
+                vec![("文", 100)],
+            ),
+            (
+                vec![
+                    crate::syl![bpmf::ZH, bpmf::U, bpmf::ENG],
+                    crate::syl![bpmf::U, bpmf::EN, bpmf::TONE2],
+                ],
+                vec![("中文", 1000)],
+            ),
+        ]);
+        let mut editor = editor_with_dict(dict);
+        editor
+            .set_editor_options(|opt| {
+                opt.phonetic_confusions = ConfusionSet::new().with(Confusion::ZhZ, true)
+            })
+            .unwrap();
+
+        for key in [b'y', b'j', b'/', b' '] {
+            editor.process_keyevent(map_ascii(&QWERTY_MAP, key));
+        }
+        assert_eq!("宗", editor.display());
+
+        for key in [b'j', b'p', b'6'] {
+            editor.process_keyevent(map_ascii(&QWERTY_MAP, key));
+        }
+        assert_eq!("中文", editor.display());
+
+        editor
+            .set_editor_options(|opt| opt.phonetic_confusions = ConfusionSet::new())
+            .unwrap();
+        assert_eq!("宗文", editor.display());
+    }
+
+    #[test]
+    fn matching_syllables_of_confusions() {
+        let strategy = LookupStrategy::Confusable(ConfusionSet::new().with(Confusion::ZhZ, true));
+        assert_eq!(
+            vec![
+                crate::syl![bpmf::Z, bpmf::U, bpmf::ENG],
+                crate::syl![bpmf::ZH, bpmf::U, bpmf::ENG]
+            ],
+            matching_syllables(crate::syl![bpmf::Z, bpmf::U, bpmf::ENG], strategy)
+        );
+        assert_eq!(
+            vec![
+                crate::syl![bpmf::Z, bpmf::U, bpmf::ENG],
+                crate::syl![bpmf::Z, bpmf::U, bpmf::ENG, bpmf::TONE2],
+                crate::syl![bpmf::Z, bpmf::U, bpmf::ENG, bpmf::TONE3],
+                crate::syl![bpmf::Z, bpmf::U, bpmf::ENG, bpmf::TONE4],
+                crate::syl![bpmf::Z, bpmf::U, bpmf::ENG, bpmf::TONE5],
+            ],
+            matching_syllables(
+                crate::syl![bpmf::Z, bpmf::U, bpmf::ENG],
+                LookupStrategy::Toneless
+            )
+        );
+    }
+
+    #[test]
+    fn select_conversion_alternative() {
+        let dict = TrieBuf::from([
+            (
+                vec![crate::syl![bpmf::C, bpmf::E, bpmf::TONE4]],
+                vec![("測", 1)],
+            ),
+            (
+                vec![crate::syl![bpmf::SH, bpmf::TONE4]],
+                vec![("是", 1), ("試", 1)],
+            ),
+            (
+                vec![
+                    crate::syl![bpmf::C, bpmf::E, bpmf::TONE4],
+                    crate::syl![bpmf::SH, bpmf::TONE4],
+                ],
+                vec![("測試", 100)],
+            ),
+        ]);
+        let mut editor = editor_with_dict(dict);
+
+        assert!(editor.conversion_alternatives().is_empty());
+        assert!(editor.select_conversion(0).is_err());
+
+        for key in [b'h', b'k', b'4', b'g', b'4'] {
+            editor.process_keyevent(map_ascii(&QWERTY_MAP, key));
+        }
+        let alternatives: Vec<String> = editor
+            .conversion_alternatives()
+            .iter()
+            .map(|outcome| outcome.text())
+            .collect();
+        assert_eq!(vec!["測試", "測是"], alternatives);
+        assert_eq!(0, editor.selected_conversion());
+
+        editor.select_conversion(1).unwrap();
+        assert_eq!(1, editor.selected_conversion());
+        assert_eq!("測是", editor.display());
+        assert!(editor.select_conversion(2).is_err());
+
+        let tab = KeyboardEvent::builder()
+            .code(keycode::KEY_TAB)
+            .ksym(keysym::SYM_TAB)
+            .build();
+        editor.process_keyevent(tab);
+        assert_eq!(0, editor.selected_conversion());
+        assert_eq!("測試", editor.display());
+    }
+
+    #[test]
+    fn learn_bigrams_on_commit() {
+        let dict = TrieBuf::from([
+            (
+                vec![crate::syl![bpmf::M, bpmf::AN, bpmf::TONE4]],
+                vec![("慢", 500)],
+            ),
+            (
+                vec![
+                    crate::syl![bpmf::M, bpmf::AN, bpmf::TONE4],
+                    crate::syl![bpmf::M, bpmf::AN, bpmf::TONE4],
+                ],
+                vec![("慢慢", 300)],
+            ),
+            (
+                vec![crate::syl![bpmf::D, bpmf::E, bpmf::TONE5]],
+                vec![("的", 10000), ("地", 500)],
+            ),
+        ]);
+        let mut editor = editor_with_dict(dict);
+
+        for _ in 0..2 {
+            for key in [b'a', b'0', b'4', b'a', b'0', b'4', b'2', b'k', b'7'] {
+                editor.process_keyevent(map_ascii(&QWERTY_MAP, key));
+            }
+            assert_eq!("慢慢的", editor.display());
+            editor.commit().unwrap();
+        }
+        assert_eq!(
+            Some(2),
+            editor
+                .shared
+                .dict
+                .lookup_bigram("慢慢", "的")
+                .map(|(count, _)| count)
+        );
+        assert_eq!(None, editor.shared.dict.lookup_bigram("的", "慢慢"));
+    }
+
+    #[test]
+    fn reject_conflicting_lookup_options() {
+        let mut editor = editor_with_dict(TrieBuf::new_in_memory());
+
+        editor
+            .set_editor_options(|opt| opt.toneless_input = true)
+            .unwrap();
+        // The rejected update is not applied at all.
+        assert!(
+            editor
+                .set_editor_options(|opt| {
+                    opt.phonetic_confusions = ConfusionSet::all();
+                    opt.user_phrase_bonus = 1.0;
+                })
+                .is_err()
+        );
+        let options = editor.editor_options();
+        assert!(options.toneless_input);
+        assert!(options.phonetic_confusions.is_empty());
+        assert_eq!(0.0, options.user_phrase_bonus);
+
+        assert!(
+            editor
+                .set_editor_options(|opt| opt.abbreviated_input = true)
+                .is_err()
+        );
+        assert!(!editor.editor_options().abbreviated_input);
+
+        assert!(
+            editor
+                .set_editor_options(|opt| {
+                    opt.conversion_engine = ConversionEngineKind::FuzzyChewingEngine;
+                    opt.lookup_strategy = LookupStrategy::FuzzyPartialPrefix;
+                })
+                .is_err()
+        );
+        assert_eq!(
+            ConversionEngineKind::ChewingEngine,
+            editor.editor_options().conversion_engine
+        );
+
+        editor
+            .set_editor_options(|opt| {
+                opt.toneless_input = false;
+                opt.abbreviated_input = true;
+            })
+            .unwrap();
+        assert!(editor.editor_options().abbreviated_input);
+        assert!(editor.editor_options().validate().is_ok());
+    }
+
+    #[test]
+    fn keep_custom_conversion_engine() {
+        let mut editor = editor_with_dict(TrieBuf::new_in_memory());
+
+        editor.set_conversion_engine(Box::new(SimpleEngine::new()));
+        editor
+            .set_editor_options(|opt| {
+                opt.toneless_input = true;
+                opt.max_phrase_len = 2;
+            })
+            .unwrap();
+        assert_eq!("SimpleEngine", format!("{:?}", editor.shared.conv));
+
+        editor
+            .set_editor_options(|opt| opt.conversion_engine = ConversionEngineKind::SimpleEngine)
+            .unwrap();
+        editor
+            .set_editor_options(|opt| opt.max_phrase_len = 3)
+            .unwrap();
+        assert!(!editor.shared.custom_conv);
+    }
+
+    #[test]
+    fn learn_bigrams_on_auto_commit() {
+        let dict = TrieBuf::from([
+            (
+                vec![crate::syl![bpmf::M, bpmf::AN, bpmf::TONE4]],
+                vec![("慢", 500)],
+            ),
+            (
+                vec![
+                    crate::syl![bpmf::M, bpmf::AN, bpmf::TONE4],
+                    crate::syl![bpmf::M, bpmf::AN, bpmf::TONE4],
+                ],
+                vec![("慢慢", 300)],
+            ),
+            (
+                vec![crate::syl![bpmf::D, bpmf::E, bpmf::TONE5]],
+                vec![("的", 10000)],
+            ),
+        ]);
+        let mut editor = editor_with_dict(dict);
+        editor
+            .set_editor_options(|opt| opt.auto_commit_threshold = 1)
+            .unwrap();
+
+        for key in [
+            b'a', b'0', b'4', b'a', b'0', b'4', b'2', b'k', b'7', b'2', b'k', b'7',
+        ] {
+            editor.process_keyevent(map_ascii(&QWERTY_MAP, key));
+        }
+        assert_eq!("的", editor.display());
+        assert_eq!(
+            Some(1),
+            editor
+                .shared
+                .dict
+                .lookup_bigram("慢慢", "的")
+                .map(|(count, _)| count)
+        );
+
+        editor
+            .set_editor_options(|opt| opt.disable_auto_learn_phrase = true)
+            .unwrap();
+        for key in [b'a', b'0', b'4', b'a', b'0', b'4'] {
+            editor.process_keyevent(map_ascii(&QWERTY_MAP, key));
+        }
+        assert_eq!(None, editor.shared.dict.lookup_bigram("的", "的"));
+    }
+
+    #[test]
+    fn surrounding_text_as_conversion_context() {
+        let dict = TrieBuf::from([
+            (
+                vec![crate::syl![bpmf::C, bpmf::E, bpmf::TONE4]],
+                vec![("測", 100)],
+            ),
+            (
+                vec![crate::syl![bpmf::SH, bpmf::TONE4]],
+                vec![("是", 100), ("試", 10)],
+            ),
+            (
+                vec![
+                    crate::syl![bpmf::C, bpmf::E, bpmf::TONE4],
+                    crate::syl![bpmf::SH, bpmf::TONE4],
+                ],
+                vec![("測試", 9318)],
+            ),
+        ]);
+        let mut editor = editor_with_dict(dict);
+        editor
+            .set_editor_options(|opt| opt.disable_auto_learn_phrase = true)
+            .unwrap();
+
+        editor.set_surrounding_text("我要", "");
+        for key in [b'h', b'k', b'4'] {
+            editor.process_keyevent(map_ascii(&QWERTY_MAP, key));
+        }
+        editor.commit().unwrap();
+        assert_eq!("測", editor.display_commit());
+
+        // The frontend reports the committed text back.
+        editor.set_surrounding_text("我要測", "");
+        for key in [b'g', b'4'] {
+            editor.process_keyevent(map_ascii(&QWERTY_MAP, key));
+        }
+        assert_eq!("試", editor.display());
+
+        editor.clear_surrounding_text();
+        assert_eq!("是", editor.display());
+    }
+
+    #[test]
+    fn surrounding_text_from_application() {
+        let dict = TrieBuf::from([
+            (
+                vec![crate::syl![bpmf::C, bpmf::E, bpmf::TONE4]],
+                vec![("測", 100)],
+            ),
+            (
+                vec![crate::syl![bpmf::SH, bpmf::TONE4]],
+                vec![("是", 100), ("試", 10)],
+            ),
+            (
+                vec![
+                    crate::syl![bpmf::C, bpmf::E, bpmf::TONE4],
+                    crate::syl![bpmf::SH, bpmf::TONE4],
+                ],
+                vec![("測試", 9318)],
+            ),
+        ]);
+        let dict = Layered::new(vec![Box::new(dict)]);
+        let mut editor = editor_with_layered(dict);
+
+        for key in [b'g', b'4'] {
+            editor.process_keyevent(map_ascii(&QWERTY_MAP, key));
+        }
+        assert_eq!("是", editor.display());
+
+        // Text never committed by the editor
+        editor.set_surrounding_text("我要測", "");
+        assert_eq!("試", editor.display());
+    }
+
+    #[test]
+    fn surrounding_text_does_not_learn_rest_of_phrase() {
+        let tai2 = crate::syl![bpmf::T, bpmf::AI, bpmf::TONE2];
+        let bei3 = crate::syl![bpmf::B, bpmf::EI, bpmf::TONE3];
+        let shi4 = crate::syl![bpmf::SH, bpmf::TONE4];
+        let dict = TrieBuf::from([
+            (vec![tai2, bei3], vec![("台北", 100)]),
+            (vec![tai2, bei3, shi4], vec![("台北市", 9000)]),
+            (vec![shi4], vec![("是", 100)]),
+        ]);
+        let dict = Layered::new(vec![Box::new(dict)]);
+        let mut editor = editor_with_layered(dict);
+
+        editor.set_surrounding_text("台北", "");
+        for key in [b'g', b'4'] {
+            editor.process_keyevent(map_ascii(&QWERTY_MAP, key));
+        }
+        assert_eq!("是", editor.display());
+        editor.commit().unwrap();
+        assert_eq!("是", editor.display_commit());
+        assert!(
+            editor
+                .shared
+                .dict
+                .user_dict_ref()
+                .entries()
+                .all(|(_, phrase)| phrase.as_str() != "市")
+        );
+    }
+
     #[test]
     fn editing_mode_input_bopomofo_select() {
         let dict = TrieBuf::from([(
@@ -1748,7 +4114,9 @@ mod tests {
         let sym_sel = SymbolSelector::default();
         let mut editor = Editor::new(conversion_engine, dict, estimate, abbrev, sym_sel);
 
-        editor.set_editor_options(|opt| opt.sort_candidates_by_frequency = false);
+        editor
+            .set_editor_options(|opt| opt.sort_candidates_by_frequency = false)
+            .unwrap();
 
         editor.process_keyevent(
             KeyboardEvent::builder()
@@ -1793,7 +4161,9 @@ mod tests {
         let sym_sel = SymbolSelector::default();
         let mut editor = Editor::new(conversion_engine, dict, estimate, abbrev, sym_sel);
 
-        editor.set_editor_options(|opt| opt.sort_candidates_by_frequency = true);
+        editor
+            .set_editor_options(|opt| opt.sort_candidates_by_frequency = true)
+            .unwrap();
 
         editor.process_keyevent(
             KeyboardEvent::builder()
@@ -1933,11 +4303,15 @@ mod tests {
         let sym_sel = SymbolSelector::default();
         let mut editor = Editor::new(conversion_engine, dict, estimate, abbrev, sym_sel);
 
-        editor.set_editor_options(|opt| opt.language_mode = LanguageMode::English);
+        editor
+            .set_editor_options(|opt| opt.language_mode = LanguageMode::English)
+            .unwrap();
 
         editor.process_keyevent(map_ascii(&QWERTY_MAP, b'X'));
 
-        editor.set_editor_options(|opt| opt.language_mode = LanguageMode::Chinese);
+        editor
+            .set_editor_options(|opt| opt.language_mode = LanguageMode::Chinese)
+            .unwrap();
 
         assert_eq!(EditorKeyBehavior::Commit, editor.last_key_behavior());
         assert_eq!("X", editor.display_commit());
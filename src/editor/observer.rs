@@ -0,0 +1,217 @@
+//! Notifications of editor state changes.
+
+use std::fmt::Debug;
+
+use super::{CharacterForm, LanguageMode};
+
+/// Events emitted by the [`Editor`](super::Editor) when its state changes.
+///
+/// Subscribe with [`Editor::subscribe`](super::Editor::subscribe) to render
+/// changes incrementally instead of polling the editor after every key
+/// event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum EditorEvent {
+    /// The pre-edit buffer or the syllable buffer changed.
+    PreeditChanged {
+        /// Same as [`Editor::display`](super::Editor::display).
+        display: String,
+        /// Same as
+        /// [`Editor::syllable_buffer_display`](super::Editor::syllable_buffer_display).
+        syllable: String,
+    },
+    /// The cursor moved to a new position.
+    CursorMoved { cursor: usize },
+    /// The candidate list was opened.
+    CandidatesOpened { page_no: usize, total_page: usize },
+    /// The candidate list was moved to another page or refreshed.
+    CandidatesPaged { page_no: usize, total_page: usize },
+    /// The candidate list was closed.
+    CandidatesClosed,
+    /// Text was committed.
+    Commit { text: String },
+    /// A new notification message is available.
+    Notification { text: String },
+    /// The language mode was toggled.
+    LanguageModeChanged(LanguageMode),
+    /// The character form was toggled.
+    CharacterFormChanged(CharacterForm),
+    /// A phrase was learned to the user dictionary.
+    PhraseLearned { phrase: String },
+    /// A phrase was removed from the user dictionary.
+    PhraseUnlearned { phrase: String },
+}
+
+/// Identifies an observer registered with
+/// [`Editor::subscribe`](super::Editor::subscribe).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SubscriptionId(u64);
+
+/// Editor properties watched for changes.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct EditorSnapshot {
+    pub(crate) display: String,
+    pub(crate) syllable: String,
+    pub(crate) cursor: usize,
+    pub(crate) candidates: Option<(usize, usize)>,
+    pub(crate) language_mode: LanguageMode,
+    pub(crate) character_form: CharacterForm,
+    pub(crate) commit: String,
+    pub(crate) notification: String,
+}
+
+impl EditorSnapshot {
+    /// Returns the events needed to go from `self` to `after`.
+    pub(crate) fn diff(&self, after: &EditorSnapshot) -> Vec<EditorEvent> {
+        let mut events = vec![];
+        if after.commit != self.commit && !after.commit.is_empty() {
+            events.push(EditorEvent::Commit {
+                text: after.commit.clone(),
+            });
+        }
+        if after.display != self.display || after.syllable != self.syllable {
+            events.push(EditorEvent::PreeditChanged {
+                display: after.display.clone(),
+                syllable: after.syllable.clone(),
+            });
+        }
+        if after.cursor != self.cursor {
+            events.push(EditorEvent::CursorMoved {
+                cursor: after.cursor,
+            });
+        }
+        match (self.candidates, after.candidates) {
+            (None, Some((page_no, total_page))) => {
+                events.push(EditorEvent::CandidatesOpened {
+                    page_no,
+                    total_page,
+                });
+            }
+            (Some(before), Some((page_no, total_page))) if before != (page_no, total_page) => {
+                events.push(EditorEvent::CandidatesPaged {
+                    page_no,
+                    total_page,
+                });
+            }
+            (Some(_), None) => events.push(EditorEvent::CandidatesClosed),
+            _ => {}
+        }
+        if after.language_mode != self.language_mode {
+            events.push(EditorEvent::LanguageModeChanged(after.language_mode));
+        }
+        if after.character_form != self.character_form {
+            events.push(EditorEvent::CharacterFormChanged(after.character_form));
+        }
+        if after.notification != self.notification && !after.notification.is_empty() {
+            events.push(EditorEvent::Notification {
+                text: after.notification.clone(),
+            });
+        }
+        events
+    }
+}
+
+type Observer = Box<dyn FnMut(&EditorEvent)>;
+
+/// Registered observers.
+#[derive(Default)]
+pub(crate) struct Observers {
+    next_id: u64,
+    observers: Vec<(SubscriptionId, Observer)>,
+}
+
+impl Observers {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.observers.is_empty()
+    }
+    pub(crate) fn subscribe(&mut self, observer: Observer) -> SubscriptionId {
+        let id = SubscriptionId(self.next_id);
+        self.next_id += 1;
+        self.observers.push((id, observer));
+        id
+    }
+    pub(crate) fn unsubscribe(&mut self, id: SubscriptionId) -> bool {
+        let len = self.observers.len();
+        self.observers.retain(|(it, _)| *it != id);
+        len != self.observers.len()
+    }
+    pub(crate) fn notify(&mut self, events: &[EditorEvent]) {
+        for event in events {
+            for (_, observer) in self.observers.iter_mut() {
+                observer(event);
+            }
+        }
+    }
+}
+
+impl Debug for Observers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Observers")
+            .field("len", &self.observers.len())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::{EditorEvent, EditorSnapshot, Observers};
+    use crate::editor::{CharacterForm, LanguageMode};
+
+    fn snapshot() -> EditorSnapshot {
+        EditorSnapshot {
+            display: String::new(),
+            syllable: String::new(),
+            cursor: 0,
+            candidates: None,
+            language_mode: LanguageMode::Chinese,
+            character_form: CharacterForm::Halfwidth,
+            commit: String::new(),
+            notification: String::new(),
+        }
+    }
+
+    #[test]
+    fn no_change_no_event() {
+        assert!(snapshot().diff(&snapshot()).is_empty());
+    }
+
+    #[test]
+    fn candidates_lifecycle() {
+        let before = snapshot();
+        let mut opened = snapshot();
+        opened.candidates = Some((0, 2));
+        let mut paged = snapshot();
+        paged.candidates = Some((1, 2));
+
+        assert_eq!(
+            vec![EditorEvent::CandidatesOpened {
+                page_no: 0,
+                total_page: 2
+            }],
+            before.diff(&opened)
+        );
+        assert_eq!(
+            vec![EditorEvent::CandidatesPaged {
+                page_no: 1,
+                total_page: 2
+            }],
+            opened.diff(&paged)
+        );
+        assert_eq!(vec![EditorEvent::CandidatesClosed], paged.diff(&before));
+    }
+
+    #[test]
+    fn unsubscribe_stops_notification() {
+        let count = Rc::new(RefCell::new(0));
+        let mut observers = Observers::default();
+        let counter = count.clone();
+        let id = observers.subscribe(Box::new(move |_| *counter.borrow_mut() += 1));
+        observers.notify(&[EditorEvent::CandidatesClosed]);
+        assert!(observers.unsubscribe(id));
+        assert!(!observers.unsubscribe(id));
+        observers.notify(&[EditorEvent::CandidatesClosed]);
+        assert_eq!(1, *count.borrow());
+    }
+}
@@ -0,0 +1,194 @@
+//! Structured pre-edit buffer.
+
+use std::ops::Range;
+
+use crate::conversion::{Composition, Gap, Interval};
+
+/// Display attributes of a [`PreeditSegment`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PreeditAttributes {
+    /// The segment was selected by the user instead of chosen by the
+    /// conversion engine.
+    pub selected: bool,
+    /// The segment is inside the highlighted range.
+    pub highlighted: bool,
+    /// The segment is the conversion segment under the cursor.
+    pub cursor: bool,
+    /// The segment is the bopomofo or key sequence still being entered.
+    pub syllable: bool,
+    /// The user marked a break before the segment.
+    pub break_before: bool,
+    /// The user marked a glue before the segment.
+    pub glue_before: bool,
+}
+
+impl PreeditAttributes {
+    fn same_style(&self, other: &PreeditAttributes) -> bool {
+        self.selected == other.selected
+            && self.highlighted == other.highlighted
+            && self.cursor == other.cursor
+            && self.syllable == other.syllable
+    }
+}
+
+/// A segment of the pre-edit buffer with uniform display attributes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreeditSegment {
+    /// Text of the segment.
+    pub text: String,
+    /// Character range of the segment in the full pre-edit text, including
+    /// the in-progress syllable.
+    pub range: Range<usize>,
+    /// Display attributes of the segment.
+    pub attributes: PreeditAttributes,
+}
+
+/// Splits the converted pre-edit text into segments.
+///
+/// A new segment starts at every conversion interval boundary, at every
+/// explicit break or glue, and wherever the display attributes change. The
+/// in-progress `syllable` is inserted at `cursor` as its own segment.
+pub(crate) fn build_segments(
+    intervals: &[Interval],
+    com: &Composition,
+    cursor: usize,
+    highlight: Option<Range<usize>>,
+    syllable: &str,
+) -> Vec<PreeditSegment> {
+    let mut segments: Vec<PreeditSegment> = vec![];
+    let mut offset = 0;
+    let mut last_interval = None;
+    let push_syllable = |segments: &mut Vec<PreeditSegment>, offset: &mut usize| {
+        let len = syllable.chars().count();
+        if len > 0 {
+            segments.push(PreeditSegment {
+                text: syllable.to_owned(),
+                range: *offset..*offset + len,
+                attributes: PreeditAttributes {
+                    syllable: true,
+                    ..Default::default()
+                },
+            });
+            *offset += len;
+        }
+    };
+    for (i, interval) in intervals.iter().enumerate() {
+        let selected = com
+            .selections()
+            .iter()
+            .any(|selection| selection.contains(interval));
+        let under_cursor = interval.start <= cursor && cursor < interval.end;
+        for (index, ch) in (interval.start..interval.end).zip(interval.text.chars()) {
+            if index == cursor {
+                push_syllable(&mut segments, &mut offset);
+                // Always start a new segment after the syllable.
+                last_interval = None;
+            }
+            let gap = com.gap(index);
+            let attributes = PreeditAttributes {
+                selected,
+                highlighted: highlight.as_ref().is_some_and(|r| r.contains(&index)),
+                cursor: under_cursor,
+                syllable: false,
+                break_before: gap == Some(Gap::Break),
+                glue_before: gap == Some(Gap::Glue),
+            };
+            let extend = last_interval == Some(i)
+                && !attributes.break_before
+                && !attributes.glue_before
+                && segments
+                    .last()
+                    .is_some_and(|last| last.attributes.same_style(&attributes));
+            match segments.last_mut() {
+                Some(last) if extend => {
+                    last.text.push(ch);
+                    last.range.end += 1;
+                }
+                _ => segments.push(PreeditSegment {
+                    text: ch.to_string(),
+                    range: offset..offset + 1,
+                    attributes,
+                }),
+            }
+            offset += 1;
+            last_interval = Some(i);
+        }
+    }
+    if cursor >= com.len() {
+        push_syllable(&mut segments, &mut offset);
+    }
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PreeditAttributes, build_segments};
+    use crate::{
+        conversion::{Composition, Gap, Interval, Symbol},
+        syl,
+        zhuyin::Bopomofo::*,
+    };
+
+    fn interval(start: usize, end: usize, text: &str) -> Interval {
+        Interval {
+            start,
+            end,
+            is_phrase: true,
+            text: text.into(),
+        }
+    }
+
+    fn composition(len: usize) -> Composition {
+        let mut com = Composition::new();
+        for _ in 0..len {
+            com.push(Symbol::from(syl![C, E, TONE4]));
+        }
+        com
+    }
+
+    #[test]
+    fn one_segment_per_interval() {
+        let com = composition(3);
+        let intervals = [interval(0, 2, "測試"), interval(2, 3, "冊")];
+        let segments = build_segments(&intervals, &com, 3, None, "");
+        assert_eq!(2, segments.len());
+        assert_eq!("測試", segments[0].text);
+        assert_eq!(0..2, segments[0].range);
+        assert_eq!("冊", segments[1].text);
+        assert_eq!(2..3, segments[1].range);
+        assert_eq!(PreeditAttributes::default(), segments[1].attributes);
+    }
+
+    #[test]
+    fn syllable_inserted_at_cursor() {
+        let com = composition(2);
+        let intervals = [interval(0, 2, "測試")];
+        let segments = build_segments(&intervals, &com, 1, None, "ㄘㄜ");
+        let texts: Vec<_> = segments.iter().map(|s| s.text.as_str()).collect();
+        assert_eq!(vec!["測", "ㄘㄜ", "試"], texts);
+        assert!(segments[1].attributes.syllable);
+        assert_eq!(1..3, segments[1].range);
+        assert_eq!(3..4, segments[2].range);
+        assert!(segments[2].attributes.cursor);
+    }
+
+    #[test]
+    fn selections_highlight_and_gaps() {
+        let mut com = composition(4);
+        com.set_gap(3, Gap::Break);
+        com.push_selection(interval(0, 2, "測試"));
+        let intervals = [
+            interval(0, 2, "測試"),
+            interval(2, 3, "冊"),
+            interval(3, 4, "冊"),
+        ];
+        let segments = build_segments(&intervals, &com, 4, Some(1..3), "");
+        let texts: Vec<_> = segments.iter().map(|s| s.text.as_str()).collect();
+        assert_eq!(vec!["測", "試", "冊", "冊"], texts);
+        assert!(segments[0].attributes.selected);
+        assert!(!segments[0].attributes.highlighted);
+        assert!(segments[1].attributes.selected && segments[1].attributes.highlighted);
+        assert!(!segments[2].attributes.selected && segments[2].attributes.highlighted);
+        assert!(segments[3].attributes.break_before);
+    }
+}
@@ -1,2 +1,3 @@
 pub(crate) mod phrase;
+pub(crate) mod prediction;
 pub(crate) mod symbol;
@@ -2,8 +2,8 @@ use std::cmp::{Reverse, min};
 
 use crate::{
     conversion::{Composition, Gap, Interval},
-    dictionary::{Dictionary, Layered, LookupStrategy},
-    editor::{EditorError, EditorErrorKind, SharedState},
+    dictionary::{Dictionary, Layered, LookupStrategy, Phrase},
+    editor::{Candidate, EditorError, EditorErrorKind, SharedState},
     zhuyin::Syllable,
 };
 
@@ -222,7 +222,13 @@ impl PhraseSelector {
         cursor
     }
 
-    pub(crate) fn candidates(&self, editor: &SharedState, dict: &Layered) -> Vec<String> {
+    /// Returns the candidate phrases with the syllables they were found
+    /// with.
+    fn candidate_phrases(
+        &self,
+        editor: &SharedState,
+        dict: &Layered,
+    ) -> Vec<(Phrase, Vec<Syllable>)> {
         let syllables: Vec<Syllable> = self.com.symbols()[self.begin..self.end]
             .iter()
             .map(|s| s.to_syllable().unwrap_or_default())
@@ -230,19 +236,77 @@ impl PhraseSelector {
         let mut candidates = dict
             .lookup(&syllables, self.lookup_strategy)
             .into_iter()
+            .map(|phrase| (phrase, syllables.clone()))
             .collect::<Vec<_>>();
         if self.end - self.begin == 1 {
-            let alt = editor
-                .syl
-                .alt_syllables(self.com.symbol(self.begin).unwrap().to_syllable().unwrap());
-            for &syl in alt {
-                candidates.extend(dict.lookup(&[syl], self.lookup_strategy).into_iter())
+            let syllable = syllables[0];
+            let alt = editor.syl.alt_syllables(syllable);
+            let corrections = editor.com.corrections(self.begin);
+            for &syl in alt.iter().chain(corrections) {
+                candidates.extend(
+                    dict.lookup(&[syl], self.lookup_strategy)
+                        .into_iter()
+                        .map(|phrase| (phrase, vec![syl])),
+                )
             }
         }
         if editor.options.sort_candidates_by_frequency {
-            candidates.sort_by_key(|ph| Reverse(ph.freq()));
+            candidates.sort_by_key(|(phrase, _)| Reverse(phrase.freq()));
+        }
+        candidates
+    }
+
+    pub(crate) fn candidates(&self, editor: &SharedState, dict: &Layered) -> Vec<String> {
+        self.candidate_phrases(editor, dict)
+            .into_iter()
+            .map(|(phrase, _)| phrase.into())
+            .collect()
+    }
+
+    pub(crate) fn candidate_details(&self, editor: &SharedState, dict: &Layered) -> Vec<Candidate> {
+        self.candidate_phrases(editor, dict)
+            .into_iter()
+            .map(|(phrase, syllables)| self.details(editor, dict, phrase, syllables))
+            .collect()
+    }
+
+    /// Returns the details of the nth candidate only.
+    pub(crate) fn nth_candidate_details(
+        &self,
+        editor: &SharedState,
+        dict: &Layered,
+        n: usize,
+    ) -> Option<Candidate> {
+        let (phrase, syllables) = self.candidate_phrases(editor, dict).into_iter().nth(n)?;
+        Some(self.details(editor, dict, phrase, syllables))
+    }
+
+    fn details(
+        &self,
+        editor: &SharedState,
+        dict: &Layered,
+        phrase: Phrase,
+        syllables: Vec<Syllable>,
+    ) -> Candidate {
+        let reading = editor
+            .resolve_reading(&syllables, phrase.as_str())
+            .unwrap_or_else(|| syllables.clone());
+        let source = dict
+            .lookup_source(&reading, phrase.as_str(), LookupStrategy::Standard)
+            .or_else(|| dict.lookup_source(&syllables, phrase.as_str(), self.lookup_strategy));
+        let user_phrase = dict
+            .user_dict_ref()
+            .lookup(&reading, LookupStrategy::Standard)
+            .iter()
+            .any(|it| it.as_str() == phrase.as_str());
+        Candidate {
+            freq: phrase.freq(),
+            comment: phrase.comment().map(str::to_owned),
+            text: phrase.into(),
+            reading,
+            source,
+            user_phrase,
         }
-        candidates.into_iter().map(|ph| ph.into()).collect()
     }
 
     pub(crate) fn interval(&self, phrase: impl Into<Box<str>>) -> Interval {
@@ -0,0 +1,155 @@
+//! Predictions of the phrases following the committed phrase.
+//!
+//! The phrases committed after the same phrase before, recorded as phrase
+//! pairs in the user dictionary, are predicted first. Then the rest of the
+//! longer phrases starting with the committed phrase, e.g. 行 after 銀 for
+//! 銀行, are predicted by their frequencies.
+
+use std::collections::BTreeSet;
+
+use crate::{
+    dictionary::{Dictionary, Layered},
+    editor::Candidate,
+    zhuyin::Syllable,
+};
+
+#[derive(Debug)]
+pub(crate) struct PredictionSelector {
+    phrase: String,
+    predictions: Vec<Candidate>,
+}
+
+impl PredictionSelector {
+    /// Predicts at most `limit` phrases following the phrase with the
+    /// reading.
+    ///
+    /// The readings of the learned successors are left empty, they are
+    /// looked up only when committed.
+    pub(crate) fn new(
+        dict: &Layered,
+        phrase: &str,
+        reading: &[Syllable],
+        limit: usize,
+    ) -> PredictionSelector {
+        let mut seen = BTreeSet::new();
+        let mut predictions = vec![];
+        for (text, count, _) in dict.lookup_successors(phrase) {
+            if predictions.len() == limit {
+                break;
+            }
+            if !seen.insert(text.clone()) {
+                continue;
+            }
+            predictions.push(Candidate {
+                text,
+                freq: count,
+                ..Default::default()
+            });
+        }
+        let len = phrase.chars().count();
+        if predictions.len() < limit && !reading.is_empty() && reading.len() == len {
+            // The phrase itself and the predicted successors are skipped.
+            let found = dict.lookup_prefix(reading, phrase, limit + 1);
+            for (full_reading, found) in found {
+                if predictions.len() == limit {
+                    break;
+                }
+                if full_reading.len() == len {
+                    continue;
+                }
+                let text: String = found.as_str().chars().skip(len).collect();
+                if !seen.insert(text.clone()) {
+                    continue;
+                }
+                predictions.push(Candidate {
+                    text,
+                    freq: found.freq(),
+                    reading: full_reading[len..].to_vec(),
+                    ..Default::default()
+                });
+            }
+        }
+        PredictionSelector {
+            phrase: phrase.to_owned(),
+            predictions,
+        }
+    }
+    /// Returns the phrase the predictions follow.
+    pub(crate) fn phrase(&self) -> &str {
+        &self.phrase
+    }
+    pub(crate) fn is_empty(&self) -> bool {
+        self.predictions.is_empty()
+    }
+    pub(crate) fn menu(&self) -> Vec<String> {
+        self.predictions.iter().map(|it| it.text.clone()).collect()
+    }
+    pub(crate) fn candidate_details(&self) -> Vec<Candidate> {
+        self.predictions.clone()
+    }
+    pub(crate) fn select(&self, n: usize) -> Option<&Candidate> {
+        self.predictions.get(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PredictionSelector;
+    use crate::{
+        dictionary::{Layered, TrieBuf},
+        syl,
+        zhuyin::Bopomofo::*,
+    };
+
+    #[test]
+    fn predict_successors_then_longer_phrases() {
+        let sys_dict = TrieBuf::from([
+            (vec![syl![I, EN, TONE2]], vec![("銀", 50)]),
+            (
+                vec![syl![I, EN, TONE2], syl![H, ANG, TONE2]],
+                vec![("銀行", 80)],
+            ),
+            (
+                vec![syl![I, EN, TONE2], syl![H, E, TONE2]],
+                vec![("銀河", 30)],
+            ),
+            (
+                vec![syl![I, EN, TONE2], syl![D, ANG, TONE4]],
+                vec![("淫蕩", 10)],
+            ),
+            (vec![syl![B, AU, TONE4]], vec![("報", 10)]),
+        ]);
+        let mut dict = Layered::new(vec![Box::new(sys_dict)]);
+        dict.user_dict().update_bigram("銀", "報", 3, 1).unwrap();
+        dict.user_dict().update_bigram("銀", "行", 1, 1).unwrap();
+
+        let sel = PredictionSelector::new(&dict, "銀", &[syl![I, EN, TONE2]], 10);
+        assert_eq!("銀", sel.phrase());
+        assert_eq!(vec!["報", "行", "河"], sel.menu());
+        assert!(sel.select(0).unwrap().reading.is_empty());
+        assert_eq!(vec![syl![H, E, TONE2]], sel.select(2).unwrap().reading);
+
+        let sel = PredictionSelector::new(&dict, "銀", &[syl![I, EN, TONE2]], 1);
+        assert_eq!(vec!["報"], sel.menu());
+
+        let sel = PredictionSelector::new(&dict, "河", &[syl![H, E, TONE2]], 10);
+        assert!(sel.is_empty());
+    }
+
+    #[test]
+    fn skip_homophones_before_limiting() {
+        let reading = [syl![I, EN, TONE2], syl![H, ANG, TONE2]];
+        let mut sys_dict = TrieBuf::new_in_memory();
+        sys_dict.add_phrase(&reading, ("銀行", 1).into()).unwrap();
+        for i in 0..100 {
+            let homophone = format!("吟{i}");
+            sys_dict
+                .add_phrase(&reading, (homophone.as_str(), 100).into())
+                .unwrap();
+        }
+        let dict = Layered::new(vec![Box::new(sys_dict)]);
+
+        let sel = PredictionSelector::new(&dict, "銀", &reading[..1], 1);
+        assert_eq!(vec!["行"], sel.menu());
+    }
+}
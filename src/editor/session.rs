@@ -0,0 +1,360 @@
+//! Serialization of the editor session state.
+//!
+//! The session is encoded as a DER document. See `session.asn1` for the
+//! schema.
+
+use der::{
+    Decode, DecodeValue, Encode, EncodeValue, ErrorKind, FixedTag, Length, Reader, Sequence, Tag,
+    Writer,
+    asn1::{OctetStringRef, Utf8StringRef},
+};
+
+use crate::{
+    conversion::{Composition, Gap, Interval, Symbol},
+    zhuyin::Syllable,
+};
+
+const SESSION_FORMAT_VERSION: u8 = 0;
+
+/// The editor state saved in a session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SessionStateKind {
+    Entering,
+    EnteringSyllable,
+    Selecting { page_no: usize },
+    Highlighting { moving_cursor: usize },
+}
+
+/// Snapshot of an in-flight editor session.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Session {
+    pub(crate) state: SessionStateKind,
+    pub(crate) cursor: usize,
+    pub(crate) com: Composition,
+    pub(crate) syllable: Syllable,
+    pub(crate) key_seq: Option<String>,
+}
+
+impl Session {
+    pub(crate) fn encode(&self) -> der::Result<Vec<u8>> {
+        self.to_der()
+    }
+    pub(crate) fn decode(bytes: &[u8]) -> der::Result<Session> {
+        Session::from_der(bytes)
+    }
+}
+
+fn gap_to_u8(gap: Gap) -> u8 {
+    match gap {
+        Gap::Begin => 0,
+        Gap::Break => 1,
+        Gap::Glue => 2,
+        Gap::Normal => 3,
+    }
+}
+
+fn gap_from_u8(value: u8) -> Option<Gap> {
+    match value {
+        0 => Some(Gap::Begin),
+        1 => Some(Gap::Break),
+        2 => Some(Gap::Glue),
+        3 => Some(Gap::Normal),
+        _ => None,
+    }
+}
+
+impl Sequence<'_> for Session {}
+
+impl<'a> DecodeValue<'a> for Session {
+    fn decode_value<R: Reader<'a>>(reader: &mut R, header: der::Header) -> der::Result<Self> {
+        let invalid = ErrorKind::Value { tag: header.tag };
+        reader.read_nested(header.length, |reader| {
+            let magic: Utf8StringRef<'_> = reader.decode()?;
+            let version: u8 = reader.decode()?;
+            if magic.as_str() != "CHWS" || version != SESSION_FORMAT_VERSION {
+                return Err(invalid.at(reader.position()));
+            }
+            let state: u8 = reader.decode()?;
+            let state_arg: u64 = reader.decode()?;
+            let cursor: u64 = reader.decode()?;
+            let symbols: Vec<Symbol> = reader.decode()?;
+            let gaps: OctetStringRef<'_> = reader.decode()?;
+            let selections: Vec<Interval> = reader.decode()?;
+            let syllable: u16 = reader.decode()?;
+            let key_seq: Option<Utf8StringRef<'_>> = reader.decode()?;
+            // consume the remaining unknown data
+            let _ = reader.read_slice(reader.remaining_len());
+
+            let state_arg =
+                usize::try_from(state_arg).map_err(|_| invalid.at(reader.position()))?;
+            let state = match state {
+                0 => SessionStateKind::Entering,
+                1 => SessionStateKind::EnteringSyllable,
+                2 => SessionStateKind::Selecting { page_no: state_arg },
+                3 => SessionStateKind::Highlighting {
+                    moving_cursor: state_arg,
+                },
+                _ => return Err(invalid.at(reader.position())),
+            };
+            let cursor = usize::try_from(cursor).map_err(|_| invalid.at(reader.position()))?;
+            let syllable =
+                Syllable::try_from(syllable).map_err(|_| invalid.at(reader.position()))?;
+
+            // Rebuild the composition with its own API so that the
+            // invariants are always maintained.
+            let gaps = gaps.as_bytes();
+            if gaps.len() != symbols.len() || cursor > symbols.len() {
+                return Err(invalid.at(reader.position()));
+            }
+            let mut com = Composition::new();
+            for sym in symbols {
+                com.push(sym);
+            }
+            for (i, &gap) in gaps.iter().enumerate() {
+                match (i, gap_from_u8(gap)) {
+                    (0, Some(Gap::Begin)) => {}
+                    (0, _) | (_, Some(Gap::Begin)) | (_, None) => {
+                        return Err(invalid.at(reader.position()));
+                    }
+                    (_, Some(gap)) => com.set_gap(i, gap),
+                }
+            }
+            for interval in selections {
+                if interval.start >= interval.end
+                    || interval.end > com.len()
+                    || interval.text.chars().count() != interval.end - interval.start
+                {
+                    return Err(invalid.at(reader.position()));
+                }
+                com.push_selection(interval);
+            }
+
+            Ok(Session {
+                state,
+                cursor,
+                com,
+                syllable,
+                key_seq: key_seq.map(String::from),
+            })
+        })
+    }
+}
+
+impl EncodeValue for Session {
+    fn value_len(&self) -> der::Result<Length> {
+        let (state, state_arg) = self.state_fields();
+        let gaps = self.gaps();
+        let key_seq = self.key_seq_ref()?;
+        Utf8StringRef::new("CHWS")?.encoded_len()?
+            + SESSION_FORMAT_VERSION.encoded_len()?
+            + state.encoded_len()?
+            + state_arg.encoded_len()?
+            + (self.cursor as u64).encoded_len()?
+            + self.com.symbols().to_vec().encoded_len()?
+            + OctetStringRef::new(&gaps)?.encoded_len()?
+            + self.com.selections().to_vec().encoded_len()?
+            + self.syllable.to_u16().encoded_len()?
+            + key_seq.encoded_len()?
+    }
+
+    fn encode_value(&self, encoder: &mut impl Writer) -> der::Result<()> {
+        let (state, state_arg) = self.state_fields();
+        let gaps = self.gaps();
+        Utf8StringRef::new("CHWS")?.encode(encoder)?;
+        SESSION_FORMAT_VERSION.encode(encoder)?;
+        state.encode(encoder)?;
+        state_arg.encode(encoder)?;
+        (self.cursor as u64).encode(encoder)?;
+        self.com.symbols().to_vec().encode(encoder)?;
+        OctetStringRef::new(&gaps)?.encode(encoder)?;
+        self.com.selections().to_vec().encode(encoder)?;
+        self.syllable.to_u16().encode(encoder)?;
+        self.key_seq_ref()?.encode(encoder)?;
+        Ok(())
+    }
+}
+
+impl Session {
+    fn state_fields(&self) -> (u8, u64) {
+        match self.state {
+            SessionStateKind::Entering => (0, 0),
+            SessionStateKind::EnteringSyllable => (1, 0),
+            SessionStateKind::Selecting { page_no } => (2, page_no as u64),
+            SessionStateKind::Highlighting { moving_cursor } => (3, moving_cursor as u64),
+        }
+    }
+    fn gaps(&self) -> Vec<u8> {
+        (0..self.com.len())
+            .filter_map(|i| self.com.gap(i))
+            .map(gap_to_u8)
+            .collect()
+    }
+    fn key_seq_ref(&self) -> der::Result<Option<Utf8StringRef<'_>>> {
+        self.key_seq.as_deref().map(Utf8StringRef::new).transpose()
+    }
+}
+
+impl<'a> Decode<'a> for Symbol {
+    fn decode<R: Reader<'a>>(reader: &mut R) -> der::Result<Self> {
+        match reader.peek_tag()? {
+            Tag::Integer => {
+                let value: u16 = reader.decode()?;
+                Syllable::try_from(value)
+                    .map(Symbol::from)
+                    .map_err(|_| ErrorKind::Value { tag: Tag::Integer }.at(reader.position()))
+            }
+            Tag::Utf8String => {
+                let value: Utf8StringRef<'_> = reader.decode()?;
+                let mut chars = value.as_str().chars();
+                match (chars.next(), chars.next()) {
+                    (Some(ch), None) => Ok(Symbol::from(ch)),
+                    _ => Err(ErrorKind::Value {
+                        tag: Tag::Utf8String,
+                    }
+                    .at(reader.position())),
+                }
+            }
+            tag => Err(tag.unexpected_error(None)),
+        }
+    }
+}
+
+impl Encode for Symbol {
+    fn encoded_len(&self) -> der::Result<Length> {
+        match self {
+            Symbol::Syllable(syl) => syl.to_u16().encoded_len(),
+            Symbol::Char(ch) => Utf8StringRef::new(ch.encode_utf8(&mut [0; 4]))?.encoded_len(),
+        }
+    }
+
+    fn encode(&self, encoder: &mut impl Writer) -> der::Result<()> {
+        match self {
+            Symbol::Syllable(syl) => syl.to_u16().encode(encoder),
+            Symbol::Char(ch) => Utf8StringRef::new(ch.encode_utf8(&mut [0; 4]))?.encode(encoder),
+        }
+    }
+}
+
+impl FixedTag for Interval {
+    const TAG: Tag = Tag::Sequence;
+}
+
+impl<'a> DecodeValue<'a> for Interval {
+    fn decode_value<R: Reader<'a>>(reader: &mut R, header: der::Header) -> der::Result<Self> {
+        reader.read_nested(header.length, |reader| {
+            let start: u64 = reader.decode()?;
+            let end: u64 = reader.decode()?;
+            let is_phrase = reader.decode()?;
+            let text: Utf8StringRef<'_> = reader.decode()?;
+            // consume the remaining unknown data
+            let _ = reader.read_slice(reader.remaining_len());
+            let invalid = || ErrorKind::Value { tag: header.tag }.at(reader.position());
+            Ok(Interval {
+                start: usize::try_from(start).map_err(|_| invalid())?,
+                end: usize::try_from(end).map_err(|_| invalid())?,
+                is_phrase,
+                text: text.as_str().into(),
+            })
+        })
+    }
+}
+
+impl EncodeValue for Interval {
+    fn value_len(&self) -> der::Result<Length> {
+        (self.start as u64).encoded_len()?
+            + (self.end as u64).encoded_len()?
+            + self.is_phrase.encoded_len()?
+            + Utf8StringRef::new(&*self.text)?.encoded_len()?
+    }
+
+    fn encode_value(&self, encoder: &mut impl Writer) -> der::Result<()> {
+        (self.start as u64).encode(encoder)?;
+        (self.end as u64).encode(encoder)?;
+        self.is_phrase.encode(encoder)?;
+        Utf8StringRef::new(&*self.text)?.encode(encoder)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Session, SessionStateKind};
+    use crate::{
+        conversion::{Composition, Gap, Interval, Symbol},
+        syl,
+        zhuyin::{Bopomofo::*, Syllable},
+    };
+
+    fn sample() -> Session {
+        let mut com = Composition::new();
+        com.push(Symbol::from(syl![C, E, TONE4]));
+        com.push(Symbol::from(syl![SH, TONE4]));
+        com.push(Symbol::from('，'));
+        com.push(Symbol::from(syl![I, TONE1]));
+        com.set_gap(2, Gap::Break);
+        com.push_selection(Interval {
+            start: 0,
+            end: 2,
+            is_phrase: true,
+            text: "測試".into(),
+        });
+        Session {
+            state: SessionStateKind::Selecting { page_no: 1 },
+            cursor: 3,
+            com,
+            syllable: syl![X, I],
+            key_seq: Some("xi".to_string()),
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let session = sample();
+        let bytes = session.encode().unwrap();
+        assert_eq!(session, Session::decode(&bytes).unwrap());
+    }
+
+    #[test]
+    fn round_trip_empty() {
+        let session = Session {
+            state: SessionStateKind::Entering,
+            cursor: 0,
+            com: Composition::new(),
+            syllable: Syllable::new(),
+            key_seq: None,
+        };
+        let bytes = session.encode().unwrap();
+        assert_eq!(session, Session::decode(&bytes).unwrap());
+    }
+
+    #[test]
+    fn reject_corrupted_data() {
+        let bytes = sample().encode().unwrap();
+        assert!(Session::decode(&bytes[..bytes.len() - 1]).is_err());
+        assert!(Session::decode(b"CHWS").is_err());
+
+        let mut session = sample();
+        session.cursor = 10;
+        let bytes = session.encode().unwrap();
+        assert!(Session::decode(&bytes).is_err());
+    }
+
+    #[test]
+    fn reject_selection_text_length_mismatch() {
+        for text in ["測", "測試試"] {
+            let mut session = sample();
+            session.com = Composition::new();
+            session.com.push(Symbol::from(syl![C, E, TONE4]));
+            session.com.push(Symbol::from(syl![SH, TONE4]));
+            session.com.push_selection(Interval {
+                start: 0,
+                end: 2,
+                is_phrase: true,
+                text: text.into(),
+            });
+            session.cursor = 2;
+            let bytes = session.encode().unwrap();
+            assert!(Session::decode(&bytes).is_err());
+        }
+    }
+}
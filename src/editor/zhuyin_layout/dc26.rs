@@ -179,6 +179,10 @@ impl SyllableEditor for DaiChien26 {
         self.syllable
     }
 
+    fn restore(&mut self, syl: Syllable, _key_seq: Option<&str>) {
+        self.syllable = syl;
+    }
+
     fn key_seq(&self) -> Option<String> {
         None
     }
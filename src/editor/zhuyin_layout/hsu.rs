@@ -277,6 +277,10 @@ impl SyllableEditor for Hsu {
         self.syllable
     }
 
+    fn restore(&mut self, syl: Syllable, _key_seq: Option<&str>) {
+        self.syllable = syl;
+    }
+
     fn alt_syllables(&self, syl: Syllable) -> &[Syllable] {
         for entry in Self::ALT_TABLE {
             if entry.0 == syl {
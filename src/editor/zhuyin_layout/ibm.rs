@@ -113,6 +113,10 @@ impl SyllableEditor for Ibm {
         self.syllable
     }
 
+    fn restore(&mut self, syl: Syllable, _key_seq: Option<&str>) {
+        self.syllable = syl;
+    }
+
     fn key_seq(&self) -> Option<String> {
         None
     }
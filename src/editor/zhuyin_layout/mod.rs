@@ -30,7 +30,16 @@ pub use self::{
     pinyin::{Pinyin, PinyinVariant},
     standard::Standard,
 };
-use crate::{input::KeyboardEvent, zhuyin::Syllable};
+use crate::{
+    input::{
+        KeyboardEvent,
+        keymap::{
+            DVORAK_MAP, INVERTED_COLEMAK_DH_ANSI_MAP, INVERTED_COLEMAK_DH_ORTH_MAP,
+            INVERTED_COLEMAK_MAP, INVERTED_DVORAK_MAP, INVERTED_WORKMAN_MAP, Keymap, QWERTY_MAP,
+        },
+    },
+    zhuyin::Syllable,
+};
 
 mod dc26;
 mod et;
@@ -81,6 +90,22 @@ pub enum KeyboardLayoutCompat {
     Colemak,
 }
 
+impl KeyboardLayoutCompat {
+    /// Returns the keymap used to map the ASCII keys of the layout to key
+    /// events.
+    pub fn keymap(self) -> &'static Keymap {
+        match self {
+            Self::Dvorak => &INVERTED_DVORAK_MAP,
+            Self::DvorakHsu => &DVORAK_MAP,
+            Self::Colemak => &INVERTED_COLEMAK_MAP,
+            Self::ColemakDhAnsi => &INVERTED_COLEMAK_DH_ANSI_MAP,
+            Self::ColemakDhOrth => &INVERTED_COLEMAK_DH_ORTH_MAP,
+            Self::Workman => &INVERTED_WORKMAN_MAP,
+            _ => &QWERTY_MAP,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct ParseKeyboardLayoutError;
 
@@ -220,6 +245,52 @@ pub trait SyllableEditor: Debug {
         }
         self.key_press(key)
     }
+    /// Handles a key press event in tone-less input mode.
+    ///
+    /// A syllable can be completed without a tone key by the initial of the
+    /// next syllable, then `Fuzzy(Syllable)` will be returned.
+    fn toneless_key_press(&mut self, key: KeyboardEvent) -> KeyBehavior {
+        if self.is_empty() {
+            return self.key_press(key);
+        }
+        let current_syl = self.read();
+        let mut next = self.clone();
+        let behavior = next.key_press(key);
+        let mut clone = self.clone();
+        clone.clear();
+        clone.key_press(key);
+        let new_syl = clone.read();
+        // The key starts a new syllable if it would replace or prepend the
+        // initial of the current syllable.
+        if behavior == KeyBehavior::Absorb
+            && new_syl.has_initial()
+            && next.read().initial() != current_syl.initial()
+        {
+            self.clear();
+            self.key_press(key);
+            return KeyBehavior::Fuzzy(current_syl);
+        }
+        self.key_press(key)
+    }
+    /// Handles a key press event in abbreviated input mode.
+    ///
+    /// A syllable with only an initial is completed by the initial of the
+    /// next syllable, then `Fuzzy(Syllable)` will be returned.
+    fn abbreviated_key_press(&mut self, key: KeyboardEvent) -> KeyBehavior {
+        let current_syl = self.read();
+        if !current_syl.is_initial_only() {
+            return self.key_press(key);
+        }
+        let mut clone = self.clone();
+        clone.clear();
+        clone.key_press(key);
+        if clone.read().has_initial() {
+            self.clear();
+            self.key_press(key);
+            return KeyBehavior::Fuzzy(current_syl);
+        }
+        self.key_press(key)
+    }
     /// Removes the last input from the buffer.
     fn remove_last(&mut self);
     /// Clears the phonetic key buffer, removing all values.
@@ -232,6 +303,15 @@ pub trait SyllableEditor: Debug {
     fn key_seq(&self) -> Option<String> {
         None
     }
+    /// Restores the editor buffer from a saved syllable and key sequence.
+    ///
+    /// `syl` and `key_seq` are values previously returned by
+    /// [`read`](SyllableEditor::read) and [`key_seq`](SyllableEditor::key_seq).
+    /// The default implementation clears the buffer.
+    fn restore(&mut self, syl: Syllable, key_seq: Option<&str>) {
+        let _ = (syl, key_seq);
+        self.clear();
+    }
     /// Returns the alternative syllable, if supported by the layout.
     fn alt_syllables(&self, syl: Syllable) -> &[Syllable] {
         let _ = syl;
@@ -290,6 +290,18 @@ impl SyllableEditor for Pinyin {
         self.syllable
     }
 
+    fn restore(&mut self, syl: Syllable, key_seq: Option<&str>) {
+        self.clear();
+        self.syllable = syl;
+        if let Some(key_seq) = key_seq {
+            self.key_seq = key_seq
+                .chars()
+                .filter(char::is_ascii)
+                .take(MAX_PINYIN_LEN)
+                .collect();
+        }
+    }
+
     fn key_seq(&self) -> Option<String> {
         Some(self.key_seq.clone())
     }
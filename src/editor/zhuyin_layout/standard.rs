@@ -114,6 +114,10 @@ impl SyllableEditor for Standard {
         self.syllable
     }
 
+    fn restore(&mut self, syl: Syllable, _key_seq: Option<&str>) {
+        self.syllable = syl;
+    }
+
     fn key_seq(&self) -> Option<String> {
         None
     }
@@ -128,7 +132,14 @@ mod test {
     use super::Standard;
     use crate::{
         editor::zhuyin_layout::{KeyBehavior, SyllableEditor},
-        input::{KeyboardEvent, keycode::KEY_SPACE, keysym::SYM_SPACE},
+        input::{
+            KeyboardEvent,
+            keycode::KEY_SPACE,
+            keymap::{QWERTY_MAP, map_ascii},
+            keysym::SYM_SPACE,
+        },
+        syl,
+        zhuyin::Bopomofo::*,
     };
 
     #[test]
@@ -141,4 +152,20 @@ mod test {
         });
         assert_eq!(KeyBehavior::KeyError, behavior);
     }
+
+    #[test]
+    fn toneless_commit_on_next_initial() {
+        let mut editor = Standard::new();
+        for key in [b'h', b'k'] {
+            let behavior = editor.toneless_key_press(map_ascii(&QWERTY_MAP, key));
+            assert_eq!(KeyBehavior::Absorb, behavior);
+        }
+        let behavior = editor.toneless_key_press(map_ascii(&QWERTY_MAP, b'g'));
+        assert_eq!(KeyBehavior::Fuzzy(syl![C, E]), behavior);
+        assert_eq!(syl![SH], editor.read());
+
+        let behavior = editor.toneless_key_press(map_ascii(&QWERTY_MAP, b'4'));
+        assert_eq!(KeyBehavior::Commit, behavior);
+        assert_eq!(syl![SH, TONE4], editor.read());
+    }
 }
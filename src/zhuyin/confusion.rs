@@ -0,0 +1,137 @@
+use super::Bopomofo;
+
+/// A pair of bopomofo commonly merged by speakers of some accents, e.g.
+/// Taiwanese Mandarin.
+///
+/// See [`Syllable::matches_confusable`](super::Syllable::matches_confusable).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Confusion {
+    /// The initials ㄓ and ㄗ.
+    ZhZ,
+    /// The initials ㄔ and ㄘ.
+    ChC,
+    /// The initials ㄕ and ㄙ.
+    ShS,
+    /// The initials ㄋ and ㄌ.
+    NL,
+    /// The initials ㄈ and ㄏ.
+    FH,
+    /// The initials ㄖ and ㄌ.
+    RL,
+    /// The rimes ㄢ and ㄤ.
+    AnAng,
+    /// The rimes ㄣ and ㄥ, without the medial ㄧ.
+    EnEng,
+    /// The rimes ㄣ and ㄥ after the medial ㄧ, i.e. ㄧㄣ and ㄧㄥ.
+    InIng,
+}
+
+impl Confusion {
+    /// All confusions.
+    pub const ALL: [Confusion; 9] = [
+        Confusion::ZhZ,
+        Confusion::ChC,
+        Confusion::ShS,
+        Confusion::NL,
+        Confusion::FH,
+        Confusion::RL,
+        Confusion::AnAng,
+        Confusion::EnEng,
+        Confusion::InIng,
+    ];
+    /// Returns the confused pair of bopomofo.
+    pub const fn pair(self) -> (Bopomofo, Bopomofo) {
+        match self {
+            Confusion::ZhZ => (Bopomofo::ZH, Bopomofo::Z),
+            Confusion::ChC => (Bopomofo::CH, Bopomofo::C),
+            Confusion::ShS => (Bopomofo::SH, Bopomofo::S),
+            Confusion::NL => (Bopomofo::N, Bopomofo::L),
+            Confusion::FH => (Bopomofo::F, Bopomofo::H),
+            Confusion::RL => (Bopomofo::R, Bopomofo::L),
+            Confusion::AnAng => (Bopomofo::AN, Bopomofo::ANG),
+            Confusion::EnEng | Confusion::InIng => (Bopomofo::EN, Bopomofo::ENG),
+        }
+    }
+    const fn bit(self) -> u16 {
+        1 << self as u16
+    }
+}
+
+/// A set of enabled [`Confusion`]s.
+///
+/// # Examples
+///
+/// ```
+/// use chewing::zhuyin::{Confusion, ConfusionSet};
+///
+/// let confusions = ConfusionSet::new()
+///     .with(Confusion::ZhZ, true)
+///     .with(Confusion::EnEng, true);
+/// assert!(confusions.contains(Confusion::ZhZ));
+/// assert!(!confusions.contains(Confusion::ShS));
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ConfusionSet {
+    bits: u16,
+}
+
+impl ConfusionSet {
+    /// Creates an empty set.
+    pub const fn new() -> ConfusionSet {
+        ConfusionSet { bits: 0 }
+    }
+    /// Creates a set with all confusions enabled.
+    pub const fn all() -> ConfusionSet {
+        let mut bits = 0;
+        let mut i = 0;
+        while i < Confusion::ALL.len() {
+            bits |= Confusion::ALL[i].bit();
+            i += 1;
+        }
+        ConfusionSet { bits }
+    }
+    /// Enables or disables a confusion.
+    pub const fn with(mut self, confusion: Confusion, enabled: bool) -> ConfusionSet {
+        self.set(confusion, enabled);
+        self
+    }
+    /// Enables or disables a confusion.
+    pub const fn set(&mut self, confusion: Confusion, enabled: bool) {
+        if enabled {
+            self.bits |= confusion.bit();
+        } else {
+            self.bits &= !confusion.bit();
+        }
+    }
+    /// Returns whether a confusion is enabled.
+    pub const fn contains(&self, confusion: Confusion) -> bool {
+        self.bits & confusion.bit() != 0
+    }
+    /// Returns whether no confusion is enabled.
+    pub const fn is_empty(&self) -> bool {
+        self.bits == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Confusion, ConfusionSet};
+
+    #[test]
+    fn enable_and_disable() {
+        let mut confusions = ConfusionSet::new()
+            .with(Confusion::ZhZ, true)
+            .with(Confusion::InIng, true);
+        assert!(confusions.contains(Confusion::ZhZ));
+        assert!(confusions.contains(Confusion::InIng));
+        assert!(!confusions.contains(Confusion::EnEng));
+        confusions.set(Confusion::ZhZ, false);
+        confusions.set(Confusion::InIng, false);
+        assert!(confusions.is_empty());
+        assert!(
+            Confusion::ALL
+                .into_iter()
+                .all(|confusion| ConfusionSet::all().contains(confusion))
+        );
+    }
+}
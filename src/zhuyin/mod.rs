@@ -1,10 +1,12 @@
 //! Chinese syllables and bopomofo phonetic symbols.
 
 pub use self::bopomofo::{Bopomofo, BopomofoErrorKind, BopomofoKind, ParseBopomofoError};
+pub use self::confusion::{Confusion, ConfusionSet};
 pub use self::syllable::{
     BuildSyllableError, DecodeSyllableError, ParseSyllableError, Syllable, SyllableBuilder,
     SyllableErrorKind,
 };
 
 mod bopomofo;
+mod confusion;
 mod syllable;
@@ -6,7 +6,7 @@ use std::{
     str::FromStr,
 };
 
-use super::{Bopomofo, BopomofoKind};
+use super::{Bopomofo, BopomofoKind, Confusion, ConfusionSet};
 use crate::exn::{Exn, ResultExt};
 
 /// The consonants and vowels that are taken together to make a single sound.
@@ -144,6 +144,13 @@ impl Syllable {
     pub fn has_tone(&self) -> bool {
         self.tone().is_some()
     }
+    /// Returns whether the syllable has only an initial, like an
+    /// abbreviation of a full syllable.
+    ///
+    /// Note that some initials, like ㄓ and ㄙ, are also full syllables.
+    pub fn is_initial_only(&self) -> bool {
+        self.has_initial() && !self.has_medial() && !self.has_rime() && !self.has_tone()
+    }
     /// Returns whether the syllable partially matches another syllable.
     pub fn starts_with(&self, other: Syllable) -> bool {
         let trailing_zeros = other.to_u16().trailing_zeros();
@@ -160,6 +167,97 @@ impl Syllable {
         let other_prefix = other.to_u16() >> mask;
         self_prefix == other_prefix
     }
+    /// Returns whether the syllable matches `other`, treating a syllable
+    /// without a tone as matching all tones.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use chewing::{syl, zhuyin::Bopomofo::*};
+    ///
+    /// assert!(syl![M, A].matches_toneless(syl![M, A, TONE3]));
+    /// assert!(syl![M, A].matches_toneless(syl![M, A]));
+    /// assert!(!syl![M, A, TONE2].matches_toneless(syl![M, A, TONE3]));
+    /// assert!(!syl![M, A].matches_toneless(syl![M, AI]));
+    /// ```
+    pub fn matches_toneless(&self, other: Syllable) -> bool {
+        if self.has_tone() {
+            return *self == other;
+        }
+        other.to_u16() & !0b111 == self.to_u16()
+    }
+    /// Returns whether the syllable matches `other`, treating a syllable
+    /// with only an initial as an abbreviation of all syllables with the
+    /// initial.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use chewing::{syl, zhuyin::Bopomofo::*};
+    ///
+    /// assert!(syl![T].matches_abbreviated(syl![T, AI, TONE2]));
+    /// assert!(syl![SH].matches_abbreviated(syl![SH]));
+    /// assert!(!syl![T, AI].matches_abbreviated(syl![T, AI, TONE2]));
+    /// assert!(!syl![T].matches_abbreviated(syl![B, EI, TONE3]));
+    /// ```
+    pub fn matches_abbreviated(&self, other: Syllable) -> bool {
+        if self.is_initial_only() {
+            return self.initial() == other.initial();
+        }
+        *self == other
+    }
+    /// Returns whether the syllable matches `other`, treating the pairs of
+    /// bopomofo in `confusions` as the same sound.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use chewing::{
+    ///     syl,
+    ///     zhuyin::{Bopomofo::*, Confusion, ConfusionSet},
+    /// };
+    ///
+    /// let confusions = ConfusionSet::new()
+    ///     .with(Confusion::ZhZ, true)
+    ///     .with(Confusion::EnEng, true);
+    /// assert!(syl![Z, U, ENG].matches_confusable(syl![ZH, U, EN], confusions));
+    /// assert!(!syl![Z, U, ENG].matches_confusable(syl![ZH, U, EN, TONE2], confusions));
+    /// assert!(!syl![I, ENG].matches_confusable(syl![I, EN], confusions));
+    /// ```
+    pub fn matches_confusable(&self, other: Syllable, confusions: ConfusionSet) -> bool {
+        if *self == other {
+            return true;
+        }
+        if self.medial() != other.medial() || self.tone() != other.tone() {
+            return false;
+        }
+        let confused = |a: Option<Bopomofo>, b: Option<Bopomofo>, confusion: Confusion| {
+            let (x, y) = confusion.pair();
+            confusions.contains(confusion)
+                && (a == Some(x) && b == Some(y) || a == Some(y) && b == Some(x))
+        };
+        let initial_matches = self.initial() == other.initial()
+            || [
+                Confusion::ZhZ,
+                Confusion::ChC,
+                Confusion::ShS,
+                Confusion::NL,
+                Confusion::FH,
+                Confusion::RL,
+            ]
+            .into_iter()
+            .any(|confusion| confused(self.initial(), other.initial(), confusion));
+        let en_eng = if self.medial() == Some(Bopomofo::I) {
+            Confusion::InIng
+        } else {
+            Confusion::EnEng
+        };
+        let rime_matches = self.rime() == other.rime()
+            || [Confusion::AnAng, en_eng]
+                .into_iter()
+                .any(|confusion| confused(self.rime(), other.rime(), confusion));
+        initial_matches && rime_matches
+    }
     /// Returns the `Syllable` encoded in a u16 integer.
     ///
     /// The data layout used:
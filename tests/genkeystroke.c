@@ -155,6 +155,9 @@ void showBopomofo(ChewingContext *ctx)
             case 2:
                 addstr("[糊]");
                 break;
+            case 3:
+                addstr("[詞]");
+                break;
         }
     } else {
         addstr("[英]");
@@ -467,7 +470,7 @@ int main(int argc, char *argv[])
         case KEY_CTRL_('S'):
             conversion_engine = chewing_config_get_int(ctx, "chewing.conversion_engine");
             conversion_engine += 1;
-            conversion_engine %= 3;
+            conversion_engine %= 4;
             chewing_config_set_int(ctx, "chewing.conversion_engine", conversion_engine);
             break;
         case KEY_CTRL_('H'):   /* emulate Shift+Space */
@@ -1557,6 +1557,23 @@ void test_SimpleEngine()
     chewing_delete(ctx);
 }
 
+void test_BigramEngine()
+{
+    ChewingContext *ctx;
+
+    ctx = chewing_new();
+    start_testcase(ctx);
+    chewing_config_set_int(ctx, "chewing.conversion_engine", BIGRAM_CONVERSION_ENGINE);
+    ok(chewing_config_get_int(ctx, "chewing.conversion_engine") == BIGRAM_CONVERSION_ENGINE,
+       "chewing.conversion_engine shall be BIGRAM_CONVERSION_ENGINE");
+
+    /* Without bigram.dat the engine backs off to unigram phrasing. */
+    type_keystroke_by_string(ctx, "hk4g4<E>");
+    ok_commit_buffer(ctx, "測試");
+
+    chewing_delete(ctx);
+}
+
 void test_Acknowledge()
 {
     ChewingContext *ctx;
@@ -2698,6 +2715,7 @@ int main(int argc, char *argv[])
     test_FuzzySearchMode();
     test_FuzzySearchMode_Hanyu();
     test_SimpleEngine();
+    test_BigramEngine();
     test_Acknowledge();
     test_BellCondition();
 
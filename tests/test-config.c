@@ -54,6 +54,17 @@ void test_has_option()
         ,"chewing.space_is_select_key"
         ,"chewing.conversion_engine"
         ,"chewing.enable_fullwidth_toggle_key"
+        ,"chewing.key_bindings"
+        ,"chewing.undo_depth"
+        ,"chewing.max_conversion_paths"
+        ,"chewing.max_phrase_len"
+        ,"chewing.conversion_prune_threshold"
+        ,"chewing.toneless_input"
+        ,"chewing.phonetic_confusions"
+        ,"chewing.abbreviated_input"
+        ,"chewing.syllable_correction"
+        ,"chewing.auto_correct_syllables"
+        ,"chewing.next_phrase_prediction"
     };
 
     ctx = chewing_new();
@@ -295,6 +306,281 @@ void test_set_selKey_normal()
     chewing_delete(ctx);
 }
 
+void test_set_key_bindings()
+{
+    ChewingContext *ctx;
+    char *key_bindings;
+
+    ctx = chewing_new();
+    start_testcase(ctx);
+
+    ok(chewing_config_set_str(ctx, "chewing.key_bindings",
+        "commit = Tab\nnext_conversion =\ninsert_break_or_glue =\n") == 0,
+        "chewing_config_set_str should return OK");
+    ok(chewing_config_get_str(ctx, "chewing.key_bindings", &key_bindings) == 0,
+        "chewing_config_get_str should return OK");
+    ok(strstr(key_bindings, "commit = Tab\n") != NULL, "key bindings shall be updated");
+    chewing_free(key_bindings);
+
+    type_keystroke_by_string(ctx, "hk4<T>");
+    ok_commit_buffer(ctx, "\xE6\xB8\xAC" /* 測 */);
+
+    ok(chewing_config_set_str(ctx, "chewing.key_bindings", "no_such_action = Tab") == -1,
+        "chewing_config_set_str should return ERROR for unknown action");
+
+    chewing_delete(ctx);
+}
+
+void test_undo_redo()
+{
+    ChewingContext *ctx;
+
+    ctx = chewing_new();
+    start_testcase(ctx);
+
+    ok(chewing_undo(ctx) == -1, "chewing_undo should return ERROR on empty buffer");
+
+    type_keystroke_by_string(ctx, "hk4g4");
+    ok_preedit_buffer(ctx, "\xE6\xB8\xAC\xE8\xA9\xA6" /* 測試 */);
+    ok(chewing_undo(ctx) == 0, "chewing_undo should return OK");
+    ok_preedit_buffer(ctx, "\xE6\xB8\xAC" /* 測 */);
+    ok(chewing_redo(ctx) == 0, "chewing_redo should return OK");
+    ok_preedit_buffer(ctx, "\xE6\xB8\xAC\xE8\xA9\xA6" /* 測試 */);
+    ok(chewing_redo(ctx) == -1, "chewing_redo should return ERROR when nothing to redo");
+
+    ok(chewing_config_set_int(ctx, "chewing.undo_depth", 0) == 0,
+        "chewing_config_set_int should return OK");
+    ok(chewing_config_get_int(ctx, "chewing.undo_depth") == 0, "undo_depth shall be 0");
+    ok(chewing_config_set_int(ctx, "chewing.undo_depth", MAX_UNDO_DEPTH) == 0,
+        "chewing_config_set_int should return OK");
+    ok(chewing_config_set_int(ctx, "chewing.undo_depth", MAX_UNDO_DEPTH + 1) == -1,
+        "chewing_config_set_int should return ERROR above MAX_UNDO_DEPTH");
+    ok(chewing_config_set_int(ctx, "chewing.undo_depth", -1) == -1,
+        "chewing_config_set_int should return ERROR for negative value");
+    ok(chewing_config_get_int(ctx, "chewing.undo_depth") == MAX_UNDO_DEPTH,
+        "undo_depth shall be MAX_UNDO_DEPTH");
+
+    chewing_delete(ctx);
+}
+
+void test_set_conversion_search()
+{
+    ChewingContext *ctx;
+
+    ctx = chewing_new();
+    start_testcase(ctx);
+
+    ok(chewing_config_get_int(ctx, "chewing.max_conversion_paths") == 10,
+        "default max_conversion_paths shall be 10");
+    ok(chewing_config_get_int(ctx, "chewing.max_phrase_len") == MAX_PHRASE_LEN,
+        "default max_phrase_len shall be %d", MAX_PHRASE_LEN);
+    ok(chewing_config_get_int(ctx, "chewing.conversion_prune_threshold") == 0,
+        "default conversion_prune_threshold shall be 0");
+
+    ok(chewing_config_set_int(ctx, "chewing.max_conversion_paths", 0) == -1,
+        "max_conversion_paths shall not be 0");
+    ok(chewing_config_set_int(ctx, "chewing.max_phrase_len", 0) == -1,
+        "max_phrase_len shall not be 0");
+    ok(chewing_config_set_int(ctx, "chewing.max_phrase_len", MAX_PHRASE_LEN + 1) == -1,
+        "max_phrase_len shall not exceed %d", MAX_PHRASE_LEN);
+    ok(chewing_config_set_int(ctx, "chewing.conversion_prune_threshold", -1) == -1,
+        "conversion_prune_threshold shall not be negative");
+
+    type_keystroke_by_string(ctx, "hk4g4");
+    ok_preedit_buffer(ctx, "\xE6\xB8\xAC\xE8\xA9\xA6" /* 測試 */);
+
+    ok(chewing_config_set_int(ctx, "chewing.max_conversion_paths", 20) == 0,
+        "chewing_config_set_int should return OK");
+    ok(chewing_config_get_int(ctx, "chewing.max_conversion_paths") == 20,
+        "max_conversion_paths shall be 20");
+    ok(chewing_config_set_int(ctx, "chewing.conversion_prune_threshold", 30) == 0,
+        "chewing_config_set_int should return OK");
+    ok(chewing_config_get_int(ctx, "chewing.conversion_prune_threshold") == 30,
+        "conversion_prune_threshold shall be 30");
+    ok_preedit_buffer(ctx, "\xE6\xB8\xAC\xE8\xA9\xA6" /* 測試 */);
+
+    ok(chewing_config_set_int(ctx, "chewing.max_phrase_len", 1) == 0,
+        "chewing_config_set_int should return OK");
+    ok(chewing_config_get_int(ctx, "chewing.max_phrase_len") == 1, "max_phrase_len shall be 1");
+
+    chewing_delete(ctx);
+}
+
+void test_set_toneless_input()
+{
+    ChewingContext *ctx;
+
+    ctx = chewing_new();
+    start_testcase(ctx);
+
+    ok(chewing_config_get_int(ctx, "chewing.toneless_input") == 0,
+        "default toneless_input shall be 0");
+    ok(chewing_config_set_int(ctx, "chewing.toneless_input", 2) == -1,
+        "toneless_input shall be a boolean");
+
+    ok(chewing_config_set_int(ctx, "chewing.toneless_input", 1) == 0,
+        "chewing_config_set_int should return OK");
+    ok(chewing_config_get_int(ctx, "chewing.toneless_input") == 1, "toneless_input shall be 1");
+
+    type_keystroke_by_string(ctx, "hkg ");
+    ok_preedit_buffer(ctx, "\xE6\xB8\xAC\xE8\xA9\xA6" /* 測試 */);
+
+    chewing_delete(ctx);
+}
+
+void test_set_phonetic_confusions()
+{
+    ChewingContext *ctx;
+
+    ctx = chewing_new();
+    start_testcase(ctx);
+
+    ok(chewing_config_get_int(ctx, "chewing.phonetic_confusions") == 0,
+        "default phonetic_confusions shall be 0");
+    ok(chewing_config_set_int(ctx, "chewing.phonetic_confusions", 1 << 9) == -1,
+        "phonetic_confusions shall not have unknown bits");
+
+    ok(chewing_config_set_int(ctx, "chewing.phonetic_confusions",
+            CHEWING_CONFUSION_ZH_Z | CHEWING_CONFUSION_EN_ENG) == 0,
+        "chewing_config_set_int should return OK");
+    ok(chewing_config_get_int(ctx, "chewing.phonetic_confusions") ==
+        (CHEWING_CONFUSION_ZH_Z | CHEWING_CONFUSION_EN_ENG),
+        "phonetic_confusions shall be CHEWING_CONFUSION_ZH_Z | CHEWING_CONFUSION_EN_ENG");
+
+    chewing_delete(ctx);
+}
+
+void test_set_abbreviated_input()
+{
+    ChewingContext *ctx;
+
+    ctx = chewing_new();
+    start_testcase(ctx);
+
+    ok(chewing_config_get_int(ctx, "chewing.abbreviated_input") == 0,
+        "default abbreviated_input shall be 0");
+    ok(chewing_config_set_int(ctx, "chewing.abbreviated_input", 2) == -1,
+        "abbreviated_input shall be a boolean");
+
+    ok(chewing_config_set_int(ctx, "chewing.abbreviated_input", 1) == 0,
+        "chewing_config_set_int should return OK");
+    ok(chewing_config_get_int(ctx, "chewing.abbreviated_input") == 1,
+        "abbreviated_input shall be 1");
+
+    type_keystroke_by_string(ctx, "hg ");
+    ok_preedit_buffer(ctx, "\xE6\xB8\xAC\xE8\xA9\xA6" /* 測試 */);
+
+    ok(chewing_config_set_int(ctx, "chewing.toneless_input", 1) == -1,
+        "toneless_input shall conflict with abbreviated_input");
+    ok(chewing_config_set_int(ctx, "chewing.phonetic_confusions", CHEWING_CONFUSION_ZH_Z) == -1,
+        "phonetic_confusions shall conflict with abbreviated_input");
+    ok(chewing_config_set_int(ctx, "chewing.conversion_engine",
+            FUZZY_CHEWING_CONVERSION_ENGINE) == -1,
+        "fuzzy conversion engine shall conflict with abbreviated_input");
+    ok(chewing_config_get_int(ctx, "chewing.toneless_input") == 0, "toneless_input shall be 0");
+
+    ok(chewing_config_set_int(ctx, "chewing.abbreviated_input", 0) == 0,
+        "chewing_config_set_int should return OK");
+    ok(chewing_config_set_int(ctx, "chewing.toneless_input", 1) == 0,
+        "chewing_config_set_int should return OK");
+
+    chewing_delete(ctx);
+}
+
+void test_set_syllable_correction()
+{
+    ChewingContext *ctx;
+
+    ctx = chewing_new();
+    start_testcase(ctx);
+
+    ok(chewing_config_get_int(ctx, "chewing.syllable_correction") == 0,
+        "default syllable_correction shall be 0");
+    ok(chewing_config_set_int(ctx, "chewing.syllable_correction", 2) == -1,
+        "syllable_correction shall be a boolean");
+
+    ok(chewing_config_set_int(ctx, "chewing.syllable_correction", 1) == 0,
+        "chewing_config_set_int should return OK");
+    ok(chewing_config_get_int(ctx, "chewing.syllable_correction") == 1,
+        "syllable_correction shall be 1");
+
+    /* ㄕㄜˋ is corrected to ㄘㄜˋ */
+    type_keystroke_by_string(ctx, "gk4");
+    ok_preedit_buffer(ctx, "\xE6\xB8\xAC" /* 測 */);
+    ok(chewing_cand_TotalChoice(ctx) > 0, "candidate window shall be opened");
+
+    chewing_delete(ctx);
+}
+
+void test_set_auto_correct_syllables()
+{
+    ChewingContext *ctx;
+
+    ctx = chewing_new();
+    start_testcase(ctx);
+
+    ok(chewing_config_get_int(ctx, "chewing.auto_correct_syllables") == 0,
+        "default auto_correct_syllables shall be 0");
+    ok(chewing_config_set_int(ctx, "chewing.auto_correct_syllables", 2) == -1,
+        "auto_correct_syllables shall be a boolean");
+
+    ok(chewing_config_set_int(ctx, "chewing.auto_correct_syllables", 1) == 0,
+        "chewing_config_set_int should return OK");
+    ok(chewing_config_get_int(ctx, "chewing.auto_correct_syllables") == 1,
+        "auto_correct_syllables shall be 1");
+    chewing_config_set_int(ctx, "chewing.syllable_correction", 1);
+
+    type_keystroke_by_string(ctx, "gk4");
+    ok_preedit_buffer(ctx, "\xE6\xB8\xAC" /* 測 */);
+    ok(chewing_cand_TotalChoice(ctx) == 0, "candidate window shall not be opened");
+
+    chewing_delete(ctx);
+}
+
+void test_set_next_phrase_prediction()
+{
+    ChewingContext *ctx;
+    const char *cand;
+
+    ctx = chewing_new();
+    start_testcase(ctx);
+
+    ok(chewing_config_get_int(ctx, "chewing.next_phrase_prediction") == 0,
+        "default next_phrase_prediction shall be 0");
+    ok(chewing_config_set_int(ctx, "chewing.next_phrase_prediction", 2) == -1,
+        "next_phrase_prediction shall be a boolean");
+
+    ok(chewing_config_set_int(ctx, "chewing.next_phrase_prediction", 1) == 0,
+        "chewing_config_set_int should return OK");
+    ok(chewing_config_get_int(ctx, "chewing.next_phrase_prediction") == 1,
+        "next_phrase_prediction shall be 1");
+
+    /* 測 is followed by 試 in 測試 */
+    type_keystroke_by_string(ctx, "hk4<E>");
+    ok_commit_buffer(ctx, "\xE6\xB8\xAC" /* 測 */);
+    ok(chewing_cand_TotalChoice(ctx) > 0, "candidate window shall be opened");
+    chewing_cand_Enumerate(ctx);
+    ok(chewing_cand_hasNext(ctx), "shall have predictions");
+
+    cand = chewing_cand_string_by_index_static(ctx, 0);
+    ok(strlen(cand) > 0, "prediction shall not be empty");
+    ok(chewing_cand_choose_by_index(ctx, 0) == 0,
+        "chewing_cand_choose_by_index should return OK");
+    ok(chewing_commit_Check(ctx), "prediction shall be committed");
+
+    type_keystroke_by_string(ctx, "<EE>");
+    ok(chewing_cand_TotalChoice(ctx) == 0, "candidate window shall be closed");
+
+    /* Digits type bopomofo instead of picking predictions */
+    type_keystroke_by_string(ctx, "hk4<E>");
+    ok(chewing_cand_TotalChoice(ctx) > 0, "candidate window shall be opened");
+    type_keystroke_by_string(ctx, "1");
+    ok(chewing_cand_TotalChoice(ctx) == 0, "candidate window shall be closed");
+    ok_bopomofo_buffer(ctx, "\xE3\x84\x85" /* ㄅ */);
+
+    chewing_delete(ctx);
+}
+
 void test_set_selKey_error_handling()
 {
     ChewingContext *ctx;
@@ -788,6 +1074,15 @@ int main(int argc, char *argv[])
     test_set_maxChiSymbolLen();
     test_maxChiSymbolLen();
     test_set_selKey();
+    test_set_key_bindings();
+    test_undo_redo();
+    test_set_conversion_search();
+    test_set_toneless_input();
+    test_set_phonetic_confusions();
+    test_set_abbreviated_input();
+    test_set_syllable_correction();
+    test_set_auto_correct_syllables();
+    test_set_next_phrase_prediction();
     test_set_addPhraseDirection();
     test_set_spaceAsSelection();
     test_set_escCleanAllBuf();
@@ -9,6 +9,7 @@
  */
 #include <assert.h>
 #include <stdlib.h>
+#include <string.h>
 
 #include "testhelper.h"
 #include "chewing.h"
@@ -960,6 +961,155 @@ void test_clean_bopomofo()
     test_clean_bopomofo_during_cand_selecting();
 }
 
+void test_conversion_alternatives()
+{
+    ChewingContext *ctx;
+    int ret;
+    int count;
+    double best;
+    double score;
+    char *text;
+
+    ctx = chewing_new();
+    start_testcase(ctx);
+
+    count = chewing_conversion_count(ctx);
+    ok(count == 0, "chewing_conversion_count() returns `%d' shall be `%d'", count, 0);
+    ret = chewing_conversion_choose_by_index(ctx, 0);
+    ok(ret == -1, "chewing_conversion_choose_by_index() returns `%d' shall be `%d'", ret, -1);
+
+    type_keystroke_by_string(ctx, "hk4g4" /* 測試 */ );
+
+    count = chewing_conversion_count(ctx);
+    ok(count > 1, "chewing_conversion_count() returns `%d' shall be greater than `%d'", count, 1);
+    ret = chewing_conversion_selected(ctx);
+    ok(ret == 0, "chewing_conversion_selected() returns `%d' shall be `%d'", ret, 0);
+
+    text = chewing_conversion_string_by_index(ctx, 0);
+    ok(strcmp(text, "測試") == 0, "chewing_conversion_string_by_index() returns `%s' shall be `%s'", text, "測試");
+    chewing_free(text);
+
+    ret = chewing_conversion_score_by_index(ctx, 0, &best);
+    ok(ret == 0, "chewing_conversion_score_by_index() returns `%d' shall be `%d'", ret, 0);
+    ret = chewing_conversion_score_by_index(ctx, 1, &score);
+    ok(ret == 0, "chewing_conversion_score_by_index() returns `%d' shall be `%d'", ret, 0);
+    ok(best >= score, "the first conversion shall have the best score");
+
+    ret = chewing_conversion_choose_by_index(ctx, 1);
+    ok(ret == 0, "chewing_conversion_choose_by_index() returns `%d' shall be `%d'", ret, 0);
+    ret = chewing_conversion_selected(ctx);
+    ok(ret == 1, "chewing_conversion_selected() returns `%d' shall be `%d'", ret, 1);
+    text = chewing_conversion_string_by_index(ctx, 1);
+    ok(strcmp(text, "測試") != 0, "alternative conversions shall have distinct text");
+    ok_preedit_buffer(ctx, text);
+    chewing_free(text);
+
+    ret = chewing_conversion_choose_by_index(ctx, count);
+    ok(ret == -1, "chewing_conversion_choose_by_index() returns `%d' shall be `%d'", ret, -1);
+    ret = chewing_conversion_score_by_index(ctx, count, &score);
+    ok(ret == -1, "chewing_conversion_score_by_index() returns `%d' shall be `%d'", ret, -1);
+
+    chewing_delete(ctx);
+}
+
+void test_cand_details()
+{
+    ChewingContext *ctx;
+    int ret;
+    int index;
+    int total;
+    const char *str;
+
+    ctx = chewing_new();
+    start_testcase(ctx);
+
+    ret = chewing_cand_freq_by_index(ctx, 0);
+    ok(ret == -1, "chewing_cand_freq_by_index() returns `%d' shall be `%d'", ret, -1);
+
+    type_keystroke_by_string(ctx, "hk4" /* 測 */ );
+    ret = chewing_cand_open(ctx);
+    ok(ret == 0, "chewing_cand_open() returns `%d' shall be `%d'", ret, 0);
+
+    total = chewing_cand_TotalChoice(ctx);
+    for (index = 0; index < total; ++index) {
+        if (strcmp(chewing_cand_string_by_index_static(ctx, index), "\xE6\xB8\xAC" /* 測 */) == 0)
+            break;
+    }
+    ok(index < total, "candidates shall contain 測");
+
+    ret = chewing_cand_freq_by_index(ctx, index);
+    ok(ret >= 0, "chewing_cand_freq_by_index() returns `%d' shall not be `%d'", ret, -1);
+    ret = chewing_cand_is_user_phrase_by_index(ctx, index);
+    ok(ret == 0, "chewing_cand_is_user_phrase_by_index() returns `%d' shall be `%d'", ret, 0);
+    str = chewing_cand_reading_by_index_static(ctx, index);
+    ok(strcmp(str, "\xE3\x84\x98\xE3\x84\x9C\xCB\x8B" /* ㄘㄜˋ */) == 0,
+        "chewing_cand_reading_by_index_static() returns `%s' shall be `%s'", str, "ㄘㄜˋ");
+    str = chewing_cand_comment_by_index_static(ctx, index);
+    ok(strcmp(str, "") == 0, "chewing_cand_comment_by_index_static() returns `%s' shall be empty", str);
+    str = chewing_cand_source_by_index_static(ctx, index);
+    ok(strcmp(str, "") != 0, "chewing_cand_source_by_index_static() shall not be empty");
+
+    ret = chewing_cand_freq_by_index(ctx, total);
+    ok(ret == -1, "chewing_cand_freq_by_index() returns `%d' shall be `%d'", ret, -1);
+    ret = chewing_cand_is_user_phrase_by_index(ctx, total);
+    ok(ret == -1, "chewing_cand_is_user_phrase_by_index() returns `%d' shall be `%d'", ret, -1);
+    str = chewing_cand_reading_by_index_static(ctx, total);
+    ok(strcmp(str, "") == 0, "chewing_cand_reading_by_index_static() returns `%s' shall be empty", str);
+
+    chewing_delete(ctx);
+}
+
+void test_cand_delete_and_demote()
+{
+    ChewingContext *ctx;
+    int ret;
+    int index;
+    int total;
+    const char *phrase = "\xE5\x97\xB0" /* 嗰 */ ;
+    const char *bopomofo = "\xE3\x84\x98\xE3\x84\x9C\xCB\x8B" /* ㄘㄜˋ */ ;
+
+    ctx = chewing_new();
+    start_testcase(ctx);
+
+    ret = chewing_cand_delete_by_index(ctx, 0);
+    ok(ret == -1, "chewing_cand_delete_by_index() returns `%d' shall be `%d'", ret, -1);
+    ret = chewing_cand_demote_by_index(ctx, 0);
+    ok(ret == -1, "chewing_cand_demote_by_index() returns `%d' shall be `%d'", ret, -1);
+
+    ret = chewing_userphrase_add(ctx, phrase, bopomofo);
+    ok(ret == 1, "chewing_userphrase_add() returns `%d' shall be `%d'", ret, 1);
+
+    type_keystroke_by_string(ctx, "hk4" /* 測 */ );
+    ret = chewing_cand_open(ctx);
+    ok(ret == 0, "chewing_cand_open() returns `%d' shall be `%d'", ret, 0);
+
+    total = chewing_cand_TotalChoice(ctx);
+    for (index = 0; index < total; ++index) {
+        if (strcmp(chewing_cand_string_by_index_static(ctx, index), phrase) == 0)
+            break;
+    }
+    ok(index < total, "candidates shall contain 嗰");
+
+    ret = chewing_cand_demote_by_index(ctx, index);
+    ok(ret == 0, "chewing_cand_demote_by_index() returns `%d' shall be `%d'", ret, 0);
+    ret = chewing_cand_is_user_phrase_by_index(ctx, index);
+    ok(ret == 1, "chewing_cand_is_user_phrase_by_index() returns `%d' shall be `%d'", ret, 1);
+
+    ret = chewing_cand_delete_by_index(ctx, index);
+    ok(ret == 0, "chewing_cand_delete_by_index() returns `%d' shall be `%d'", ret, 0);
+    ret = chewing_cand_TotalChoice(ctx);
+    ok(ret == total - 1, "chewing_cand_TotalChoice() returns `%d' shall be `%d'", ret, total - 1);
+    ret = chewing_userphrase_lookup(ctx, phrase, bopomofo);
+    ok(ret == 0, "chewing_userphrase_lookup() returns `%d' shall be `%d'", ret, 0);
+
+    ret = chewing_cand_delete_by_index(ctx, total);
+    ok(ret == -1, "chewing_cand_delete_by_index() returns `%d' shall be `%d'", ret, -1);
+    ret = chewing_cand_demote_by_index(ctx, total);
+    ok(ret == -1, "chewing_cand_demote_by_index() returns `%d' shall be `%d'", ret, -1);
+
+    chewing_delete(ctx);
+}
+
 int main(int argc, char *argv[])
 {
     putenv("CHEWING_PATH=" CHEWING_DATA_PREFIX);
@@ -969,11 +1119,15 @@ int main(int argc, char *argv[])
     test_cand_close();
     test_cand_choose();
     test_cand_list();
+    test_cand_details();
+    test_cand_delete_and_demote();
 
     test_commit_preedit();
     test_clean_preedit();
 
     test_clean_bopomofo();
 
+    test_conversion_alternatives();
+
     return exit_status();
 }
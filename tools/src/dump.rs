@@ -57,7 +57,7 @@ fn dump_dict_tsi_src(mut sink: BufWriter<Box<dyn Write>>, dict: &dyn Dictionary)
     writeln!(sink, "# dc:type {}", info.usage)?;
     writeln!(sink, "# 詞(phrase) 詞頻(freq) 注音(bopomofo)")?;
     for (syllables, phrase) in dict.entries() {
-        writeln!(
+        write!(
             sink,
             "{} {} {}",
             phrase,
@@ -68,6 +68,7 @@ fn dump_dict_tsi_src(mut sink: BufWriter<Box<dyn Write>>, dict: &dyn Dictionary)
                 .collect::<Vec<_>>()
                 .join(" ")
         )?;
+        write_comment(&mut sink, phrase.comment())?;
     }
     Ok(())
 }
@@ -81,7 +82,7 @@ fn dump_dict_csv(mut sink: BufWriter<Box<dyn Write>>, dict: &dyn Dictionary) ->
     writeln!(sink, "# dc:type,{},", info.usage)?;
     writeln!(sink, "# 詞(phrase),詞頻(freq),注音(bopomofo)")?;
     for (syllables, phrase) in dict.entries() {
-        writeln!(
+        write!(
             sink,
             "{},{},{}",
             phrase,
@@ -92,6 +93,15 @@ fn dump_dict_csv(mut sink: BufWriter<Box<dyn Write>>, dict: &dyn Dictionary) ->
                 .collect::<Vec<_>>()
                 .join("　")
         )?;
+        write_comment(&mut sink, phrase.comment())?;
+    }
+    Ok(())
+}
+
+fn write_comment(sink: &mut BufWriter<Box<dyn Write>>, comment: Option<&str>) -> Result<()> {
+    match comment {
+        Some(comment) => writeln!(sink, " # {comment}")?,
+        None => writeln!(sink)?,
     }
     Ok(())
 }
@@ -19,6 +19,8 @@ pub(crate) enum ChewingCliCmd {
     Info(Info),
     /// Dump the dictionary entries into tsi.src formatted stream
     Dump(Dump),
+    /// Explain how a syllable sequence is converted
+    Trace(Trace),
 }
 
 #[derive(Args)]
@@ -50,6 +52,9 @@ pub(crate) struct InitDatabase {
     /// Read the dictionary source as CSV with header
     #[arg(long)]
     pub(crate) csv: bool,
+    /// Add a reverse index from phrases to their readings (trie only)
+    #[arg(long)]
+    pub(crate) reverse_index: bool,
     /// Path to the dictionary source file
     pub(crate) tsi_src: PathBuf,
     /// Path to the output file
@@ -85,6 +90,37 @@ pub(crate) struct Dump {
     pub(crate) csv: bool,
 }
 
+#[derive(Args)]
+pub(crate) struct Trace {
+    /// Choose the conversion engine to trace.
+    #[arg(short, long, value_enum, default_value = "chewing")]
+    pub(crate) engine: EngineType,
+    /// Location of the dictionary files
+    ///
+    /// Defaults to the detected system dictionaries.
+    #[arg(short, long)]
+    pub(crate) path: Vec<PathBuf>,
+    /// Also use the detected user dictionary
+    #[arg(short, long)]
+    pub(crate) user: bool,
+    /// Text committed before the syllables
+    #[arg(short, long)]
+    pub(crate) context: Option<String>,
+    /// Output in JSON format
+    #[arg(short, long)]
+    pub(crate) json: bool,
+    /// Syllables to convert, for example ㄘㄜˋ ㄕˋ
+    #[arg(required = true)]
+    pub(crate) syllables: Vec<String>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub(crate) enum EngineType {
+    Chewing,
+    Fuzzy,
+    Bigram,
+}
+
 #[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
 pub(crate) enum DbType {
     Trie,
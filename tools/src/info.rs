@@ -45,7 +45,7 @@ pub(crate) fn run(args: flags::Info) -> Result<()> {
     Ok(())
 }
 
-fn escape_json(str: String) -> String {
+pub(crate) fn escape_json(str: String) -> String {
     use std::fmt::Write;
     let mut out = String::new();
     str.chars().for_each(|ch| {
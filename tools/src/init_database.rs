@@ -11,7 +11,7 @@ use anyhow::{Context, Result};
 #[cfg(feature = "sqlite")]
 use chewing::dictionary::SqliteDictionaryBuilder;
 use chewing::{
-    dictionary::{DictionaryBuilder, DictionaryInfo, TrieBuilder},
+    dictionary::{DictionaryBuilder, DictionaryInfo, Phrase, TrieBuilder},
     zhuyin::{Bopomofo, Syllable},
 };
 
@@ -32,7 +32,11 @@ pub(crate) fn run(args: flags::InitDatabase) -> Result<()> {
             #[cfg(not(feature = "sqlite"))]
             bail!("sqlite3 dictionary format support was not enabled.");
         }
-        flags::DbType::Trie => Box::new(TrieBuilder::new()),
+        flags::DbType::Trie => {
+            let mut builder = TrieBuilder::new();
+            builder.enable_reverse_index(args.reverse_index);
+            Box::new(builder)
+        }
     };
 
     let mut name = args.name;
@@ -77,14 +81,16 @@ pub(crate) fn run(args: flags::InitDatabase) -> Result<()> {
             continue;
         }
         match parse_line(delimiter, &line, args.fix) {
-            Ok((syllables, phrase, freq)) => {
+            Ok((syllables, phrase, freq, comment)) => {
                 if syllables.len() != phrase.chars().count() {
                     errors.push(parse_error(line_num, line, "Word count doesn't match"));
                     continue;
                 }
-                builder
-                    .insert(&syllables, (phrase, freq).into())
-                    .context(error)?;
+                let phrase = match comment {
+                    Some(comment) => Phrase::new(phrase, freq).with_comment(comment),
+                    None => Phrase::new(phrase, freq),
+                };
+                builder.insert(&syllables, phrase).context(error)?;
             }
             Err(error) => errors.push(error.context(parse_error(line_num, line, "Parse error"))),
         };
@@ -139,7 +145,11 @@ pub(crate) fn run(args: flags::InitDatabase) -> Result<()> {
     Ok(())
 }
 
-fn parse_line(delimiter: char, line: &str, fix: bool) -> Result<(Vec<Syllable>, &str, u32)> {
+fn parse_line(
+    delimiter: char,
+    line: &str,
+    fix: bool,
+) -> Result<(Vec<Syllable>, &str, u32, Option<&str>)> {
     let phrase = line
         .split(delimiter)
         .find(|s| !s.is_empty())
@@ -184,7 +194,14 @@ fn parse_line(delimiter: char, line: &str, fix: bool) -> Result<(Vec<Syllable>,
         syllables.push(syllable_builder.build());
     }
 
-    Ok((syllables, phrase, freq))
+    // The text after # is the comment of the phrase
+    let comment = line
+        .split_once(delimiter)
+        .and_then(|(_, rest)| rest.split_once('#'))
+        .map(|(_, comment)| comment.trim_end_matches('"').trim())
+        .filter(|comment| !comment.is_empty());
+
+    Ok((syllables, phrase, freq, comment))
 }
 
 fn fix_common_syllable_errors(c: char) -> char {
@@ -205,10 +222,11 @@ mod tests {
     #[test]
     fn parse_ssv() {
         let line = "鑰匙 668 ㄧㄠˋ ㄔˊ # not official";
-        if let Ok((syllables, phrase, freq)) = parse_line(' ', &line, false) {
+        if let Ok((syllables, phrase, freq, comment)) = parse_line(' ', &line, false) {
             assert_eq!(syllables, vec![syl![I, AU, TONE4], syl![CH, TONE2]]);
             assert_eq!("鑰匙", phrase);
             assert_eq!(668, freq);
+            assert_eq!(Some("not official"), comment);
         } else {
             panic!()
         }
@@ -217,10 +235,11 @@ mod tests {
     #[test]
     fn parse_ssv_multiple_whitespace() {
         let line = "鑰匙     668 ㄧㄠˋ ㄔˊ # not official";
-        if let Ok((syllables, phrase, freq)) = parse_line(' ', &line, false) {
+        if let Ok((syllables, phrase, freq, comment)) = parse_line(' ', &line, false) {
             assert_eq!(syllables, vec![syl![I, AU, TONE4], syl![CH, TONE2]]);
             assert_eq!("鑰匙", phrase);
             assert_eq!(668, freq);
+            assert_eq!(Some("not official"), comment);
         } else {
             panic!()
         }
@@ -229,7 +248,7 @@ mod tests {
     #[test]
     fn parse_ssv_syllable_errors() {
         let line = "地永天長 50 ㄉ一ˋ ㄩㄥˇ ㄊ一ㄢ ㄔ丫ˊ";
-        if let Ok((syllables, phrase, freq)) = parse_line(' ', &line, true) {
+        if let Ok((syllables, phrase, freq, comment)) = parse_line(' ', &line, true) {
             assert_eq!(
                 syllables,
                 vec![
@@ -241,6 +260,7 @@ mod tests {
             );
             assert_eq!("地永天長", phrase);
             assert_eq!(50, freq);
+            assert_eq!(None, comment);
         } else {
             panic!()
         }
@@ -249,10 +269,11 @@ mod tests {
     #[test]
     fn parse_csv() {
         let line = "鑰匙,668,ㄧㄠˋ ㄔˊ # not official";
-        if let Ok((syllables, phrase, freq)) = parse_line(',', &line, false) {
+        if let Ok((syllables, phrase, freq, comment)) = parse_line(',', &line, false) {
             assert_eq!(syllables, vec![syl![I, AU, TONE4], syl![CH, TONE2]]);
             assert_eq!("鑰匙", phrase);
             assert_eq!(668, freq);
+            assert_eq!(Some("not official"), comment);
         } else {
             panic!()
         }
@@ -261,10 +282,11 @@ mod tests {
     #[test]
     fn parse_csv_quoted() {
         let line = "\"鑰匙\",668,\"ㄧㄠˋ ㄔˊ # not official\"";
-        if let Ok((syllables, phrase, freq)) = parse_line(',', &line, false) {
+        if let Ok((syllables, phrase, freq, comment)) = parse_line(',', &line, false) {
             assert_eq!(syllables, vec![syl![I, AU, TONE4], syl![CH, TONE2]]);
             assert_eq!("鑰匙", phrase);
             assert_eq!(668, freq);
+            assert_eq!(Some("not official"), comment);
         } else {
             panic!()
         }
@@ -5,6 +5,7 @@ mod dump;
 mod flags;
 mod info;
 mod init_database;
+mod trace;
 
 fn main() -> Result<()> {
     env_logger::init();
@@ -24,6 +25,7 @@ fn main() -> Result<()> {
         flags::ChewingCliCmd::InitDatabase(args) => init_database::run(args)?,
         flags::ChewingCliCmd::Info(args) => info::run(args)?,
         flags::ChewingCliCmd::Dump(args) => dump::run(args)?,
+        flags::ChewingCliCmd::Trace(args) => trace::run(args)?,
     }
     Ok(())
 }
@@ -0,0 +1,157 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Result, bail};
+use chewing::{
+    conversion::{
+        BigramEngine, ChewingEngine, Composition, ConversionContext, ConversionEngine,
+        ConversionTrace, FuzzyChewingEngine, Symbol, TraceEdge,
+    },
+    dictionary::{AssetLoader, Dictionary, Layered, SingleDictionaryLoader, UserDictionaryManager},
+    zhuyin::Syllable,
+};
+
+use crate::{flags, info::escape_json};
+
+pub(crate) fn run(args: flags::Trace) -> Result<()> {
+    let mut dicts: Vec<Box<dyn Dictionary>> = if args.path.is_empty() {
+        AssetLoader::new().load(&["word.dat", "tsi.dat"])
+    } else {
+        let loader = SingleDictionaryLoader::new();
+        args.path
+            .iter()
+            .map(|path| {
+                loader
+                    .guess_format_and_load(path)
+                    .with_context(|| format!("failed to load {}", path.display()))
+            })
+            .collect::<Result<_>>()?
+    };
+    if dicts.is_empty() {
+        bail!("no dictionary found");
+    }
+    if args.user {
+        let dict = UserDictionaryManager::new()
+            .init()
+            .context("failed to load user dictionary")?;
+        dicts.push(dict);
+    }
+    let dict = Layered::new(dicts);
+
+    let mut composition = Composition::new();
+    for syllable in &args.syllables {
+        let syllable: Syllable = syllable
+            .parse()
+            .with_context(|| format!("invalid syllable {syllable}"))?;
+        composition.push(Symbol::from(syllable));
+    }
+    let mut context = ConversionContext::new();
+    if let Some(text) = &args.context {
+        context.set_text(text);
+    }
+
+    let engine: Box<dyn ConversionEngine> = match args.engine {
+        flags::EngineType::Chewing => Box::new(ChewingEngine::new()),
+        flags::EngineType::Fuzzy => Box::new(FuzzyChewingEngine::new()),
+        flags::EngineType::Bigram => {
+            let model = AssetLoader::new()
+                .load_bigram_model()
+                .context("failed to load bigram model")?;
+            Box::new(BigramEngine::new(Arc::new(model)))
+        }
+    };
+    let Some(trace) = engine.trace(&dict, &composition, &context) else {
+        bail!("the conversion engine does not support tracing");
+    };
+    if args.json {
+        print_json_trace(&trace);
+    } else {
+        print_trace(&trace);
+    }
+    Ok(())
+}
+
+fn source_name(edge: &TraceEdge) -> String {
+    match &edge.source {
+        Some(info) if info.name.is_empty() => info.usage.to_string(),
+        Some(info) => info.name.clone(),
+        None => "-".to_string(),
+    }
+}
+
+fn print_edge(edge: &TraceEdge, indent: &str) {
+    println!(
+        "{indent}{:<7} {:>8} {:>10.4}  {:<16} {}",
+        format!("{}..{}", edge.start, edge.end),
+        edge.freq,
+        edge.log_prob,
+        source_name(edge),
+        edge.text
+    );
+}
+
+fn print_trace(trace: &ConversionTrace) {
+    println!("Edges:");
+    println!(
+        "  {:<7} {:>8} {:>10}  {:<16} Phrase",
+        "Range", "Freq", "LogProb", "Source"
+    );
+    for edge in &trace.edges {
+        print_edge(edge, "  ");
+    }
+    println!();
+    println!("Paths:");
+    for (i, path) in trace.paths.iter().enumerate() {
+        let text: String = path.edges.iter().map(|edge| &*edge.text).collect();
+        println!("  #{} {:.4} {}", i + 1, path.log_prob, text);
+        for edge in &path.edges {
+            print_edge(edge, "    ");
+        }
+    }
+}
+
+fn json_edge(edge: &TraceEdge) -> String {
+    let source = match &edge.source {
+        Some(info) => format!(
+            r#"{{ "name": "{}", "usage": "{}" }}"#,
+            escape_json(info.name.clone()),
+            escape_json(info.usage.to_string())
+        ),
+        None => "null".to_string(),
+    };
+    format!(
+        r#"{{ "start": {}, "end": {}, "text": "{}", "freq": {}, "source": {}, "log_prob": {} }}"#,
+        edge.start,
+        edge.end,
+        escape_json(edge.text.to_string()),
+        edge.freq,
+        source,
+        edge.log_prob
+    )
+}
+
+fn print_json_trace(trace: &ConversionTrace) {
+    println!("{{");
+    println!(r#"  "edges": ["#);
+    let mut iter = trace.edges.iter().peekable();
+    while let Some(edge) = iter.next() {
+        let comma = if iter.peek().is_some() { "," } else { "" };
+        println!("    {}{comma}", json_edge(edge));
+    }
+    println!("  ],");
+    println!(r#"  "paths": ["#);
+    let mut iter = trace.paths.iter().peekable();
+    while let Some(path) = iter.next() {
+        println!("    {{");
+        println!(r#"      "log_prob": {},"#, path.log_prob);
+        println!(r#"      "edges": ["#);
+        let mut edges = path.edges.iter().peekable();
+        while let Some(edge) = edges.next() {
+            let comma = if edges.peek().is_some() { "," } else { "" };
+            println!("        {}{comma}", json_edge(edge));
+        }
+        println!("      ]");
+        println!("    }}{}", if iter.peek().is_some() { "," } else { "" });
+    }
+    println!("  ]");
+    println!("}}");
+}